@@ -0,0 +1,20 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use sparrow_installer::render_bench::screens;
+
+fn frames(c: &mut Criterion) {
+    let mut terminal = Terminal::new(TestBackend::new(100, 30)).expect("terminal");
+    for screen in screens() {
+        c.bench_function(&format!("frame/{}", screen.name), |b| {
+            b.iter(|| screen.draw(&mut terminal))
+        });
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().without_plots();
+    targets = frames
+}
+criterion_main!(benches);
@@ -0,0 +1,380 @@
+use crate::{App, AppState, ProgressType, REBOOT_CHOICES, RECOVERY_ACTIONS, StatusType};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Heading,
+    // One of several choices, Enter acts on it
+    Option,
+    // A choice that Space turns on and off
+    CheckBox,
+    PasswordInput,
+    PathInput,
+    ProgressBar,
+    Text,
+    Status,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub role: Role,
+    pub label: String,
+    // Details of the element, shown in the description area while it has focus
+    pub description: String,
+    // Setting or state spoken after the label, like the resolution of a display row
+    pub value: Option<String>,
+    pub checked: Option<bool>,
+    pub disabled: bool,
+}
+
+impl Node {
+    pub fn new(role: Role, label: impl Into<String>) -> Self {
+        Self {
+            role,
+            label: label.into(),
+            description: String::new(),
+            value: None,
+            checked: None,
+            disabled: false,
+        }
+    }
+
+    pub fn option(label: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            ..Self::new(Role::Option, label)
+        }
+    }
+
+    pub fn check_box(
+        label: impl Into<String>,
+        description: impl Into<String>,
+        checked: bool,
+    ) -> Self {
+        Self {
+            description: description.into(),
+            checked: Some(checked),
+            ..Self::new(Role::CheckBox, label)
+        }
+    }
+
+    pub fn focusable(&self) -> bool {
+        matches!(
+            self.role,
+            Role::Option | Role::CheckBox | Role::PasswordInput | Role::PathInput
+        )
+    }
+
+    // One line naming the element for a screen reader or a text log
+    pub fn describe(&self) -> String {
+        let mut text = format!("{}: {}", self.role.name(), self.label);
+        if let Some(value) = &self.value {
+            text.push_str(&format!(", {}", value));
+        }
+        match self.checked {
+            Some(true) => text.push_str(", checked"),
+            Some(false) => text.push_str(", not checked"),
+            None => {}
+        }
+        if self.disabled {
+            text.push_str(", unavailable");
+        }
+        text
+    }
+}
+
+impl Role {
+    fn name(self) -> &'static str {
+        match self {
+            Role::Heading => "heading",
+            Role::Option => "option",
+            Role::CheckBox => "check box",
+            Role::PasswordInput => "password",
+            Role::PathInput => "path",
+            Role::ProgressBar => "progress",
+            Role::Text => "text",
+            Role::Status => "status",
+        }
+    }
+}
+
+// What a screen shows in reading order, along with the element that has keyboard focus.
+// Focus moves through the focusable nodes in the order they were added.
+#[derive(Debug, Default)]
+pub struct Tree {
+    pub nodes: Vec<Node>,
+    pub focus: Option<usize>,
+}
+
+impl Tree {
+    pub fn push(&mut self, node: Node) {
+        self.nodes.push(node);
+    }
+
+    // Adds the choices of a list, focusing the one under the cursor
+    pub fn list(&mut self, choices: impl IntoIterator<Item = Node>, cursor: usize) {
+        for (i, node) in choices.into_iter().enumerate() {
+            if i == cursor {
+                self.focus = Some(self.nodes.len());
+            }
+            self.nodes.push(node);
+        }
+    }
+
+    pub fn focused(&self) -> Option<&Node> {
+        self.focus.and_then(|index| self.nodes.get(index))
+    }
+
+    // Where the focused element falls in the focus order, counting from 0
+    pub fn focus_position(&self) -> Option<usize> {
+        let focus = self.focus?;
+        Some(
+            self.nodes[..focus]
+                .iter()
+                .filter(|node| node.focusable())
+                .count(),
+        )
+    }
+
+    pub fn focus_order(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().filter(|node| node.focusable())
+    }
+
+    // The whole screen as text, the focused element marked
+    pub fn describe(&self) -> String {
+        let options = self.focus_order().count();
+        let mut position = 0;
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                if !node.focusable() {
+                    return node.describe();
+                }
+                position += 1;
+                let focus = if self.focus == Some(i) { "> " } else { "  " };
+                format!("{}{} ({} of {})", focus, node.describe(), position, options)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// The elements of the screen `app` is on
+pub fn screen(app: &App) -> Tree {
+    let messages = &app.text.messages;
+    let mut tree = Tree::default();
+    tree.push(Node::new(Role::Heading, app.get_title_text()));
+
+    if app.app_state == AppState::PasswordInput {
+        if let Some(reason) = app.password_reason() {
+            tree.push(Node {
+                description: app.password_summary().unwrap_or_default(),
+                ..Node::new(Role::Text, reason)
+            });
+        }
+        tree.focus = Some(tree.nodes.len());
+        tree.push(Node {
+            description: messages.password_help.clone(),
+            ..Node::new(Role::PasswordInput, &messages.password_prompt)
+        });
+    } else if app.show_confirmation {
+        tree.push(Node::new(Role::Text, app.confirmation_text()));
+    } else if let Some(progress) = &app.progress_type {
+        let action = match &app.app_state {
+            AppState::Processing(action) => action.as_str(),
+            _ => "",
+        };
+        let value = match progress {
+            ProgressType::Determinant(_) => Some(app.countdown_remaining.to_string()),
+            ProgressType::Indeterminate => app
+                .step_fraction
+                .map(|fraction| format!("{:.0}%", fraction * 100.0)),
+        };
+        tree.push(Node {
+            value,
+            ..Node::new(Role::ProgressBar, action)
+        });
+        if let Some(line) = app.action_output.last() {
+            tree.push(Node::new(Role::Text, line));
+        }
+    } else {
+        match app.app_state {
+            AppState::UserSelect => tree.list(
+                app.available_users
+                    .iter()
+                    .zip(&app.user_picks)
+                    .map(|(user, picked)| Node::check_box(&user.name, &user.home, *picked)),
+                app.user_cursor,
+            ),
+            AppState::WallpaperSelect => {
+                tree.list(
+                    app.profile.wallpapers.iter().zip(&app.wallpaper_picks).map(
+                        |(pack, picked)| Node::check_box(&pack.name, &pack.description, *picked),
+                    ),
+                    app.wallpaper_cursor,
+                )
+            }
+            AppState::ComponentSelect => tree.list(
+                app.profile
+                    .optional_steps()
+                    .zip(&app.component_picks)
+                    .map(|(step, picked)| Node::check_box(&step.name, &step.description, *picked)),
+                app.component_cursor,
+            ),
+            AppState::CustomSelect => tree.list(
+                app.profile
+                    .steps
+                    .iter()
+                    .zip(&app.custom_picks)
+                    .map(|(step, picked)| Node::check_box(&step.name, &step.description, *picked)),
+                app.custom_cursor,
+            ),
+            AppState::MonitorSelect => tree.list(
+                app.monitors.iter().flat_map(|monitor| {
+                    let details = app.monitor_details(monitor);
+                    let mode = monitor
+                        .selected_mode()
+                        .map(crate::monitors::Mode::label)
+                        .unwrap_or_default();
+                    [
+                        Node {
+                            value: Some(mode),
+                            ..Node::option(
+                                format!("{} {}", monitor.name, messages.monitor_resolution),
+                                details.clone(),
+                            )
+                        },
+                        Node {
+                            value: Some(monitor.scale_value().to_string()),
+                            ..Node::option(
+                                format!("{} {}", monitor.name, messages.monitor_scale),
+                                details,
+                            )
+                        },
+                    ]
+                }),
+                app.monitor_cursor,
+            ),
+            AppState::Locked => tree.push(Node::new(Role::Text, app.lock_details())),
+            AppState::RecoverySelect => tree.list(
+                RECOVERY_ACTIONS.iter().map(|action| {
+                    let (title, description) = app.recovery_label(*action);
+                    Node::option(title, description)
+                }),
+                app.recovery_cursor,
+            ),
+            AppState::RebootSelect => tree.list(
+                REBOOT_CHOICES.iter().map(|choice| {
+                    let (title, description) = app.reboot_label(*choice);
+                    Node::option(title, description)
+                }),
+                app.reboot_cursor,
+            ),
+            AppState::VolumeSelect => {
+                let other = if app.volumes.is_empty() {
+                    format!(
+                        "{} {}",
+                        messages.volumes_none, messages.volumes_other_description
+                    )
+                } else {
+                    messages.volumes_other_description.clone()
+                };
+                tree.list(
+                    app.volumes
+                        .iter()
+                        .map(|volume| {
+                            Node::option(volume.title(), volume.mount.display().to_string())
+                        })
+                        .chain([Node::option(&messages.volumes_other, other)]),
+                    app.volume_cursor,
+                )
+            }
+            AppState::SessionsWarning => tree.push(Node::new(Role::Text, app.sessions_warning())),
+            AppState::Failed => tree.push(Node::new(Role::Text, &app.failure_error)),
+            AppState::BugReport => {
+                tree.push(Node::new(Role::Text, app.bug_report_preview()));
+                tree.list(
+                    app.bug_report_actions().into_iter().map(|action| {
+                        let (title, description) = app.bug_report_label(action);
+                        Node::option(title, description)
+                    }),
+                    app.bug_report_cursor,
+                );
+            }
+            AppState::PathInput => {
+                if let Some(input) = &app.path_input {
+                    tree.focus = Some(tree.nodes.len());
+                    tree.push(Node {
+                        description: messages.path_help.clone(),
+                        ..Node::new(Role::PathInput, &input.value)
+                    });
+                    match input.error {
+                        Some(error) => tree.push(Node::new(Role::Status, app.path_error(error))),
+                        None => {
+                            for completion in &input.completions {
+                                tree.push(Node::new(Role::Text, completion));
+                            }
+                        }
+                    }
+                }
+            }
+            AppState::LogView => {
+                for line in app.log_lines.iter().skip(app.log_scroll) {
+                    tree.push(Node::new(Role::Text, line));
+                }
+            }
+            AppState::Plan => {
+                for line in app.plan_lines.iter().skip(app.plan_scroll) {
+                    tree.push(Node::new(Role::Text, line));
+                }
+            }
+            AppState::DryRunResult => {
+                for line in app.dry_run_lines.iter().skip(app.dry_run_scroll) {
+                    tree.push(Node::new(Role::Text, line));
+                }
+            }
+            _ => {
+                if app.app_state == AppState::MainMenu && !app.health.is_empty() {
+                    tree.push(Node::new(
+                        Role::Status,
+                        crate::health::banner(&app.health, messages),
+                    ));
+                }
+                tree.list(
+                    app.options.iter().map(|option| {
+                        if option.is_enabled() {
+                            Node::option(app.option_title(option), app.option_description(option))
+                        } else {
+                            Node {
+                                disabled: true,
+                                ..Node::option(app.option_title(option), &messages.disabled_help)
+                            }
+                        }
+                    }),
+                    app.selected,
+                )
+            }
+        }
+    }
+
+    if let Some((message, status_type)) = &app.status_message {
+        let prefix = match status_type {
+            StatusType::Success => &app.text.ui_text.success_prefix,
+            StatusType::Error => &app.text.ui_text.error_prefix,
+            StatusType::Fail => &app.text.ui_text.fail_prefix,
+        };
+        tree.push(Node::new(Role::Status, format!("{}: {}", prefix, message)));
+    }
+    if let Some(warning) = app.session_warning() {
+        tree.push(Node::new(Role::Status, warning));
+    }
+    // The tour's popup is what the keys act on
+    if let Some(stop) = crate::tour::current(app) {
+        tree.focus = Some(tree.nodes.len());
+        tree.push(Node {
+            description: stop.body.clone(),
+            ..Node::new(Role::Text, &stop.title)
+        });
+    }
+    tree
+}
@@ -0,0 +1,1058 @@
+use crate::ticks::Tick;
+use crate::{
+    App, AppState, BugReportAction, FailurePolicy, InstallerOption, LOG_PAGE, PathPurpose,
+    ProgressType, REBOOT_CHOICES, RECOVERY_ACTIONS, RecoveryAction, StatusType, SuccessAction,
+    SystemAction, bug_report, credentials, cycle_index, escalation, helper, keymap, logging,
+    metrics, path_input, pipeline, power, recovery, removable, report, support, ticks, tour,
+    typing, worker,
+};
+use anyhow::Result;
+use cli_log::*;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use std::time::Duration;
+
+impl App {
+    // Asks for what the configuration wants before `option` runs, the password first
+    pub(crate) async fn request_operation(&mut self, option: InstallerOption) -> Result<()> {
+        let gate = self.gate(&option);
+        // The helper already holds root for this session
+        let password_known = self.uses_helper() && helper::running();
+        if gate.asks_password() && !password_known && escalation::asks_password() {
+            let stored = if self.credential_rejected {
+                None
+            } else {
+                credentials::lookup(&self.settings.credentials).await
+            };
+            self.show_password_input(option);
+            if let Some(password) = stored {
+                self.password_input = password;
+                self.confirm_password().await?;
+            }
+        } else if gate.confirms(self.dry_run) {
+            self.show_operation_confirmation(&option);
+        } else {
+            self.run_operation(&option).await;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn select_reboot_choice(&mut self) {
+        let choice = REBOOT_CHOICES[self.reboot_cursor];
+        let mut reboot_at = None;
+        match choice {
+            report::RebootChoice::Now => self.start_reboot(),
+            report::RebootChoice::Scheduled => {
+                let minutes = self.settings.system_actions.reboot_delay_minutes;
+                let scheduled = if self.dry_run {
+                    Ok(())
+                } else {
+                    power::schedule_reboot(&self.settings.system_actions, minutes).await
+                };
+                if let Err(e) = scheduled {
+                    logging::event!(
+                        Level::Error,
+                        "power",
+                        "Could not schedule the reboot: {}",
+                        e
+                    );
+                    self.status_message = Some((format!("Error: {}", e), StatusType::Error));
+                    return;
+                }
+                let at = chrono::Local::now() + chrono::Duration::minutes(minutes as i64);
+                reboot_at = Some(at.to_rfc3339());
+                self.app_state = AppState::MainMenu;
+                self.status_message = Some((
+                    format!(
+                        "{} {}",
+                        self.text.messages.reboot_scheduled,
+                        at.format("%H:%M")
+                    ),
+                    StatusType::Success,
+                ));
+            }
+            report::RebootChoice::Later => {
+                self.app_state = AppState::MainMenu;
+                self.status_message = Some((
+                    self.text.messages.reboot_postponed.clone(),
+                    StatusType::Success,
+                ));
+            }
+        }
+
+        // The update's report was already written, the choice is added to it
+        if let Some(mut report) = report::RunReport::load() {
+            report.reboot = Some(choice);
+            report.reboot_at = reboot_at;
+            if let Err(e) = report.save() {
+                logging::event!(Level::Warn, "report", "Could not write report: {}", e);
+            }
+        }
+    }
+
+    pub(crate) async fn select_recovery_action(&mut self) {
+        let Some(failed) = self.last_failure.clone() else {
+            return;
+        };
+        let action = RECOVERY_ACTIONS[self.recovery_cursor];
+        match action {
+            RecoveryAction::Resume => {
+                let steps: Vec<String> =
+                    recovery::remaining_steps(&self.profile, &self.recovery_marker())
+                        .into_iter()
+                        .map(|step| step.label())
+                        .collect();
+                self.recovery_action = Some(action);
+                self.show_confirmation(
+                    InstallerOption::Recover,
+                    format!(
+                        "{}\n{}",
+                        self.text.messages.confirm_resume,
+                        steps.join("\n")
+                    ),
+                );
+            }
+            RecoveryAction::Rollback => {
+                self.rollback_plan = recovery::rollback_plan(
+                    &self.profile,
+                    &self.recovery_marker(),
+                    &failed,
+                    &self.step_context(),
+                );
+                if self.rollback_plan.is_empty() {
+                    self.app_state = AppState::MainMenu;
+                    self.status_message = Some((
+                        self.text.messages.rollback_nothing_to_do.clone(),
+                        StatusType::Success,
+                    ));
+                    return;
+                }
+                let files: Vec<String> = self
+                    .rollback_plan
+                    .iter()
+                    .map(|restore| restore.dest.display().to_string())
+                    .collect();
+                self.recovery_action = Some(action);
+                self.show_confirmation(
+                    InstallerOption::Recover,
+                    format!(
+                        "{}\n{}",
+                        self.text.messages.confirm_rollback,
+                        files.join("\n")
+                    ),
+                );
+            }
+            RecoveryAction::ViewLog => {
+                self.log_lines = recovery::log_lines(&failed);
+                if self.log_lines.is_empty() {
+                    self.log_lines.push(self.text.messages.log_empty.clone());
+                }
+                // Start at the end, where the failure is
+                self.log_scroll = self.log_lines.len().saturating_sub(LOG_PAGE);
+                self.app_state = AppState::LogView;
+            }
+            RecoveryAction::Export => self.show_volumes().await,
+        }
+    }
+
+    // Acts on the typed path once it is valid, otherwise the prompt shows what is wrong
+    pub(crate) async fn confirm_path(&mut self) {
+        let Some(Ok(path)) = self
+            .path_input
+            .as_mut()
+            .map(path_input::PathInput::validate)
+        else {
+            return;
+        };
+        let result = match self.path_purpose {
+            PathPurpose::SaveLog => {
+                let mut contents = self.log_lines.join("\n");
+                contents.push('\n');
+                std::fs::write(&path, contents)
+                    .map(|_| path.clone())
+                    .map_err(anyhow::Error::from)
+            }
+            PathPurpose::Export => removable::export(&path, &self.export_files()),
+            PathPurpose::SupportBundle => support::collect(&self.settings, &path).await,
+            PathPurpose::BugReport => match &self.bug_report {
+                Some(report) => report.save(&path).map(|_| path.clone()),
+                None => return,
+            },
+        };
+        match result {
+            Ok(saved) => {
+                logging::event!(Level::Info, "export", "Saved to {}", saved.display());
+                self.hide_path_input();
+                match self.path_purpose {
+                    PathPurpose::Export => self.app_state = AppState::RecoverySelect,
+                    PathPurpose::BugReport => self.app_state = AppState::Failed,
+                    _ => {}
+                }
+                self.status_message = Some((
+                    format!("{} {}", self.text.messages.path_saved, saved.display()),
+                    StatusType::Success,
+                ));
+            }
+            Err(e) => {
+                logging::event!(
+                    Level::Error,
+                    "export",
+                    "Cannot save to {}: {}",
+                    path.display(),
+                    e
+                );
+                self.status_message = Some((format!("Error: {:#}", e), StatusType::Error));
+            }
+        }
+    }
+
+    pub(crate) async fn execute_option(&mut self) -> Result<()> {
+        let option = &self.options[self.selected];
+
+        match option {
+            InstallerOption::Default => {
+                if option.is_enabled() {
+                    self.start_default_setup().await;
+                } else {
+                    self.status_message =
+                        Some((self.text.messages.option_disabled.clone(), StatusType::Fail));
+                }
+            }
+            InstallerOption::UpdateSystem => {
+                if option.is_enabled() {
+                    self.request_operation(option.clone()).await?;
+                } else {
+                    self.status_message =
+                        Some((self.text.messages.option_disabled.clone(), StatusType::Fail));
+                }
+            }
+            InstallerOption::Repair => {
+                let Some(marker) = &self.dotfiles_marker else {
+                    return Ok(());
+                };
+                self.repair_plan =
+                    pipeline::plan_repair(&self.profile, marker, &self.step_context()).await;
+                if self.repair_plan.is_empty() {
+                    self.status_message = Some((
+                        self.text.messages.repair_nothing_to_do.clone(),
+                        StatusType::Success,
+                    ));
+                } else {
+                    self.request_operation(InstallerOption::Repair).await?;
+                }
+            }
+            InstallerOption::Recover => {
+                self.recovery_cursor = 0;
+                self.app_state = AppState::RecoverySelect;
+            }
+            InstallerOption::SupportBundle => {
+                self.show_path_input(
+                    PathPurpose::SupportBundle,
+                    "~/".to_string(),
+                    path_input::PathKind::Directory,
+                );
+            }
+            InstallerOption::Exit => {
+                self.request_poweroff().await;
+            }
+            InstallerOption::Custom => self.show_custom_select(),
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn confirm_password(&mut self) -> Result<()> {
+        if let Some(operation) = self.pending_operation.clone() {
+            if !self.password_accepted().await? {
+                return Ok(());
+            }
+            logging::register_password(&self.password_input);
+            // The password is kept until the operation starts, it would be thrown away here
+            if self.gate(&operation).confirms(self.dry_run) {
+                self.show_operation_confirmation(&operation);
+            } else {
+                self.run_operation(&operation).await;
+            }
+        }
+        Ok(())
+    }
+
+    // Checked before anything starts, a wrong password would otherwise only show once the
+    // operation failed. It goes back to the prompt until the attempts run out.
+    async fn password_accepted(&mut self) -> Result<bool> {
+        let Some(backend) = escalation::current()
+            .filter(|backend| backend.password_input() != escalation::PasswordInput::Agent)
+            .filter(|_| !self.dry_run)
+        else {
+            return Ok(true);
+        };
+        let accepted = escalation::validate(backend, &self.password_input).await;
+        if !matches!(accepted, Ok(true)) {
+            escalation::wipe(&mut self.password_input);
+        }
+        if !accepted? {
+            self.password_rejected();
+            return Ok(false);
+        }
+        self.password_failures = 0;
+        Ok(true)
+    }
+
+    fn password_rejected(&mut self) {
+        logging::event!(Level::Warn, "auth", "The password was turned down");
+        // A stored password is not tried again after this
+        self.credential_rejected = true;
+        self.password_failures += 1;
+        let messages = &self.text.messages;
+        let limit = self.settings.privileges.password_attempts;
+        if limit == 0 {
+            self.status_message = Some((messages.password_auth_failed.clone(), StatusType::Error));
+        } else if self.password_failures < limit {
+            self.status_message = Some((
+                format!(
+                    "{} ({} {})",
+                    messages.password_auth_failed,
+                    limit - self.password_failures,
+                    messages.password_attempts_left
+                ),
+                StatusType::Error,
+            ));
+        } else {
+            self.password_failures = 0;
+            self.status_message = Some((
+                messages.password_attempts_exceeded.clone(),
+                StatusType::Error,
+            ));
+            self.hide_password_input();
+        }
+    }
+
+    pub(crate) async fn confirm_action(&mut self) -> Result<()> {
+        let operation = self.pending_operation.take();
+        self.hide_confirmation();
+        if let Some(operation) = operation {
+            self.run_operation(&operation).await;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn run_operation(&mut self, option: &InstallerOption) {
+        // Set again below when the password turns out wrong
+        self.pending_operation = None;
+        let action_description = match option {
+            InstallerOption::Default | InstallerOption::Custom => {
+                self.text.messages.progress_installing.clone()
+            }
+            InstallerOption::Repair => self.text.messages.progress_repairing.clone(),
+            InstallerOption::Recover => self.text.messages.progress_recovering.clone(),
+            InstallerOption::UpdateSystem => self.text.messages.progress_updating.clone(),
+            _ => self.text.messages.processing.clone(),
+        };
+
+        self.app_state = AppState::Processing(action_description.clone());
+
+        // Set up progress type based on action
+        self.progress_type = Some(match option {
+            InstallerOption::Default => ProgressType::Indeterminate,
+            InstallerOption::UpdateSystem => ProgressType::Indeterminate,
+            _ => ProgressType::Indeterminate,
+        });
+
+        self.progress_step = 0;
+        self.step_fraction = None;
+        self.progress_bar_position = 0;
+        self.countdown_remaining = self.text.progress.countdown_seconds;
+        self.action_output.clear();
+        self.checklist.clear();
+        self.dry_run_lines.clear();
+        self.start_ticks();
+
+        self.report = Some(report::RunReport::start(
+            self.option_title(option),
+            self.dry_run,
+        ));
+
+        if self.dry_run && matches!(option, InstallerOption::UpdateSystem) {
+            // Staging an image cannot be tried out, the update finishes with its scenario
+            self.start_simulation(option);
+            return;
+        }
+        let job = match option {
+            InstallerOption::Default => self.install_job(),
+            InstallerOption::Custom => self.custom_job(),
+            InstallerOption::Repair => self.repair_job(),
+            InstallerOption::Recover => self.recover_job(),
+            InstallerOption::UpdateSystem => worker::Job::Update,
+            _ => worker::Job::Nothing,
+        };
+        self.start_worker(option.clone(), job);
+    }
+
+    // Starts the timers of the operation that just began, replacing any earlier ones
+    pub(crate) fn start_ticks(&mut self) {
+        let progress = &self.theme.progress;
+        let mut ticker = ticks::Ticker::new();
+        match self.progress_type {
+            Some(ProgressType::Indeterminate) => {
+                ticker.every(Duration::from_millis(progress.spinner_speed), Tick::Spinner);
+                ticker.every(
+                    Duration::from_millis(progress.progress_bar_speed),
+                    Tick::ProgressBar,
+                );
+            }
+            Some(ProgressType::Determinant(_)) => {
+                ticker.every(Duration::from_secs(1), Tick::Countdown)
+            }
+            None => {}
+        }
+        self.ticker = Some(ticker);
+    }
+
+    // Output lines of the running step and how far along it is
+    pub(crate) fn show_step_progress(&mut self, update: pipeline::StepProgress) {
+        if update.fraction.is_some() {
+            self.step_fraction = update.fraction;
+        }
+        if !update.message.is_empty() && self.action_output.last() != Some(&update.message) {
+            self.action_output.push(update.message);
+        }
+    }
+
+    pub(crate) fn update_progress(&mut self) {
+        while let Ok(update) = self.step_updates.try_recv() {
+            self.show_step_progress(update);
+        }
+        while let Some(tick) = self.ticker.as_mut().and_then(ticks::Ticker::try_next) {
+            match tick {
+                Tick::SimulationDone => {
+                    self.finish_current_operation();
+                    return;
+                }
+                Tick::Spinner => {
+                    self.progress_step =
+                        (self.progress_step + 1) % self.text.messages.spinner_chars.len();
+                }
+                Tick::ProgressBar => self.progress_bar_position += 1,
+                Tick::Countdown => {
+                    if self.countdown_remaining > 0 {
+                        self.countdown_remaining -= 1;
+                    } else {
+                        self.finish_current_operation();
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn finish_current_operation(&mut self) {
+        // Check if we're finishing a determinant action (reboot/poweroff)
+        if let Some(ProgressType::Determinant(_)) = self.progress_type {
+            // Check if this is a poweroff action
+            let is_poweroff = matches!(self.pending_system_action, Some(SystemAction::Poweroff));
+
+            // For dry-run mode
+            if self.dry_run {
+                self.progress_type = None;
+                self.action_output.clear();
+                self.checklist.clear();
+                self.progress_bar_position = 0;
+                self.ticker = None;
+                self.forget_password();
+
+                if is_poweroff {
+                    // For poweroff, quit the app even in dry-run
+                    self.should_quit = true;
+                } else {
+                    // For reboot, return to menu in dry-run
+                    self.app_state = AppState::MainMenu;
+                    self.status_message = Some((
+                        "DRY-RUN: System action simulation complete".to_string(),
+                        StatusType::Success,
+                    ));
+                }
+                return;
+            }
+
+            // Set should_quit to true for both reboot and poweroff
+            // The actual system command execution will happen after the UI loop exits
+            self.should_quit = true;
+            return;
+        }
+
+        // Standard operation completion
+        self.complete_report(&Ok(()));
+        self.progress_type = None;
+        self.app_state = AppState::MainMenu;
+        self.status_message = Some((
+            self.text.messages.operation_success.clone(),
+            StatusType::Success,
+        ));
+        self.action_output.clear();
+        self.checklist.clear();
+        self.progress_bar_position = 0;
+        self.ticker = None;
+        self.forget_password();
+    }
+
+    pub(crate) fn complete_report(&mut self, result: &Result<()>) {
+        match result {
+            Ok(()) => self.set_status(&self.text.messages.operation_success.clone()),
+            Err(e) => self.set_status(&format!("{}: {}", self.text.messages.operation_failed, e)),
+        }
+        if let Some(mut report) = self.report.take() {
+            report.finish(result);
+            let textfile = &self.settings.metrics.textfile;
+            if !textfile.is_empty()
+                && !report.dry_run
+                && let Err(e) = metrics::write_textfile(std::path::Path::new(textfile), &report)
+            {
+                logging::event!(
+                    Level::Warn,
+                    "metrics",
+                    "Could not write {}: {}",
+                    textfile,
+                    e
+                );
+            }
+            match report.save() {
+                Ok(path) => {
+                    logging::event!(
+                        Level::Info,
+                        "report",
+                        "Report written to {}",
+                        path.display()
+                    )
+                }
+                Err(e) => logging::event!(Level::Warn, "report", "Could not write report: {}", e),
+            }
+            self.last_failure = Some(report).filter(report::RunReport::needs_recovery);
+            self.refresh_options();
+        }
+    }
+
+    pub(crate) fn finish_operation(&mut self, operation: &InstallerOption, result: Result<()>) {
+        self.complete_report(&result);
+        match result {
+            Ok(()) if !self.dry_run_lines.is_empty() => {
+                self.progress_type = None;
+                self.status_message = None;
+                self.app_state = AppState::DryRunResult;
+            }
+            // Checks that failed are worth reading before anything else happens
+            Ok(()) if !self.failed_checks.is_empty() => {
+                self.progress_type = None;
+                self.app_state = AppState::MainMenu;
+                self.status_message = Some((
+                    format!(
+                        "{} {}",
+                        self.text.messages.operation_degraded,
+                        self.failed_checks.join(", ")
+                    ),
+                    StatusType::Fail,
+                ));
+            }
+            Ok(()) => {
+                self.progress_type = None;
+                match self.settings.on_success.action(operation) {
+                    SuccessAction::Menu => {
+                        self.app_state = AppState::MainMenu;
+                        self.status_message = Some((
+                            self.text.messages.operation_success.clone(),
+                            StatusType::Success,
+                        ));
+                    }
+                    SuccessAction::AskReboot => {
+                        self.status_message = None;
+                        self.reboot_cursor = 0;
+                        self.app_state = AppState::RebootSelect;
+                        self.arm_confirmation();
+                    }
+                    SuccessAction::Reboot => {
+                        self.start_reboot();
+                        return;
+                    }
+                    SuccessAction::Launch => {
+                        logging::event!(
+                            Level::Info,
+                            "operation",
+                            "Closing to run {}",
+                            self.settings.on_success.launch_command.join(" ")
+                        );
+                        self.pending_launch = true;
+                        self.should_quit = true;
+                    }
+                    SuccessAction::Exit => self.should_quit = true,
+                }
+            }
+            Err(e) => {
+                debug!("Operation failed: {}", logging::redact(&e.to_string()));
+                logging::event!(Level::Error, "operation", "{}", e);
+                self.progress_type = None;
+                match self.settings.on_failure.policy(operation) {
+                    FailurePolicy::ShowError => {
+                        self.failure_error = e.to_string();
+                        self.failed_operation = operation.id();
+                        self.app_state = AppState::Failed;
+                    }
+                    FailurePolicy::Reboot => {
+                        self.start_reboot();
+                        return;
+                    }
+                    FailurePolicy::Poweroff => {
+                        self.start_poweroff();
+                        return;
+                    }
+                    FailurePolicy::RollbackThenReboot => {
+                        self.roll_back_failed_run();
+                        self.start_reboot();
+                        return;
+                    }
+                }
+            }
+        }
+        self.action_output.clear();
+        self.checklist.clear();
+        self.progress_bar_position = 0;
+        self.ticker = None;
+        self.forget_password();
+    }
+
+    // Puts back the files the failed run replaced, before the machine restarts
+    pub(crate) fn roll_back_failed_run(&mut self) {
+        let Some(failed) = self.last_failure.clone() else {
+            return;
+        };
+        let plan = recovery::rollback_plan(
+            &self.profile,
+            &self.recovery_marker(),
+            &failed,
+            &self.step_context(),
+        );
+        match recovery::rollback(&plan) {
+            Ok(()) => logging::event!(
+                Level::Info,
+                "recovery",
+                "Restored {} files after the failure",
+                plan.len()
+            ),
+            Err(e) => logging::event!(Level::Error, "recovery", "Rollback failed: {:#}", e),
+        }
+    }
+
+    pub(crate) async fn show_bug_report(&mut self) {
+        self.bug_report = Some(
+            bug_report::BugReport::compose(
+                &self.settings.logging,
+                &self.settings.bug_report,
+                self.failed_operation,
+                &self.failure_error,
+            )
+            .await,
+        );
+        self.bug_report_cursor = 0;
+        self.clear_status();
+        self.app_state = AppState::BugReport;
+    }
+
+    pub(crate) async fn select_bug_report_action(&mut self) {
+        match self.bug_report_actions()[self.bug_report_cursor] {
+            #[cfg(feature = "report-upload")]
+            BugReportAction::Send => {
+                let Some(report) = self.bug_report.clone() else {
+                    return;
+                };
+                match report
+                    .upload(&self.settings.bug_report, &self.settings.network)
+                    .await
+                {
+                    Ok(()) => {
+                        self.app_state = AppState::Failed;
+                        self.status_message = Some((
+                            self.text.messages.bug_report_sent.clone(),
+                            StatusType::Success,
+                        ));
+                    }
+                    Err(e) => {
+                        logging::event!(Level::Error, "bug-report", "{:#}", e);
+                        self.status_message = Some((format!("Error: {:#}", e), StatusType::Error));
+                    }
+                }
+            }
+            BugReportAction::Save => self.show_path_input(
+                PathPurpose::BugReport,
+                "~/sparrow-bug-report.md".to_string(),
+                path_input::PathKind::NewFile,
+            ),
+        }
+    }
+
+    pub(crate) fn dismiss_failure(&mut self, recover: bool) {
+        let error = std::mem::take(&mut self.failure_error);
+        self.status_message = Some((format!("Error: {}", error), StatusType::Error));
+        if recover && self.last_failure.is_some() {
+            self.recovery_cursor = 0;
+            self.app_state = AppState::RecoverySelect;
+        } else {
+            self.app_state = AppState::MainMenu;
+        }
+    }
+
+    pub(crate) fn start_reboot(&mut self) {
+        self.app_state = AppState::Processing(self.text.messages.progress_rebooting.clone());
+        self.progress_type = Some(ProgressType::Determinant(
+            self.text.progress.countdown_seconds,
+        ));
+        self.countdown_remaining = self.text.progress.countdown_seconds;
+        self.progress_step = 0;
+        self.step_fraction = None;
+        self.progress_bar_position = 0;
+        self.action_output.clear();
+        self.checklist.clear();
+        self.start_ticks();
+        self.pending_system_action = Some(SystemAction::Reboot);
+    }
+
+    // Powers off unless other people are logged in, then asks first
+    pub(crate) async fn request_poweroff(&mut self) {
+        self.other_users = power::other_users().await;
+        if self.other_users.is_empty() {
+            self.start_poweroff();
+        } else {
+            logging::event!(
+                Level::Info,
+                "power",
+                "Power off held, other users are logged in: {}",
+                self.other_users.join(", ")
+            );
+            self.clear_status();
+            self.app_state = AppState::SessionsWarning;
+            self.arm_confirmation();
+        }
+    }
+
+    pub(crate) fn start_poweroff(&mut self) {
+        self.app_state = AppState::Processing(self.text.messages.progress_poweroff.clone());
+        self.progress_type = Some(ProgressType::Determinant(
+            self.text.progress.countdown_seconds,
+        ));
+        self.countdown_remaining = self.text.progress.countdown_seconds;
+        self.progress_step = 0;
+        self.step_fraction = None;
+        self.progress_bar_position = 0;
+        self.action_output.clear();
+        self.checklist.clear();
+        self.start_ticks();
+        self.pending_system_action = Some(SystemAction::Poweroff);
+    }
+}
+
+// Bracketed paste hands over the whole text at once, only text fields take it
+pub(crate) fn handle_paste(app: &mut App, text: &str) {
+    if app.tour.is_some() {
+        return;
+    }
+    if app.app_state == AppState::PasswordInput {
+        for c in typing::pasted(text) {
+            typing::push(&mut app.password_input, c);
+        }
+    } else if app.app_state == AppState::PathInput
+        && let Some(input) = app.path_input.as_mut()
+    {
+        for c in typing::pasted(text) {
+            input.push(c);
+        }
+    }
+}
+
+pub(crate) async fn handle_key(app: &mut App, key: KeyEvent) {
+    // With kitty's keyboard protocol or on Windows a key also reports its release and
+    // repeats, each would act again
+    if key.kind != KeyEventKind::Press {
+        return;
+    }
+    let code = keymap::code(&key);
+
+    // The tour sets up the screens itself, its keys only move between stops
+    if app.tour.is_some() {
+        match code {
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char(' ') => tour::advance(app, 1).await,
+            KeyCode::Left | KeyCode::Backspace => tour::advance(app, -1).await,
+            KeyCode::Esc | KeyCode::Char('q') => tour::end(app),
+            _ => {}
+        }
+        return;
+    }
+
+    // A bouncing key from the previous screen must not answer the dialog
+    if app.awaits_confirmation()
+        && matches!(code, KeyCode::Enter | KeyCode::Char('y'))
+        && tokio::time::Instant::now() < app.confirm_armed_at
+    {
+        logging::event!(
+            Level::Debug,
+            "input",
+            "Ignored {:?} before the dialog was ready for it",
+            key.code
+        );
+        return;
+    }
+
+    if app.app_state == AppState::PasswordInput {
+        match code {
+            KeyCode::Enter => {
+                if !app.password_input.is_empty() {
+                    if let Err(e) = app.confirm_password().await {
+                        app.status_message = Some((format!("Error: {}", e), StatusType::Error));
+                    }
+                } else {
+                    app.status_message = Some((
+                        app.text.messages.password_empty_error.clone(),
+                        StatusType::Error,
+                    ));
+                }
+            }
+            KeyCode::Esc => {
+                app.hide_password_input();
+            }
+            KeyCode::Tab => {
+                app.show_password = !app.show_password;
+            }
+            KeyCode::Backspace => typing::pop(&mut app.password_input),
+            _ => {
+                if let Some(c) = typing::typed(&key) {
+                    typing::push(&mut app.password_input, c);
+                }
+            }
+        }
+    } else if app.app_state == AppState::UserSelect {
+        match code {
+            KeyCode::Down => app.move_user_cursor(true),
+            KeyCode::Up => app.move_user_cursor(false),
+            KeyCode::Char(' ') => app.toggle_user(),
+            KeyCode::Enter => app.confirm_users(),
+            KeyCode::Esc => app.app_state = AppState::MainMenu,
+            _ => {}
+        }
+    } else if app.app_state == AppState::WallpaperSelect {
+        match code {
+            KeyCode::Down => app.move_wallpaper_cursor(true),
+            KeyCode::Up => app.move_wallpaper_cursor(false),
+            KeyCode::Char(' ') => app.toggle_wallpaper(),
+            KeyCode::Enter => app.confirm_wallpapers(),
+            KeyCode::Esc => app.hide_wallpaper_select(),
+            _ => {}
+        }
+    } else if app.app_state == AppState::ComponentSelect {
+        match code {
+            KeyCode::Down => app.move_component_cursor(true),
+            KeyCode::Up => app.move_component_cursor(false),
+            KeyCode::Char(' ') => app.toggle_component(),
+            KeyCode::Enter => app.confirm_components(),
+            KeyCode::Esc => app.app_state = AppState::MainMenu,
+            _ => {}
+        }
+    } else if app.app_state == AppState::CustomSelect {
+        match code {
+            KeyCode::Down => app.move_custom_cursor(true),
+            KeyCode::Up => app.move_custom_cursor(false),
+            KeyCode::Char(' ') => app.toggle_custom(),
+            KeyCode::Enter => {
+                if let Err(e) = app.confirm_custom().await {
+                    app.status_message = Some((format!("Error: {}", e), StatusType::Error));
+                }
+            }
+            KeyCode::Esc => app.app_state = AppState::MainMenu,
+            _ => {}
+        }
+    } else if app.app_state == AppState::MonitorSelect {
+        match code {
+            KeyCode::Down => app.move_monitor_cursor(true),
+            KeyCode::Up => app.move_monitor_cursor(false),
+            KeyCode::Right => app.change_monitor_value(true),
+            KeyCode::Left => app.change_monitor_value(false),
+            KeyCode::Enter => app.confirm_monitors(),
+            KeyCode::Esc => app.app_state = AppState::MainMenu,
+            _ => {}
+        }
+    } else if app.app_state == AppState::Locked {
+        // Read-only, the only way out is quitting without touching the system
+        if matches!(code, KeyCode::Char('q') | KeyCode::Esc) {
+            app.should_quit = true;
+        }
+    } else if app.app_state == AppState::RecoverySelect {
+        match code {
+            KeyCode::Down => app.move_recovery_cursor(true),
+            KeyCode::Up => app.move_recovery_cursor(false),
+            KeyCode::Enter => app.select_recovery_action().await,
+            KeyCode::Esc => app.app_state = AppState::MainMenu,
+            _ => {}
+        }
+    } else if app.app_state == AppState::RebootSelect {
+        match code {
+            KeyCode::Down => app.move_reboot_cursor(true),
+            KeyCode::Up => app.move_reboot_cursor(false),
+            KeyCode::Enter => app.select_reboot_choice().await,
+            _ => {}
+        }
+    } else if app.app_state == AppState::SessionsWarning {
+        match code {
+            KeyCode::Char('y') => app.start_poweroff(),
+            KeyCode::Esc | KeyCode::Char('n') => app.app_state = AppState::MainMenu,
+            _ => {}
+        }
+    } else if app.app_state == AppState::Failed {
+        match code {
+            KeyCode::Char('r') => app.dismiss_failure(true),
+            KeyCode::Char('b') => app.show_bug_report().await,
+            KeyCode::Enter | KeyCode::Esc => app.dismiss_failure(false),
+            _ => {}
+        }
+    } else if app.app_state == AppState::BugReport {
+        let count = app.bug_report_actions().len();
+        match code {
+            KeyCode::Down => {
+                app.bug_report_cursor = cycle_index(app.bug_report_cursor, count, true)
+            }
+            KeyCode::Up => app.bug_report_cursor = cycle_index(app.bug_report_cursor, count, false),
+            KeyCode::Enter => app.select_bug_report_action().await,
+            KeyCode::Esc => {
+                app.clear_status();
+                app.app_state = AppState::Failed;
+            }
+            _ => {}
+        }
+    } else if app.app_state == AppState::VolumeSelect {
+        match code {
+            KeyCode::Down => app.move_volume_cursor(true),
+            KeyCode::Up => app.move_volume_cursor(false),
+            KeyCode::Enter => app.select_volume(),
+            KeyCode::Char('r') => app.show_volumes().await,
+            KeyCode::Esc => app.app_state = AppState::RecoverySelect,
+            _ => {}
+        }
+    } else if app.app_state == AppState::PathInput
+        && let Some(input) = app.path_input.as_mut()
+    {
+        match code {
+            KeyCode::Tab => input.complete(),
+            KeyCode::Backspace => input.pop(),
+            KeyCode::Enter => app.confirm_path().await,
+            KeyCode::Esc => app.hide_path_input(),
+            _ => {
+                if let Some(c) = typing::typed(&key) {
+                    input.push(c);
+                }
+            }
+        }
+    } else if app.app_state == AppState::Plan {
+        match code {
+            KeyCode::Down => app.scroll_plan(1),
+            KeyCode::Up => app.scroll_plan(-1),
+            KeyCode::PageDown => app.scroll_plan(LOG_PAGE as isize),
+            KeyCode::PageUp => app.scroll_plan(-(LOG_PAGE as isize)),
+            KeyCode::Enter => {
+                if let Err(e) = app.request_operation(InstallerOption::Default).await {
+                    app.status_message = Some((format!("Error: {}", e), StatusType::Error));
+                }
+            }
+            KeyCode::Esc => app.app_state = AppState::MainMenu,
+            _ => {}
+        }
+    } else if app.app_state == AppState::DryRunResult {
+        match code {
+            KeyCode::Down => app.scroll_dry_run(1),
+            KeyCode::Up => app.scroll_dry_run(-1),
+            KeyCode::PageDown => app.scroll_dry_run(LOG_PAGE as isize),
+            KeyCode::PageUp => app.scroll_dry_run(-(LOG_PAGE as isize)),
+            KeyCode::Enter | KeyCode::Esc => {
+                app.dry_run_lines.clear();
+                app.app_state = AppState::MainMenu;
+            }
+            _ => {}
+        }
+    } else if app.app_state == AppState::LogView {
+        match code {
+            KeyCode::Char('s') => app.show_path_input(
+                PathPurpose::SaveLog,
+                format!("~/{}", app.settings.logging.file_name),
+                path_input::PathKind::NewFile,
+            ),
+            KeyCode::Down => app.scroll_log(1),
+            KeyCode::Up => app.scroll_log(-1),
+            KeyCode::PageDown => app.scroll_log(LOG_PAGE as isize),
+            KeyCode::PageUp => app.scroll_log(-(LOG_PAGE as isize)),
+            KeyCode::Esc => app.app_state = AppState::RecoverySelect,
+            _ => {}
+        }
+    } else if app.show_confirmation {
+        match code {
+            KeyCode::Enter => {
+                if let Err(e) = app.confirm_action().await {
+                    app.status_message = Some((format!("Error: {}", e), StatusType::Error));
+                }
+            }
+            KeyCode::Char('y') => {
+                if let Err(e) = app.confirm_action().await {
+                    app.status_message = Some((format!("Error: {}", e), StatusType::Error));
+                }
+            }
+            KeyCode::Char('d') => app.toggle_details(),
+            KeyCode::Esc | KeyCode::Char('n') => {
+                app.hide_confirmation();
+                // A password given before the dialog is not kept for later
+                app.forget_password();
+            }
+            _ => {}
+        }
+    } else if app.progress_type.is_some() {
+        // Prevent ESC during processing operations (installations/updates)
+        if code == KeyCode::Esc {
+            // Only allow ESC cancellation during dry-run simulations
+            if app.dry_run {
+                app.progress_type = None;
+                app.app_state = AppState::MainMenu;
+                app.status_message = Some(("Simulation cancelled.".to_string(), StatusType::Error));
+                app.action_output.clear();
+                app.ticker = None;
+                app.worker = None;
+            }
+            // For actual installations/updates, ESC is ignored
+        }
+    } else {
+        match code {
+            KeyCode::Char('q') => {
+                app.request_poweroff().await;
+            }
+            KeyCode::Down => {
+                app.next();
+                app.clear_status();
+            }
+            KeyCode::Up => {
+                app.previous();
+                app.clear_status();
+            }
+            KeyCode::PageDown => app.scroll_document(LOG_PAGE as isize),
+            KeyCode::PageUp => app.scroll_document(-(LOG_PAGE as isize)),
+            KeyCode::Enter => {
+                if let Err(e) = app.execute_option().await {
+                    app.status_message = Some((format!("Error: {}", e), StatusType::Error));
+                }
+            }
+            KeyCode::Char(digit @ '1'..='9') if app.theme.ui.show_numbers => {
+                // The number shown next to the entry, picked and activated at once
+                let index = digit as usize - '1' as usize;
+                if index < app.options.len() {
+                    app.selected = index;
+                    app.clear_status();
+                    if let Err(e) = app.execute_option().await {
+                        app.status_message = Some((format!("Error: {}", e), StatusType::Error));
+                    }
+                }
+            }
+            KeyCode::Esc => {
+                app.clear_status();
+            }
+            _ => {}
+        }
+    }
+}
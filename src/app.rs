@@ -0,0 +1,1619 @@
+use crate::{
+    Gate, SessionEnd, SettingsConfig, TextConfig, ThemeConfig, bug_report, colors, console,
+    control, escalation, health, load_backdrop, load_documents, lock, logging, monitors, notify,
+    path_input, pipeline, plan, progress, recovery, removable, report, state, steps, styles, ticks,
+    tools, tour, utf8_locale, widgets, worker,
+};
+use anyhow::Result;
+use cli_log::*;
+use ratatui::text::Text;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+pub(crate) fn menu_options(
+    dotfiles_installed: bool,
+    recoverable: bool,
+    updatable: bool,
+) -> Vec<InstallerOption> {
+    let mut options = Vec::new();
+    if recoverable {
+        options.push(InstallerOption::Recover);
+    }
+    options.push(InstallerOption::Default);
+    if dotfiles_installed {
+        options.push(InstallerOption::Repair);
+    }
+    options.push(InstallerOption::Custom);
+    if updatable {
+        options.push(InstallerOption::UpdateSystem);
+    }
+    options.extend([InstallerOption::SupportBundle, InstallerOption::Exit]);
+    options
+}
+
+// Next or previous position in a list of `count` entries, wrapping around at both ends
+pub(crate) fn cycle_index(current: usize, count: usize, forward: bool) -> usize {
+    if count == 0 {
+        0
+    } else if forward {
+        (current + 1) % count
+    } else {
+        (current + count - 1) % count
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum InstallerOption {
+    Recover,
+    Default,
+    Repair,
+    Custom,
+    UpdateSystem,
+    SupportBundle,
+    Exit,
+}
+
+impl InstallerOption {
+    pub(crate) fn title<'a>(&self, text_config: &'a TextConfig) -> &'a str {
+        match self {
+            InstallerOption::Default => &text_config.ui_text.default_title,
+            InstallerOption::Recover => &text_config.ui_text.recover_title,
+            InstallerOption::Repair => &text_config.ui_text.repair_title,
+            InstallerOption::Custom => &text_config.ui_text.custom_title,
+            InstallerOption::UpdateSystem => &text_config.ui_text.update_title,
+            InstallerOption::SupportBundle => &text_config.ui_text.support_title,
+            InstallerOption::Exit => &text_config.ui_text.exit_title,
+        }
+    }
+
+    pub(crate) fn description<'a>(&self, text_config: &'a TextConfig) -> &'a str {
+        match self {
+            InstallerOption::Default => &text_config.ui_text.default_description,
+            InstallerOption::Recover => &text_config.ui_text.recover_description,
+            InstallerOption::Repair => &text_config.ui_text.repair_description,
+            InstallerOption::Custom => &text_config.ui_text.custom_description,
+            InstallerOption::UpdateSystem => &text_config.ui_text.update_description,
+            InstallerOption::SupportBundle => &text_config.ui_text.support_description,
+            InstallerOption::Exit => &text_config.ui_text.exit_description,
+        }
+    }
+
+    // Every option can be picked for now, disabled ones are still drawn and announced as such
+    pub(crate) fn is_enabled(&self) -> bool {
+        true
+    }
+
+    // Stable name for reports, independent of the text configuration
+    pub(crate) fn id(&self) -> &'static str {
+        match self {
+            InstallerOption::Default => "install",
+            InstallerOption::Recover => "recover",
+            InstallerOption::Repair => "repair",
+            InstallerOption::Custom => "custom",
+            InstallerOption::UpdateSystem => "update",
+            InstallerOption::SupportBundle => "support-bundle",
+            InstallerOption::Exit => "exit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum AppState {
+    MainMenu,
+    Confirmation,
+    PasswordInput,
+    UserSelect,
+    WallpaperSelect,
+    ComponentSelect,
+    // The custom install's pick of profile steps
+    CustomSelect,
+    MonitorSelect,
+    // Another instance holds the lock, only its status is shown
+    Locked,
+    RecoverySelect,
+    LogView,
+    // A path is being typed, see path_purpose
+    PathInput,
+    // Removable drives to save the report on
+    VolumeSelect,
+    // A system update was staged, when to reboot into it
+    RebootSelect,
+    // Other people are logged in, powering off needs a second confirmation
+    SessionsWarning,
+    // An operation failed, its error stays up until dismissed
+    Failed,
+    // A bug report about the failure, before it is sent or saved
+    BugReport,
+    // What the install will run and leave out, before it is confirmed
+    Plan,
+    // What a dry run would have changed
+    DryRunResult,
+    Processing(String), // Processing with action description
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RecoveryAction {
+    Resume,
+    Rollback,
+    ViewLog,
+    Export,
+}
+
+pub(crate) const RECOVERY_ACTIONS: [RecoveryAction; 4] = [
+    RecoveryAction::Resume,
+    RecoveryAction::Rollback,
+    RecoveryAction::ViewLog,
+    RecoveryAction::Export,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BugReportAction {
+    #[cfg(feature = "report-upload")]
+    Send,
+    Save,
+}
+
+// What a path typed into the path prompt is for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PathPurpose {
+    SaveLog,
+    // A folder to copy the log, report and crash dump into
+    Export,
+    // A folder to write the support bundle into
+    SupportBundle,
+    // The file a bug report is written to
+    BugReport,
+}
+
+pub(crate) const REBOOT_CHOICES: [report::RebootChoice; 3] = [
+    report::RebootChoice::Now,
+    report::RebootChoice::Scheduled,
+    report::RebootChoice::Later,
+];
+
+// Lines moved by PgUp/PgDn in the log view
+pub(crate) const LOG_PAGE: usize = 10;
+pub(crate) const STATUS_FADE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub(crate) enum ProgressType {
+    Indeterminate,
+    Determinant(u16), // countdown seconds
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum StatusType {
+    Success,
+    Error,
+    Fail,
+}
+
+pub(crate) struct App {
+    pub(crate) options: Vec<InstallerOption>,
+    pub(crate) selected: usize,
+    pub(crate) should_quit: bool,
+    // For the session limit of shared machines
+    pub(crate) session_started: tokio::time::Instant,
+    // Seconds left once the session limit's warning shows
+    pub(crate) session_remaining: Option<u64>,
+    pub(crate) dry_run: bool,
+    // Dry runs try the steps that fit in a bwrap overlay, see sandbox.rs
+    pub(crate) sandbox: bool,
+    pub(crate) status_message: Option<(String, StatusType)>,
+    // The message on screen and since when, for clearing it after a while
+    pub(crate) status_shown: Option<(String, tokio::time::Instant)>,
+    pub(crate) show_confirmation: bool,
+    pub(crate) confirmation_message: String,
+    // The commands behind the dialog on screen, listed while expanded
+    pub(crate) confirmation_details: Vec<String>,
+    pub(crate) show_details: bool,
+    pub(crate) app_state: AppState,
+    pub(crate) theme: ThemeConfig,
+    pub(crate) styles: styles::Styles,
+    pub(crate) color_support: colors::ColorSupport,
+    // Only ASCII glyphs, the locale would not draw the rest
+    pub(crate) ascii: bool,
+    pub(crate) widgets: widgets::WidgetCache,
+    // The theme's ANSI art, in the colors the terminal can show
+    pub(crate) backdrop: Option<Text<'static>>,
+    pub(crate) text: TextConfig,
+    pub(crate) settings: SettingsConfig,
+    pub(crate) progress_type: Option<ProgressType>,
+    pub(crate) progress_step: usize,
+    pub(crate) progress_bar_position: usize,
+    pub(crate) countdown_remaining: u16,
+    pub(crate) action_output: Vec<String>,
+    // Set once the running step reports how far along it is
+    pub(crate) step_fraction: Option<f32>,
+    // Progress of pipeline steps and the dry-run simulation alike
+    pub(crate) step_updates: UnboundedReceiver<pipeline::StepProgress>,
+    // The interface, the log, systemd, the console and attached viewers
+    pub(crate) progress: progress::FanOut,
+    // The operation running in the background
+    pub(crate) worker: Option<worker::Worker>,
+    #[cfg(feature = "simulator")]
+    pub(crate) scenario: crate::simulation::Scenario,
+    // Spinner, progress bar, countdown and dry-run timeout of the running operation
+    pub(crate) ticker: Option<ticks::Ticker>,
+    pub(crate) password_input: String,
+    pub(crate) pending_operation: Option<InstallerOption>,
+    pub(crate) show_password: bool,
+    pub(crate) pending_system_action: Option<SystemAction>,
+    // The on_success launch command runs once the interface has closed
+    pub(crate) pending_launch: bool,
+    // Set once sudo turned down the stored password, the prompt asks from then on
+    pub(crate) credential_rejected: bool,
+    // Wrong passwords in a row, the prompt gives up at privileges.password_attempts
+    pub(crate) password_failures: u32,
+    pub(crate) report: Option<report::RunReport>,
+    pub(crate) profile: pipeline::ProfileConfig,
+    pub(crate) dotfiles_marker: Option<state::InstallMarker>,
+    pub(crate) dotfiles_outdated: bool,
+    pub(crate) repair_plan: Vec<pipeline::RepairItem>,
+    pub(crate) checklist: Vec<pipeline::ChecklistItem>,
+    pub(crate) variables: BTreeMap<String, String>,
+    pub(crate) wallpaper_cursor: usize,
+    pub(crate) wallpaper_picks: Vec<bool>,
+    pub(crate) component_cursor: usize,
+    // Parallel to the profile's opt-in steps
+    pub(crate) component_picks: Vec<bool>,
+    pub(crate) custom_cursor: usize,
+    // Parallel to all of the profile's steps
+    pub(crate) custom_picks: Vec<bool>,
+    pub(crate) monitors: Vec<monitors::Monitor>,
+    pub(crate) monitor_cursor: usize,
+    // Accounts offered when root installs on a machine with several people
+    pub(crate) available_users: Vec<pipeline::TargetUser>,
+    pub(crate) user_picks: Vec<bool>,
+    pub(crate) user_cursor: usize,
+    // Picked accounts, empty when installing for the invoking user only
+    pub(crate) target_users: Vec<pipeline::TargetUser>,
+    // Choices made on the setup screens, listed in the final confirmation
+    pub(crate) wizard_summary: Vec<String>,
+    pub(crate) failed_checks: Vec<String>,
+    // Report of the last install when it failed or never finished
+    pub(crate) last_failure: Option<report::RunReport>,
+    pub(crate) recovery_cursor: usize,
+    pub(crate) recovery_action: Option<RecoveryAction>,
+    pub(crate) reboot_cursor: usize,
+    // Until then the confirmation dialog on screen ignores Enter and y
+    pub(crate) confirm_armed_at: tokio::time::Instant,
+    // Users whose sessions a power off would end
+    pub(crate) other_users: Vec<String>,
+    // Error shown by the Failed screen
+    pub(crate) failure_error: String,
+    // The operation that failed, as `InstallerOption::id` names it
+    pub(crate) failed_operation: &'static str,
+    pub(crate) bug_report: Option<bug_report::BugReport>,
+    pub(crate) bug_report_cursor: usize,
+    pub(crate) rollback_plan: Vec<recovery::Restore>,
+    pub(crate) log_lines: Vec<String>,
+    // Latest findings of the background health checks, shown above the menu
+    pub(crate) health: Vec<health::Issue>,
+    pub(crate) health_rx: Option<tokio::sync::watch::Receiver<Vec<health::Issue>>>,
+    pub(crate) log_scroll: usize,
+    pub(crate) plan_lines: Vec<String>,
+    pub(crate) plan_scroll: usize,
+    pub(crate) dry_run_lines: Vec<String>,
+    pub(crate) dry_run_scroll: usize,
+    // Description documents by option id, and how far the one on screen is scrolled
+    pub(crate) documents: BTreeMap<String, String>,
+    pub(crate) document_scroll: usize,
+    pub(crate) path_input: Option<path_input::PathInput>,
+    pub(crate) path_purpose: PathPurpose,
+    pub(crate) volumes: Vec<removable::Volume>,
+    pub(crate) volume_cursor: usize,
+    pub(crate) instance_lock: Option<lock::InstanceLock>,
+    pub(crate) lock_holder: Option<lock::LockOwner>,
+    pub(crate) last_lock_check: Instant,
+    // Mirrors this instance to `--attach` viewers while it holds the lock
+    pub(crate) control: Option<Arc<control::ControlServer>>,
+    pub(crate) last_status: String,
+    // This instance is only a viewer of another one
+    pub(crate) attached: bool,
+    // Set while --tour walks through the screens
+    pub(crate) tour: Option<tour::Tour>,
+    // Set up without the interface, shown as a badge when attached to it
+    pub(crate) unattended: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum SystemAction {
+    Reboot,
+    Poweroff,
+}
+
+impl App {
+    pub(crate) fn new(dry_run: bool, settings: SettingsConfig) -> Result<Self> {
+        escalation::prefer(settings.privileges.escalation);
+        let theme = ThemeConfig::load()?;
+        let text = TextConfig::load()?;
+        let profile = pipeline::ProfileConfig::load()?;
+        let status_message = (!profile.ignored.is_empty()).then(|| {
+            let names: Vec<String> = profile
+                .ignored
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            (
+                format!("{} {}", text.messages.drop_ins_ignored, names.join(", ")),
+                StatusType::Error,
+            )
+        });
+        let dotfiles_marker = state::InstallMarker::load();
+        let dotfiles_outdated = dotfiles_marker
+            .as_ref()
+            .is_some_and(|marker| marker.is_outdated(&profile));
+        let wallpaper_picks = profile.wallpapers.iter().map(|pack| pack.default).collect();
+        let component_picks = profile.optional_steps().map(|_| false).collect();
+        let last_failure = report::RunReport::load().filter(report::RunReport::needs_recovery);
+        let (step_updates_tx, step_updates) = unbounded_channel();
+        let color_support = colors::detect();
+        let documents = load_documents(&settings.documents);
+        // Every status and step update goes to all of these at once
+        let progress = progress::FanOut::default();
+        progress.add(progress::ChannelSink(step_updates_tx));
+        progress.add(progress::LogSink::default());
+        progress.add(progress::NotifySink);
+        if let Some(console) = console::open(&settings.console) {
+            progress.add(console);
+        }
+
+        Ok(Self {
+            options: menu_options(
+                dotfiles_marker.is_some(),
+                last_failure.is_some(),
+                dry_run || tools::find("bootc").is_some(),
+            ),
+            selected: 0,
+            should_quit: false,
+            session_started: tokio::time::Instant::now(),
+            session_remaining: None,
+            dry_run,
+            sandbox: false,
+            status_message,
+            status_shown: None,
+            show_confirmation: false,
+            confirmation_message: String::new(),
+            confirmation_details: Vec::new(),
+            show_details: false,
+            app_state: AppState::MainMenu,
+            styles: styles::Styles::new(&theme, color_support, dry_run),
+            color_support,
+            ascii: theme.ui.ascii || !utf8_locale(),
+            widgets: widgets::WidgetCache::default(),
+            backdrop: load_backdrop(&theme.backdrop, color_support),
+            theme,
+            text,
+            settings,
+            progress_type: None,
+            progress_step: 0,
+            progress_bar_position: 0,
+            countdown_remaining: 0,
+            action_output: Vec::new(),
+            step_fraction: None,
+            step_updates,
+            progress,
+            worker: None,
+            #[cfg(feature = "simulator")]
+            scenario: crate::simulation::Scenario::load()?,
+            ticker: None,
+            password_input: String::new(),
+            pending_operation: None,
+            show_password: false,
+            pending_system_action: None,
+            pending_launch: false,
+            credential_rejected: false,
+            password_failures: 0,
+            report: None,
+            profile,
+            dotfiles_marker,
+            dotfiles_outdated,
+            repair_plan: Vec::new(),
+            checklist: Vec::new(),
+            variables: pipeline::system_variables(),
+            wallpaper_cursor: 0,
+            wallpaper_picks,
+            component_cursor: 0,
+            component_picks,
+            custom_cursor: 0,
+            custom_picks: Vec::new(),
+            monitors: Vec::new(),
+            monitor_cursor: 0,
+            available_users: Vec::new(),
+            user_picks: Vec::new(),
+            user_cursor: 0,
+            target_users: Vec::new(),
+            wizard_summary: Vec::new(),
+            failed_checks: Vec::new(),
+            last_failure,
+            recovery_cursor: 0,
+            recovery_action: None,
+            reboot_cursor: 0,
+            confirm_armed_at: tokio::time::Instant::now(),
+            other_users: Vec::new(),
+            failure_error: String::new(),
+            failed_operation: "",
+            bug_report: None,
+            bug_report_cursor: 0,
+            rollback_plan: Vec::new(),
+            log_lines: Vec::new(),
+            health: Vec::new(),
+            health_rx: None,
+            log_scroll: 0,
+            plan_lines: Vec::new(),
+            plan_scroll: 0,
+            dry_run_lines: Vec::new(),
+            dry_run_scroll: 0,
+            documents,
+            document_scroll: 0,
+            path_input: None,
+            path_purpose: PathPurpose::SaveLog,
+            volumes: Vec::new(),
+            volume_cursor: 0,
+            instance_lock: None,
+            lock_holder: None,
+            last_lock_check: Instant::now(),
+            control: None,
+            last_status: String::new(),
+            attached: false,
+            tour: None,
+            unattended: false,
+        })
+    }
+
+    // Shown by `systemctl status` and to other instances waiting on the lock
+    pub(crate) fn set_status(&mut self, message: &str) {
+        self.progress.status(message);
+        if let Some(lock) = self.instance_lock.as_mut() {
+            lock.set_status(message);
+        }
+        self.last_status = message.to_string();
+        self.publish();
+    }
+
+    pub(crate) fn publish(&self) {
+        let Some(control) = &self.control else {
+            return;
+        };
+        let title = match &self.app_state {
+            AppState::Processing(action) => action.clone(),
+            _ => self.get_title_text(),
+        };
+        control.publish(control::Snapshot {
+            title,
+            status: self.last_status.clone(),
+            checklist: self.checklist.clone(),
+            output: self.action_output.clone(),
+            unattended: self.unattended,
+        });
+    }
+
+    pub(crate) fn take_lock(&mut self, mode: &str) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        match lock::acquire(mode)? {
+            lock::LockState::Acquired(lock) => {
+                self.instance_lock = Some(lock);
+                self.lock_holder = None;
+                match control::serve() {
+                    Ok(server) => {
+                        let server = Arc::new(server);
+                        self.progress.add(server.sink());
+                        self.control = Some(server);
+                    }
+                    Err(e) => logging::event!(Level::Warn, "control", "{}", e),
+                }
+                if self.app_state == AppState::Locked {
+                    self.app_state = AppState::MainMenu;
+                }
+            }
+            lock::LockState::Held(path, owner) => {
+                if self.app_state != AppState::Locked {
+                    logging::event!(
+                        Level::Warn,
+                        "lock",
+                        "{} is held by {}",
+                        path.display(),
+                        owner.as_ref().map_or_else(
+                            || "an unknown process".to_string(),
+                            |o| o.pid.to_string()
+                        )
+                    );
+                }
+                self.lock_holder = owner;
+                self.app_state = AppState::Locked;
+            }
+        }
+        Ok(())
+    }
+
+    // While locked out, follow the other instance and take over once it exits
+    pub(crate) fn check_lock(&mut self) {
+        if self.last_lock_check.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+        self.last_lock_check = Instant::now();
+        if let Err(e) = self.take_lock("interactive") {
+            logging::event!(
+                Level::Warn,
+                "lock",
+                "Cannot check the installer lock: {}",
+                e
+            );
+        }
+    }
+
+    pub(crate) fn refresh_options(&mut self) {
+        // Images without bootc cannot be updated from here, a dry run shows the option anyway
+        self.options = menu_options(
+            self.dotfiles_marker.is_some(),
+            self.last_failure.is_some(),
+            self.dry_run || tools::find("bootc").is_some(),
+        );
+        let menu = &self.settings.menu;
+        self.options.retain(|option| {
+            *option == InstallerOption::Exit
+                || menu
+                    .get(option.id())
+                    .is_none_or(|entry| entry.offered(option.id(), std::env::consts::ARCH))
+        });
+        self.selected = self.selected.min(self.options.len() - 1);
+    }
+
+    pub(crate) fn next(&mut self) {
+        self.selected = (self.selected + 1) % self.options.len();
+        self.document_scroll = 0;
+    }
+
+    pub(crate) fn previous(&mut self) {
+        self.selected = if self.selected == 0 {
+            self.options.len() - 1
+        } else {
+            self.selected - 1
+        };
+        self.document_scroll = 0;
+    }
+
+    pub(crate) fn option_document(&self, option: &InstallerOption) -> Option<&str> {
+        self.documents.get(option.id()).map(String::as_str)
+    }
+
+    pub(crate) fn scroll_document(&mut self, lines: isize) {
+        let last = self
+            .options
+            .get(self.selected)
+            .and_then(|option| self.option_document(option))
+            .map_or(0, |document| document.lines().count().saturating_sub(1));
+        self.document_scroll = self.document_scroll.saturating_add_signed(lines).min(last);
+    }
+
+    pub(crate) fn option_title(&self, option: &InstallerOption) -> &str {
+        match option {
+            InstallerOption::Default if self.dotfiles_marker.is_some() => {
+                &self.text.ui_text.reinstall_title
+            }
+            _ => option.title(&self.text),
+        }
+    }
+
+    // Icons outside ASCII are left out where the terminal cannot be trusted to draw them
+    pub(crate) fn option_icon(&self, option: &InstallerOption) -> Option<&str> {
+        self.theme
+            .icons
+            .get(option.id())
+            .map(String::as_str)
+            .filter(|icon| !icon.is_empty() && (!self.ascii || icon.is_ascii()))
+    }
+
+    pub(crate) fn option_description(&self, option: &InstallerOption) -> &str {
+        match option {
+            InstallerOption::Default if self.dotfiles_outdated => {
+                &self.text.ui_text.reinstall_outdated_description
+            }
+            InstallerOption::Default if self.dotfiles_marker.is_some() => {
+                &self.text.ui_text.reinstall_description
+            }
+            _ => option.description(&self.text),
+        }
+    }
+
+    pub(crate) fn take_interactive_lock(&mut self) {
+        if let Err(e) = self.take_lock("interactive") {
+            warn!("Running without the installer lock: {}", e);
+        }
+        notify::notify("READY=1");
+        self.set_status(&self.text.messages.welcome.clone());
+    }
+
+    pub(crate) fn set_theme(&mut self, theme: ThemeConfig) {
+        self.ascii = theme.ui.ascii || !utf8_locale();
+        self.theme = theme;
+        self.set_color_support(self.color_support);
+    }
+
+    pub(crate) fn set_color_support(&mut self, support: colors::ColorSupport) {
+        self.color_support = support;
+        self.styles = styles::Styles::new(&self.theme, support, self.dry_run);
+        self.widgets = widgets::WidgetCache::default();
+        self.backdrop = load_backdrop(&self.theme.backdrop, support);
+    }
+
+    pub(crate) fn default_install_confirmation(&self) -> String {
+        if self.dotfiles_marker.is_some() {
+            self.text.messages.confirm_reinstall.clone()
+        } else {
+            self.text.messages.confirm_default_install.clone()
+        }
+    }
+
+    // The dialog before `operation` runs, which is carried to it instead of read back
+    // from the menu selection
+    pub(crate) fn show_confirmation(&mut self, operation: InstallerOption, message: String) {
+        self.pending_operation = Some(operation);
+        self.confirmation_message = message;
+        self.confirmation_details.clear();
+        self.show_details = false;
+        self.show_confirmation = true;
+        self.app_state = AppState::Confirmation;
+        self.arm_confirmation();
+    }
+
+    // Starts the delay before the dialog on screen takes Enter or y
+    pub(crate) fn arm_confirmation(&mut self) {
+        self.confirm_armed_at = tokio::time::Instant::now()
+            + Duration::from_millis(self.settings.input.confirm_delay_ms);
+    }
+
+    // Dialogs whose Enter or y starts something that changes or restarts the system
+    pub(crate) fn awaits_confirmation(&self) -> bool {
+        self.show_confirmation
+            || matches!(
+                self.app_state,
+                AppState::SessionsWarning | AppState::RebootSelect
+            )
+    }
+
+    // Success and failure notes clear themselves after a while, errors stay until the user
+    // moves on
+    pub(crate) fn expire_status(&mut self) {
+        let Some((message, status_type)) = &self.status_message else {
+            self.status_shown = None;
+            return;
+        };
+        let now = tokio::time::Instant::now();
+        let since = match &self.status_shown {
+            Some((shown, since)) if shown == message => *since,
+            _ => {
+                self.status_shown = Some((message.clone(), now));
+                now
+            }
+        };
+        let display = Duration::from_secs(self.settings.status.display_seconds);
+        if !display.is_zero()
+            && !matches!(status_type, StatusType::Error)
+            && now.duration_since(since) >= display
+        {
+            self.status_message = None;
+            self.status_shown = None;
+        }
+    }
+
+    // Counts down the last seconds of the session, then exits or powers off. A running
+    // operation is let finish first, cutting it off would leave the system half set up.
+    pub(crate) fn check_session(&mut self) {
+        let config = &self.settings.session;
+        if config.limit_minutes == 0 {
+            return;
+        }
+        let limit = Duration::from_secs(config.limit_minutes * 60);
+        let remaining = limit.saturating_sub(self.session_started.elapsed());
+        if remaining > Duration::from_secs(config.warning_seconds) {
+            return;
+        }
+        self.session_remaining = Some(remaining.as_secs_f64().ceil() as u64);
+        if !remaining.is_zero() || self.progress_type.is_some() {
+            return;
+        }
+        logging::event!(
+            Level::Info,
+            "session",
+            "Session limit of {} minutes reached, {:?}",
+            config.limit_minutes,
+            config.action
+        );
+        if config.action == SessionEnd::Poweroff {
+            self.pending_system_action = Some(SystemAction::Poweroff);
+        }
+        self.should_quit = true;
+    }
+
+    pub(crate) fn session_warning(&self) -> Option<String> {
+        let seconds = self.session_remaining?;
+        let messages = &self.text.messages;
+        let action = match self.settings.session.action {
+            SessionEnd::Exit => &messages.session_exit,
+            SessionEnd::Poweroff => &messages.session_poweroff,
+        };
+        Some(format!(
+            "{} {} {} {}",
+            messages.session_ending, seconds, messages.session_seconds, action
+        ))
+    }
+
+    // The checks start the first time the main menu shows and keep running in the background
+    pub(crate) fn poll_health(&mut self) {
+        if self.health_rx.is_none() && self.app_state == AppState::MainMenu {
+            self.health_rx = Some(health::spawn(self.settings.health.clone()));
+        }
+        if let Some(rx) = self.health_rx.as_mut()
+            && rx.has_changed().unwrap_or(false)
+        {
+            self.health = rx.borrow_and_update().clone();
+        }
+    }
+
+    // In its last second a message that is about to clear is drawn dimmed
+    pub(crate) fn status_fading(&self) -> bool {
+        let display = Duration::from_secs(self.settings.status.display_seconds);
+        match (&self.status_message, &self.status_shown) {
+            (Some((_, StatusType::Error)), _) | (_, None) => false,
+            _ if display.is_zero() => false,
+            (_, Some((_, since))) => since.elapsed() + STATUS_FADE >= display,
+        }
+    }
+
+    pub(crate) fn hide_confirmation(&mut self) {
+        self.show_confirmation = false;
+        self.confirmation_message.clear();
+        self.confirmation_details.clear();
+        self.pending_operation = None;
+        self.app_state = AppState::MainMenu;
+    }
+
+    // Walks through the optional setup screens before the default install confirmation
+    pub(crate) async fn start_default_setup(&mut self) {
+        self.wizard_summary.clear();
+        self.monitors = if self.profile.configures_monitors() {
+            monitors::detect().await
+        } else {
+            Vec::new()
+        };
+
+        self.target_users.clear();
+        self.available_users = if steps::is_root() {
+            steps::human_users()
+        } else {
+            Vec::new()
+        };
+        if self.available_users.len() > 1 {
+            let invoking = std::env::var("SUDO_USER").unwrap_or_default();
+            let default = self
+                .available_users
+                .iter()
+                .position(|user| user.name == invoking)
+                .unwrap_or(0);
+            self.user_picks = (0..self.available_users.len())
+                .map(|i| i == default)
+                .collect();
+            self.user_cursor = 0;
+            self.app_state = AppState::UserSelect;
+        } else {
+            self.after_users();
+        }
+    }
+
+    pub(crate) fn move_user_cursor(&mut self, down: bool) {
+        self.user_cursor = cycle_index(self.user_cursor, self.available_users.len(), down);
+    }
+
+    pub(crate) fn toggle_user(&mut self) {
+        if let Some(pick) = self.user_picks.get_mut(self.user_cursor) {
+            *pick = !*pick;
+        }
+    }
+
+    pub(crate) fn confirm_users(&mut self) {
+        self.target_users = self
+            .available_users
+            .iter()
+            .zip(&self.user_picks)
+            .filter(|(_, picked)| **picked)
+            .map(|(user, _)| user.clone())
+            .collect();
+        if self.target_users.is_empty() {
+            self.status_message = Some((
+                self.text.messages.users_none_picked.clone(),
+                StatusType::Fail,
+            ));
+            return;
+        }
+        self.status_message = None;
+        let names: Vec<&str> = self
+            .target_users
+            .iter()
+            .map(|user| user.name.as_str())
+            .collect();
+        self.wizard_summary.push(format!(
+            "{} {}",
+            self.text.messages.users_summary,
+            names.join(", ")
+        ));
+        self.after_users();
+    }
+
+    pub(crate) fn after_users(&mut self) {
+        if self.profile.wallpapers.is_empty() {
+            self.after_wallpapers();
+        } else {
+            self.show_wallpaper_select();
+        }
+    }
+
+    pub(crate) fn after_wallpapers(&mut self) {
+        if self.component_picks.is_empty() {
+            self.after_components();
+        } else {
+            self.component_cursor = 0;
+            self.app_state = AppState::ComponentSelect;
+        }
+    }
+
+    pub(crate) fn after_components(&mut self) {
+        if self.monitors.is_empty() {
+            self.show_plan();
+        } else {
+            self.monitor_cursor = 0;
+            self.app_state = AppState::MonitorSelect;
+        }
+    }
+
+    // Lists what the install will run and leave out given the choices made so far
+    pub(crate) fn show_plan(&mut self) {
+        let plan = self.install_plan();
+        let messages = &self.text.messages;
+        let mut lines = vec![messages.plan_run.clone()];
+        lines.extend(
+            plan.run
+                .iter()
+                .map(|(label, summary)| format!("  {}: {}", label, summary)),
+        );
+        if !plan.skipped.is_empty() {
+            lines.push(String::new());
+            lines.push(messages.plan_skipped.clone());
+            lines.extend(
+                plan.skipped
+                    .iter()
+                    .map(|(label, reason)| format!("  {}: {}", label, reason)),
+            );
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "{} {} {}",
+            messages.plan_estimate,
+            plan::minutes(plan.seconds),
+            messages.plan_minutes
+        ));
+
+        self.plan_lines = lines;
+        self.plan_scroll = 0;
+        self.app_state = AppState::Plan;
+    }
+
+    pub(crate) fn install_plan(&self) -> plan::Plan {
+        let declined = self.declined_steps();
+        let steps = self
+            .profile
+            .steps
+            .iter()
+            .filter(|step| !declined.contains(&step.id));
+        plan::Plan::build(
+            &pipeline::plan_steps(steps, &self.target_users),
+            &self.settings.plan,
+        )
+    }
+
+    pub(crate) fn scroll_plan(&mut self, lines: isize) {
+        let last = self.plan_lines.len().saturating_sub(1);
+        self.plan_scroll = self.plan_scroll.saturating_add_signed(lines).min(last);
+    }
+
+    // What the confirmation dialog says before `option` starts
+    pub(crate) fn operation_confirmation(&self, option: &InstallerOption) -> String {
+        match option {
+            InstallerOption::Default => {
+                let mut message = self.default_install_confirmation();
+                for line in &self.wizard_summary {
+                    message.push('\n');
+                    message.push_str(line);
+                }
+                message
+            }
+            InstallerOption::Repair => {
+                let steps: Vec<String> = self
+                    .repair_plan
+                    .iter()
+                    .map(|item| format!("{} ({})", item.step.label(), item.reasons.join(", ")))
+                    .collect();
+                format!(
+                    "{}\n{}",
+                    self.text.messages.confirm_repair,
+                    steps.join("\n")
+                )
+            }
+            InstallerOption::Custom => {
+                let steps: Vec<String> =
+                    self.custom_steps().map(|step| step.name.clone()).collect();
+                format!(
+                    "{}\n{}",
+                    self.text.messages.confirm_custom,
+                    steps.join("\n")
+                )
+            }
+            InstallerOption::UpdateSystem => self.text.messages.confirm_system_update.clone(),
+            _ => "Confirm operation?".to_string(),
+        }
+    }
+
+    // The commands `option` runs, as the plan and the update spell them
+    pub(crate) fn operation_details(&self, option: &InstallerOption) -> Vec<String> {
+        match option {
+            InstallerOption::Default => self
+                .install_plan()
+                .run
+                .into_iter()
+                .map(|(label, summary)| format!("{}: {}", label, summary))
+                .collect(),
+            InstallerOption::Custom => self
+                .custom_plan()
+                .run
+                .into_iter()
+                .map(|(label, summary)| format!("{}: {}", label, summary))
+                .collect(),
+            InstallerOption::Repair => self
+                .repair_plan
+                .iter()
+                .map(|item| format!("{}: {}", item.step.label(), plan::summary(&item.step.step)))
+                .collect(),
+            InstallerOption::UpdateSystem if self.uses_helper() => {
+                vec!["bootc update (privileged helper)".to_string()]
+            }
+            InstallerOption::UpdateSystem => vec![escalation::describe("bootc update")],
+            _ => Vec::new(),
+        }
+    }
+
+    pub(crate) fn show_operation_confirmation(&mut self, option: &InstallerOption) {
+        self.show_confirmation(option.clone(), self.operation_confirmation(option));
+        if self.settings.confirmation.details {
+            self.confirmation_details = self.operation_details(option);
+        }
+    }
+
+    pub(crate) fn toggle_details(&mut self) {
+        if !self.confirmation_details.is_empty() {
+            self.show_details = !self.show_details;
+        }
+    }
+
+    // The dialog's text with the details section, folded unless expanded
+    pub(crate) fn confirmation_text(&self) -> String {
+        let mut text = self.confirmation_message.clone();
+        if !self.confirmation_details.is_empty() {
+            let marker = if self.show_details { "▾" } else { "▸" };
+            text.push_str(&format!(
+                "\n\n{} {}",
+                marker, self.text.messages.confirmation_details
+            ));
+            if self.show_details {
+                for line in &self.confirmation_details {
+                    text.push_str(&format!("\n  $ {}", line));
+                }
+            }
+        }
+        text
+    }
+
+    pub(crate) fn gate(&self, option: &InstallerOption) -> Gate {
+        let gates = &self.settings.confirmation;
+        match option {
+            InstallerOption::Default | InstallerOption::Custom => gates.install,
+            InstallerOption::Repair => gates.repair,
+            InstallerOption::UpdateSystem => gates.update,
+            _ => Gate::Confirm,
+        }
+    }
+
+    pub(crate) fn show_wallpaper_select(&mut self) {
+        self.wallpaper_cursor = 0;
+        self.app_state = AppState::WallpaperSelect;
+    }
+
+    pub(crate) fn hide_wallpaper_select(&mut self) {
+        self.app_state = AppState::MainMenu;
+    }
+
+    pub(crate) fn toggle_wallpaper(&mut self) {
+        if let Some(pick) = self.wallpaper_picks.get_mut(self.wallpaper_cursor) {
+            *pick = !*pick;
+        }
+    }
+
+    pub(crate) fn move_wallpaper_cursor(&mut self, down: bool) {
+        self.wallpaper_cursor =
+            cycle_index(self.wallpaper_cursor, self.profile.wallpapers.len(), down);
+    }
+
+    // Hands the picks to the install steps and continues to the usual confirmation
+    pub(crate) fn confirm_wallpapers(&mut self) {
+        self.apply_wallpaper_picks();
+        self.after_wallpapers();
+    }
+
+    pub(crate) fn apply_wallpaper_picks(&mut self) {
+        let picked: Vec<&pipeline::WallpaperPack> = self
+            .profile
+            .wallpapers
+            .iter()
+            .zip(&self.wallpaper_picks)
+            .filter(|(_, picked)| **picked)
+            .map(|(pack, _)| pack)
+            .collect();
+        let ids: Vec<&str> = picked.iter().map(|pack| pack.id.as_str()).collect();
+        let names: Vec<&str> = picked.iter().map(|pack| pack.name.as_str()).collect();
+        self.variables
+            .insert("wallpaper_packs".to_string(), ids.join(","));
+
+        let summary = if names.is_empty() {
+            self.text.messages.wallpaper_none.clone()
+        } else {
+            names.join(", ")
+        };
+        self.wizard_summary.push(format!(
+            "{} {}",
+            self.text.messages.wallpaper_summary, summary
+        ));
+    }
+
+    pub(crate) fn move_component_cursor(&mut self, down: bool) {
+        self.component_cursor =
+            cycle_index(self.component_cursor, self.component_picks.len(), down);
+    }
+
+    pub(crate) fn toggle_component(&mut self) {
+        if let Some(pick) = self.component_picks.get_mut(self.component_cursor) {
+            *pick = !*pick;
+        }
+    }
+
+    pub(crate) fn confirm_components(&mut self) {
+        let names: Vec<&str> = self
+            .profile
+            .optional_steps()
+            .zip(&self.component_picks)
+            .filter(|(_, picked)| **picked)
+            .map(|(step, _)| step.name.as_str())
+            .collect();
+        let summary = if names.is_empty() {
+            self.text.messages.components_none.clone()
+        } else {
+            names.join(", ")
+        };
+        self.wizard_summary.push(format!(
+            "{} {}",
+            self.text.messages.components_summary, summary
+        ));
+        self.after_components();
+    }
+
+    // Ids of the opt-in steps left unticked on the components screen
+    pub(crate) fn declined_steps(&self) -> Vec<String> {
+        self.profile
+            .optional_steps()
+            .zip(&self.component_picks)
+            .filter(|(_, picked)| !**picked)
+            .map(|(step, _)| step.id.clone())
+            .collect()
+    }
+
+    // Every step of the profile, those the default install runs ticked
+    pub(crate) fn show_custom_select(&mut self) {
+        self.custom_picks = self.profile.steps.iter().map(|step| !step.opt_in).collect();
+        self.custom_cursor = 0;
+        self.app_state = AppState::CustomSelect;
+    }
+
+    pub(crate) fn move_custom_cursor(&mut self, down: bool) {
+        self.custom_cursor = cycle_index(self.custom_cursor, self.custom_picks.len(), down);
+    }
+
+    pub(crate) fn toggle_custom(&mut self) {
+        if let Some(pick) = self.custom_picks.get_mut(self.custom_cursor) {
+            *pick = !*pick;
+        }
+    }
+
+    pub(crate) async fn confirm_custom(&mut self) -> Result<()> {
+        if !self.custom_picks.contains(&true) {
+            self.status_message = Some((
+                self.text.messages.custom_none_picked.clone(),
+                StatusType::Fail,
+            ));
+            return Ok(());
+        }
+        self.status_message = None;
+        self.request_operation(InstallerOption::Custom).await
+    }
+
+    // The steps ticked on the custom screen, in profile order
+    pub(crate) fn custom_steps(&self) -> impl Iterator<Item = &pipeline::StepConfig> {
+        self.profile
+            .steps
+            .iter()
+            .zip(&self.custom_picks)
+            .filter(|(_, picked)| **picked)
+            .map(|(step, _)| step)
+    }
+
+    pub(crate) fn custom_plan(&self) -> plan::Plan {
+        plan::Plan::build(
+            &pipeline::plan_steps(self.custom_steps(), &self.target_users),
+            &self.settings.plan,
+        )
+    }
+
+    pub(crate) fn move_monitor_cursor(&mut self, down: bool) {
+        // Every display has a resolution row and a scale row
+        self.monitor_cursor = cycle_index(self.monitor_cursor, self.monitors.len() * 2, down);
+    }
+
+    pub(crate) fn change_monitor_value(&mut self, forward: bool) {
+        let row = self.monitor_cursor;
+        let Some(monitor) = self.monitors.get_mut(row / 2) else {
+            return;
+        };
+        let (value, count) = if row.is_multiple_of(2) {
+            (&mut monitor.mode, monitor.modes.len())
+        } else {
+            (&mut monitor.scale, monitors::SCALES.len())
+        };
+        *value = cycle_index(*value, count, forward);
+    }
+
+    pub(crate) fn confirm_monitors(&mut self) {
+        self.apply_monitor_choices();
+        self.show_plan();
+    }
+
+    pub(crate) fn apply_monitor_choices(&mut self) {
+        let lines: Vec<String> = self.monitors.iter().map(|m| m.hyprland_line()).collect();
+        self.variables
+            .insert("monitor_config".to_string(), lines.join("\n"));
+
+        let choices: Vec<String> = self
+            .monitors
+            .iter()
+            .map(|monitor| {
+                let mode = monitor
+                    .selected_mode()
+                    .map(monitors::Mode::label)
+                    .unwrap_or_default();
+                format!("{} {} x{}", monitor.name, mode, monitor.scale_value())
+            })
+            .collect();
+        self.wizard_summary.push(format!(
+            "{} {}",
+            self.text.messages.monitor_summary,
+            choices.join(", ")
+        ));
+    }
+
+    pub(crate) fn move_recovery_cursor(&mut self, forward: bool) {
+        self.recovery_cursor = cycle_index(self.recovery_cursor, RECOVERY_ACTIONS.len(), forward);
+    }
+
+    pub(crate) fn move_reboot_cursor(&mut self, forward: bool) {
+        self.reboot_cursor = cycle_index(self.reboot_cursor, REBOOT_CHOICES.len(), forward);
+    }
+
+    pub(crate) fn reboot_label(&self, choice: report::RebootChoice) -> (String, &str) {
+        let messages = &self.text.messages;
+        match choice {
+            report::RebootChoice::Now => (
+                messages.reboot_now.clone(),
+                &messages.reboot_now_description,
+            ),
+            report::RebootChoice::Scheduled => (
+                format!(
+                    "{} {} {}",
+                    messages.reboot_delayed,
+                    self.settings.system_actions.reboot_delay_minutes,
+                    messages.reboot_minutes
+                ),
+                &messages.reboot_delayed_description,
+            ),
+            report::RebootChoice::Later => (
+                messages.reboot_later.clone(),
+                &messages.reboot_later_description,
+            ),
+        }
+    }
+
+    // Who holds the installer lock and what it is doing
+    pub(crate) fn lock_details(&self) -> String {
+        let messages = &self.text.messages;
+        match &self.lock_holder {
+            Some(owner) => format!(
+                "{} {} (pid {}, {})\n{} {}\n\n{} {}",
+                messages.locked_owner,
+                owner.user,
+                owner.pid,
+                owner.mode,
+                messages.locked_since,
+                owner.started_at,
+                messages.locked_status,
+                owner.status
+            ),
+            None => messages.locked_unknown.clone(),
+        }
+    }
+
+    pub(crate) fn monitor_details(&self, monitor: &monitors::Monitor) -> String {
+        let messages = &self.text.messages;
+        let size = monitor.size_mm.map_or_else(
+            || messages.monitor_size_unknown.clone(),
+            |(width, height)| format!("{} x {} mm", width, height),
+        );
+        let size_line = format!("{}: {}", messages.monitor_size, size);
+        if monitor.description.is_empty() {
+            size_line
+        } else {
+            format!("{}, {}", monitor.description, size_line)
+        }
+    }
+
+    pub(crate) fn recovery_label(&self, action: RecoveryAction) -> (&str, &str) {
+        let messages = &self.text.messages;
+        match action {
+            RecoveryAction::Resume => (
+                &messages.recovery_resume,
+                &messages.recovery_resume_description,
+            ),
+            RecoveryAction::Rollback => (
+                &messages.recovery_rollback,
+                &messages.recovery_rollback_description,
+            ),
+            RecoveryAction::ViewLog => (&messages.recovery_log, &messages.recovery_log_description),
+            RecoveryAction::Export => (
+                &messages.recovery_export,
+                &messages.recovery_export_description,
+            ),
+        }
+    }
+
+    // Progress of the failed run: the partial marker of a fresh install or the marker of a repair
+    pub(crate) fn recovery_marker(&self) -> state::InstallMarker {
+        self.dotfiles_marker
+            .clone()
+            .or_else(state::InstallMarker::load_partial)
+            .unwrap_or_else(|| {
+                // Nothing was recorded, so opt-in steps keep their default of not being picked
+                let mut marker = state::InstallMarker::new(&self.profile);
+                marker.declined = self
+                    .profile
+                    .optional_steps()
+                    .map(|step| step.id.clone())
+                    .collect();
+                marker
+            })
+    }
+
+    pub(crate) fn scroll_log(&mut self, lines: isize) {
+        let last = self.log_lines.len().saturating_sub(1);
+        self.log_scroll = self.log_scroll.saturating_add_signed(lines).min(last);
+    }
+
+    pub(crate) fn show_path_input(
+        &mut self,
+        purpose: PathPurpose,
+        value: String,
+        kind: path_input::PathKind,
+    ) {
+        self.path_input = Some(path_input::PathInput::new(value, kind));
+        self.path_purpose = purpose;
+        self.clear_status();
+        self.app_state = AppState::PathInput;
+    }
+
+    pub(crate) fn hide_path_input(&mut self) {
+        self.path_input = None;
+        self.app_state = match self.path_purpose {
+            PathPurpose::SaveLog => AppState::LogView,
+            PathPurpose::Export => AppState::VolumeSelect,
+            PathPurpose::SupportBundle => AppState::MainMenu,
+            PathPurpose::BugReport => AppState::BugReport,
+        };
+    }
+
+    // The installer log, the last run report and the crash dump, whichever exist
+    pub(crate) fn export_files(&self) -> Vec<PathBuf> {
+        let log = logging::log_path().or_else(|| {
+            self.last_failure
+                .as_ref()
+                .and_then(|failed| failed.log_file.as_ref())
+                .map(PathBuf::from)
+        });
+        log.into_iter()
+            .chain([report::report_path(), report::crash_path()])
+            .collect()
+    }
+
+    pub(crate) async fn show_volumes(&mut self) {
+        self.volumes = removable::list().await;
+        self.volume_cursor = 0;
+        self.clear_status();
+        self.app_state = AppState::VolumeSelect;
+    }
+
+    pub(crate) fn move_volume_cursor(&mut self, forward: bool) {
+        // The last entry asks for another folder
+        self.volume_cursor = cycle_index(self.volume_cursor, self.volumes.len() + 1, forward);
+    }
+
+    pub(crate) fn select_volume(&mut self) {
+        let Some(volume) = self.volumes.get(self.volume_cursor) else {
+            self.show_path_input(
+                PathPurpose::Export,
+                "~/".to_string(),
+                path_input::PathKind::Directory,
+            );
+            return;
+        };
+        match removable::export(&volume.mount, &self.export_files()) {
+            Ok(saved) => {
+                self.app_state = AppState::RecoverySelect;
+                self.status_message = Some((
+                    format!("{} {}", self.text.messages.path_saved, saved.display()),
+                    StatusType::Success,
+                ));
+            }
+            Err(e) => {
+                logging::event!(Level::Error, "export", "{:#}", e);
+                self.status_message = Some((format!("Error: {:#}", e), StatusType::Error));
+            }
+        }
+    }
+
+    pub(crate) fn path_error(&self, error: path_input::PathError) -> &str {
+        let messages = &self.text.messages;
+        match error {
+            path_input::PathError::Empty => &messages.path_empty,
+            path_input::PathError::NoParent => &messages.path_no_parent,
+            path_input::PathError::Missing => &messages.path_missing,
+            path_input::PathError::NotADirectory => &messages.path_not_directory,
+            path_input::PathError::NotAFile => &messages.path_not_file,
+        }
+    }
+
+    pub(crate) fn show_password_input(&mut self, operation: InstallerOption) {
+        self.app_state = AppState::PasswordInput;
+        self.pending_operation = Some(operation);
+        escalation::wipe(&mut self.password_input);
+    }
+
+    // Names the operation waiting for the password
+    pub(crate) fn password_reason(&self) -> Option<String> {
+        self.pending_operation.as_ref().map(|operation| {
+            format!(
+                "{} {}",
+                self.text.messages.password_reason,
+                self.option_title(operation)
+            )
+        })
+    }
+
+    // One line on what the operation waiting for the password will do
+    pub(crate) fn password_summary(&self) -> Option<String> {
+        let messages = &self.text.messages;
+        let summary = match self.pending_operation.as_ref()? {
+            InstallerOption::Default => {
+                let plan = self.install_plan();
+                format!(
+                    "{} {} · {} {} {}",
+                    plan.run.len(),
+                    messages.password_steps,
+                    messages.plan_estimate,
+                    plan::minutes(plan.seconds),
+                    messages.plan_minutes
+                )
+            }
+            InstallerOption::Repair => {
+                let steps: Vec<String> = self
+                    .repair_plan
+                    .iter()
+                    .map(|item| item.step.label())
+                    .collect();
+                steps.join(", ")
+            }
+            operation => self.operation_details(operation).join(", "),
+        };
+        Some(summary).filter(|summary| !summary.is_empty())
+    }
+
+    pub(crate) fn hide_password_input(&mut self) {
+        self.app_state = AppState::MainMenu;
+        self.pending_operation = None;
+        self.forget_password();
+        self.show_password = false;
+    }
+
+    // Clears the typed password and stops the log from holding on to it
+    pub(crate) fn forget_password(&mut self) {
+        escalation::wipe(&mut self.password_input);
+        logging::forget_passwords();
+    }
+
+    // Sending is only offered once an endpoint is configured
+    pub(crate) fn bug_report_actions(&self) -> Vec<BugReportAction> {
+        #[cfg_attr(not(feature = "report-upload"), allow(unused_mut))]
+        let mut actions = vec![BugReportAction::Save];
+        #[cfg(feature = "report-upload")]
+        if !self.settings.bug_report.endpoint.is_empty() {
+            actions.insert(0, BugReportAction::Send);
+        }
+        actions
+    }
+
+    pub(crate) fn bug_report_label(&self, action: BugReportAction) -> (&str, &str) {
+        let messages = &self.text.messages;
+        match action {
+            #[cfg(feature = "report-upload")]
+            BugReportAction::Send => (
+                &messages.bug_report_send,
+                &messages.bug_report_send_description,
+            ),
+            BugReportAction::Save => (
+                &messages.bug_report_save,
+                &messages.bug_report_save_description,
+            ),
+        }
+    }
+
+    // The summary that goes into the report, with the issue link below it when there is one
+    pub(crate) fn bug_report_preview(&self) -> String {
+        let Some(report) = &self.bug_report else {
+            return String::new();
+        };
+        match report.issue_link(&self.settings.bug_report.issue_url) {
+            Some(link) => format!(
+                "{}\n\n{}\n{}",
+                report.summary(),
+                self.text.messages.bug_report_link,
+                link
+            ),
+            None => report.summary(),
+        }
+    }
+
+    pub(crate) fn show_dry_run(&mut self, lines: Vec<String>) {
+        let text = &self.text.dry_run;
+        logging::event!(Level::Info, "dry-run", "{}", text.mode_active);
+        let mut result = vec![text.simulation_header.clone()];
+        result.extend(lines);
+        result.push(text.simulation_footer.clone());
+        for line in &result {
+            logging::event!(Level::Info, "dry-run", "{}", line.trim());
+        }
+        self.dry_run_lines = result;
+        self.dry_run_scroll = 0;
+    }
+
+    pub(crate) fn scroll_dry_run(&mut self, lines: isize) {
+        let last = self.dry_run_lines.len().saturating_sub(1);
+        self.dry_run_scroll = self.dry_run_scroll.saturating_add_signed(lines).min(last);
+    }
+
+    pub(crate) fn sessions_warning(&self) -> String {
+        let messages = &self.text.messages;
+        let logged_in = if self.other_users.len() == 1 {
+            &messages.sessions_one
+        } else {
+            &messages.sessions_many
+        };
+        format!(
+            "{} {} {}\n{}",
+            self.other_users.len(),
+            logged_in,
+            self.other_users.join(", "),
+            messages.sessions_warning
+        )
+    }
+
+    pub(crate) fn clear_status(&mut self) {
+        self.status_message = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+
+    fn pack(id: &str, name: &str) -> pipeline::WallpaperPack {
+        pipeline::WallpaperPack {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            default: false,
+        }
+    }
+
+    #[test]
+    fn picked_wallpaper_packs_reach_the_install_steps() {
+        let _system = MockSystem::new();
+        let mut app = App::new(false, SettingsConfig::load().expect("settings")).expect("app");
+        app.profile.wallpapers = vec![
+            pack("nature", "Nature"),
+            pack("city", "City at night"),
+            pack("space", "Space"),
+        ];
+        app.wallpaper_picks = vec![true, true, false];
+
+        app.show_wallpaper_select();
+        app.move_wallpaper_cursor(true);
+        app.toggle_wallpaper();
+        app.move_wallpaper_cursor(true);
+        app.toggle_wallpaper();
+        // Wraps around to the first pack
+        app.move_wallpaper_cursor(true);
+        assert_eq!(app.wallpaper_cursor, 0);
+        app.apply_wallpaper_picks();
+
+        assert_eq!(app.variables["wallpaper_packs"], "nature,space");
+        assert_eq!(
+            app.wizard_summary.last().map(String::as_str),
+            Some("Wallpapers: Nature, Space")
+        );
+
+        app.wallpaper_picks = vec![false; 3];
+        app.apply_wallpaper_picks();
+        assert_eq!(app.variables["wallpaper_packs"], "");
+        assert_eq!(
+            app.wizard_summary.last().map(String::as_str),
+            Some("Wallpapers: none")
+        );
+    }
+
+    #[test]
+    fn unticked_services_stay_out_of_the_install() {
+        let _system = MockSystem::new();
+        let mut app = App::new(false, SettingsConfig::load().expect("settings")).expect("app");
+        let optional: Vec<&str> = app
+            .profile
+            .optional_steps()
+            .map(|step| step.id.as_str())
+            .collect();
+        assert_eq!(optional, ["printing", "bluetooth"]);
+        // Nothing opted into unless picked
+        assert_eq!(app.declined_steps(), ["printing", "bluetooth"]);
+
+        app.move_component_cursor(true);
+        app.toggle_component();
+        app.confirm_components();
+
+        assert_eq!(app.declined_steps(), ["printing"]);
+        assert_eq!(
+            app.wizard_summary.last().map(String::as_str),
+            Some("Optional components: Bluetooth")
+        );
+        let plan = app.install_plan();
+        let runs = |name: &str| plan.run.iter().any(|(label, _)| label == name);
+        assert!(runs("Bluetooth"));
+        assert!(!runs("Printing (CUPS)"));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{AuditConfig, logging};
+use anyhow::{Result, bail};
+use cli_log::Level;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::process::Output;
+use std::sync::Mutex;
+
+// journald's native protocol, one datagram of FIELD=value lines per entry
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+const JOURNAL_IDENTIFIER: &str = "sparrow-installer-audit";
+
+static AUDIT_LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+
+struct AuditLog {
+    file: File,
+    journald: bool,
+}
+
+pub fn audit_file(config: &AuditConfig) -> PathBuf {
+    PathBuf::from(&config.directory).join(&config.file_name)
+}
+
+// Only root writes the log: the privileged helper for what it runs, and the installer
+// itself when it runs as root. An unprivileged installer could write anything into it.
+pub fn init(config: &AuditConfig) -> Result<Option<PathBuf>> {
+    if !config.enabled || !crate::steps::is_root() {
+        return Ok(None);
+    }
+    let path = audit_file(config);
+    if let Some(parent) = path.parent() {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)?;
+        let meta = fs::symlink_metadata(parent)?;
+        if !meta.is_dir() || meta.uid() != 0 {
+            bail!("{} is not a folder owned by root", parent.display());
+        }
+    }
+    // Entries are only ever added, earlier sessions stay as they were
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(&path)?;
+    *AUDIT_LOG.lock().unwrap() = Some(AuditLog {
+        file,
+        journald: config.journald,
+    });
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+pub fn close() {
+    *AUDIT_LOG.lock().unwrap() = None;
+}
+
+// The command line as it is run
+pub fn argv(cmd: &std::process::Command) -> Vec<String> {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+// One privileged command as it was run, `via` the helper or directly
+pub fn record(argv: &[String], via: &str, result: &io::Result<Output>) {
+    let mut guard = AUDIT_LOG.lock().unwrap();
+    let Some(log) = guard.as_mut() else {
+        return;
+    };
+    let argv: Vec<String> = argv.iter().map(|arg| logging::redact(arg)).collect();
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    // Under sudo the account that started the installer is the one to blame
+    let user = ["SUDO_USER", "PKEXEC_UID", "DOAS_USER", "USER", "LOGNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_default();
+    let (status, error) = match result {
+        Ok(output) => (output.status.code(), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let success = matches!(result, Ok(output) if output.status.success());
+    let entry = serde_json::json!({
+        "timestamp": chrono::Local::now().to_rfc3339(),
+        "user": user,
+        "uid": uid,
+        "argv": argv,
+        "via": via,
+        "status": status,
+        "success": success,
+        "error": error,
+    });
+
+    if writeln!(log.file, "{}", entry).is_err() {
+        logging::event!(Level::Error, "audit", "Cannot write the audit log");
+    }
+    if log.journald {
+        let message = format!(
+            "{} ran {} via {}: {}",
+            user,
+            argv.join(" "),
+            via,
+            error.unwrap_or_else(|| if success { "ok" } else { "failed" }.to_string())
+        );
+        let priority = if success { 5 } else { 4 };
+        if let Err(e) = send_journal(&message, priority, &entry.to_string()) {
+            logging::event!(Level::Warn, "audit", "Cannot forward to journald: {}", e);
+        }
+    }
+}
+
+fn send_journal(message: &str, priority: u8, entry: &str) -> io::Result<()> {
+    let datagram = format!(
+        "MESSAGE={}\nPRIORITY={}\nSYSLOG_IDENTIFIER={}\nSPARROW_AUDIT={}\n",
+        message.replace('\n', " "),
+        priority,
+        JOURNAL_IDENTIFIER,
+        entry
+    );
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to(datagram.as_bytes(), JOURNAL_SOCKET)?;
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use crate::colors::{self, ColorSupport};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+
+// ANSI art as the terminal would show it: SGR colors and attributes are kept, in the
+// colors the terminal can show, every other escape sequence is dropped
+pub fn parse(art: &str, support: ColorSupport) -> Text<'static> {
+    let mut lines = Vec::new();
+    let mut spans = Vec::new();
+    let mut text = String::new();
+    let mut style = Style::default();
+    let mut chars = art.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                if chars.next_if_eq(&'[').is_none() {
+                    // Two-character sequences, e.g. a charset switch
+                    chars.next();
+                    continue;
+                }
+                let mut params = String::new();
+                let mut command = None;
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        command = Some(c);
+                        break;
+                    }
+                    params.push(c);
+                }
+                if command == Some('m') {
+                    if !text.is_empty() {
+                        spans.push(Span::styled(std::mem::take(&mut text), style));
+                    }
+                    style = sgr(style, &params, support);
+                }
+            }
+            '\n' => {
+                if !text.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut text), style));
+                }
+                lines.push(Line::from(std::mem::take(&mut spans)));
+            }
+            '\r' => {}
+            // The end-of-file mark of SAUCE-tagged art, the record after it is not art
+            '\x1a' => break,
+            c if c.is_control() => {}
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        spans.push(Span::styled(text, style));
+    }
+    if !spans.is_empty() {
+        lines.push(Line::from(spans));
+    }
+    Text::from(lines)
+}
+
+// Applies one "ESC [ ... m", an empty parameter list resets like 0 does
+fn sgr(mut style: Style, params: &str, support: ColorSupport) -> Style {
+    let mut codes = params
+        .split(';')
+        .map(|code| code.parse::<u16>().unwrap_or(0));
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            25 => style.remove_modifier(Modifier::SLOW_BLINK),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            30..=37 => style.fg(colors::degrade(Color::Indexed((code - 30) as u8), support)),
+            90..=97 => style.fg(colors::degrade(Color::Indexed((code - 82) as u8), support)),
+            40..=47 => style.bg(colors::degrade(Color::Indexed((code - 40) as u8), support)),
+            100..=107 => style.bg(colors::degrade(Color::Indexed((code - 92) as u8), support)),
+            39 => style.fg(Color::Reset),
+            49 => style.bg(Color::Reset),
+            38 | 48 => {
+                let color = match codes.next() {
+                    Some(5) => codes.next().map(|index| Color::Indexed(index as u8)),
+                    Some(2) => match (codes.next(), codes.next(), codes.next()) {
+                        (Some(r), Some(g), Some(b)) => Some(Color::Rgb(r as u8, g as u8, b as u8)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                match color.map(|color| colors::degrade(color, support)) {
+                    Some(color) if code == 38 => style.fg(color),
+                    Some(color) => style.bg(color),
+                    None => style,
+                }
+            }
+            _ => style,
+        };
+    }
+    style
+}
+
+// Columns the widest line takes
+pub fn width(art: &Text) -> u16 {
+    art.lines
+        .iter()
+        .map(Line::width)
+        .max()
+        .unwrap_or(0)
+        .min(u16::MAX as usize) as u16
+}
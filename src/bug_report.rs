@@ -0,0 +1,180 @@
+use crate::logging::{self, Redactor};
+use crate::tools;
+use crate::{BugReportConfig, LoggingConfig};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tokio::process::Command as AsyncCommand;
+
+// Rough kind of failure, so reports can be sorted without reading every log
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    Authentication,
+    Network,
+    DiskFull,
+    Permission,
+    Update,
+    Other,
+}
+
+impl Category {
+    pub fn classify(operation: &str, error: &str) -> Self {
+        let error = error.to_lowercase();
+        let mentions = |words: &[&str]| words.iter().any(|word| error.contains(word));
+        if mentions(&["incorrect password", "authentication", "sudo:", "doas:"]) {
+            Category::Authentication
+        } else if mentions(&["no space left", "disk quota"]) {
+            Category::DiskFull
+        } else if mentions(&["permission denied", "read-only file system"]) {
+            Category::Permission
+        } else if mentions(&["network", "connect", "dns", "timed out", "download", "http"]) {
+            Category::Network
+        } else if operation == "update" {
+            Category::Update
+        } else {
+            Category::Other
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Authentication => "authentication",
+            Category::Network => "network",
+            Category::DiskFull => "disk-full",
+            Category::Permission => "permission",
+            Category::Update => "update",
+            Category::Other => "other",
+        }
+    }
+}
+
+// What a bug report about a failed operation is filled in with, secrets already removed
+#[derive(Debug, Clone, Serialize)]
+pub struct BugReport {
+    pub version: String,
+    pub image: String,
+    pub operation: String,
+    pub category: Category,
+    pub error: String,
+    pub log_tail: Vec<String>,
+}
+
+impl BugReport {
+    pub async fn compose(
+        logging_config: &LoggingConfig,
+        config: &BugReportConfig,
+        operation: &str,
+        error: &str,
+    ) -> Self {
+        let redactor = Redactor::new(logging_config).ok();
+        let redact = |text: &str| {
+            let text = logging::redact(text);
+            match &redactor {
+                Some(redactor) => redactor.redact(&text),
+                None => text,
+            }
+        };
+        let log = fs::read_to_string(logging::log_file(logging_config)).unwrap_or_default();
+        let lines: Vec<&str> = log.lines().collect();
+        let log_tail = lines[lines.len().saturating_sub(config.log_lines)..]
+            .iter()
+            .map(|line| redact(line))
+            .collect();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            image: booted_image().await,
+            operation: operation.to_string(),
+            category: Category::classify(operation, error),
+            error: redact(error),
+            log_tail,
+        }
+    }
+
+    // The first lines of the report, shown before it is sent and used as the issue body
+    pub fn summary(&self) -> String {
+        format!(
+            "Installer version: {}\nImage: {}\nOperation: {}\nCategory: {}\nError: {}",
+            self.version,
+            self.image,
+            self.operation,
+            self.category.as_str(),
+            self.error
+        )
+    }
+
+    pub fn to_markdown(&self) -> String {
+        format!(
+            "## Sparrow installer bug report\n\n{}\n\n### Log\n\n```\n{}\n```\n",
+            self.summary()
+                .lines()
+                .map(|line| format!("- {}", line))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            self.log_tail.join("\n")
+        )
+    }
+
+    // A new-issue page with the summary filled in, short enough to open from a phone
+    pub fn issue_link(&self, issue_url: &str) -> Option<String> {
+        if issue_url.is_empty() {
+            return None;
+        }
+        let title = format!(
+            "Installer failed: {} ({})",
+            self.operation,
+            self.category.as_str()
+        );
+        reqwest::Url::parse_with_params(issue_url, [("title", title), ("body", self.summary())])
+            .ok()
+            .map(String::from)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_markdown())
+            .with_context(|| format!("Cannot write {}", path.display()))
+    }
+
+    // Posts the report as JSON to the configured endpoint
+    #[cfg(feature = "report-upload")]
+    pub async fn upload(
+        &self,
+        config: &BugReportConfig,
+        network: &crate::NetworkConfig,
+    ) -> Result<()> {
+        let client = crate::steps::download::build_client(network)?;
+        client
+            .post(&config.endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(self)?)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("Cannot send the report to {}", config.endpoint))?;
+        logging::event!(
+            cli_log::Level::Info,
+            "bug-report",
+            "Sent the report to {}",
+            config.endpoint
+        );
+        Ok(())
+    }
+}
+
+// The image bootc booted, or the name of the OS when bootc cannot tell
+async fn booted_image() -> String {
+    let output = AsyncCommand::new("bootc")
+        .args(["status", "--format", "json"])
+        .output()
+        .await;
+    if let Ok(output) = output
+        && output.status.success()
+        && let Ok(status) = serde_json::from_slice::<serde_json::Value>(&output.stdout)
+        && let Some(image) = status["status"]["booted"]["image"]["image"]["image"].as_str()
+    {
+        return image.to_string();
+    }
+    tools::os_release("PRETTY_NAME").unwrap_or_else(|| "unknown".to_string())
+}
@@ -0,0 +1,142 @@
+use ratatui::style::Color;
+
+// Colors the terminal can show, from none at all to 24-bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    None,
+    // The 16 named ANSI colors
+    Basic,
+    // The xterm 256-color palette
+    Indexed,
+    TrueColor,
+}
+
+// xterm's defaults for the 16 named colors, to measure RGB colors against
+const ANSI: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// Channel values of the 6x6x6 color cube in the 256-color palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// From NO_COLOR, COLORTERM and TERM, asking terminfo when none of them settles it
+pub fn detect() -> ColorSupport {
+    // https://no-color.org: set and not empty turns colors off
+    if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return ColorSupport::None;
+    }
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return ColorSupport::TrueColor;
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    if term.ends_with("-direct") || term.contains("truecolor") {
+        return ColorSupport::TrueColor;
+    }
+    if term.contains("256color") {
+        return ColorSupport::Indexed;
+    }
+    if term.is_empty() || term == "dumb" {
+        return ColorSupport::None;
+    }
+
+    let colors = std::process::Command::new("tput")
+        .arg("colors")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .parse::<i64>()
+                .ok()
+        });
+    match colors {
+        Some(n) if n >= 1 << 24 => ColorSupport::TrueColor,
+        Some(n) if n >= 256 => ColorSupport::Indexed,
+        Some(n) if n >= 8 => ColorSupport::Basic,
+        Some(_) => ColorSupport::None,
+        // No terminfo to ask, the named colors work nearly everywhere
+        None => ColorSupport::Basic,
+    }
+}
+
+// The closest color the terminal can show, the default color when it shows none
+pub fn degrade(color: Color, support: ColorSupport) -> Color {
+    match (support, color) {
+        (ColorSupport::None, _) => Color::Reset,
+        (ColorSupport::TrueColor, _) => color,
+        (ColorSupport::Indexed, Color::Rgb(r, g, b)) => Color::Indexed(nearest_indexed((r, g, b))),
+        (ColorSupport::Basic, Color::Rgb(r, g, b)) => nearest_ansi((r, g, b)),
+        (ColorSupport::Basic, Color::Indexed(index)) if index >= 16 => {
+            nearest_ansi(indexed_rgb(index))
+        }
+        (ColorSupport::Basic, Color::Indexed(index)) => ANSI[index as usize].0,
+        _ => color,
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn nearest_ansi(rgb: (u8, u8, u8)) -> Color {
+    ANSI.iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::White)
+}
+
+// The closer of the nearest cube color and the nearest gray of the ramp
+fn nearest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    let level = |channel: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (CUBE[i] as i32 - channel as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = 16 + 36 * r + 6 * g + b;
+
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance(rgb, indexed_rgb(gray)) < distance(rgb, indexed_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..16 => ANSI[index as usize].1,
+        16..232 => {
+            let cube = index - 16;
+            (
+                CUBE[(cube / 36) as usize],
+                CUBE[(cube / 6 % 6) as usize],
+                CUBE[(cube % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
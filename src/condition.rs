@@ -0,0 +1,263 @@
+use crate::snapshot::expand_home_in;
+use crate::{sysroot, verify};
+use anyhow::{Result, bail};
+
+// Deeper nesting of `!` and `(` than any real condition needs, the parser recurses on each
+const MAX_DEPTH: usize = 32;
+
+// A step condition such as `which nvidia-smi && env.SPARROW_PROFILE == "full"`:
+//
+//   which NAME               the program is installed
+//   file_exists("PATH")      also dir_exists, ~ is the home of the account set up
+//   env.NAME                 the variable's value, true on its own when set and not empty
+//   arch                     the CPU architecture, e.g. "x86_64"
+//   a == b, a != b           compares values and "strings"
+//   !, &&, || and ( )        in the usual order
+//
+// Programs and paths are looked up in the target root with --sysroot.
+pub fn evaluate(condition: &str) -> Result<bool> {
+    evaluate_in(condition, None)
+}
+
+// With `~` standing for `home`, the account a per-user step is for, rather than $HOME
+pub fn evaluate_in(condition: &str, home: Option<&str>) -> Result<bool> {
+    let tokens = tokenize(condition)?;
+    let mut parser = Parser {
+        tokens,
+        next: 0,
+        depth: 0,
+        home,
+    };
+    let value = parser.or()?;
+    if let Some(token) = parser.peek() {
+        bail!("unexpected {} in `{}`", token, condition);
+    }
+    Ok(value)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Equal,
+    NotEqual,
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Text(text) => write!(f, "\"{}\"", text),
+            Token::Equal => f.write_str("`==`"),
+            Token::NotEqual => f.write_str("`!=`"),
+            Token::And => f.write_str("`&&`"),
+            Token::Or => f.write_str("`||`"),
+            Token::Not => f.write_str("`!`"),
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+        }
+    }
+}
+
+fn tokenize(condition: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = condition.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            _ if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Equal,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::NotEqual,
+            '!' => Token::Not,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => text.extend(chars.next()),
+                        Some(c) => text.push(c),
+                        None => bail!("unterminated string in `{}`", condition),
+                    }
+                }
+                Token::Text(text)
+            }
+            _ if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| is_word_char(*c)) {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+            _ => bail!("unexpected `{}` in `{}`", c, condition),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+// Program names and paths after `which` are written without quotes
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | '+' | '~')
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    next: usize,
+    depth: usize,
+    home: Option<&'a str>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn take(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("the condition ends too early"))?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.take()? {
+            token if token == expected => Ok(()),
+            token => bail!("expected {} but found {}", expected, token),
+        }
+    }
+
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Result<bool>) -> Result<bool> {
+        if self.depth == MAX_DEPTH {
+            bail!(
+                "the condition nests `!` and `(` more than {} deep",
+                MAX_DEPTH
+            );
+        }
+        self.depth += 1;
+        let value = parse(self)?;
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    // Both sides are always parsed, so a typo after a false `&&` is still reported
+    fn or(&mut self) -> Result<bool> {
+        let mut value = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            value |= self.and()?;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<bool> {
+        let mut value = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next += 1;
+            value &= self.unary()?;
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<bool> {
+        if self.peek() == Some(&Token::Not) {
+            self.next += 1;
+            return Ok(!self.nested(Self::unary)?);
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<bool> {
+        match self.take()? {
+            Token::Open => self.nested(|parser| {
+                let value = parser.or()?;
+                parser.expect(Token::Close)?;
+                Ok(value)
+            }),
+            Token::Word(word) if word == "true" => Ok(true),
+            Token::Word(word) if word == "false" => Ok(false),
+            Token::Word(word) if word == "which" => match self.take()? {
+                Token::Word(program) | Token::Text(program) => Ok(verify::in_path(&program)),
+                token => bail!("expected a program after `which` but found {}", token),
+            },
+            Token::Word(word) if word == "file_exists" || word == "dir_exists" => {
+                self.expect(Token::Open)?;
+                let path = match self.take()? {
+                    Token::Text(path) => path,
+                    token => bail!("expected a \"path\" but found {}", token),
+                };
+                self.expect(Token::Close)?;
+                let path = sysroot::target(expand_home_in(&path, self.home));
+                Ok(if word == "file_exists" {
+                    path.is_file()
+                } else {
+                    path.is_dir()
+                })
+            }
+            token => {
+                let left = value(token)?;
+                let equal = match self.peek() {
+                    Some(Token::Equal) => true,
+                    Some(Token::NotEqual) => false,
+                    _ => return Ok(!left.is_empty()),
+                };
+                self.next += 1;
+                let right = value(self.take()?)?;
+                Ok((left == right) == equal)
+            }
+        }
+    }
+}
+
+fn value(token: Token) -> Result<String> {
+    match token {
+        Token::Text(text) => Ok(text),
+        Token::Word(word) if word == "arch" => Ok(std::env::consts::ARCH.to_string()),
+        Token::Word(word) if word.starts_with("env.") => {
+            Ok(std::env::var(&word["env.".len()..]).unwrap_or_default())
+        }
+        token => bail!("unexpected {}", token),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_nesting_is_a_parse_error() {
+        assert!(
+            evaluate(&format!(
+                "{}true{}",
+                "(".repeat(MAX_DEPTH),
+                ")".repeat(MAX_DEPTH)
+            ))
+            .expect("nested")
+        );
+        assert!(evaluate(&format!("{}true", "!".repeat(MAX_DEPTH))).expect("negated"));
+
+        for condition in [
+            format!(
+                "{}true{}",
+                "(".repeat(MAX_DEPTH + 1),
+                ")".repeat(MAX_DEPTH + 1)
+            ),
+            format!("{}true", "!".repeat(MAX_DEPTH + 1)),
+            // Far past what the stack would take without the limit
+            "!(".repeat(100_000),
+        ] {
+            let error = evaluate(&condition).expect_err("too deep");
+            assert!(error.to_string().contains("more than 32 deep"), "{}", error);
+        }
+    }
+}
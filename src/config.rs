@@ -0,0 +1,1173 @@
+use crate::{
+    InstallerOption, backdrop, colors, condition, escalation, logging, power, snapshot, steps,
+    sysroot, tour,
+};
+use anyhow::Result;
+use clap::ValueEnum;
+use cli_log::*;
+use directories::ProjectDirs;
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Color, Modifier},
+    text::Text,
+    widgets::{Block, BorderType, Borders},
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub(crate) const THEME_CONFIG: &str = include_str!("theme.toml");
+
+pub(crate) const MONOCHROME_THEME: &str = include_str!("themes/monochrome.toml");
+
+pub(crate) const TEXT_CONFIG: &str = include_str!("text.toml");
+
+pub(crate) const SETTINGS_CONFIG: &str = include_str!("settings.toml");
+
+pub(crate) const PROFILE_CONFIG: &str = include_str!("profile.toml");
+
+// Rebranded theme.toml and text.toml for every user of the machine
+pub(crate) const CONFIG_DIR: &str = "/etc/sparrow-installer";
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub(crate) struct ThemeConfig {
+    pub(crate) colors: ThemeColors,
+    pub(crate) ui: UiConfig,
+    pub(crate) layout: LayoutConfig,
+    pub(crate) progress: ThemeProgressConfig,
+    // Glyph before each menu entry, keyed by the option's id
+    #[serde(default)]
+    pub(crate) icons: BTreeMap<String, String>,
+    #[serde(default)]
+    pub(crate) modifiers: ElementModifiers,
+    #[serde(default)]
+    pub(crate) borders: ElementBorders,
+    #[serde(default)]
+    pub(crate) backdrop: BackdropConfig,
+}
+
+// ANSI art drawn with the main menu on terminals that have room for it
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct BackdropConfig {
+    // Empty for none
+    pub(crate) path: String,
+    pub(crate) placement: BackdropPlacement,
+    pub(crate) min_width: u16,
+    pub(crate) min_height: u16,
+}
+
+impl Default for BackdropConfig {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            placement: BackdropPlacement::Beside,
+            min_width: 120,
+            min_height: 36,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BackdropPlacement {
+    // Under the menu, right-aligned so it shows through where the entries leave room
+    Behind,
+    // In a column right of the menu
+    Beside,
+}
+
+// Elements drawn inside a border of their own, which takes a row or column on each side
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ElementBorders {
+    #[serde(rename = "type")]
+    pub(crate) kind: BorderKind,
+    pub(crate) title: bool,
+    pub(crate) menu: bool,
+    // The description, status and progress rows
+    pub(crate) description: bool,
+    // The password and path input boxes
+    pub(crate) input: bool,
+    // The running operation's output
+    pub(crate) operation: bool,
+    // The plan, dry-run result and log viewers
+    pub(crate) viewer: bool,
+    pub(crate) document: bool,
+    pub(crate) tour: bool,
+}
+
+impl Default for ElementBorders {
+    fn default() -> Self {
+        Self {
+            kind: BorderKind::Plain,
+            title: false,
+            menu: false,
+            description: false,
+            input: true,
+            operation: true,
+            viewer: true,
+            document: true,
+            tour: true,
+        }
+    }
+}
+
+impl ElementBorders {
+    // A block with the theme's border if the element has one, a bare one otherwise
+    pub(crate) fn block(&self, border: bool) -> Block<'static> {
+        let border_type = match self.kind {
+            BorderKind::Plain => BorderType::Plain,
+            BorderKind::Rounded => BorderType::Rounded,
+            BorderKind::Double => BorderType::Double,
+            BorderKind::Thick => BorderType::Thick,
+            BorderKind::None => return Block::default(),
+        };
+        if !border {
+            return Block::default();
+        }
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(border_type)
+    }
+}
+
+// How borders are drawn, "none" leaves every element without one
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BorderKind {
+    Plain,
+    Rounded,
+    Double,
+    Thick,
+    None,
+}
+
+// Text attributes added to an element's colors, so a theme can tell states apart
+// without relying on color
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ElementModifiers {
+    pub(crate) title: Vec<String>,
+    pub(crate) content: Vec<String>,
+    pub(crate) selected: Vec<String>,
+    pub(crate) disabled: Vec<String>,
+    pub(crate) confirmation: Vec<String>,
+    pub(crate) description: Vec<String>,
+    pub(crate) success: Vec<String>,
+    pub(crate) error: Vec<String>,
+    pub(crate) fail: Vec<String>,
+    // Text of status notes after their prefix
+    pub(crate) status: Vec<String>,
+    pub(crate) progress: Vec<String>,
+}
+
+// Themes built into the binary, picked with --theme
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub(crate) enum ThemePreset {
+    Default,
+    // Colorless, states shown with bold, underline and reverse video
+    Monochrome,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct TextConfig {
+    pub(crate) messages: Messages,
+    pub(crate) ui_text: UiText,
+    pub(crate) errors: Errors,
+    pub(crate) dry_run: DryRun,
+    pub(crate) keys: KeyLabels,
+    pub(crate) progress: ProgressConfig,
+    pub(crate) tour: tour::TourText,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct Messages {
+    pub(crate) welcome: String,
+    pub(crate) confirmation_prompt: String,
+    pub(crate) processing: String,
+    pub(crate) dry_run_testing: String,
+    pub(crate) dry_run_progress: String,
+    pub(crate) dry_run_default_install: String,
+    pub(crate) dry_run_system_update: String,
+    pub(crate) dry_run_complete: String,
+    pub(crate) dry_run_misc_text: String,
+    pub(crate) operation_success: String,
+    pub(crate) operation_failed: String,
+    pub(crate) operation_degraded: String,
+    pub(crate) locked_prompt: String,
+    pub(crate) locked_owner: String,
+    pub(crate) locked_since: String,
+    pub(crate) locked_status: String,
+    pub(crate) locked_unknown: String,
+    pub(crate) locked_help: String,
+    pub(crate) attach_prompt: String,
+    pub(crate) attach_help: String,
+    pub(crate) attach_closed: String,
+    pub(crate) option_disabled: String,
+    pub(crate) confirmation_help: String,
+    pub(crate) confirmation_details: String,
+    pub(crate) processing_help: String,
+    pub(crate) disabled_help: String,
+    pub(crate) password_help: String,
+    pub(crate) wallpaper_prompt: String,
+    pub(crate) wallpaper_summary: String,
+    pub(crate) wallpaper_none: String,
+    pub(crate) components_prompt: String,
+    pub(crate) components_summary: String,
+    pub(crate) components_none: String,
+    pub(crate) custom_prompt: String,
+    pub(crate) custom_none_picked: String,
+    pub(crate) users_prompt: String,
+    pub(crate) users_summary: String,
+    pub(crate) users_none_picked: String,
+    pub(crate) monitor_prompt: String,
+    pub(crate) monitor_summary: String,
+    pub(crate) monitor_resolution: String,
+    pub(crate) monitor_scale: String,
+    pub(crate) monitor_size: String,
+    pub(crate) monitor_size_unknown: String,
+    pub(crate) password_prompt: String,
+    pub(crate) password_label: String,
+    pub(crate) password_reason: String,
+    pub(crate) password_steps: String,
+    pub(crate) password_instructions: String,
+    pub(crate) password_empty_error: String,
+    pub(crate) password_auth_failed: String,
+    pub(crate) password_attempts_left: String,
+    pub(crate) password_attempts_exceeded: String,
+    pub(crate) drop_ins_ignored: String,
+    pub(crate) confirm_default_install: String,
+    pub(crate) confirm_reinstall: String,
+    pub(crate) confirm_repair: String,
+    pub(crate) confirm_custom: String,
+    pub(crate) repair_nothing_to_do: String,
+    pub(crate) recovery_prompt: String,
+    pub(crate) recovery_resume: String,
+    pub(crate) recovery_resume_description: String,
+    pub(crate) recovery_rollback: String,
+    pub(crate) recovery_rollback_description: String,
+    pub(crate) recovery_log: String,
+    pub(crate) recovery_log_description: String,
+    pub(crate) confirm_resume: String,
+    pub(crate) confirm_rollback: String,
+    pub(crate) rollback_nothing_to_do: String,
+    pub(crate) log_prompt: String,
+    pub(crate) log_empty: String,
+    pub(crate) plain_help: String,
+    pub(crate) plain_unknown: String,
+    pub(crate) log_save_prompt: String,
+    pub(crate) recovery_export: String,
+    pub(crate) recovery_export_description: String,
+    pub(crate) volumes_prompt: String,
+    pub(crate) volumes_other: String,
+    pub(crate) volumes_other_description: String,
+    pub(crate) volumes_none: String,
+    pub(crate) export_prompt: String,
+    pub(crate) support_prompt: String,
+    pub(crate) path_help: String,
+    pub(crate) path_saved: String,
+    pub(crate) path_empty: String,
+    pub(crate) path_no_parent: String,
+    pub(crate) path_missing: String,
+    pub(crate) path_not_directory: String,
+    pub(crate) path_not_file: String,
+    pub(crate) reboot_prompt: String,
+    pub(crate) reboot_now: String,
+    pub(crate) reboot_now_description: String,
+    pub(crate) reboot_delayed: String,
+    pub(crate) reboot_minutes: String,
+    pub(crate) reboot_delayed_description: String,
+    pub(crate) reboot_later: String,
+    pub(crate) reboot_later_description: String,
+    pub(crate) reboot_scheduled: String,
+    pub(crate) reboot_postponed: String,
+    pub(crate) sessions_prompt: String,
+    pub(crate) sessions_one: String,
+    pub(crate) sessions_many: String,
+    pub(crate) sessions_warning: String,
+    pub(crate) failure_prompt: String,
+    pub(crate) bug_report_prompt: String,
+    pub(crate) bug_report_send: String,
+    pub(crate) bug_report_send_description: String,
+    pub(crate) bug_report_save: String,
+    pub(crate) bug_report_save_description: String,
+    pub(crate) bug_report_link: String,
+    pub(crate) bug_report_sent: String,
+    pub(crate) bug_report_save_prompt: String,
+    pub(crate) plan_prompt: String,
+    pub(crate) plan_run: String,
+    pub(crate) plan_skipped: String,
+    pub(crate) plan_estimate: String,
+    pub(crate) plan_minutes: String,
+    pub(crate) health_prefix: String,
+    pub(crate) health_offline: String,
+    pub(crate) health_low_disk: String,
+    pub(crate) health_staged: String,
+    pub(crate) session_ending: String,
+    pub(crate) session_seconds: String,
+    pub(crate) session_exit: String,
+    pub(crate) session_poweroff: String,
+    pub(crate) confirm_system_update: String,
+    pub(crate) progress_installing: String,
+    pub(crate) progress_updating: String,
+    pub(crate) progress_repairing: String,
+    pub(crate) progress_recovering: String,
+    pub(crate) progress_preparing: String,
+    pub(crate) progress_finalizing: String,
+    pub(crate) progress_rebooting: String,
+    pub(crate) progress_poweroff: String,
+    pub(crate) spinner_chars: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct UiText {
+    pub(crate) app_title: String,
+    pub(crate) dry_run_indicator: String,
+    pub(crate) offline_indicator: String,
+    pub(crate) unattended_indicator: String,
+    pub(crate) simulation_watermark: String,
+    pub(crate) default_title: String,
+    pub(crate) default_description: String,
+    pub(crate) reinstall_title: String,
+    pub(crate) reinstall_description: String,
+    pub(crate) reinstall_outdated_description: String,
+    pub(crate) repair_title: String,
+    pub(crate) repair_description: String,
+    pub(crate) recover_title: String,
+    pub(crate) recover_description: String,
+    pub(crate) custom_title: String,
+    pub(crate) custom_description: String,
+    pub(crate) update_title: String,
+    pub(crate) update_description: String,
+    pub(crate) support_title: String,
+    pub(crate) support_description: String,
+    pub(crate) exit_title: String,
+    pub(crate) exit_description: String,
+    pub(crate) success_prefix: String,
+    pub(crate) error_prefix: String,
+    pub(crate) fail_prefix: String,
+    pub(crate) info_prefix: String,
+    pub(crate) warning_prefix: String,
+}
+
+// What each key does, for the hint bar at the bottom
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub(crate) struct KeyLabels {
+    pub(crate) navigate: String,
+    pub(crate) pick: String,
+    pub(crate) select: String,
+    pub(crate) quit: String,
+    pub(crate) back: String,
+    pub(crate) next: String,
+    pub(crate) toggle: String,
+    pub(crate) change: String,
+    pub(crate) scroll: String,
+    pub(crate) save: String,
+    pub(crate) save_log: String,
+    pub(crate) rescan: String,
+    pub(crate) complete: String,
+    pub(crate) confirm: String,
+    pub(crate) show_password: String,
+    pub(crate) yes: String,
+    pub(crate) no: String,
+    pub(crate) details: String,
+    pub(crate) cancel: String,
+    pub(crate) power_off: String,
+    pub(crate) recover: String,
+    pub(crate) report: String,
+    pub(crate) menu: String,
+    pub(crate) detach: String,
+    pub(crate) end_tour: String,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct Errors {
+    pub(crate) script_not_found: String,
+    pub(crate) permission_denied: String,
+    pub(crate) command_failed: String,
+    pub(crate) network_error: String,
+    pub(crate) disk_space_error: String,
+    pub(crate) unknown_error: String,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub(crate) struct DryRun {
+    pub(crate) mode_active: String,
+    pub(crate) simulation_header: String,
+    pub(crate) simulation_footer: String,
+    pub(crate) would_execute: String,
+    pub(crate) would_install: String,
+    pub(crate) would_update: String,
+    pub(crate) would_create: String,
+    pub(crate) would_modify: String,
+    pub(crate) would_remove: String,
+    pub(crate) sandbox_failed: String,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct ProgressConfig {
+    pub(crate) bar_fill_char: String,
+    pub(crate) bar_empty_char: String,
+    pub(crate) bar_width: u16,
+    pub(crate) countdown_seconds: u16,
+    pub(crate) dry_run_pace: f64,
+    pub(crate) dry_run_seconds: u64,
+    pub(crate) indeterminate_actions: Vec<String>,
+    pub(crate) determinante_actions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct ThemeColors {
+    #[serde(deserialize_with = "color")]
+    pub(crate) primary: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) accent: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) title_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) main_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) content_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) description_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) title_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) main_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) content_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) description_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) selected_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) selected_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) disabled_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) disabled_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) confirmation_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) confirmation_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) success_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) success_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) error_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) error_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) fail_bg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) fail_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) dry_run_fg: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) separator_fg: String,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct UiConfig {
+    pub(crate) title_height: u16,
+    pub(crate) description_height: u16,
+    pub(crate) show_separator: bool,
+    pub(crate) separator_char: String,
+    pub(crate) dry_run_icon: String,
+    pub(crate) selection_prefix: String,
+    pub(crate) disabled_suffix: String,
+    pub(crate) show_numbers: bool,
+    pub(crate) ascii: bool,
+    pub(crate) show_version: bool,
+    pub(crate) show_clock: bool,
+    pub(crate) clock_format: String,
+    pub(crate) check_pending: String,
+    pub(crate) check_running: String,
+    pub(crate) check_done: String,
+    pub(crate) check_failed: String,
+    pub(crate) check_warning: String,
+    pub(crate) check_skipped: String,
+    pub(crate) toggle_on: String,
+    pub(crate) toggle_off: String,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct LayoutConfig {
+    pub(crate) title_alignment: String,
+    pub(crate) content_alignment: String,
+    pub(crate) description_alignment: String,
+    pub(crate) confirmation_alignment: String,
+    pub(crate) content_padding: u16,
+    pub(crate) document_panel: PanelSide,
+    // Share of the menu area the description document takes, in percent
+    pub(crate) document_size: u16,
+    #[serde(default)]
+    pub(crate) breakpoints: Vec<Breakpoint>,
+}
+
+// Overrides of the [ui] sizes for terminals up to `max_width` columns or `max_height` rows
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+pub(crate) struct Breakpoint {
+    pub(crate) max_width: Option<u16>,
+    pub(crate) max_height: Option<u16>,
+    pub(crate) title_height: Option<u16>,
+    pub(crate) description_height: Option<u16>,
+    pub(crate) show_separator: Option<bool>,
+    pub(crate) document_panel: Option<PanelSide>,
+}
+
+impl Breakpoint {
+    pub(crate) fn applies(&self, area: Rect) -> bool {
+        self.max_width.is_none_or(|width| area.width <= width)
+            && self.max_height.is_none_or(|height| area.height <= height)
+    }
+}
+
+// Sizes of the title and description rows for the terminal at hand
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Sizes {
+    pub(crate) title_height: u16,
+    pub(crate) description_height: u16,
+    pub(crate) show_separator: bool,
+    pub(crate) document_panel: PanelSide,
+}
+
+// Where an option's description document goes next to the menu
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PanelSide {
+    Right,
+    Bottom,
+}
+
+impl ThemeConfig {
+    // Every breakpoint that fits applies, later ones winning over earlier ones
+    pub(crate) fn sizes(&self, area: Rect) -> Sizes {
+        let mut sizes = Sizes {
+            title_height: self.ui.title_height,
+            description_height: self.ui.description_height,
+            show_separator: self.ui.show_separator,
+            document_panel: self.layout.document_panel,
+        };
+        for breakpoint in self.layout.breakpoints.iter().filter(|b| b.applies(area)) {
+            sizes.title_height = breakpoint.title_height.unwrap_or(sizes.title_height);
+            sizes.description_height = breakpoint
+                .description_height
+                .unwrap_or(sizes.description_height);
+            sizes.show_separator = breakpoint.show_separator.unwrap_or(sizes.show_separator);
+            sizes.document_panel = breakpoint.document_panel.unwrap_or(sizes.document_panel);
+        }
+        sizes
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct ThemeProgressConfig {
+    #[serde(deserialize_with = "color")]
+    pub(crate) bar_color: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) bar_background: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) border_color: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) border_active_color: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) spinner_color: String,
+    #[serde(deserialize_with = "color")]
+    pub(crate) countdown_color: String,
+    pub(crate) spinner_speed: u64,
+    pub(crate) progress_bar_speed: u64,
+    pub(crate) countdown_speed: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct SettingsConfig {
+    pub(crate) logging: LoggingConfig,
+    pub(crate) snapshot: SnapshotConfig,
+    pub(crate) network: NetworkConfig,
+    pub(crate) metrics: MetricsConfig,
+    pub(crate) on_failure: OnFailureConfig,
+    pub(crate) on_success: OnSuccessConfig,
+    pub(crate) input: InputConfig,
+    pub(crate) status: StatusConfig,
+    pub(crate) health: HealthConfig,
+    pub(crate) session: SessionConfig,
+    pub(crate) system_actions: SystemActionsConfig,
+    pub(crate) selinux: SelinuxConfig,
+    pub(crate) verify: VerifyConfig,
+    pub(crate) watchdog: WatchdogConfig,
+    pub(crate) support: SupportConfig,
+    pub(crate) bug_report: BugReportConfig,
+    pub(crate) plan: PlanConfig,
+    pub(crate) privileges: PrivilegesConfig,
+    pub(crate) credentials: CredentialsConfig,
+    pub(crate) audit: AuditConfig,
+    pub(crate) confirmation: ConfirmationConfig,
+    pub(crate) console: ConsoleConfig,
+    // Markdown file with the longer description of an option, by option id
+    pub(crate) documents: BTreeMap<String, String>,
+    // Where an option is offered, by option id
+    pub(crate) menu: BTreeMap<String, MenuEntryConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct MenuEntryConfig {
+    // CPU architectures the option is offered on, as Rust names them, all when empty
+    #[serde(default)]
+    pub(crate) arch: Vec<String>,
+    #[serde(default)]
+    pub(crate) condition: Option<String>,
+}
+
+impl MenuEntryConfig {
+    // Limited like a profile step, a condition that does not parse leaves the option in
+    pub(crate) fn offered(&self, id: &str, arch: &str) -> bool {
+        if !self.arch.is_empty() && !self.arch.iter().any(|a| a == arch) {
+            logging::event!(
+                Level::Debug,
+                "menu",
+                "Left out {} on {}, it is for {}",
+                id,
+                arch,
+                self.arch.join(", ")
+            );
+            return false;
+        }
+        let Some(condition) = &self.condition else {
+            return true;
+        };
+        match condition::evaluate(condition) {
+            Ok(holds) => holds,
+            Err(e) => {
+                logging::event!(Level::Warn, "menu", "Condition of {}: {}", id, e);
+                true
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct LoggingConfig {
+    pub(crate) level: String,
+    pub(crate) format: logging::LogFormat,
+    pub(crate) directory: String,
+    pub(crate) file_name: String,
+    pub(crate) redact_replacement: String,
+    pub(crate) redact_username: bool,
+    pub(crate) redact_patterns: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct NetworkConfig {
+    pub(crate) proxy: String,
+    pub(crate) connect_timeout: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct MetricsConfig {
+    pub(crate) textfile: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct BugReportConfig {
+    #[cfg_attr(not(feature = "report-upload"), allow(dead_code))]
+    pub(crate) endpoint: String,
+    pub(crate) issue_url: String,
+    pub(crate) log_lines: usize,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct PlanConfig {
+    pub(crate) default_estimate: u64,
+    pub(crate) estimates: BTreeMap<String, u64>,
+}
+
+// What an action asks for before it runs
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Gate {
+    None,
+    Confirm,
+    Password,
+    Both,
+}
+
+impl Gate {
+    pub(crate) fn asks_password(self) -> bool {
+        matches!(self, Gate::Password | Gate::Both)
+    }
+
+    // Nothing checks the password in a dry run, the confirmation takes its place
+    pub(crate) fn confirms(self, dry_run: bool) -> bool {
+        match self {
+            Gate::Confirm | Gate::Both => true,
+            Gate::Password => dry_run,
+            Gate::None => false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct ConsoleConfig {
+    pub(crate) mirror: bool,
+    pub(crate) path: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct ConfirmationConfig {
+    pub(crate) install: Gate,
+    pub(crate) repair: Gate,
+    pub(crate) update: Gate,
+    pub(crate) details: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct AuditConfig {
+    pub(crate) enabled: bool,
+    pub(crate) directory: String,
+    pub(crate) file_name: String,
+    pub(crate) journald: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct PrivilegesConfig {
+    pub(crate) helper: bool,
+    pub(crate) escalation: escalation::Preference,
+    pub(crate) password_attempts: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct CredentialsConfig {
+    pub(crate) credential: String,
+    pub(crate) file: String,
+    pub(crate) secret_service: bool,
+    pub(crate) secret_attributes: Vec<String>,
+    pub(crate) secret_timeout: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct SupportConfig {
+    pub(crate) journal_lines: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct InputConfig {
+    pub(crate) confirm_delay_ms: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct HealthConfig {
+    pub(crate) refresh_seconds: u64,
+    pub(crate) network_probe: String,
+    pub(crate) probe_timeout: u64,
+    pub(crate) disk_paths: Vec<String>,
+    pub(crate) min_free_mb: u64,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SessionEnd {
+    Exit,
+    Poweroff,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct SessionConfig {
+    pub(crate) limit_minutes: u64,
+    pub(crate) warning_seconds: u64,
+    pub(crate) action: SessionEnd,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct StatusConfig {
+    pub(crate) display_seconds: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct SystemActionsConfig {
+    pub(crate) backend: power::Backend,
+    // Used by the "command" backend
+    #[serde(default)]
+    pub(crate) reboot_command: Vec<String>,
+    #[serde(default)]
+    pub(crate) poweroff_command: Vec<String>,
+    pub(crate) reboot_delay_minutes: u32,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum FailurePolicy {
+    ShowError,
+    Reboot,
+    Poweroff,
+    RollbackThenReboot,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct OnFailureConfig {
+    pub(crate) install: FailurePolicy,
+    pub(crate) repair: FailurePolicy,
+    pub(crate) recover: FailurePolicy,
+    pub(crate) update: FailurePolicy,
+}
+
+impl OnFailureConfig {
+    pub(crate) fn policy(&self, operation: &InstallerOption) -> FailurePolicy {
+        match operation {
+            InstallerOption::Default | InstallerOption::Custom => self.install,
+            InstallerOption::Repair => self.repair,
+            InstallerOption::Recover => self.recover,
+            InstallerOption::UpdateSystem => self.update,
+            InstallerOption::SupportBundle | InstallerOption::Exit => FailurePolicy::ShowError,
+        }
+    }
+}
+
+// What follows an operation that went through without failed checks
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum SuccessAction {
+    Menu,
+    AskReboot,
+    Reboot,
+    Launch,
+    Exit,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct OnSuccessConfig {
+    pub(crate) install: SuccessAction,
+    pub(crate) repair: SuccessAction,
+    pub(crate) recover: SuccessAction,
+    pub(crate) update: SuccessAction,
+    // Run by "launch" once the interface has closed, in place of the installer
+    pub(crate) launch_command: Vec<String>,
+}
+
+impl OnSuccessConfig {
+    pub(crate) fn action(&self, operation: &InstallerOption) -> SuccessAction {
+        match operation {
+            InstallerOption::Default | InstallerOption::Custom => self.install,
+            InstallerOption::Repair => self.repair,
+            InstallerOption::Recover => self.recover,
+            InstallerOption::UpdateSystem => self.update,
+            InstallerOption::SupportBundle | InstallerOption::Exit => SuccessAction::Menu,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct SelinuxConfig {
+    pub(crate) relabel: steps::selinux::RelabelMode,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct WatchdogConfig {
+    pub(crate) stall_timeout: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct VerifyConfig {
+    pub(crate) smoke_tests: bool,
+    pub(crate) smoke_timeout: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct SnapshotConfig {
+    pub(crate) enabled: bool,
+    pub(crate) paths: Vec<String>,
+    pub(crate) max_files: usize,
+    pub(crate) max_hash_size: u64,
+}
+
+impl ThemeConfig {
+    pub(crate) fn load() -> Result<Self> {
+        Self::preset(ThemePreset::Default)
+    }
+
+    // A rebranded theme.toml only replaces the default, monochrome stays as built
+    pub(crate) fn preset(preset: ThemePreset) -> Result<Self> {
+        match preset {
+            ThemePreset::Default => layered("theme.toml", THEME_CONFIG, &config_dirs()),
+            ThemePreset::Monochrome => Ok(toml::from_str(MONOCHROME_THEME)?),
+        }
+    }
+}
+
+impl TextConfig {
+    pub(crate) fn load() -> Result<Self> {
+        layered("text.toml", TEXT_CONFIG, &config_dirs())
+    }
+}
+
+impl SettingsConfig {
+    pub(crate) fn load() -> Result<Self> {
+        let config: SettingsConfig = toml::from_str(SETTINGS_CONFIG)?;
+        Ok(config)
+    }
+}
+
+// Where a theme.toml or text.toml of its own is looked for, the user's before the system's
+pub(crate) fn config_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(project) = ProjectDirs::from("", "", "sparrow-installer") {
+        dirs.push(project.config_dir().to_path_buf());
+    }
+    dirs.push(PathBuf::from(CONFIG_DIR));
+    dirs
+}
+
+// The embedded file with the first override that loads on top of it, so a rebrand only
+// needs the keys it changes. One that does not load is skipped, the self-test reports it.
+pub(crate) fn layered<T: DeserializeOwned>(
+    name: &str,
+    embedded: &str,
+    dirs: &[PathBuf],
+) -> Result<T> {
+    for path in dirs.iter().map(|dir| dir.join(name)) {
+        if !path.exists() {
+            continue;
+        }
+        match load_override(&path, embedded) {
+            Ok(config) => {
+                logging::event!(Level::Info, "config", "Using {}", path.display());
+                return Ok(config);
+            }
+            Err(e) => logging::event!(
+                Level::Warn,
+                "config",
+                "Ignoring {}: {:#}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    Ok(toml::from_str(embedded)?)
+}
+
+pub(crate) fn load_override<T: DeserializeOwned>(path: &Path, embedded: &str) -> Result<T> {
+    let source = std::fs::read_to_string(path)?;
+    let mut config: toml::Table = toml::from_str(embedded)?;
+    merge(&mut config, source.parse()?);
+    Ok(toml::Value::Table(config).try_into()?)
+}
+
+// Tables are merged key by key, anything else is replaced whole
+fn merge(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+// Persistent installer data (logs, reports) lives in the XDG state directory
+pub(crate) fn state_dir() -> PathBuf {
+    // The marker travels with the image, so rebuilding it only runs what changed
+    if let Some(root) = sysroot::get() {
+        return root.join("var/lib/sparrow-installer");
+    }
+    ProjectDirs::from("", "", "sparrow-installer")
+        .map(|dirs| {
+            dirs.state_dir()
+                .unwrap_or_else(|| dirs.data_local_dir())
+                .to_path_buf()
+        })
+        .unwrap_or_else(|| std::env::temp_dir().join("sparrow-installer"))
+}
+
+// A name, #RRGGBB, rgb(r, g, b) or an index into the 256-color palette
+pub(crate) fn parse_color(color_str: &str) -> Result<Color> {
+    if let Some(color) = color_by_name(color_str) {
+        return Ok(color);
+    }
+    let text = color_str.trim();
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!(
+                "\"{}\" is not a color, #RRGGBB takes six hex digits",
+                color_str
+            );
+        }
+        let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).unwrap_or_default();
+        return Ok(Color::Rgb(channel(0), channel(2), channel(4)));
+    }
+    let lower = text.to_ascii_lowercase();
+    if let Some(channels) = lower
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let channels: Vec<u8> = channels
+            .split(',')
+            .map(|channel| channel.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "\"{}\" is not a color, rgb() takes three values from 0 to 255",
+                    color_str
+                )
+            })?;
+        let [r, g, b] = channels[..] else {
+            anyhow::bail!("\"{}\" is not a color, rgb() takes three values", color_str);
+        };
+        return Ok(Color::Rgb(r, g, b));
+    }
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_digit()) {
+        let index = text.parse().map_err(|_| {
+            anyhow::anyhow!(
+                "\"{}\" is not a color, palette indexes go from 0 to 255",
+                color_str
+            )
+        })?;
+        return Ok(Color::Indexed(index));
+    }
+    anyhow::bail!(
+        "\"{}\" is not a color, use a name like LightBlue, #RRGGBB, rgb(r, g, b) or 0 to 255",
+        color_str
+    )
+}
+
+// Theme colors are checked as the theme loads, a palette index may be written as a number
+fn color<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Index(u64),
+        Text(String),
+    }
+    let text = match Value::deserialize(deserializer)? {
+        Value::Index(index) => index.to_string(),
+        Value::Text(text) => text,
+    };
+    parse_color(&text).map_err(serde::de::Error::custom)?;
+    Ok(text)
+}
+
+// Names are matched ignoring case and surrounding whitespace, "dark gray" included
+pub(crate) fn color_by_name(name: &str) -> Option<Color> {
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect();
+    let color = match name.as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "darkgray" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        "gold" => Color::Rgb(255, 215, 0),
+        // The terminal's own foreground or background
+        "reset" | "default" => Color::Reset,
+        _ => return None,
+    };
+    Some(color)
+}
+
+// Unknown names are left out, `self-test` lists them
+pub(crate) fn parse_modifiers(names: &[String]) -> Modifier {
+    names
+        .iter()
+        .filter_map(|name| modifier_by_name(name))
+        .fold(Modifier::empty(), |all, modifier| all | modifier)
+}
+
+pub(crate) fn modifier_by_name(name: &str) -> Option<Modifier> {
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect();
+    let modifier = match name.as_str() {
+        "bold" => Modifier::BOLD,
+        "dim" => Modifier::DIM,
+        "italic" => Modifier::ITALIC,
+        "underline" | "underlined" => Modifier::UNDERLINED,
+        "blink" | "slowblink" => Modifier::SLOW_BLINK,
+        "rapidblink" => Modifier::RAPID_BLINK,
+        "reverse" | "reversed" => Modifier::REVERSED,
+        "hidden" => Modifier::HIDDEN,
+        "crossedout" | "strikethrough" => Modifier::CROSSED_OUT,
+        _ => return None,
+    };
+    Some(modifier)
+}
+
+pub(crate) fn parse_alignment(alignment_str: &str) -> Alignment {
+    match alignment_str.trim().to_ascii_lowercase().as_str() {
+        "left" => Alignment::Left,
+        "center" => Alignment::Center,
+        "right" => Alignment::Right,
+        _ => Alignment::Center,
+    }
+}
+
+// Documents that cannot be read are left out, the option keeps its short description
+pub(crate) fn load_documents(files: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    files
+        .iter()
+        .filter_map(
+            |(id, path)| match std::fs::read_to_string(snapshot::expand_home(path)) {
+                Ok(document) => Some((id.clone(), document)),
+                Err(e) => {
+                    warn!("Cannot read the description of {} at {}: {}", id, path, e);
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+pub(crate) fn load_backdrop(
+    config: &BackdropConfig,
+    support: colors::ColorSupport,
+) -> Option<Text<'static>> {
+    if config.path.is_empty() {
+        return None;
+    }
+    match std::fs::read(snapshot::expand_home(&config.path)) {
+        Ok(art) => Some(backdrop::parse(&String::from_utf8_lossy(&art), support)),
+        Err(e) => {
+            warn!("Cannot read the backdrop at {}: {}", config.path, e);
+            None
+        }
+    }
+}
+
+// The first of LC_ALL, LC_CTYPE and LANG that is set decides, as for the C library
+pub(crate) fn utf8_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        .is_some_and(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
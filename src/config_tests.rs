@@ -0,0 +1,577 @@
+use super::*;
+use proptest::prelude::*;
+use ratatui::layout::{Alignment, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use std::collections::BTreeMap;
+
+const COLOR_NAMES: [&str; 17] = [
+    "Black",
+    "Red",
+    "Green",
+    "Yellow",
+    "Blue",
+    "Magenta",
+    "Cyan",
+    "Gray",
+    "DarkGray",
+    "LightRed",
+    "LightGreen",
+    "LightYellow",
+    "LightBlue",
+    "LightMagenta",
+    "LightCyan",
+    "White",
+    "Gold",
+];
+
+const CONFIGS: [&str; 4] = [THEME_CONFIG, TEXT_CONFIG, SETTINGS_CONFIG, PROFILE_CONFIG];
+
+// Parses with the config type matching the embedded file, reporting only whether it worked
+fn parses(config: usize, input: &str) -> bool {
+    match config {
+        0 => toml::from_str::<ThemeConfig>(input).is_ok(),
+        1 => toml::from_str::<TextConfig>(input).is_ok(),
+        2 => toml::from_str::<SettingsConfig>(input).is_ok(),
+        _ => toml::from_str::<pipeline::ProfileConfig>(input).is_ok(),
+    }
+}
+
+#[test]
+fn embedded_configs_parse() {
+    for (config, contents) in CONFIGS.iter().enumerate() {
+        assert!(parses(config, contents), "config {} is broken", config);
+    }
+}
+
+#[test]
+fn gold_is_approximated_on_smaller_palettes() {
+    let gold = parse_color("Gold").expect("gold");
+    assert_eq!(colors::degrade(gold, colors::ColorSupport::TrueColor), gold);
+    assert_eq!(
+        colors::degrade(gold, colors::ColorSupport::Indexed),
+        Color::Indexed(220)
+    );
+    assert_eq!(
+        colors::degrade(gold, colors::ColorSupport::Basic),
+        Color::LightYellow
+    );
+    assert_eq!(
+        colors::degrade(gold, colors::ColorSupport::None),
+        Color::Reset
+    );
+}
+
+proptest! {
+    #[test]
+    fn rgb_colors_fit_the_palette(r in any::<u8>(), g in any::<u8>(), b in any::<u8>()) {
+        let color = Color::Rgb(r, g, b);
+        prop_assert!(matches!(
+            colors::degrade(color, colors::ColorSupport::Indexed),
+            Color::Indexed(16..)
+        ));
+        prop_assert!(!matches!(
+            colors::degrade(color, colors::ColorSupport::Basic),
+            Color::Rgb(..) | Color::Indexed(_)
+        ));
+    }
+
+    #[test]
+    fn named_colors_are_kept_on_basic_terminals(index in 0..COLOR_NAMES.len() - 1) {
+        let color = parse_color(COLOR_NAMES[index]).expect("named color");
+        prop_assert_eq!(colors::degrade(color, colors::ColorSupport::Basic), color);
+    }
+
+    #[test]
+    fn any_color_string_parses_the_same_every_time(name in ".*") {
+        prop_assert_eq!(parse_color(&name).ok(), parse_color(&name).ok());
+    }
+
+    #[test]
+    fn unknown_color_names_are_rejected(name in "[g-z ]{0,16}") {
+        prop_assume!(color_by_name(&name).is_none());
+        prop_assert!(parse_color(&name).is_err());
+    }
+
+    #[test]
+    fn hex_and_rgb_colors_give_their_channels(r in any::<u8>(), g in any::<u8>(), b in any::<u8>()) {
+        let hex = format!("#{:02x}{:02X}{:02x}", r, g, b);
+        prop_assert_eq!(parse_color(&hex).ok(), Some(Color::Rgb(r, g, b)));
+        let rgb = format!(" RGB({}, {},{}) ", r, g, b);
+        prop_assert_eq!(parse_color(&rgb).ok(), Some(Color::Rgb(r, g, b)));
+    }
+
+    #[test]
+    fn palette_indexes_parse(index in any::<u8>()) {
+        prop_assert_eq!(parse_color(&index.to_string()).ok(), Some(Color::Indexed(index)));
+    }
+
+    #[test]
+    fn color_names_ignore_case_and_spacing(
+        index in 0..COLOR_NAMES.len(),
+        upper in proptest::collection::vec(any::<bool>(), 12),
+        padding in " {0,3}",
+    ) {
+        let canonical = COLOR_NAMES[index];
+        let varied: String = canonical
+            .chars()
+            .zip(upper.iter().cycle())
+            .map(|(c, upper)| if *upper { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() })
+            .collect();
+        let name = format!("{}{}{}", padding, varied, padding);
+        prop_assert_eq!(color_by_name(&name), color_by_name(canonical));
+        prop_assert!(color_by_name(&name).is_some());
+    }
+
+    #[test]
+    fn unknown_alignments_fall_back_to_center(text in ".*") {
+        let known = ["left", "center", "right"].contains(&text.trim().to_ascii_lowercase().as_str());
+        prop_assume!(!known);
+        prop_assert_eq!(parse_alignment(&text), Alignment::Center);
+    }
+
+    #[test]
+    fn arbitrary_toml_never_panics(config in 0..CONFIGS.len(), input in ".*") {
+        let first = parses(config, &input);
+        prop_assert_eq!(first, parses(config, &input));
+    }
+
+    // A file cut short by a bad edit or a broken package is rejected, not a crash
+    #[test]
+    fn truncated_configs_never_panic(config in 0..CONFIGS.len(), keep in 0.0f64..1.0) {
+        let lines: Vec<&str> = CONFIGS[config].lines().collect();
+        let truncated = lines[..(lines.len() as f64 * keep) as usize].join("\n");
+        let first = parses(config, &truncated);
+        prop_assert_eq!(first, parses(config, &truncated));
+    }
+
+    #[test]
+    fn edited_values_never_panic(
+        config in 0..CONFIGS.len(),
+        line in any::<prop::sample::Index>(),
+        value in "[ -~]{0,24}",
+    ) {
+        let mut lines: Vec<String> = CONFIGS[config].lines().map(str::to_string).collect();
+        let line = line.index(lines.len());
+        if let Some((key, _)) = lines[line].split_once('=') {
+            lines[line] = format!("{}= {}", key, value);
+        }
+        let edited = lines.join("\n");
+        let first = parses(config, &edited);
+        prop_assert_eq!(first, parses(config, &edited));
+    }
+
+    #[test]
+    fn templates_render_or_report_missing_variables(template in ".*", value in ".*") {
+        let variables = BTreeMap::from([("home".to_string(), value)]);
+        let first = steps::files::render(&template, &variables).ok();
+        prop_assert_eq!(first, steps::files::render(&template, &variables).ok());
+    }
+}
+
+#[test]
+fn steps_for_other_architectures_are_left_out() {
+    let mut profile: pipeline::ProfileConfig = toml::from_str(
+        r#"
+        [[steps]]
+        id = "common"
+        name = "Common"
+        systemd_enable = ["a.service"]
+
+        [[steps]]
+        id = "intel"
+        name = "Intel only"
+        arch = ["x86_64"]
+        systemd_enable = ["thermald.service"]
+
+        [[steps]]
+        id = "arm"
+        name = "ARM only"
+        arch = ["aarch64"]
+        systemd_enable = ["b.service"]
+        "#,
+    )
+    .expect("profile");
+    profile.retain_arch("aarch64");
+    let ids: Vec<&str> = profile.steps.iter().map(|step| step.id.as_str()).collect();
+    assert_eq!(ids, ["common", "arm"]);
+}
+
+#[test]
+fn conditions_evaluate_against_the_system() {
+    let _env = fixtures::lock_env();
+    let holds = |condition: &str| condition::evaluate(condition).expect(condition);
+    assert!(holds("true && !false"));
+    assert!(holds("which sh"));
+    assert!(!holds("which sparrow-no-such-program || false"));
+    assert!(holds(r#"dir_exists("/") && !file_exists("/")"#));
+    assert!(holds(&format!(r#"arch == "{}""#, std::env::consts::ARCH)));
+    assert!(holds("env.PATH"));
+    assert!(holds(
+        r#"env.SPARROW_NO_SUCH_VARIABLE == "" && !env.SPARROW_NO_SUCH_VARIABLE"#
+    ));
+    assert!(holds(r#"(false || true) && "a\"b" != "a""#));
+}
+
+#[test]
+fn broken_conditions_are_errors() {
+    for condition in [
+        "",
+        "which",
+        "file_exists(/tmp)",
+        r#"env.A = "b""#,
+        r#"("x""#,
+        "true false",
+        "nvidia",
+    ] {
+        assert!(
+            condition::evaluate(condition).is_err(),
+            "`{}` should not parse",
+            condition
+        );
+    }
+}
+
+#[test]
+fn drop_ins_add_steps_and_reject_untrusted_actions() {
+    let dir = std::env::temp_dir().join(format!("sparrow-actions-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("actions dir");
+    std::fs::write(
+        dir.join("10-vendor.toml"),
+        r#"
+        [[steps]]
+        id = "vendor"
+        name = "Vendor tweaks"
+        script = "/etc/sparrow-installer/actions.d/vendor.sh"
+        untrusted = true
+        writable = ["~/.config/vendor"]
+        "#,
+    )
+    .expect("drop-in");
+    std::fs::write(
+        dir.join("20-broken.toml"),
+        r#"
+        [[steps]]
+        id = "broken"
+        name = "Copies as a stranger"
+        untrusted = true
+        copy = { source = "a", dest = "~/b" }
+        "#,
+    )
+    .expect("drop-in");
+    std::fs::write(dir.join("notes.txt"), "not a drop-in").expect("notes");
+
+    let drop_ins = pipeline::read_drop_ins(&dir);
+    let profile = pipeline::ProfileConfig::load_from(&dir).expect("profile");
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(profile.steps.iter().any(|step| step.id == "vendor"));
+    assert_eq!(profile.ignored, [dir.join("20-broken.toml")]);
+    assert_eq!(drop_ins.len(), 2);
+    let vendor = drop_ins[0].1.as_ref().expect("vendor drop-in");
+    assert!(vendor[0].untrusted && !vendor[0].network);
+    assert_eq!(
+        plan::summary(&vendor[0]),
+        "run /etc/sparrow-installer/actions.d/vendor.sh \
+         (sandboxed: read-only system, no network, writes ~/.config/vendor)"
+    );
+    let error = drop_ins[1].1.as_ref().expect_err("untrusted copy");
+    assert!(error.to_string().contains("only run a script"));
+}
+
+#[test]
+fn layout_breakpoints_shrink_the_frame_on_small_terminals() {
+    let theme: ThemeConfig = toml::from_str(THEME_CONFIG).expect("theme");
+    let sizes = |width, height| theme.sizes(Rect::new(0, 0, width, height));
+    let full = Sizes {
+        title_height: theme.ui.title_height,
+        description_height: theme.ui.description_height,
+        show_separator: theme.ui.show_separator,
+        document_panel: theme.layout.document_panel,
+    };
+
+    assert_eq!(sizes(160, 50), full);
+    assert_eq!(
+        sizes(90, 40),
+        Sizes {
+            title_height: 3,
+            description_height: 2,
+            ..full
+        }
+    );
+    assert!(!sizes(79, 40).show_separator);
+    assert_eq!(sizes(79, 40).title_height, 3);
+    assert_eq!(sizes(79, 40).document_panel, PanelSide::Bottom);
+    assert_eq!(
+        sizes(80, 24),
+        Sizes {
+            title_height: 2,
+            description_height: 2,
+            show_separator: false,
+            document_panel: PanelSide::Right,
+        }
+    );
+}
+
+#[test]
+fn monochrome_theme_tells_states_apart_without_color() {
+    let theme = ThemeConfig::preset(ThemePreset::Monochrome).expect("monochrome theme");
+    let styles = styles::Styles::new(&theme, colors::ColorSupport::TrueColor, false);
+    let states = [
+        styles.content,
+        styles.selected,
+        styles.disabled,
+        styles.error,
+        styles.fail,
+    ];
+    for style in states {
+        assert_eq!(style.fg, Some(Color::Reset));
+        assert_eq!(style.bg, Some(Color::Reset));
+    }
+    for (i, a) in states.iter().enumerate() {
+        for b in &states[i + 1..] {
+            assert_ne!(a.add_modifier, b.add_modifier);
+        }
+    }
+    assert!(styles.selected.add_modifier.contains(Modifier::REVERSED));
+    assert!(styles.error.add_modifier.contains(Modifier::UNDERLINED));
+}
+
+#[test]
+fn rebranded_files_override_only_the_keys_they_set() {
+    let root = std::env::temp_dir().join(format!("sparrow-rebrand-{}", std::process::id()));
+    let (user, system) = (root.join("user"), root.join("etc"));
+    std::fs::create_dir_all(&user).expect("user dir");
+    std::fs::create_dir_all(&system).expect("system dir");
+    // The user's broken file is skipped for the system's
+    std::fs::write(user.join("text.toml"), "[messages\nwelcome = ").expect("broken text");
+    std::fs::write(
+        system.join("text.toml"),
+        "[messages]\nwelcome = \"Welcome to ACME OS\"\n",
+    )
+    .expect("system text");
+    std::fs::write(
+        system.join("theme.toml"),
+        "[colors]\nmain_bg = \"#12345\"\n",
+    )
+    .expect("bad theme");
+    let dirs = [user, system];
+
+    let text: TextConfig = layered("text.toml", TEXT_CONFIG, &dirs).expect("text");
+    let embedded: TextConfig = toml::from_str(TEXT_CONFIG).expect("embedded text");
+    assert_eq!(text.messages.welcome, "Welcome to ACME OS");
+    assert_eq!(
+        text.messages.password_prompt,
+        embedded.messages.password_prompt
+    );
+    // A theme that does not load leaves the built-in one
+    let theme: ThemeConfig = layered("theme.toml", THEME_CONFIG, &dirs).expect("theme");
+    let embedded: ThemeConfig = toml::from_str(THEME_CONFIG).expect("embedded theme");
+    assert_eq!(theme.colors.main_bg, embedded.colors.main_bg);
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn malformed_colors_say_what_is_wrong() {
+    for (color, hint) in [
+        ("#12345", "six hex digits"),
+        ("#12345g", "six hex digits"),
+        ("rgb(256, 0, 0)", "0 to 255"),
+        ("rgb(1, 2)", "three values"),
+        ("256", "0 to 255"),
+        ("Goldish", "#RRGGBB"),
+    ] {
+        let error = parse_color(color).expect_err(color).to_string();
+        assert!(error.contains(hint), "{}: {}", color, error);
+    }
+    // The theme's error names the key, a bare number is a palette index
+    let path = std::env::temp_dir().join(format!("sparrow-colors-{}.toml", std::process::id()));
+    std::fs::write(&path, "[colors]\nprimary = \"rgb(1, 2)\"\n").expect("theme");
+    let error = load_override::<ThemeConfig>(&path, THEME_CONFIG).expect_err("bad color");
+    assert!(format!("{:#}", error).contains("primary"));
+    std::fs::write(&path, "[colors]\naccent = 208\n").expect("theme");
+    let theme = load_override::<ThemeConfig>(&path, THEME_CONFIG).expect("indexed color");
+    assert_eq!(
+        parse_color(&theme.colors.accent).ok(),
+        Some(Color::Indexed(208))
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn modifier_names_are_forgiving_about_spelling() {
+    let names = ["Bold", "crossed-out", "underline", "nonsense"].map(String::from);
+    assert_eq!(
+        parse_modifiers(&names),
+        Modifier::BOLD | Modifier::CROSSED_OUT | Modifier::UNDERLINED
+    );
+}
+
+#[test]
+fn ansi_art_keeps_its_colors_and_drops_other_escapes() {
+    let art = "\x1b[2J\x1b[1;31mred\x1b[0m plain\r\n\x1b[38;2;255;215;0mgold\x1a\nSAUCE00";
+    let text = backdrop::parse(art, colors::ColorSupport::Basic);
+    assert_eq!(text.lines.len(), 2);
+    let red = &text.lines[0].spans[0];
+    assert_eq!(red.content, "red");
+    assert_eq!(red.style.fg, Some(Color::Red));
+    assert!(red.style.add_modifier.contains(Modifier::BOLD));
+    assert_eq!(text.lines[0].spans[1].style, Style::default());
+    assert_eq!(text.lines[1].spans[0].style.fg, Some(Color::LightYellow));
+    assert_eq!(backdrop::width(&text), 9);
+}
+
+#[test]
+fn schemas_describe_the_embedded_configs() {
+    let theme = serde_json::to_value(schema::schema(schema::ConfigFile::Theme)).expect("schema");
+    let embedded: toml::Table = toml::from_str(THEME_CONFIG).expect("theme.toml");
+    for table in embedded.keys() {
+        assert!(
+            theme["properties"].get(table).is_some(),
+            "{} is missing",
+            table
+        );
+    }
+    // Misspelled keys are errors in the file, so they are in the schema too
+    assert_eq!(
+        theme["$defs"]["ElementBorders"]["additionalProperties"],
+        false
+    );
+    assert_eq!(
+        theme["$defs"]["ElementBorders"]["properties"]["type"]["$ref"],
+        "#/$defs/BorderKind"
+    );
+
+    let text = serde_json::to_value(schema::schema(schema::ConfigFile::Text)).expect("schema");
+    assert!(text["properties"].get("tour").is_some());
+    let pipeline =
+        serde_json::to_value(schema::schema(schema::ConfigFile::Pipeline)).expect("schema");
+    assert_eq!(
+        pipeline["properties"]["steps"]["items"]["$ref"],
+        "#/$defs/StepConfig"
+    );
+    let profile =
+        serde_json::to_value(schema::schema(schema::ConfigFile::Profile)).expect("schema");
+    // Each step takes exactly one action
+    let actions = profile["$defs"]["StepConfig"]["oneOf"]
+        .as_array()
+        .expect("actions");
+    assert!(
+        actions
+            .iter()
+            .any(|action| action["required"][0] == "script")
+    );
+}
+
+#[test]
+fn older_text_files_are_migrated_to_the_current_keys() {
+    let old = TEXT_CONFIG
+        .replace("plan_skipped =", "confirm_skipped =")
+        .replace(
+            "[messages]\n",
+            "[messages]\nnavigation_help = \"Use ↑/↓\"\n",
+        )
+        .lines()
+        .filter(|line| !line.starts_with("password_label ="))
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(toml::from_str::<TextConfig>(&old).is_err());
+    assert_eq!(
+        migrate::guess(std::path::Path::new("text.toml.orig"), &old),
+        Some(schema::ConfigFile::Text)
+    );
+
+    let (migrated, changes) = migrate::migrate(&old, schema::ConfigFile::Text).expect("migrate");
+    assert_eq!(
+        changes,
+        [
+            "renamed messages.confirm_skipped to messages.plan_skipped",
+            "removed messages.navigation_help, it is no longer used",
+            "added messages.password_label",
+        ]
+    );
+    let text: TextConfig = toml::from_str(&migrated).expect("current text");
+    assert_eq!(text.messages.plan_skipped, "Left out on this system:");
+    // Comments of the original survive
+    assert!(migrated.starts_with("# Sparrow Installer Text Configuration"));
+
+    let (_, changes) = migrate::migrate(&migrated, schema::ConfigFile::Text).expect("migrate");
+    assert!(changes.is_empty());
+}
+
+#[test]
+fn current_configs_need_no_migration() {
+    let files = [
+        ("theme.toml", THEME_CONFIG, schema::ConfigFile::Theme),
+        (
+            "monochrome.toml",
+            MONOCHROME_THEME,
+            schema::ConfigFile::Theme,
+        ),
+        ("text.toml", TEXT_CONFIG, schema::ConfigFile::Text),
+        ("profile.toml", PROFILE_CONFIG, schema::ConfigFile::Profile),
+    ];
+    for (name, source, file) in files {
+        assert_eq!(
+            migrate::guess(std::path::Path::new(name), source),
+            Some(file)
+        );
+        let (migrated, changes) = migrate::migrate(source, file).expect("migrate");
+        assert!(changes.is_empty(), "{:?}: {:?}", file, changes);
+        assert_eq!(migrated, source);
+    }
+}
+
+#[test]
+fn drop_ins_are_told_apart_from_profiles_by_name() {
+    let steps = "[[steps]]\nid = \"extra\"\nname = \"Extra\"\nscript = \"extra.sh\"\n";
+    assert_eq!(
+        migrate::guess(
+            std::path::Path::new("/etc/sparrow-installer/actions.d/50-extra.toml"),
+            steps
+        ),
+        Some(schema::ConfigFile::Pipeline)
+    );
+    let old = format!("{}retired = true\n", steps);
+    let (migrated, changes) =
+        migrate::migrate(&old, schema::ConfigFile::Pipeline).expect("migrate");
+    assert_eq!(changes, ["removed steps.retired, it is no longer used"]);
+    assert_eq!(migrated, steps);
+}
+
+#[test]
+fn redaction_hides_configured_patterns_and_secrets() {
+    let settings: SettingsConfig = toml::from_str(SETTINGS_CONFIG).expect("settings");
+    let config = LoggingConfig {
+        redact_username: false,
+        ..settings.logging
+    };
+    let mut redactor = logging::Redactor::new(&config).expect("patterns");
+    assert_eq!(redactor.redact("token = abc123 ok"), "[REDACTED] ok");
+    assert_eq!(
+        redactor.redact("Authorization: Bearer eyJhbGciOi.x-y"),
+        "Authorization: [REDACTED]"
+    );
+    assert_eq!(
+        redactor.redact("cp /home/ada/.bashrc /etc/skel"),
+        "cp [REDACTED]/.bashrc /etc/skel"
+    );
+    assert_eq!(redactor.redact("nothing to hide"), "nothing to hide");
+
+    // Too short to be told apart from ordinary text
+    redactor.add_secret("ab");
+    redactor.add_secret("hunter");
+    redactor.add_password("hunter2");
+    assert_eq!(
+        redactor.redact("ab hunter hunter2"),
+        "ab [REDACTED] [REDACTED]"
+    );
+    // The longer secret goes first, then patterns match over what is left
+    assert_eq!(redactor.redact("passwd: hunter2!"), "[REDACTED]");
+    assert_eq!(
+        redactor.redact("/home/hunter2x/.config"),
+        "[REDACTED]/.config"
+    );
+
+    redactor.forget_passwords();
+    assert_eq!(redactor.redact("hunter2"), "[REDACTED]2");
+}
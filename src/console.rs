@@ -0,0 +1,54 @@
+use crate::{ConsoleConfig, logging};
+use cli_log::Level;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+// Terminals that are the machine's own screen, mirroring there would draw over the installer
+const LOCAL_SCREENS: [&str; 3] = ["/dev/console", "/dev/tty0", "/dev/tty1"];
+
+// Copies the status lines to the console, for someone watching the physical screen
+// while the installer runs on another virtual terminal or over SSH
+pub struct ConsoleMirror {
+    console: File,
+}
+
+pub fn open(config: &ConsoleConfig) -> Option<ConsoleMirror> {
+    if !config.mirror || on_local_screen() {
+        return None;
+    }
+    match OpenOptions::new().append(true).open(&config.path) {
+        Ok(console) => Some(ConsoleMirror { console }),
+        Err(e) => {
+            logging::event!(
+                Level::Warn,
+                "console",
+                "Not mirroring progress to {}: {}",
+                config.path,
+                e
+            );
+            None
+        }
+    }
+}
+
+// The terminal the installer draws on, as its standard output points to it
+fn on_local_screen() -> bool {
+    std::fs::read_link("/proc/self/fd/1")
+        .is_ok_and(|tty| LOCAL_SCREENS.iter().any(|screen| tty == Path::new(screen)))
+}
+
+impl ConsoleMirror {
+    pub fn line(&mut self, message: &str) {
+        // The console may be in raw mode too, \r keeps each line at the left edge
+        let line = format!("sparrow-installer: {}\r\n", message.replace('\n', " "));
+        if let Err(e) = self.console.write_all(line.as_bytes()) {
+            logging::event!(
+                Level::Debug,
+                "console",
+                "Cannot write to the console: {}",
+                e
+            );
+        }
+    }
+}
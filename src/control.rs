@@ -0,0 +1,224 @@
+use crate::pipeline::{ChecklistItem, StepProgress};
+use crate::progress::ProgressSink;
+use crate::{lock, logging, steps};
+use anyhow::{Context, Result};
+use cli_log::Level;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::watch;
+
+const SOCKET_NAME: &str = "sparrow-installer.sock";
+
+// What an attached instance mirrors, sent as one JSON line per change
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub title: String,
+    pub status: String,
+    pub checklist: Vec<ChecklistItem>,
+    pub output: Vec<String>,
+    #[serde(default)]
+    pub unattended: bool,
+}
+
+// Next to the instance lock, where no other user can put a socket of their own
+fn socket_path() -> Option<PathBuf> {
+    lock::runtime_dir().map(|dir| dir.join(SOCKET_NAME))
+}
+
+// The user's own instance first, then a root one
+fn candidates() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = socket_path().into_iter().collect();
+    if !steps::is_root() {
+        paths.push(lock::root_runtime_dir().join(SOCKET_NAME));
+    }
+    paths
+}
+
+// Only started by the instance holding the lock, so an existing socket file is stale
+pub struct ControlServer {
+    updates: watch::Sender<Snapshot>,
+    path: PathBuf,
+}
+
+pub fn serve() -> Result<ControlServer> {
+    let path = socket_path().context("No runtime folder for the control socket")?;
+    serve_at(path)
+}
+
+fn serve_at(path: PathBuf) -> Result<ControlServer> {
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("Cannot bind the control socket {}", path.display()))?;
+    let (updates, receiver) = watch::channel(Snapshot::default());
+    tokio::spawn(accept(listener, receiver));
+    logging::event!(
+        Level::Info,
+        "control",
+        "Listening for --attach on {}",
+        path.display()
+    );
+    Ok(ControlServer { updates, path })
+}
+
+async fn accept(listener: UnixListener, receiver: watch::Receiver<Snapshot>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let updates = receiver.clone();
+        tokio::spawn(async move {
+            if let Err(e) = stream_updates(stream, updates).await {
+                logging::event!(Level::Debug, "control", "Attached viewer left: {}", e);
+            }
+        });
+    }
+}
+
+async fn stream_updates(
+    mut stream: UnixStream,
+    mut updates: watch::Receiver<Snapshot>,
+) -> Result<()> {
+    loop {
+        let mut line = serde_json::to_string(&*updates.borrow_and_update())?;
+        line.push('\n');
+        stream.write_all(line.as_bytes()).await?;
+        // Ends once the server is dropped
+        if updates.changed().await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+impl ControlServer {
+    pub fn publish(&self, snapshot: Snapshot) {
+        self.updates.send_if_modified(|current| {
+            let changed = *current != snapshot;
+            if changed {
+                *current = snapshot;
+            }
+            changed
+        });
+    }
+
+    // Keeps attached viewers current between snapshots, a no-op once the server is gone
+    pub fn sink(self: &Arc<Self>) -> ControlSink {
+        ControlSink(Arc::downgrade(self))
+    }
+}
+
+pub struct ControlSink(Weak<ControlServer>);
+
+impl ProgressSink for ControlSink {
+    fn status(&mut self, message: &str) {
+        let Some(server) = self.0.upgrade() else {
+            return;
+        };
+        server.updates.send_if_modified(|current| {
+            let changed = current.status != message;
+            if changed {
+                current.status = message.to_string();
+            }
+            changed
+        });
+    }
+
+    fn step(&mut self, progress: &StepProgress) {
+        let Some(server) = self.0.upgrade() else {
+            return;
+        };
+        server.updates.send_if_modified(|current| {
+            let changed =
+                !progress.message.is_empty() && current.output.last() != Some(&progress.message);
+            if changed {
+                current.output.push(progress.message.clone());
+            }
+            changed
+        });
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+pub struct Attachment {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+}
+
+pub async fn attach() -> Result<Attachment> {
+    let mut last_error = None;
+    for path in candidates() {
+        match attach_at(&path).await {
+            Ok(attachment) => return Ok(attachment),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.map_or_else(
+        || anyhow::anyhow!("No control socket found"),
+        anyhow::Error::from,
+    ))
+    .context("No running installer to attach to")
+}
+
+async fn attach_at(path: &Path) -> std::io::Result<Attachment> {
+    let (reader, _) = UnixStream::connect(path).await?.into_split();
+    Ok(Attachment {
+        lines: BufReader::new(reader).lines(),
+    })
+}
+
+impl Attachment {
+    // The next snapshot, or None once the running instance has exited
+    pub async fn next(&mut self) -> Option<Snapshot> {
+        loop {
+            let line = self.lines.next_line().await.ok()??;
+            if let Ok(snapshot) = serde_json::from_str(&line) {
+                return Some(snapshot);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn attached_viewers_follow_the_running_instance() {
+        let dir = crate::helper::private_dir("sparrow-control-test").expect("temporary folder");
+        let server = Arc::new(serve_at(dir.join(SOCKET_NAME)).expect("control socket"));
+        server.publish(Snapshot {
+            title: "Update".to_string(),
+            status: "Downloading".to_string(),
+            ..Snapshot::default()
+        });
+
+        let mut attachment = attach_at(&dir.join(SOCKET_NAME)).await.expect("attach");
+        let first = attachment.next().await.expect("current snapshot");
+        assert_eq!(first.title, "Update");
+        assert_eq!(first.status, "Downloading");
+
+        let mut sink = server.sink();
+        sink.status("Deploying");
+        assert_eq!(attachment.next().await.expect("status").status, "Deploying");
+        sink.step(&StepProgress {
+            step: "kitty".to_string(),
+            fraction: None,
+            message: "Copied kitty.conf".to_string(),
+        });
+        assert_eq!(
+            attachment.next().await.expect("output").output,
+            ["Copied kitty.conf"]
+        );
+
+        // The viewer ends with the instance, which takes its socket along
+        drop(sink);
+        drop(server);
+        assert!(attachment.next().await.is_none());
+        assert!(!dir.join(SOCKET_NAME).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{CredentialsConfig, logging, tools};
+use cli_log::Level;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command as AsyncCommand;
+
+// Where systemd puts what a unit's LoadCredential= or SetCredential= passes in
+pub const CREDENTIALS_ENV: &str = "CREDENTIALS_DIRECTORY";
+
+// The admin password kept for repeat runs, None when there is none and the prompt asks.
+// The systemd credential comes first, then the credentials file, then the Secret Service.
+pub async fn lookup(config: &CredentialsConfig) -> Option<String> {
+    if !config.credential.is_empty()
+        && let Some(dir) = std::env::var_os(CREDENTIALS_ENV)
+        && let Some(password) = read(&Path::new(&dir).join(&config.credential), false)
+    {
+        logging::event!(Level::Info, "credentials", "Using the systemd credential");
+        return Some(password);
+    }
+    if !config.file.is_empty()
+        && let Some(password) = read(Path::new(&config.file), true)
+    {
+        logging::event!(Level::Info, "credentials", "Using {}", config.file);
+        return Some(password);
+    }
+    if config.secret_service
+        && let Some(password) = secret_service(config).await
+    {
+        logging::event!(Level::Info, "credentials", "Using the Secret Service");
+        return Some(password);
+    }
+    None
+}
+
+// systemd already keeps its credentials private, a file of our own has to be: owned by
+// root or the account running the installer and readable by nobody else
+fn read(path: &Path, private: bool) -> Option<String> {
+    // Not through a symlink, the checks below are on the file that is read
+    let mut file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .ok()?;
+    let meta = file.metadata().ok()?;
+    if !meta.is_file() {
+        return None;
+    }
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    if private && meta.uid() != 0 && meta.uid() != uid {
+        logging::event!(
+            Level::Warn,
+            "credentials",
+            "Ignoring {}, it belongs to another user",
+            path.display()
+        );
+        return None;
+    }
+    if private && meta.permissions().mode() & 0o077 != 0 {
+        logging::event!(
+            Level::Warn,
+            "credentials",
+            "Ignoring {}, others can read it",
+            path.display()
+        );
+        return None;
+    }
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+    let password = contents.trim_end_matches(['\r', '\n']);
+    (!password.is_empty()).then(|| password.to_string())
+}
+
+// `secret-tool lookup` with the configured attributes, a locked keyring may ask to be
+// unlocked so it gets a while
+async fn secret_service(config: &CredentialsConfig) -> Option<String> {
+    let program = tools::find("secret-tool")?;
+    let lookup = AsyncCommand::new(program)
+        .arg("lookup")
+        .args(&config.secret_attributes)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let timeout = Duration::from_secs(config.secret_timeout);
+    match tokio::time::timeout(timeout, lookup).await {
+        Ok(Ok(output)) if output.status.success() => {
+            let password = String::from_utf8(output.stdout).ok()?;
+            let password = password.trim_end_matches(['\r', '\n']);
+            (!password.is_empty()).then(|| password.to_string())
+        }
+        Ok(_) => None,
+        Err(_) => {
+            logging::event!(
+                Level::Warn,
+                "credentials",
+                "The Secret Service did not answer within {} seconds",
+                config.secret_timeout
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn only_private_files_of_root_or_the_user_are_read() {
+        let dir = crate::helper::private_dir("sparrow-credentials-test").expect("temporary folder");
+        let write = |name: &str, mode: u32| {
+            let path = dir.join(name);
+            fs::write(&path, "hunter2\n").expect("credentials file");
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).expect("mode");
+            path
+        };
+
+        let private = write("private", 0o600);
+        assert_eq!(read(&private, true).as_deref(), Some("hunter2"));
+        let shared = write("shared", 0o644);
+        assert_eq!(read(&shared, true), None);
+        // systemd keeps its own credentials private
+        assert_eq!(read(&shared, false).as_deref(), Some("hunter2"));
+
+        let link = dir.join("link");
+        std::os::unix::fs::symlink(&private, &link).expect("symlink");
+        assert_eq!(read(&link, true), None);
+        assert_eq!(read(&link, false), None);
+
+        // Only root can hand a file to someone else
+        if crate::steps::is_root() {
+            let foreign = write("foreign", 0o600);
+            std::os::unix::fs::chown(&foreign, Some(1000), None).expect("chown");
+            assert_eq!(read(&foreign, true), None);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
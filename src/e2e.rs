@@ -0,0 +1,2429 @@
+use super::*;
+use crate::fixtures::{MockSystem, Scripted};
+use crossterm::event::KeyEvent;
+use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::backend::TestBackend;
+use ratatui::style::Modifier;
+use std::collections::BTreeMap;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
+
+fn new_app() -> App {
+    new_app_with(false)
+}
+
+fn new_app_with(dry_run: bool) -> App {
+    let mut settings = SettingsConfig::load().expect("settings.toml");
+    // Keys arrive back to back here, the arming delay has a test of its own
+    settings.input.confirm_delay_ms = 0;
+    // The helper would start the test binary itself through sudo
+    settings.privileges.helper = false;
+    App::new(dry_run, settings).expect("app")
+}
+
+async fn press(app: &mut App, code: KeyCode) {
+    handle_key(app, KeyEvent::from(code)).await;
+    settle(app).await;
+}
+
+// Lets an operation the key started run to its end, as the interface loop would
+async fn settle(app: &mut App) {
+    if let Some((option, result)) = app.wait_for_worker().await {
+        app.finish_worker(&option, result);
+    }
+}
+
+async fn type_text(app: &mut App, text: &str) {
+    for c in text.chars() {
+        press(app, KeyCode::Char(c)).await;
+    }
+}
+
+// Moves the menu cursor to the first option the predicate accepts
+async fn select(app: &mut App, wanted: impl Fn(&InstallerOption) -> bool) {
+    let index = app.options.iter().position(wanted).expect("option in menu");
+    while app.selected != index {
+        press(app, KeyCode::Down).await;
+    }
+}
+
+fn screen(app: &App) -> String {
+    screen_sized(app, 100, 30)
+}
+
+fn screen_sized(app: &App, width: u16, height: u16) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).expect("terminal");
+    terminal.draw(|f| ui(f, app)).expect("draw");
+    let buffer = terminal.backend().buffer();
+    let area = buffer.area;
+    (0..area.height)
+        .map(|y| {
+            (0..area.width)
+                .map(|x| buffer.get(x, y).symbol())
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn start_update_prompt(app: &mut App) {
+    select(app, |option| {
+        matches!(option, InstallerOption::UpdateSystem)
+    })
+    .await;
+    press(app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::PasswordInput);
+}
+
+async fn start_update(app: &mut App, password: &str) {
+    start_update_prompt(app).await;
+    type_text(app, password).await;
+    press(app, KeyCode::Enter).await;
+}
+
+#[tokio::test]
+async fn wrong_password_returns_to_the_password_prompt() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    let mut app = new_app();
+
+    start_update(&mut app, "wrong").await;
+
+    assert_eq!(app.app_state, AppState::PasswordInput);
+    assert!(app.password_input.is_empty());
+    assert!(matches!(
+        &app.status_message,
+        Some((message, StatusType::Error)) if message.starts_with(&app.text.messages.password_auth_failed)
+    ));
+    assert!(screen(&app).contains(&app.text.messages.password_auth_failed));
+    // Checked before the update starts
+    assert_eq!(system.calls("sudo"), ["-k -S -p  -v"]);
+    assert!(system.calls("bootc").is_empty());
+
+    // The third wrong one in a row gives up
+    for _ in 0..2 {
+        type_text(&mut app, "wrong").await;
+        press(&mut app, KeyCode::Enter).await;
+    }
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert!(matches!(
+        &app.status_message,
+        Some((message, StatusType::Error)) if *message == app.text.messages.password_attempts_exceeded
+    ));
+    assert!(system.calls("bootc").is_empty());
+
+    start_update(&mut app, "hunter2").await;
+    assert_eq!(system.calls("bootc"), ["update"]);
+}
+
+#[tokio::test]
+async fn password_field_takes_presses_only_and_composes_accents() {
+    let system = MockSystem::new();
+    system.password("café");
+    let mut app = new_app();
+    start_update_prompt(&mut app).await;
+
+    // Windows and the kitty keyboard protocol report every key pressed and released
+    let key = |code, kind| KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind);
+    for code in [KeyCode::Char('c'), KeyCode::Char('a'), KeyCode::Tab] {
+        handle_key(&mut app, key(code, KeyEventKind::Press)).await;
+        handle_key(&mut app, key(code, KeyEventKind::Release)).await;
+    }
+    handle_key(&mut app, key(KeyCode::Char('a'), KeyEventKind::Repeat)).await;
+    assert_eq!(app.password_input, "ca");
+    assert!(app.show_password);
+    press(&mut app, KeyCode::Tab).await;
+
+    // A compose key sending the letter and then the accent
+    type_text(&mut app, "fe\u{301}").await;
+    assert_eq!(app.password_input, "café");
+    assert!(screen(&app).contains("****█"));
+    press(&mut app, KeyCode::Backspace).await;
+    type_text(&mut app, "o\u{301}").await;
+    press(&mut app, KeyCode::Backspace).await;
+    type_text(&mut app, "é").await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(system.calls("bootc"), ["update"]);
+}
+
+#[tokio::test]
+async fn polkit_asks_for_the_password_itself_and_sudo_stands_in_without_it() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    let mut settings = SettingsConfig::load().expect("settings.toml");
+    settings.privileges.helper = false;
+    settings.privileges.escalation = escalation::Preference::Polkit;
+    let mut app = App::new(false, settings).expect("app");
+
+    // Not installed, sudo gets the password as before
+    start_update(&mut app, "hunter2").await;
+    assert_eq!(
+        system.calls("sudo"),
+        ["-k -S -p  -v", "-k -S -p  bootc update"]
+    );
+
+    // The agent asks, the installer shows no prompt of its own
+    system.program("run0", r#"exec "$@""#);
+    app.app_state = AppState::MainMenu;
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::UpdateSystem)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_ne!(app.app_state, AppState::PasswordInput);
+    assert_eq!(system.calls("bootc"), ["update", "update"]);
+    assert_eq!(system.calls("sudo").len(), 2);
+}
+
+#[tokio::test]
+async fn doas_gets_the_password_on_its_terminal_without_sudo() {
+    let mut system = MockSystem::new();
+    system.remove("sudo");
+    // Like doas, reads the password from the controlling terminal and never from stdin
+    system.program(
+        "doas",
+        r#"printf 'doas (user@host) password: ' > /dev/tty
+read -r password < /dev/tty
+if [ "$password" != hunter2 ]; then
+    echo "doas: Authentication failed" >&2
+    exit 1
+fi
+exec "$@""#,
+    );
+    let mut app = new_app();
+
+    start_update(&mut app, "wrong").await;
+    assert_eq!(app.app_state, AppState::PasswordInput);
+    assert!(system.calls("bootc").is_empty());
+
+    type_text(&mut app, "hunter2").await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(system.calls("bootc"), ["update"]);
+}
+
+#[tokio::test]
+async fn stored_password_skips_the_prompt_until_sudo_turns_it_down() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    let file = std::env::temp_dir().join(format!("sparrow-credential-{}", std::process::id()));
+    let store = |password: &str| {
+        std::fs::write(&file, format!("{}\n", password)).expect("credentials file");
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o600))
+            .expect("private credentials file");
+    };
+    let mut app = new_app();
+    app.settings.credentials.file = file.display().to_string();
+
+    store("wrong");
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::UpdateSystem)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::PasswordInput);
+    assert!(app.credential_rejected);
+    assert!(system.calls("bootc").is_empty());
+
+    // Only the prompt is asked from now on, even with a good password stored
+    store("hunter2");
+    type_text(&mut app, "hunter2").await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(system.calls("bootc"), ["update"]);
+
+    let mut app = new_app();
+    app.settings.credentials.file = file.display().to_string();
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::UpdateSystem)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::RebootSelect);
+    assert_eq!(system.calls("bootc"), ["update", "update"]);
+    let _ = std::fs::remove_file(&file);
+}
+
+#[tokio::test]
+async fn update_runs_bootc_and_reports_success() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    system.script(
+        "bootc",
+        Scripted {
+            stdout: "Queued for next boot\n".to_string(),
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+
+    start_update(&mut app, "hunter2").await;
+
+    // The update is only staged, the user picks when to reboot into it
+    assert_eq!(app.app_state, AppState::RebootSelect);
+    assert!(screen(&app).contains(&app.text.messages.reboot_later));
+    assert_eq!(system.calls("bootc"), ["update"]);
+
+    press(&mut app, KeyCode::Up).await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert!(matches!(
+        &app.status_message,
+        Some((message, StatusType::Success)) if *message == app.text.messages.reboot_postponed
+    ));
+    assert!(system.calls("systemctl").is_empty());
+    assert!(system.calls("shutdown").is_empty());
+
+    let report = report::RunReport::load().expect("report saved");
+    assert_eq!(report.success, Some(true));
+    assert!(!report.install);
+    assert_eq!(report.reboot, Some(report::RebootChoice::Later));
+}
+
+#[tokio::test]
+async fn update_output_is_shown_line_by_line_while_bootc_runs() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    system.script(
+        "bootc",
+        Scripted {
+            stdout: "Fetching ostree-unverified-registry\nLayers: 1/3\rLayers: 3/3\nQueued for next boot\n"
+                .to_string(),
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+    app.app_state = AppState::Processing("Updating".to_string());
+    app.progress_type = Some(ProgressType::Indeterminate);
+    app.password_input = "hunter2".to_string();
+
+    app.start_worker(InstallerOption::UpdateSystem, worker::Job::Update);
+    let (_, result) = app.wait_for_worker().await.expect("update finished");
+    result.expect("update");
+    app.update_progress();
+
+    // Each line as bootc printed it, a redrawn progress line only as it ended up
+    assert_eq!(
+        app.action_output,
+        [
+            "Fetching ostree-unverified-registry",
+            "Layers: 3/3",
+            "Queued for next boot"
+        ]
+    );
+    // The view keeps the newest line in sight when the output no longer fits
+    app.action_output
+        .extend((1..=40).map(|n| format!("Pulling layer {}", n)));
+    let shown = screen(&app);
+    assert!(shown.contains("Pulling layer 40"));
+    assert!(!shown.contains("Fetching ostree"));
+}
+
+// Keeps what it is handed, for checking a sink sees what the interface does
+#[derive(Clone, Default)]
+struct RecordingSink {
+    events: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+impl progress::ProgressSink for RecordingSink {
+    fn status(&mut self, message: &str) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("status: {}", message));
+    }
+
+    fn step(&mut self, progress: &pipeline::StepProgress) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("{}: {}", progress.step, progress.message));
+    }
+}
+
+#[tokio::test]
+async fn every_progress_sink_gets_the_same_updates_as_the_interface() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    system.script(
+        "bootc",
+        Scripted {
+            stdout: "Fetching ostree-unverified-registry\nQueued for next boot\n".to_string(),
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+    let recorder = RecordingSink::default();
+    app.progress.add(recorder.clone());
+    app.password_input = "hunter2".to_string();
+
+    app.start_worker(InstallerOption::UpdateSystem, worker::Job::Update);
+    let (_, result) = app.wait_for_worker().await.expect("update finished");
+    result.expect("update");
+    app.update_progress();
+
+    assert_eq!(
+        *recorder.events.lock().unwrap(),
+        [
+            format!("status: {}", app.text.messages.progress_updating),
+            "update: Fetching ostree-unverified-registry".to_string(),
+            "update: Queued for next boot".to_string(),
+        ]
+    );
+    assert_eq!(
+        app.action_output,
+        [
+            "Fetching ostree-unverified-registry",
+            "Queued for next boot"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn the_interface_keeps_drawing_while_the_update_runs() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    let mut app = new_app();
+    start_update_prompt(&mut app).await;
+    type_text(&mut app, "hunter2").await;
+
+    // The key only starts the update, it runs on the worker
+    handle_key(&mut app, KeyEvent::from(KeyCode::Enter)).await;
+    assert!(app.worker.is_some());
+    assert_eq!(
+        app.app_state,
+        AppState::Processing(app.text.messages.progress_updating.clone())
+    );
+    assert!(app.password_input.is_empty());
+    assert!(screen(&app).contains(&app.text.messages.progress_updating));
+    // Keys are still read, Esc does not stop a real update
+    handle_key(&mut app, KeyEvent::from(KeyCode::Esc)).await;
+    assert!(app.worker.is_some());
+
+    settle(&mut app).await;
+    assert!(app.worker.is_none());
+    assert_eq!(app.app_state, AppState::RebootSelect);
+    assert_eq!(system.calls("bootc"), ["update"]);
+}
+
+#[tokio::test]
+async fn reboot_can_be_scheduled_after_an_update() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    let mut app = new_app();
+
+    start_update(&mut app, "hunter2").await;
+    assert_eq!(app.app_state, AppState::RebootSelect);
+
+    press(&mut app, KeyCode::Down).await;
+    press(&mut app, KeyCode::Enter).await;
+
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert_eq!(system.calls("shutdown"), ["-r +10"]);
+    assert!(system.calls("systemctl").is_empty());
+    let report = report::RunReport::load().expect("report saved");
+    assert_eq!(report.reboot, Some(report::RebootChoice::Scheduled));
+    assert!(report.reboot_at.is_some());
+}
+
+#[tokio::test]
+async fn failed_update_shows_the_bootc_error() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    system.script(
+        "bootc",
+        Scripted {
+            stderr: "error: No space left on device\n".to_string(),
+            status: 1,
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+
+    start_update(&mut app, "hunter2").await;
+
+    // The error stays up until dismissed instead of scheduling a reboot
+    assert_eq!(app.app_state, AppState::Failed);
+    assert!(app.pending_system_action.is_none());
+    assert!(screen(&app).contains("No space left on device"));
+
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert!(matches!(
+        &app.status_message,
+        Some((message, StatusType::Error)) if message.contains("No space left on device")
+    ));
+    assert_eq!(
+        report::RunReport::load().and_then(|report| report.success),
+        Some(false)
+    );
+}
+
+// Lets the timer tasks run and deliver what is due, output passes through the log task first
+async fn advance(app: &mut App, by: Duration) {
+    tokio::time::advance(by).await;
+    for _ in 0..3 {
+        tokio::task::yield_now().await;
+    }
+    app.update_progress();
+}
+
+#[tokio::test(start_paused = true)]
+async fn reboot_counts_down_and_calls_systemctl() {
+    let system = MockSystem::new();
+    let mut app = new_app();
+
+    app.start_reboot();
+    assert!(screen(&app).contains(&app.text.messages.progress_rebooting));
+
+    // A real reboot cannot be cancelled
+    press(&mut app, KeyCode::Esc).await;
+    assert!(matches!(app.app_state, AppState::Processing(_)));
+
+    // Paused time only moves when the test advances it
+    let seconds = app.text.progress.countdown_seconds;
+    advance(&mut app, Duration::from_millis(999)).await;
+    assert_eq!(app.countdown_remaining, seconds);
+    advance(&mut app, Duration::from_millis(1)).await;
+    assert_eq!(app.countdown_remaining, seconds - 1);
+    assert!(!app.should_quit);
+
+    advance(&mut app, Duration::from_secs(seconds as u64)).await;
+    assert_eq!(app.countdown_remaining, 0);
+    assert!(app.should_quit);
+    assert!(matches!(
+        app.pending_system_action,
+        Some(SystemAction::Reboot)
+    ));
+
+    app.execute_reboot().await.expect("reboot");
+    assert_eq!(system.calls("systemctl"), ["reboot"]);
+}
+
+#[tokio::test]
+async fn refused_reboot_is_reported() {
+    let system = MockSystem::new();
+    system.script(
+        "systemctl",
+        Scripted {
+            stderr: "Access denied\n".to_string(),
+            status: 1,
+            ..Scripted::default()
+        },
+    );
+    let app = new_app();
+
+    let error = app.execute_reboot().await.expect_err("reboot refused");
+    assert!(error.to_string().contains("Access denied"));
+}
+
+#[cfg(feature = "simulator")]
+#[tokio::test(start_paused = true)]
+async fn dry_run_replays_the_scenario_on_its_schedule() {
+    let system = MockSystem::new();
+    let mut app = new_app_with(true);
+    let event = |delay_ms, line: &str, progress| simulation::ScenarioEvent {
+        delay_ms,
+        line: Some(line.to_string()),
+        progress,
+    };
+    app.scenario.update = vec![
+        event(100, "Checking for updates...", None),
+        event(2_000, "Fetching layers", Some(0.5)),
+        event(1_000, "Done", Some(1.0)),
+    ];
+
+    start_update(&mut app, "anything").await;
+    assert!(app.show_confirmation);
+    press(&mut app, KeyCode::Char('y')).await;
+    assert!(matches!(app.app_state, AppState::Processing(_)));
+    assert!(app.action_output.is_empty());
+
+    advance(&mut app, Duration::from_millis(100)).await;
+    assert_eq!(app.action_output, ["Checking for updates..."]);
+    assert_eq!(app.step_fraction, None);
+
+    advance(&mut app, Duration::from_millis(2_000)).await;
+    assert_eq!(
+        app.action_output.last().map(String::as_str),
+        Some("Fetching layers")
+    );
+    assert_eq!(app.step_fraction, Some(0.5));
+    assert!(screen(&app).contains("Fetching layers"));
+
+    // Ends with the last event, not on a fixed clock
+    advance(&mut app, Duration::from_millis(999)).await;
+    assert!(matches!(app.app_state, AppState::Processing(_)));
+    advance(&mut app, Duration::from_millis(1)).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert!(matches!(
+        &app.status_message,
+        Some((message, StatusType::Success)) if *message == app.text.messages.operation_success
+    ));
+    assert!(system.calls("bootc").is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn dry_run_update_carries_the_operation_through_confirmation() {
+    let system = MockSystem::new();
+    let mut app = new_app_with(true);
+
+    start_update(&mut app, "anything").await;
+    assert!(app.show_confirmation);
+    assert_eq!(app.pending_operation, Some(InstallerOption::UpdateSystem));
+    // The menu moving underneath the dialog does not change what runs
+    app.selected = 0;
+    press(&mut app, KeyCode::Char('y')).await;
+    assert_eq!(
+        app.app_state,
+        AppState::Processing(app.text.messages.progress_updating.clone())
+    );
+    assert_eq!(
+        app.report.as_ref().map(|report| report.operation.as_str()),
+        Some(app.option_title(&InstallerOption::UpdateSystem))
+    );
+    assert_eq!(app.pending_operation, None);
+    assert!(system.calls("sudo").is_empty());
+    assert!(system.calls("bootc").is_empty());
+}
+
+#[tokio::test]
+async fn declined_confirmation_forgets_the_operation() {
+    let system = MockSystem::new();
+    let mut app = new_app_with(true);
+
+    start_update(&mut app, "anything").await;
+    press(&mut app, KeyCode::Esc).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert_eq!(app.pending_operation, None);
+    assert!(app.password_input.is_empty());
+
+    // Confirming with nothing pending starts nothing
+    app.confirm_action().await.expect("confirm");
+    assert!(app.progress_type.is_none());
+    assert!(system.calls("bootc").is_empty());
+}
+
+#[cfg(feature = "simulator")]
+#[tokio::test(start_paused = true)]
+async fn dry_run_pacing_and_cutoff_come_from_the_config() {
+    let _system = MockSystem::new();
+    let mut app = new_app_with(true);
+    let event = |delay_ms, line: &str| simulation::ScenarioEvent {
+        delay_ms,
+        line: Some(line.to_string()),
+        progress: None,
+    };
+    app.scenario.update = vec![event(1_000, "First"), event(60_000, "Never shown")];
+    app.text.progress.dry_run_pace = 0.5;
+    app.text.progress.dry_run_seconds = 2;
+
+    start_update(&mut app, "anything").await;
+    press(&mut app, KeyCode::Char('y')).await;
+
+    advance(&mut app, Duration::from_millis(500)).await;
+    assert_eq!(app.action_output, ["First"]);
+
+    advance(&mut app, Duration::from_millis(1_500)).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+}
+
+#[tokio::test]
+async fn failure_policy_can_reboot_instead() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    system.script(
+        "bootc",
+        Scripted {
+            status: 1,
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+    app.settings.on_failure.update = FailurePolicy::Reboot;
+
+    start_update(&mut app, "hunter2").await;
+
+    assert!(matches!(app.app_state, AppState::Processing(_)));
+    assert!(matches!(
+        app.pending_system_action,
+        Some(SystemAction::Reboot)
+    ));
+}
+
+#[tokio::test]
+async fn success_action_can_close_the_installer_to_launch_a_command() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    let mut app = new_app();
+    app.settings.on_success.update = SuccessAction::Launch;
+
+    start_update(&mut app, "hunter2").await;
+
+    assert!(app.should_quit);
+    assert!(app.pending_launch);
+    assert!(app.pending_system_action.is_none());
+    assert_ne!(app.app_state, AppState::RebootSelect);
+}
+
+#[tokio::test]
+async fn system_action_backend_is_configurable() {
+    let system = MockSystem::new();
+    let mut app = new_app();
+
+    app.settings.system_actions.backend = power::Backend::None;
+    app.execute_poweroff().await.expect("no-op poweroff");
+    assert!(system.calls("systemctl").is_empty());
+
+    app.settings.system_actions.backend = power::Backend::Command;
+    app.settings.system_actions.reboot_command = vec!["systemctl".to_string(), "kexec".to_string()];
+    app.execute_reboot().await.expect("custom reboot");
+    assert_eq!(system.calls("systemctl"), ["kexec"]);
+}
+
+#[tokio::test]
+async fn poweroff_warns_about_other_sessions() {
+    let system = MockSystem::new();
+    system.script(
+        "loginctl",
+        Scripted {
+            stdout: "c1 42 gdm seat0 tty1\n3 1001 alice seat0 tty2\n5 1002 bob - pts/0\n7 1001 alice - pts/1\n"
+                .to_string(),
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+
+    press(&mut app, KeyCode::Char('q')).await;
+    assert_eq!(app.app_state, AppState::SessionsWarning);
+    assert!(app.pending_system_action.is_none());
+    assert!(screen(&app).contains("2 other users are logged in: alice, bob"));
+
+    press(&mut app, KeyCode::Esc).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+
+    press(&mut app, KeyCode::Char('q')).await;
+    press(&mut app, KeyCode::Char('y')).await;
+    assert!(matches!(
+        app.pending_system_action,
+        Some(SystemAction::Poweroff)
+    ));
+    assert_eq!(system.calls("loginctl"), ["list-sessions --no-legend"; 2]);
+}
+
+#[tokio::test]
+async fn poweroff_goes_ahead_when_alone() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+
+    press(&mut app, KeyCode::Char('q')).await;
+
+    assert!(matches!(app.app_state, AppState::Processing(_)));
+    assert!(matches!(
+        app.pending_system_action,
+        Some(SystemAction::Poweroff)
+    ));
+}
+
+#[tokio::test]
+async fn screens_describe_their_focus_order() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+
+    let tree = a11y::screen(&app);
+    assert_eq!(tree.nodes[0].role, a11y::Role::Heading);
+    assert_eq!(tree.focus_order().count(), app.options.len());
+    let focused = tree.focused().expect("menu focus");
+    assert_eq!(focused.label, app.option_title(&app.options[0]));
+    assert!(
+        tree.describe()
+            .contains(&format!("> option: {}", focused.label))
+    );
+
+    press(&mut app, KeyCode::Down).await;
+    let tree = a11y::screen(&app);
+    assert_eq!(
+        tree.focused().map(|node| node.label.as_str()),
+        Some(app.option_title(&app.options[1]))
+    );
+
+    // The custom install is offered like the others
+    let custom = a11y::screen(&app)
+        .focus_order()
+        .find(|node| node.label == app.option_title(&InstallerOption::Custom))
+        .cloned()
+        .expect("the custom option");
+    assert!(!custom.disabled);
+    assert_eq!(
+        custom.description,
+        app.text.ui_text.custom_description.as_str()
+    );
+}
+
+// Ticks exactly the steps `wanted` accepts on the custom screen
+async fn pick_custom_steps(app: &mut App, wanted: impl Fn(&str) -> bool) {
+    for index in 0..app.profile.steps.len() {
+        if app.custom_picks[index] != wanted(&app.profile.steps[index].id) {
+            press(app, KeyCode::Char(' ')).await;
+        }
+        press(app, KeyCode::Down).await;
+    }
+}
+
+#[tokio::test]
+async fn custom_install_runs_only_the_picked_steps() {
+    let system = MockSystem::new();
+    let mut app = new_app();
+    select(&mut app, |option| matches!(option, InstallerOption::Custom)).await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::CustomSelect);
+    // What the default install runs starts ticked
+    let defaults: Vec<bool> = app.profile.steps.iter().map(|step| !step.opt_in).collect();
+    assert_eq!(app.custom_picks, defaults);
+    assert!(screen(&app).contains(&app.text.messages.custom_prompt));
+
+    pick_custom_steps(&mut app, |_| false).await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::CustomSelect);
+    assert!(matches!(
+        &app.status_message,
+        Some((message, StatusType::Fail)) if *message == app.text.messages.custom_none_picked
+    ));
+
+    pick_custom_steps(&mut app, |id| id == "printing").await;
+    press(&mut app, KeyCode::Enter).await;
+    assert!(app.show_confirmation);
+    assert!(
+        app.confirmation_message
+            .starts_with(&app.text.messages.confirm_custom)
+    );
+    assert!(app.confirmation_message.contains("Printing (CUPS)"));
+    assert!(!app.confirmation_message.contains("Bluetooth"));
+
+    press(&mut app, KeyCode::Char('y')).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+    let calls = system.calls("systemctl");
+    assert!(calls.iter().any(|call| call.contains("cups.service")));
+    assert!(!calls.iter().any(|call| call.contains("bluetooth.service")));
+    // The steps left out count as declined, repair does not bring them back
+    let marker = app.dotfiles_marker.as_ref().expect("install marker");
+    assert!(marker.declined.contains("dotfiles"));
+    assert!(!marker.declined.contains("printing"));
+}
+
+#[tokio::test]
+async fn install_metrics_go_to_the_report_and_the_textfile() {
+    let _system = MockSystem::new();
+    let textfile =
+        std::env::temp_dir().join(format!("sparrow-metrics-{}.prom", std::process::id()));
+    let mut app = new_app();
+    app.settings.metrics.textfile = textfile.display().to_string();
+    select(&mut app, |option| matches!(option, InstallerOption::Custom)).await;
+    press(&mut app, KeyCode::Enter).await;
+    pick_custom_steps(&mut app, |id| id == "printing").await;
+    press(&mut app, KeyCode::Enter).await;
+    press(&mut app, KeyCode::Char('y')).await;
+
+    let report = report::RunReport::load().expect("report saved");
+    assert_eq!(report.metrics.len(), 1);
+    assert_eq!(report.metrics[0].step, "printing");
+    assert_eq!(report.metrics[0].bytes_downloaded, 0);
+    assert_eq!(report.metrics[0].retries, 0);
+
+    let written = std::fs::read_to_string(&textfile).expect("textfile written");
+    let _ = std::fs::remove_file(&textfile);
+    assert!(written.contains("# TYPE sparrow_installer_step_duration_seconds gauge"));
+    assert!(written.contains("sparrow_installer_last_run_success{operation=\"Custom\"} 1"));
+    assert!(
+        written
+            .contains("sparrow_installer_step_retries{operation=\"Custom\",step=\"printing\"} 0")
+    );
+}
+
+#[tokio::test]
+async fn plain_mode_picks_options_by_number() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    let mut app = new_app();
+    let update = app
+        .options
+        .iter()
+        .position(|option| matches!(option, InstallerOption::UpdateSystem))
+        .expect("update option");
+
+    plain::command(&mut app, &(update + 1).to_string()).await;
+    assert_eq!(app.app_state, AppState::PasswordInput);
+    plain::command(&mut app, "hunter2").await;
+    settle(&mut app).await;
+    assert_eq!(app.app_state, AppState::RebootSelect);
+    assert!(a11y::screen(&app).describe().contains(&format!(
+        "option: {} (3 of 3)",
+        app.text.messages.reboot_later
+    )));
+
+    plain::command(&mut app, "3").await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert_eq!(
+        report::RunReport::load().and_then(|report| report.reboot),
+        Some(report::RebootChoice::Later)
+    );
+
+    plain::command(&mut app, "install everything").await;
+    assert!(matches!(
+        &app.status_message,
+        Some((message, StatusType::Error)) if *message == app.text.messages.plain_unknown
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn held_keys_do_not_answer_a_new_dialog() {
+    let system = MockSystem::new();
+    system.script(
+        "loginctl",
+        Scripted {
+            stdout: "3 1001 alice seat0 tty2\n".to_string(),
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+    app.settings.input.confirm_delay_ms = 400;
+
+    press(&mut app, KeyCode::Char('q')).await;
+    assert_eq!(app.app_state, AppState::SessionsWarning);
+
+    // Too soon after the dialog appeared
+    press(&mut app, KeyCode::Char('y')).await;
+    assert_eq!(app.app_state, AppState::SessionsWarning);
+
+    tokio::time::advance(Duration::from_millis(500)).await;
+    // Auto-repeat of a held key is never taken as an answer
+    let repeat = KeyEvent::new_with_kind(
+        KeyCode::Char('y'),
+        crossterm::event::KeyModifiers::NONE,
+        KeyEventKind::Repeat,
+    );
+    handle_key(&mut app, repeat).await;
+    assert_eq!(app.app_state, AppState::SessionsWarning);
+
+    press(&mut app, KeyCode::Char('y')).await;
+    assert!(matches!(
+        app.pending_system_action,
+        Some(SystemAction::Poweroff)
+    ));
+}
+
+#[tokio::test]
+async fn keys_act_once_and_only_without_control_or_alt() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    let key = |code, modifiers, kind| KeyEvent::new_with_kind(code, modifiers, kind);
+
+    // kitty's keyboard protocol reports the press, repeats and the release
+    for kind in [
+        KeyEventKind::Press,
+        KeyEventKind::Repeat,
+        KeyEventKind::Release,
+    ] {
+        handle_key(&mut app, key(KeyCode::Down, KeyModifiers::NONE, kind)).await;
+    }
+    assert_eq!(app.selected, 1);
+
+    handle_key(
+        &mut app,
+        key(
+            KeyCode::Char('q'),
+            KeyModifiers::CONTROL,
+            KeyEventKind::Press,
+        ),
+    )
+    .await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+
+    // AltGr arrives as Control+Alt and still types into a field
+    app.show_path_input(
+        PathPurpose::SupportBundle,
+        String::new(),
+        path_input::PathKind::NewFile,
+    );
+    handle_key(
+        &mut app,
+        key(
+            KeyCode::Char('@'),
+            KeyModifiers::CONTROL | KeyModifiers::ALT,
+            KeyEventKind::Press,
+        ),
+    )
+    .await;
+    assert_eq!(
+        app.path_input.as_ref().map(|input| input.value.as_str()),
+        Some("@")
+    );
+}
+
+#[tokio::test]
+async fn menu_entries_are_picked_by_number() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    let update = app
+        .options
+        .iter()
+        .position(|option| matches!(option, InstallerOption::UpdateSystem))
+        .expect("update option");
+    let number = char::from(b'1' + update as u8);
+
+    assert!(screen(&app).contains(&format!(
+        "{}. {}",
+        number,
+        app.option_title(&InstallerOption::UpdateSystem)
+    )));
+
+    press(&mut app, KeyCode::Char(number)).await;
+    assert_eq!(app.selected, update);
+    assert_eq!(app.app_state, AppState::PasswordInput);
+
+    // Numbers past the end of the menu do nothing
+    press(&mut app, KeyCode::Esc).await;
+    press(&mut app, KeyCode::Char('9')).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert_eq!(app.selected, update);
+}
+
+#[tokio::test]
+async fn failed_run_log_is_saved_through_the_path_prompt() {
+    let _system = MockSystem::new();
+    let home = PathBuf::from(std::env::var_os("HOME").expect("mock home"));
+    std::fs::create_dir(home.join("logs")).expect("logs directory");
+    let mut app = new_app();
+    app.log_lines = vec!["step fonts failed".to_string()];
+    app.app_state = AppState::LogView;
+
+    press(&mut app, KeyCode::Char('s')).await;
+    assert_eq!(app.app_state, AppState::PathInput);
+    let typed = app.path_input.as_ref().expect("prompt").value.clone();
+    assert_eq!(typed, format!("~/{}", app.settings.logging.file_name));
+    for _ in typed.chars() {
+        press(&mut app, KeyCode::Backspace).await;
+    }
+
+    type_text(&mut app, "~/missing/run.log").await;
+    press(&mut app, KeyCode::Enter).await;
+    assert!(screen(&app).contains(&app.text.messages.path_no_parent));
+
+    for _ in "missing/run.log".chars() {
+        press(&mut app, KeyCode::Backspace).await;
+    }
+    type_text(&mut app, "lo").await;
+    press(&mut app, KeyCode::Tab).await;
+    assert_eq!(app.path_input.as_ref().expect("prompt").value, "~/logs/");
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::PathInput);
+    assert!(screen(&app).contains(&app.text.messages.path_not_file));
+
+    type_text(&mut app, "run.log").await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::LogView);
+    assert!(matches!(
+        &app.status_message,
+        Some((_, StatusType::Success))
+    ));
+    assert_eq!(
+        std::fs::read_to_string(home.join("logs/run.log")).expect("saved log"),
+        "step fonts failed\n"
+    );
+}
+
+#[tokio::test]
+async fn a_paste_fills_the_field_without_pressing_enter() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.log_lines = vec!["step fonts failed".to_string()];
+    app.app_state = AppState::LogView;
+
+    press(&mut app, KeyCode::Char('s')).await;
+    let typed = app.path_input.as_ref().expect("prompt").value.clone();
+    for _ in typed.chars() {
+        press(&mut app, KeyCode::Backspace).await;
+    }
+    handle_paste(&mut app, "~/run.log\n~/other.log\n");
+    assert_eq!(app.app_state, AppState::PathInput);
+    assert_eq!(app.path_input.as_ref().expect("prompt").value, "~/run.log");
+
+    app.app_state = AppState::PasswordInput;
+    handle_paste(&mut app, "hun\tter2\r\n");
+    assert_eq!(app.app_state, AppState::PasswordInput);
+    assert_eq!(app.password_input, "hunter2");
+}
+
+#[tokio::test]
+async fn report_is_saved_to_a_usb_drive() {
+    let system = MockSystem::new();
+    let home = PathBuf::from(std::env::var_os("HOME").expect("mock home"));
+    let usb = home.join("usb");
+    std::fs::create_dir(&usb).expect("usb mount");
+    // An internal disk next to a stick, the stick's size in the string form of older lsblk
+    system.script(
+        "lsblk",
+        Scripted {
+            stdout: format!(
+                r#"{{"blockdevices": [
+                    {{"name": "nvme0n1", "label": null, "mountpoint": null, "rm": false,
+                      "hotplug": false, "size": 512110190592, "tran": "nvme", "children": [
+                        {{"name": "nvme0n1p2", "label": "root", "mountpoint": "/", "rm": false,
+                          "hotplug": false, "size": 511036448768, "tran": null}}]}},
+                    {{"name": "sdb", "label": null, "mountpoint": null, "rm": "1",
+                      "hotplug": "1", "size": "16008609792", "tran": "usb", "children": [
+                        {{"name": "sdb1", "label": "STICK", "mountpoint": "{}", "rm": "1",
+                          "hotplug": "1", "size": "16008609792", "tran": null}}]}}]}}"#,
+                usb.display()
+            ),
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+    let mut failed = report::RunReport::start("Default", false);
+    failed.install = true;
+    failed.finish(&Err(anyhow::anyhow!("fonts step failed")));
+    failed.save().expect("report saved");
+    app.last_failure = Some(failed);
+    app.app_state = AppState::RecoverySelect;
+    app.recovery_cursor = RECOVERY_ACTIONS
+        .iter()
+        .position(|action| *action == RecoveryAction::Export)
+        .expect("export action");
+
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::VolumeSelect);
+    assert_eq!(app.volumes.len(), 1);
+    assert!(screen(&app).contains("STICK (16.0 GB)"));
+
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::RecoverySelect);
+    assert!(matches!(
+        &app.status_message,
+        Some((_, StatusType::Success))
+    ));
+    let saved: Vec<PathBuf> = std::fs::read_dir(&usb)
+        .expect("usb contents")
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    assert_eq!(saved.len(), 1);
+    let report = std::fs::read_to_string(saved[0].join("last-report.json")).expect("copied report");
+    assert!(report.contains("fonts step failed"));
+}
+
+#[tokio::test]
+async fn support_bundle_is_collected_and_redacted() {
+    let system = MockSystem::new();
+    let home = PathBuf::from(std::env::var_os("HOME").expect("mock home"));
+    system.script(
+        "bootc",
+        Scripted {
+            stdout: "Booted image: ghcr.io/voidusx/sparrow:latest\n".to_string(),
+            ..Scripted::default()
+        },
+    );
+    system.script(
+        "journalctl",
+        Scripted {
+            stdout: "sddm[812]: login failed, token=abc123xyz\n".to_string(),
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::SupportBundle)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::PathInput);
+    press(&mut app, KeyCode::Enter).await;
+
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert!(matches!(
+        &app.status_message,
+        Some((_, StatusType::Success))
+    ));
+    assert_eq!(system.calls("bootc"), ["status"]);
+    let bundle = std::fs::read_dir(&home)
+        .expect("home contents")
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.to_string_lossy().ends_with(".tar.gz"))
+        .expect("bundle written");
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(
+        std::fs::File::open(&bundle).expect("bundle"),
+    ));
+    let mut contents = BTreeMap::new();
+    for entry in archive.entries().expect("entries") {
+        let mut entry = entry.expect("entry");
+        let name = entry.path().expect("name").to_string_lossy().into_owned();
+        let mut text = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut text).expect("text");
+        contents.insert(name, text);
+    }
+    let entry = |suffix: &str| {
+        contents
+            .iter()
+            .find(|(name, _)| name.ends_with(suffix))
+            .map(|(_, text)| text.clone())
+            .unwrap_or_else(|| panic!("{} in the bundle", suffix))
+    };
+    assert!(entry("/bootc-status.txt").contains("ghcr.io/voidusx/sparrow:latest"));
+    let journal = entry("/journal.txt");
+    assert!(journal.contains("[REDACTED]"));
+    assert!(!journal.contains("abc123xyz"));
+    entry("/hardware/uname.txt");
+}
+
+#[tokio::test]
+async fn failure_is_reported_through_a_saved_bug_report() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    system.script(
+        "bootc",
+        Scripted {
+            stderr: "error: No space left on device\n".to_string(),
+            status: 1,
+            ..Scripted::default()
+        },
+    );
+    let home = PathBuf::from(std::env::var_os("HOME").expect("mock home"));
+    let mut app = new_app();
+
+    start_update(&mut app, "hunter2").await;
+    assert_eq!(app.app_state, AppState::Failed);
+
+    press(&mut app, KeyCode::Char('b')).await;
+    assert_eq!(app.app_state, AppState::BugReport);
+    let report = app.bug_report.clone().expect("composed report");
+    assert_eq!(report.operation, "update");
+    assert_eq!(report.category, bug_report::Category::DiskFull);
+    // Without an endpoint the report can only be saved
+    assert_eq!(app.bug_report_actions(), [BugReportAction::Save]);
+    assert!(
+        report
+            .issue_link(&app.settings.bug_report.issue_url)
+            .is_some_and(|link| link.contains("disk-full"))
+    );
+
+    press(&mut app, KeyCode::Enter).await;
+    assert_eq!(app.app_state, AppState::PathInput);
+    press(&mut app, KeyCode::Enter).await;
+
+    assert_eq!(app.app_state, AppState::Failed);
+    assert!(matches!(
+        &app.status_message,
+        Some((_, StatusType::Success))
+    ));
+    let saved = std::fs::read_to_string(home.join("sparrow-bug-report.md")).expect("saved report");
+    assert!(saved.contains("No space left on device"));
+    assert!(saved.contains("Category: disk-full"));
+    assert!(!saved.contains("hunter2"));
+}
+
+#[tokio::test]
+async fn missing_tools_switch_features_off() {
+    let mut system = MockSystem::new();
+    system.remove("bootc");
+    system.remove("systemctl");
+    let app = new_app();
+
+    // Nothing to update with, but a dry run still shows what updating looks like
+    assert!(!app.options.contains(&InstallerOption::UpdateSystem));
+    assert!(
+        new_app_with(true)
+            .options
+            .contains(&InstallerOption::UpdateSystem)
+    );
+
+    app.execute_reboot().await.expect("reboot through loginctl");
+    assert_eq!(system.calls("loginctl"), ["reboot"]);
+}
+
+#[tokio::test]
+async fn plan_lists_steps_before_the_install_is_confirmed() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.profile.steps[0].condition = Some("false".to_string());
+    let first = app.profile.steps[0].name.clone();
+    let second = app.profile.steps[1].name.clone();
+
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::Default)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    // Take the defaults on whatever wizard screens the profile has
+    while app.app_state != AppState::Plan {
+        assert!(!app.show_confirmation, "confirmation before the plan");
+        press(&mut app, KeyCode::Enter).await;
+    }
+
+    let skipped = app
+        .plan_lines
+        .iter()
+        .position(|line| *line == app.text.messages.plan_skipped)
+        .expect("skipped section");
+    assert!(
+        app.plan_lines[..skipped]
+            .iter()
+            .any(|line| line.contains(&second))
+    );
+    assert!(
+        app.plan_lines[skipped..]
+            .iter()
+            .any(|line| line.contains(&first) && line.contains("`false` is false"))
+    );
+    assert!(
+        app.plan_lines
+            .last()
+            .is_some_and(|line| line.starts_with(&app.text.messages.plan_estimate))
+    );
+    assert!(screen(&app).contains(&app.text.messages.plan_run));
+
+    press(&mut app, KeyCode::Enter).await;
+    assert!(app.show_confirmation);
+    press(&mut app, KeyCode::Esc).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+}
+
+#[tokio::test]
+async fn dry_run_lists_what_the_install_would_do() {
+    let system = MockSystem::new();
+    let mut app = new_app_with(true);
+    let script = match &app.profile.steps[0].action {
+        pipeline::StepAction::Script(path) => path.clone(),
+        action => panic!("the first step runs {:?}", action),
+    };
+
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::Default)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    while !app.show_confirmation {
+        press(&mut app, KeyCode::Enter).await;
+    }
+    press(&mut app, KeyCode::Char('y')).await;
+
+    assert_eq!(app.app_state, AppState::DryRunResult);
+    let dry_run = &app.text.dry_run;
+    assert_eq!(app.dry_run_lines[0], dry_run.simulation_header);
+    assert!(
+        app.dry_run_lines
+            .contains(&format!("  {} bash {}", dry_run.would_execute, script))
+    );
+    assert!(screen(&app).contains(&dry_run.would_execute));
+    assert!(system.calls("systemctl").is_empty());
+
+    press(&mut app, KeyCode::Esc).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+}
+
+#[tokio::test]
+async fn embedded_installer_plans_and_dry_runs_without_the_interface() {
+    let system = MockSystem::new();
+    let mut installer = engine::Installer::new(
+        engine::InstallerConfig::default()
+            .dry_run(true)
+            .components(["printing"]),
+    )
+    .expect("installer");
+
+    let plan = installer.plan();
+    let planned = |name: &str| plan.run.iter().any(|(label, _)| label == name);
+    assert!(planned("Printing (CUPS)"));
+    assert!(!planned("Bluetooth"));
+    assert!(plan.seconds > 0);
+
+    let (progress, _updates) = tokio::sync::mpsc::unbounded_channel();
+    let outcome = installer.run(progress).await.expect("dry run");
+    assert!(
+        outcome
+            .dry_run
+            .iter()
+            .any(|line| line.contains("cups.service"))
+    );
+    assert!(
+        !outcome
+            .dry_run
+            .iter()
+            .any(|line| line.contains("bluetooth"))
+    );
+    assert!(system.calls("systemctl").is_empty());
+}
+
+#[tokio::test]
+async fn sandboxed_dry_run_lists_the_files_steps_changed() {
+    let system = MockSystem::new();
+    // Writes into the overlay's upper layer the way a step inside it would
+    system.program(
+        "bwrap",
+        r#"while [ $# -gt 0 ]; do
+    [ "$1" = "--overlay" ] && upper="$2"
+    shift
+done
+mkdir -p "$upper/.config/sparrow"
+echo changed > "$upper/.config/sparrow/preview.conf"
+"#,
+    );
+    let mut app = new_app_with(true);
+    app.sandbox = true;
+
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::Default)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    while !app.show_confirmation {
+        press(&mut app, KeyCode::Enter).await;
+    }
+    press(&mut app, KeyCode::Char('y')).await;
+
+    assert_eq!(app.app_state, AppState::DryRunResult);
+    let home = std::env::var("HOME").expect("mock home");
+    let created = format!("  {} {}/.config", app.text.dry_run.would_create, home);
+    assert!(
+        app.dry_run_lines.contains(&created),
+        "{:?}",
+        app.dry_run_lines
+    );
+    assert!(!std::path::Path::new(&home).join(".config").exists());
+}
+
+#[tokio::test]
+async fn untrusted_scripts_run_in_bwrap_without_network() {
+    let system = MockSystem::new();
+    system.program("bwrap", r#"echo "$*" > "$HOME/bwrap-args""#);
+    let app = new_app();
+    let step: pipeline::StepConfig = toml::from_str(
+        r#"
+        id = "vendor"
+        name = "Vendor tweaks"
+        script = "/etc/sparrow-installer/actions.d/vendor.sh"
+        untrusted = true
+        writable = ["~/.config/vendor"]
+        "#,
+    )
+    .expect("step");
+
+    step.run(&app.step_context(), &mut Vec::new())
+        .await
+        .expect("sandboxed run");
+
+    let home = std::env::var("HOME").expect("mock home");
+    let args = std::fs::read_to_string(format!("{}/bwrap-args", home)).expect("bwrap ran");
+    assert!(args.starts_with("--unshare-all "));
+    assert!(!args.contains("--share-net"));
+    assert!(args.contains(&format!(
+        "--bind {0}/.config/vendor {0}/.config/vendor",
+        home
+    )));
+    assert!(args.ends_with("-- bash /etc/sparrow-installer/actions.d/vendor.sh\n"));
+}
+
+#[tokio::test]
+async fn privileged_helper_runs_only_vetted_commands() {
+    let system = MockSystem::new();
+    let (installer, helper_end) = tokio::io::duplex(4096);
+    let (helper_reader, helper_writer) = tokio::io::split(helper_end);
+    let profile: pipeline::ProfileConfig = toml::from_str(
+        r#"
+        [[steps]]
+        id = "dconf"
+        name = "Desktop defaults"
+        dconf = { keyfile = "/usr/share/sparrow/00-sparrow" }
+        "#,
+    )
+    .expect("profile");
+    let scope = helper::scope(&profile);
+    assert!(
+        scope
+            .targets
+            .contains(&PathBuf::from("/etc/dconf/profile/user"))
+    );
+    let served = tokio::spawn(helper::serve(
+        tokio::io::BufReader::new(helper_reader),
+        helper_writer,
+        scope,
+    ));
+    let (reader, writer) = tokio::io::split(installer);
+    helper::connect(reader, writer).await.expect("helper ready");
+
+    let output = steps::privileged_command("bootc")
+        .args(["status", "--json"])
+        .output()
+        .await
+        .expect("bootc through the helper");
+    assert!(output.status.success());
+    assert_eq!(system.calls("bootc"), ["status --json"]);
+    // Nothing went through sudo, the helper already is root
+    assert!(system.calls("sudo").is_empty());
+
+    let output = steps::privileged_command("rm")
+        .arg("-rf")
+        .arg("/")
+        .output()
+        .await
+        .expect("answer from the helper");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not a command"));
+
+    // System files, and backups of them, are written with install as copy steps do
+    system.program("install", r#"echo "$*" >> "$HOME/install-calls""#);
+    let home = std::env::var("HOME").expect("mock home");
+    let source = format!("{}/user", home);
+    std::fs::write(&source, "user-db:user\n").expect("source");
+    for dest in [
+        "/etc/dconf/profile/user",
+        "/etc/dconf/profile/user.sparrow-bak-20261016120000",
+    ] {
+        let output = steps::privileged_command("install")
+            .args(["-D", "-m", "0644", &source, dest])
+            .output()
+            .await
+            .expect("answer from the helper");
+        assert!(output.status.success(), "{}", dest);
+    }
+    let installed =
+        std::fs::read_to_string(format!("{}/install-calls", home)).expect("install ran");
+    assert_eq!(installed.lines().count(), 2);
+    // The helper installs its own copy of what the installer could read
+    assert!(!installed.contains(&source));
+
+    // Allowed programs with arguments no step uses
+    for argv in [
+        "bootc switch quay.io/someone/else",
+        "systemctl link /tmp/evil.service",
+        "systemctl enable --root=/ sshd.service",
+        "systemctl enable --now sshd.service",
+        "touch /etc/nologin",
+        "chown user /etc/shadow",
+        "chown --reference=/tmp/mine /etc/dconf/profile/user",
+        "install -D -m 0644 /tmp/sudoers /etc/sudoers",
+        "install -D -m 4755 /tmp/sh /etc/dconf/profile/user",
+        "install -D -m 0644 /tmp/sudoers /etc/dconf/profile/../../sudoers",
+        "install -D -m 0644 -t /etc /tmp/sudoers",
+        "install -D -m 0644 /tmp/sudoers /etc/dconf/profile/user.sparrow-bak-later",
+        "install -D -m 0644 /tmp/sudoers /etc/sudoers.sparrow-bak-20261016120000",
+        "restorecon -R -F",
+    ] {
+        let (program, args) = argv.split_once(' ').expect("program and arguments");
+        let output = steps::privileged_command(program)
+            .args(args.split(' '))
+            .output()
+            .await
+            .expect("answer from the helper");
+        assert!(!output.status.success(), "{} ran", argv);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("does not run"),
+            "{}",
+            argv
+        );
+    }
+    assert_eq!(system.calls("bootc"), ["status --json"]);
+
+    helper::stop().await;
+    served
+        .await
+        .expect("helper task")
+        .expect("helper exits cleanly");
+    assert!(!helper::running());
+}
+
+#[tokio::test]
+async fn tee_output_streams_the_log_and_command_output() {
+    use std::os::fd::AsRawFd;
+
+    let system = MockSystem::new();
+    system.password("hunter2");
+    system.script(
+        "bootc",
+        Scripted {
+            stdout: "Fetching layers\nQueued for next boot\n".to_string(),
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+    let home = PathBuf::from(std::env::var_os("HOME").expect("mock home"));
+    app.settings.logging.directory = home.display().to_string();
+    let verbosity = logging::Verbosity::resolve("info", 0, false, None);
+    let log = logging::init(&app.settings.logging, verbosity, logging::LogFormat::Json)
+        .expect("installer log");
+    let tee = std::fs::File::create(home.join("tee")).expect("tee file");
+    logging::tee(tee.as_raw_fd()).expect("tee");
+    drop(tee);
+
+    start_update(&mut app, "hunter2").await;
+    logging::close();
+
+    let teed = std::fs::read_to_string(home.join("tee")).expect("teed output");
+    assert!(teed.contains("update: Fetching layers"));
+    assert!(teed.contains("update: Queued for next boot"));
+    assert!(!teed.contains("hunter2"));
+    assert!(!teed.starts_with('{'));
+    // The file keeps its own level and format
+    let logged = std::fs::read_to_string(log).expect("installer log");
+    assert!(!logged.contains("Fetching layers"));
+    assert!(logging::tee(2).is_err());
+}
+
+#[tokio::test]
+async fn passwords_are_redacted_only_while_their_operation_runs() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    let mut app = new_app();
+    app.settings.logging.directory = std::env::var("HOME").expect("mock home");
+    let verbosity = logging::Verbosity::resolve("info", 0, false, None);
+    logging::init(&app.settings.logging, verbosity, logging::LogFormat::Text)
+        .expect("installer log");
+
+    // A turned down password is never taken into the log's secrets
+    start_update(&mut app, "swordfish").await;
+    assert_eq!(app.app_state, AppState::PasswordInput);
+    assert_eq!(logging::redact("swordfish"), "swordfish");
+
+    type_text(&mut app, "hunter2").await;
+    press(&mut app, KeyCode::Enter).await;
+    let held = logging::redact("hunter2");
+    let finished = app.app_state.clone();
+    logging::close();
+
+    assert_eq!(finished, AppState::RebootSelect);
+    assert!(app.password_input.is_empty());
+    assert_eq!(held, "hunter2");
+}
+
+#[tokio::test]
+async fn privileged_commands_are_written_to_the_audit_log() {
+    // Only root writes the audit log
+    if !steps::is_root() {
+        return;
+    }
+    let system = MockSystem::new();
+    let mut app = new_app();
+    let home = std::env::var("HOME").expect("mock home");
+    app.settings.audit.directory = home.clone();
+    let path = audit::init(&app.settings.audit)
+        .expect("audit log")
+        .expect("enabled");
+
+    steps::privileged_command("bootc")
+        .arg("status")
+        .output()
+        .await
+        .expect("bootc");
+    system.script(
+        "systemctl",
+        Scripted {
+            status: 1,
+            ..Scripted::default()
+        },
+    );
+    steps::privileged_command("systemctl")
+        .args(["enable", "sddm.service"])
+        .output()
+        .await
+        .expect("systemctl");
+    audit::close();
+
+    let entries: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+        .expect("audit log written")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("JSON entry"))
+        .collect();
+    let entry = |program: &str| {
+        entries
+            .iter()
+            .find(|entry| entry["argv"].to_string().contains(program))
+            .cloned()
+            .expect("command audited")
+    };
+    let bootc = entry("bootc");
+    assert!(bootc["argv"].to_string().ends_with(r#""bootc","status"]"#));
+    assert_eq!(bootc["success"], true);
+    assert_eq!(bootc["via"], "direct");
+    assert!(bootc["timestamp"].is_string());
+    assert!(bootc["uid"].is_number());
+    let systemctl = entry("systemctl");
+    assert_eq!(systemctl["success"], false);
+    assert_eq!(systemctl["status"], 1);
+}
+
+#[tokio::test]
+async fn actions_ask_for_what_the_config_wants() {
+    let system = MockSystem::new();
+    let mut app = new_app();
+    app.settings.confirmation.update = Gate::Confirm;
+
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::UpdateSystem)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    // No password prompt, straight to the dialog
+    assert!(app.show_confirmation);
+    assert_eq!(
+        app.confirmation_message,
+        app.text.messages.confirm_system_update
+    );
+    press(&mut app, KeyCode::Char('n')).await;
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert!(system.calls("bootc").is_empty());
+
+    app.settings.confirmation.update = Gate::Both;
+    system.password("hunter2");
+    start_update(&mut app, "hunter2").await;
+    assert!(app.show_confirmation);
+    assert!(system.calls("bootc").is_empty());
+    press(&mut app, KeyCode::Char('y')).await;
+    assert_eq!(system.calls("bootc"), ["update"]);
+    assert_eq!(app.app_state, AppState::RebootSelect);
+}
+
+#[tokio::test(start_paused = true)]
+async fn status_notes_clear_after_a_while_but_errors_stay() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.settings.status.display_seconds = 5;
+
+    app.status_message = Some(("Saved".to_string(), StatusType::Success));
+    app.expire_status();
+    tokio::time::advance(Duration::from_secs(4)).await;
+    app.expire_status();
+    assert!(app.status_message.is_some());
+    assert!(app.status_fading());
+    tokio::time::advance(Duration::from_secs(1)).await;
+    app.expire_status();
+    assert!(app.status_message.is_none());
+
+    app.status_message = Some(("Broken".to_string(), StatusType::Error));
+    app.expire_status();
+    tokio::time::advance(Duration::from_secs(60)).await;
+    app.expire_status();
+    assert!(!app.status_fading());
+    assert!(screen(&app).contains("Broken"));
+}
+
+#[tokio::test]
+async fn health_banner_lists_what_the_checks_found() {
+    let system = MockSystem::new();
+    system.script(
+        "bootc",
+        Scripted {
+            stdout: r#"{"status": {"staged": {"image": "sparrow:latest"}}}"#.to_string(),
+            ..Scripted::default()
+        },
+    );
+    let mut app = new_app();
+    app.settings.health.network_probe = String::new();
+    app.settings.health.disk_paths = vec!["/".to_string(), "/no/such/folder".to_string()];
+    app.settings.health.min_free_mb = u64::MAX;
+
+    app.health = health::check(&app.settings.health).await;
+
+    assert_eq!(
+        app.health,
+        [
+            health::Issue::LowDisk("/".to_string()),
+            health::Issue::StagedUpdate
+        ]
+    );
+    let messages = &app.text.messages;
+    assert!(screen(&app).contains(&format!(
+        "{} {} / · {}",
+        messages.health_prefix, messages.health_low_disk, messages.health_staged
+    )));
+}
+
+#[tokio::test(start_paused = true)]
+async fn session_limit_counts_down_and_powers_off() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.settings.session.limit_minutes = 1;
+    app.settings.session.warning_seconds = 30;
+    app.settings.session.action = SessionEnd::Poweroff;
+
+    tokio::time::advance(Duration::from_secs(20)).await;
+    app.check_session();
+    assert_eq!(app.session_remaining, None);
+
+    tokio::time::advance(Duration::from_secs(25)).await;
+    app.check_session();
+    assert_eq!(app.session_remaining, Some(15));
+    let warning = app.session_warning().expect("warning");
+    assert!(warning.contains(&app.text.messages.session_poweroff));
+    assert!(screen(&app).contains(&warning));
+    assert!(!app.should_quit);
+
+    // A running operation is let finish
+    app.progress_type = Some(ProgressType::Indeterminate);
+    tokio::time::advance(Duration::from_secs(15)).await;
+    app.check_session();
+    assert!(!app.should_quit);
+
+    app.progress_type = None;
+    app.check_session();
+    assert!(app.should_quit);
+    assert!(matches!(
+        app.pending_system_action,
+        Some(SystemAction::Poweroff)
+    ));
+}
+
+#[tokio::test]
+async fn hint_bar_follows_the_screen() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    let keys = app.text.keys.clone();
+
+    let menu = screen(&app);
+    assert!(menu.contains(&format!("Enter {}", keys.select)));
+    assert!(menu.contains(&format!("q {}", keys.quit)));
+
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::UpdateSystem)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    let password = screen(&app);
+    assert!(password.contains(&format!("Tab {}", keys.show_password)));
+    assert!(!password.contains(&format!("q {}", keys.quit)));
+
+    press(&mut app, KeyCode::Esc).await;
+    app.app_state = AppState::Failed;
+    assert!(!screen(&app).contains(&format!("r {}", keys.recover)));
+    assert!(screen(&app).contains(&format!("b {}", keys.report)));
+}
+
+#[tokio::test]
+async fn title_row_shows_the_version_and_mode_badges() {
+    let _system = MockSystem::new();
+    let mut app = new_app_with(true);
+    app.health = vec![health::Issue::Offline];
+
+    let title = screen(&app);
+    let title = title.lines().next().expect("title row");
+    assert!(title.contains(&format!("v{}", env!("CARGO_PKG_VERSION"))));
+    assert!(title.contains(&format!(
+        "{} {}",
+        app.theme.ui.dry_run_icon, app.text.ui_text.dry_run_indicator
+    )));
+    assert!(title.contains(&app.text.ui_text.offline_indicator));
+    assert!(!title.contains(&app.text.ui_text.unattended_indicator));
+
+    // A broken clock format leaves the clock out instead of crashing
+    app.theme.ui.show_clock = true;
+    app.theme.ui.clock_format = "%Q".to_string();
+    assert!(screen(&app).contains(&app.text.ui_text.app_title));
+}
+
+#[tokio::test]
+async fn dry_runs_are_marked_as_simulations() {
+    let _system = MockSystem::new();
+    let watermark = |app: &App| format!(" {} ", app.text.ui_text.simulation_watermark);
+
+    let mut app = new_app_with(true);
+    app.progress_type = Some(ProgressType::Indeterminate);
+    app.app_state = AppState::Processing(String::new());
+    assert!(screen(&app).contains(&watermark(&app)));
+    let dry_run_fg = parse_color(&app.theme.colors.dry_run_fg).expect("dry_run_fg");
+    assert_eq!(app.styles.border.fg, Some(dry_run_fg));
+
+    let mut app = new_app();
+    app.progress_type = Some(ProgressType::Indeterminate);
+    app.app_state = AppState::Processing(String::new());
+    assert!(!screen(&app).contains(&watermark(&app)));
+    assert_ne!(app.styles.border.fg, Some(dry_run_fg));
+}
+
+#[tokio::test]
+async fn confirmation_details_list_the_commands_when_expanded() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.settings.confirmation.update = Gate::Confirm;
+
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::UpdateSystem)
+    })
+    .await;
+    press(&mut app, KeyCode::Enter).await;
+    assert!(app.show_confirmation);
+    let command = "$ sudo bootc update";
+    let folded = screen(&app);
+    assert!(folded.contains(&format!("▸ {}", app.text.messages.confirmation_details)));
+    assert!(!folded.contains(command));
+
+    press(&mut app, KeyCode::Char('d')).await;
+    assert!(screen(&app).contains(command));
+    press(&mut app, KeyCode::Char('d')).await;
+    assert!(!screen(&app).contains(command));
+
+    // Turned off, the dialog has no section and d does nothing
+    press(&mut app, KeyCode::Esc).await;
+    app.settings.confirmation.details = false;
+    press(&mut app, KeyCode::Enter).await;
+    press(&mut app, KeyCode::Char('d')).await;
+    let plain = screen(&app);
+    assert!(!plain.contains(&app.text.messages.confirmation_details));
+    assert!(!plain.contains(command));
+}
+
+#[tokio::test]
+async fn menu_icons_are_left_out_in_ascii_mode() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.ascii = false;
+    let update = format!("⟳ {}", app.option_title(&InstallerOption::UpdateSystem));
+    assert!(screen(&app).contains(&update));
+
+    let mut app = new_app();
+    app.ascii = true;
+    app.theme.icons.insert("exit".to_string(), "x".to_string());
+    let menu = screen(&app);
+    assert!(!menu.contains(&update));
+    assert!(menu.contains(app.option_title(&InstallerOption::UpdateSystem)));
+    // Plain ASCII icons stay
+    assert!(menu.contains(&format!("x {}", app.option_title(&InstallerOption::Exit))));
+}
+
+#[tokio::test]
+async fn long_menus_scroll_to_the_selection() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.ascii = true;
+    let first = app.option_title(&app.options[0]).to_string();
+    let last = app.option_title(app.options.last().unwrap()).to_string();
+
+    // Four rows of menu, fewer than there are options
+    let menu = screen_sized(&app, 60, 9);
+    assert!(menu.contains(&first));
+    assert!(!menu.contains(&last));
+    assert!(menu.contains('#'));
+
+    // Wrapping around from the top brings the last entry into view
+    press(&mut app, KeyCode::Up).await;
+    let menu = screen_sized(&app, 60, 9);
+    assert!(menu.contains(&last));
+    assert!(!menu.contains(&first));
+
+    press(&mut app, KeyCode::Down).await;
+    assert!(screen_sized(&app, 60, 9).contains(&first));
+}
+
+#[tokio::test]
+async fn highlighted_options_show_their_description_document() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.documents.insert(
+        "update".to_string(),
+        "# Updating\nStages **the image** until `reboot`.\n\n- One\n- Two".to_string(),
+    );
+    assert!(!screen(&app).contains("Updating"));
+
+    select(&mut app, |option| {
+        matches!(option, InstallerOption::UpdateSystem)
+    })
+    .await;
+    let panel = screen(&app);
+    assert!(panel.contains("Updating"));
+    assert!(panel.contains("Stages the image until reboot."));
+    assert!(panel.contains("• One"));
+
+    press(&mut app, KeyCode::PageDown).await;
+    assert_eq!(app.document_scroll, 4);
+    assert!(!screen(&app).contains("Updating"));
+    press(&mut app, KeyCode::PageUp).await;
+    assert!(screen(&app).contains("Updating"));
+
+    press(&mut app, KeyCode::PageDown).await;
+    press(&mut app, KeyCode::Down).await;
+    assert_eq!(app.document_scroll, 0);
+    assert!(!screen(&app).contains("• One"));
+}
+
+#[tokio::test]
+async fn password_screen_says_what_it_is_for() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    start_update_prompt(&mut app).await;
+
+    let reason = format!(
+        "{} {}",
+        app.text.messages.password_reason,
+        app.option_title(&InstallerOption::UpdateSystem)
+    );
+    let prompt = screen(&app);
+    assert!(prompt.contains(&reason));
+    assert!(prompt.contains(&app.text.messages.password_label));
+    assert!(a11y::screen(&app).describe().contains(&reason));
+}
+
+#[tokio::test]
+async fn password_screen_summarizes_the_operation() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    start_update_prompt(&mut app).await;
+    assert_eq!(app.password_summary().as_deref(), Some("sudo bootc update"));
+    assert!(screen(&app).contains("sudo bootc update"));
+    press(&mut app, KeyCode::Esc).await;
+    assert_eq!(app.password_summary(), None);
+
+    app.show_password_input(InstallerOption::Default);
+    let steps = app.install_plan().run.len();
+    let summary = app.password_summary().expect("summary");
+    assert!(summary.starts_with(&format!("{} {}", steps, app.text.messages.password_steps)));
+    assert!(summary.contains(&app.text.messages.plan_estimate));
+    assert!(screen(&app).contains(&summary));
+}
+
+#[tokio::test(start_paused = true)]
+async fn tour_walks_through_the_screens_and_ends_in_a_dry_run() {
+    let system = MockSystem::new();
+    let mut app = new_app_with(true);
+    tour::start(&mut app).await;
+    let stops = app.text.tour.stops.clone();
+
+    let mut screens = Vec::new();
+    for (i, stop) in stops.iter().enumerate() {
+        let shown = screen(&app);
+        assert!(shown.contains(&stop.title));
+        assert!(shown.contains(&format!(" {}/{} ", i + 1, stops.len())));
+        assert_eq!(
+            a11y::screen(&app).focused().map(|node| &node.label),
+            Some(&stop.title)
+        );
+        screens.push(app.app_state.clone());
+        press(&mut app, KeyCode::Enter).await;
+    }
+    assert_eq!(
+        screens,
+        [
+            AppState::MainMenu,
+            AppState::Plan,
+            AppState::Confirmation,
+            AppState::PasswordInput,
+            AppState::Processing(app.text.messages.progress_updating.clone()),
+        ]
+    );
+
+    // Past the last stop the installer is left to explore, still only simulating
+    assert_eq!(app.tour, None);
+    assert_eq!(app.app_state, AppState::MainMenu);
+    assert!(app.progress_type.is_none());
+    assert!(app.dry_run);
+    assert!(matches!(
+        &app.status_message,
+        Some((message, _)) if *message == app.text.tour.finished
+    ));
+    assert!(system.calls("bootc").is_empty());
+    assert!(system.calls("sudo").is_empty());
+}
+
+#[tokio::test]
+async fn tour_goes_back_and_ends_on_escape() {
+    let _system = MockSystem::new();
+    let mut app = new_app_with(true);
+    tour::start(&mut app).await;
+
+    press(&mut app, KeyCode::Left).await;
+    assert_eq!(app.tour, Some(tour::Tour { stop: 0 }));
+    press(&mut app, KeyCode::Right).await;
+    press(&mut app, KeyCode::Right).await;
+    assert_eq!(app.app_state, AppState::Confirmation);
+    press(&mut app, KeyCode::Left).await;
+    assert_eq!(app.app_state, AppState::Plan);
+
+    // Esc ends the tour instead of acting on the screen below
+    press(&mut app, KeyCode::Esc).await;
+    assert_eq!(app.tour, None);
+    assert_eq!(app.app_state, AppState::MainMenu);
+}
+
+#[tokio::test]
+async fn progress_is_mirrored_to_the_console_when_asked() {
+    let system = MockSystem::new();
+    system.password("hunter2");
+    let console =
+        std::path::PathBuf::from(std::env::var_os("HOME").expect("mock home")).join("console");
+    std::fs::write(&console, "").expect("console");
+    let mut settings = SettingsConfig::load().expect("settings.toml");
+    settings.input.confirm_delay_ms = 0;
+    settings.privileges.helper = false;
+    settings.console.mirror = true;
+    settings.console.path = console.display().to_string();
+    let mut app = App::new(false, settings).expect("app");
+
+    start_update(&mut app, "hunter2").await;
+
+    let mirrored = std::fs::read_to_string(&console).expect("console");
+    assert!(mirrored.contains(&format!(
+        "sparrow-installer: {}\r\n",
+        app.text.messages.progress_updating
+    )));
+    assert!(mirrored.contains(&app.text.messages.operation_success));
+    assert!(!mirrored.contains("hunter2"));
+}
+
+#[tokio::test]
+async fn backdrop_is_drawn_beside_the_menu_on_large_terminals() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.backdrop = Some(backdrop::parse(
+        "\x1b[36m/\\_ART_/\\\x1b[0m",
+        app.color_support,
+    ));
+    assert!(!screen(&app).contains("/\\_ART_/\\"));
+
+    let large = screen_sized(&app, 130, 40);
+    let row = large
+        .lines()
+        .find(|row| row.contains("/\\_ART_/\\"))
+        .expect("backdrop on screen");
+    assert!(row.trim_end().ends_with("/\\_ART_/\\"));
+    assert!(large.contains(app.option_title(&app.options[0])));
+
+    app.theme.backdrop.placement = BackdropPlacement::Behind;
+    assert!(screen_sized(&app, 130, 40).contains("ART"));
+}
+
+#[tokio::test]
+async fn theme_borders_frame_the_elements_that_ask_for_one() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    let plain = screen(&app);
+    assert!(!plain.starts_with('┌'));
+
+    app.theme.borders.title = true;
+    app.theme.borders.menu = true;
+    app.theme.borders.description = true;
+    app.theme.modifiers.status = vec!["italic".to_string()];
+    app.set_color_support(app.color_support);
+    let framed = screen(&app);
+    let rows: Vec<&str> = framed.lines().collect();
+    assert!(rows[0].starts_with('┌'));
+    assert!(framed.contains(&app.text.ui_text.app_title));
+    assert!(framed.contains(app.option_title(&app.options[0])));
+    // Title, menu and description each have their own top corner
+    assert_eq!(rows.iter().filter(|row| row.starts_with('┌')).count(), 3);
+    assert!(
+        app.styles
+            .status_text
+            .add_modifier
+            .contains(Modifier::ITALIC)
+    );
+}
+
+#[tokio::test]
+async fn theme_border_type_shapes_or_removes_the_pane_borders() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    app.show_plan();
+    assert!(screen(&app).contains('┌'));
+
+    app.theme.borders.kind = BorderKind::Rounded;
+    let rounded = screen(&app);
+    assert!(rounded.contains('╭'));
+    assert!(!rounded.contains('┌'));
+
+    app.theme.borders.kind = BorderKind::None;
+    app.theme.borders.menu = true;
+    let borderless = screen(&app);
+    assert!(!borderless.contains('╭') && !borderless.contains('│'));
+    assert!(borderless.contains(app.plan_lines[0].trim()));
+
+    // A pane can lose its border while the others keep theirs
+    app.theme.borders.kind = BorderKind::Double;
+    app.theme.borders.viewer = false;
+    assert!(!screen(&app).contains('╔'));
+}
+
+// A tar holding `link` pointing at `target` and then a file written through it
+fn link_archive(path: &std::path::Path, kind: tar::EntryType, target: &str) {
+    let mut builder = tar::Builder::new(std::fs::File::create(path).expect("archive"));
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(kind);
+    header.set_path("theme/link").expect("link path");
+    header.set_link_name_literal(target).expect("link target");
+    header.set_size(0);
+    header.set_cksum();
+    builder
+        .append(&header, std::io::empty())
+        .expect("link entry");
+    let mut header = tar::Header::new_gnu();
+    header.set_path("theme/link").expect("file path");
+    header.set_size(5);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append(&header, &b"owned"[..]).expect("file entry");
+    builder.finish().expect("archive written");
+}
+
+#[tokio::test]
+async fn archive_links_cannot_reach_outside_the_destination() {
+    let _system = MockSystem::new();
+    let home = PathBuf::from(std::env::var_os("HOME").expect("mock home"));
+    let outside = home.join("secret");
+    std::fs::write(&outside, "kept").expect("outside file");
+    let ctx = new_app().step_context();
+
+    for (name, kind, target) in [
+        ("relative-symlink", tar::EntryType::Symlink, "../../secret"),
+        (
+            "absolute-symlink",
+            tar::EntryType::Symlink,
+            outside.to_str().unwrap(),
+        ),
+        ("relative-hardlink", tar::EntryType::Link, "../secret"),
+        (
+            "absolute-hardlink",
+            tar::EntryType::Link,
+            outside.to_str().unwrap(),
+        ),
+    ] {
+        let archive = home.join(format!("{}.tar", name));
+        link_archive(&archive, kind, target);
+        let dest = home.join(name);
+        let result = steps::extract::unpack_archive("theme", &archive, &dest, 0, &ctx);
+        assert!(result.is_err(), "{} was unpacked", name);
+        assert!(
+            dest.join("theme/link").symlink_metadata().is_err(),
+            "{}",
+            name
+        );
+        assert_eq!(
+            std::fs::read_to_string(&outside).expect("outside file"),
+            "kept"
+        );
+    }
+
+    // Links within the archive still unpack
+    let archive = home.join("inside.tar");
+    link_archive(&archive, tar::EntryType::Symlink, "../theme");
+    let dest = home.join("inside");
+    std::fs::create_dir_all(dest.join("theme")).expect("theme directory");
+    steps::extract::unpack_archive("theme", &archive, &dest, 0, &ctx).expect("inside link");
+    assert!(dest.join("theme/link").symlink_metadata().is_ok());
+}
+
+// Answers one connection per response in turn, handing back the requests it got
+fn serve_http(responses: Vec<String>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("listener");
+    let url = format!(
+        "http://{}/wallpapers.tar",
+        listener.local_addr().expect("address")
+    );
+    let server = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut stream, _) = listener.accept().expect("connection");
+            let mut request = Vec::new();
+            let mut byte = [0u8];
+            while !request.ends_with(b"\r\n\r\n") {
+                std::io::Read::read_exact(&mut stream, &mut byte).expect("request");
+                request.push(byte[0]);
+            }
+            requests.push(String::from_utf8_lossy(&request).to_lowercase());
+            std::io::Write::write_all(&mut stream, response.as_bytes()).expect("response");
+        }
+        requests
+    });
+    (url, server)
+}
+
+#[tokio::test]
+async fn downloads_resume_finish_and_restart_their_part_file() {
+    let _system = MockSystem::new();
+    let home = PathBuf::from(std::env::var_os("HOME").expect("mock home"));
+    let ctx = new_app().step_context();
+    let dest = home.join("wallpapers.tar");
+    let part = home.join("wallpapers.tar.part");
+    // sha256 of "hello"
+    let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    // The whole file is in the part already, the server has nothing after it
+    std::fs::write(&part, "hello").expect("part file");
+    let (url, server) = serve_http(vec![
+        "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */5\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    ]);
+    let file = steps::download::Download {
+        url,
+        dest: dest.display().to_string(),
+        sha256: Some(sha256.to_string()),
+    };
+    steps::download::download("wallpapers", &file, &ctx)
+        .await
+        .expect("completed part file");
+    assert_eq!(std::fs::read_to_string(&dest).expect("dest"), "hello");
+    assert!(!part.exists());
+    assert!(server.join().expect("server")[0].contains("range: bytes=5-"));
+
+    // A server without range support sends the whole file, it replaces the part
+    std::fs::remove_file(&dest).expect("dest removed");
+    std::fs::write(&part, "he").expect("part file");
+    let (url, server) = serve_http(vec![
+        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello".to_string(),
+    ]);
+    let file = steps::download::Download { url, ..file };
+    steps::download::download("wallpapers", &file, &ctx)
+        .await
+        .expect("restarted download");
+    assert_eq!(std::fs::read_to_string(&dest).expect("dest"), "hello");
+    assert!(server.join().expect("server")[0].contains("range: bytes=2-"));
+
+    // Without a checksum a file already there is kept, the server is not asked
+    let (url, server) = serve_http(Vec::new());
+    let file = steps::download::Download {
+        url,
+        sha256: None,
+        ..file
+    };
+    steps::download::download("wallpapers", &file, &ctx)
+        .await
+        .expect("kept download");
+    assert!(server.join().expect("server").is_empty());
+}
+
+#[tokio::test]
+async fn per_user_steps_expand_home_for_their_own_account() {
+    let _system = MockSystem::new();
+    let app = new_app();
+    let root = std::env::var("HOME").expect("mock home");
+    let users: Vec<pipeline::TargetUser> = ["ada", "bo"]
+        .iter()
+        .map(|name| {
+            let home = format!("{}/users/{}", root, name);
+            std::fs::create_dir_all(&home).expect("home");
+            // Root's own uid, so the steps run in this process rather than as the account
+            pipeline::TargetUser {
+                name: name.to_string(),
+                home,
+                uid: 0,
+                gid: 0,
+            }
+        })
+        .collect();
+    std::fs::write(format!("{}/.hushlogin", users[0].home), "").expect("marker");
+    std::fs::write(format!("{}/bashrc", root), "alias ll='ls -l'\n").expect("source");
+    let step: pipeline::StepConfig = toml::from_str(&format!(
+        r#"
+        id = "bashrc"
+        name = "Shell aliases"
+        condition = 'file_exists("~/.hushlogin")'
+        copy = {{ source = "{}/bashrc", dest = "~/.bashrc" }}
+        "#,
+        root
+    ))
+    .expect("step");
+
+    let planned = pipeline::plan_steps([&step], &users);
+    assert_eq!(planned.len(), 2);
+    assert_eq!(planned[0].skip_reason().expect("condition"), None);
+    assert!(planned[1].skip_reason().expect("condition").is_some());
+
+    let ctx = app.step_context();
+    let (mut ada, mut bo) = (Vec::new(), Vec::new());
+    let (first, second) = tokio::join!(
+        planned[0].run(&ctx, &mut ada),
+        planned[1].run(&ctx, &mut bo)
+    );
+    first.expect("ada");
+    second.expect("bo");
+    for user in &users {
+        assert!(std::path::Path::new(&user.home).join(".bashrc").exists());
+    }
+    assert!(!std::path::Path::new(&root).join(".bashrc").exists());
+}
+
+#[tokio::test]
+async fn menu_options_for_other_architectures_are_left_out() {
+    let _system = MockSystem::new();
+    let mut app = new_app();
+    assert!(app.options.contains(&InstallerOption::UpdateSystem));
+    let entry = |arch: &str, condition: Option<&str>| MenuEntryConfig {
+        arch: vec![arch.to_string()],
+        condition: condition.map(str::to_string),
+    };
+    let menu = &mut app.settings.menu;
+    menu.insert("update".to_string(), entry("sparrow-no-such-arch", None));
+    menu.insert(
+        "support-bundle".to_string(),
+        entry(std::env::consts::ARCH, Some("false")),
+    );
+    menu.insert(
+        "custom".to_string(),
+        entry(std::env::consts::ARCH, Some("which sh")),
+    );
+    app.refresh_options();
+
+    assert_eq!(
+        app.options,
+        [
+            InstallerOption::Default,
+            InstallerOption::Custom,
+            InstallerOption::Exit
+        ]
+    );
+    let shown = screen(&app);
+    assert!(!shown.contains(&app.text.ui_text.update_title));
+    assert!(shown.contains(&app.text.ui_text.custom_title));
+}
+
+#[tokio::test]
+async fn git_checkouts_stay_when_the_remote_cannot_be_reached() {
+    let _system = MockSystem::new();
+    let app = new_app();
+    let ctx = app.step_context();
+    let home = std::env::var("HOME").expect("mock home");
+    let source = format!("{}/source", home);
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args([
+                "-c",
+                "user.name=Sparrow",
+                "-c",
+                "user.email=sparrow@localhost",
+            ])
+            .args(args)
+            .status()
+            .expect("git");
+        assert!(status.success(), "git {:?}", args);
+    };
+    git(&["init", "--quiet", &source]);
+    git(&[
+        "-C",
+        &source,
+        "commit",
+        "--quiet",
+        "--allow-empty",
+        "-m",
+        "init",
+    ]);
+
+    let mut checkout = steps::git::GitCheckout {
+        url: source,
+        reference: None,
+        dest: "~/checkout".to_string(),
+        depth: None,
+    };
+    steps::git::checkout("dotfiles", &checkout, &ctx)
+        .await
+        .expect("first checkout");
+
+    // Nothing listens on the discard port, like a remote that is offline
+    checkout.url = "http://127.0.0.1:9/dotfiles.git".to_string();
+    steps::git::checkout("dotfiles", &checkout, &ctx)
+        .await
+        .expect("existing checkout is kept");
+    assert!(std::path::Path::new(&home).join("checkout/.git").exists());
+
+    checkout.dest = "~/fresh".to_string();
+    let error = steps::git::checkout("dotfiles", &checkout, &ctx)
+        .await
+        .expect_err("nothing to fall back to");
+    assert!(
+        error
+            .to_string()
+            .contains("Cannot fetch http://127.0.0.1:9")
+    );
+}
@@ -0,0 +1,95 @@
+//! The installer without its terminal interface, for front ends such as the welcome center
+//! that embed the engine instead of running the TUI.
+//!
+//! Stable within a major version: `Installer` with `new`, `plan` and `run`,
+//! `InstallerConfig`, `Outcome`, and the `Plan`, `StepProgress`, `ChecklistItem` and
+//! `CheckStatus` they hand out. Fields may be added to `InstallerConfig` (built from
+//! `Default` with its setters) and to `Outcome`, nothing is renamed or removed without a
+//! major release.
+
+pub use crate::pipeline::{CheckStatus, ChecklistItem, StepProgress};
+pub use crate::plan::Plan;
+
+use crate::progress::ChannelSink;
+use crate::{App, AppState, SettingsConfig};
+use anyhow::Result;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// What to install, e.g. `InstallerConfig::default().dry_run(true)`
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct InstallerConfig {
+    /// Only list what the steps would do
+    pub dry_run: bool,
+    /// Ids of the optional steps to include, the rest are declined
+    pub components: Vec<String>,
+}
+
+impl InstallerConfig {
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn components(mut self, components: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.components = components.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// What a finished run did
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Outcome {
+    /// Empty when the dotfiles were installed and up to date already
+    pub checklist: Vec<ChecklistItem>,
+    /// Verification checks that failed after the steps succeeded
+    pub failed_checks: Vec<String>,
+    /// What a dry run would have done, one line per step or change
+    pub dry_run: Vec<String>,
+}
+
+/// Plans and runs the profile's steps, one run at a time per system
+pub struct Installer {
+    app: App,
+}
+
+impl Installer {
+    /// Loads the embedded settings and the profile, with the optional steps picked from
+    /// `config.components`
+    pub fn new(config: InstallerConfig) -> Result<Self> {
+        let mut app = App::new(config.dry_run, SettingsConfig::load()?)?;
+        for (step, picked) in app
+            .profile
+            .optional_steps()
+            .zip(app.component_picks.iter_mut())
+        {
+            *picked = config.components.contains(&step.id);
+        }
+        Ok(Self { app })
+    }
+
+    /// The steps that would run and those left out, with an estimate of how long it takes
+    pub fn plan(&self) -> Plan {
+        self.app.install_plan()
+    }
+
+    /// Installs the dotfiles, sending the progress of each step to `progress` as it goes
+    pub async fn run(&mut self, progress: UnboundedSender<StepProgress>) -> Result<Outcome> {
+        let app = &mut self.app;
+        app.take_lock("embedded")?;
+        if app.app_state == AppState::Locked {
+            anyhow::bail!("{}", app.text.messages.locked_prompt);
+        }
+        app.progress.add(ChannelSink(progress));
+        let result = app.run_unattended().await;
+        app.instance_lock = None;
+        app.control = None;
+        result?;
+        Ok(Outcome {
+            checklist: app.checklist.clone(),
+            failed_checks: app.failed_checks.clone(),
+            dry_run: app.dry_run_lines.clone(),
+        })
+    }
+}
@@ -0,0 +1,382 @@
+use crate::{logging, tools};
+use anyhow::{Context, Result};
+use cli_log::Level;
+use serde::Deserialize;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::process::Stdio;
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command as AsyncCommand};
+
+// Not every image ships sudo, the first of these that is installed gets root
+const BACKENDS: [&dyn Escalation; 4] = [&Sudo, &Doas, &Run0, &Pkexec];
+
+static PREFERRED: Mutex<Preference> = Mutex::new(Preference::Auto);
+
+// Which backend to use, set in [privileges]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preference {
+    Auto,
+    // run0, then pkexec: the password never passes through the installer
+    Polkit,
+    Sudo,
+    Doas,
+    Run0,
+    Pkexec,
+}
+
+// How a backend learns the password
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordInput {
+    // Read as a line from stdin
+    Stdin,
+    // Read from the controlling terminal only, the installer answers on a pseudo-terminal
+    Terminal,
+    // Asked by the session's polkit agent, the installer has nothing to ask
+    Agent,
+}
+
+pub trait Escalation: Sync {
+    fn program(&self) -> &'static str;
+    fn password_input(&self) -> PasswordInput;
+    // Arguments before the program when it may authenticate
+    fn prompt_args(&self) -> &'static [&'static str];
+    // Arguments before the program when it must fail instead of asking
+    fn non_interactive_args(&self) -> &'static [&'static str];
+    // Whether `error` is the backend turning down the password
+    fn wrong_password(&self, error: &str) -> bool;
+    // Arguments that only check the password, without them `true` runs as root
+    fn validate_args(&self) -> Option<&'static [&'static str]> {
+        None
+    }
+}
+
+struct Sudo;
+struct Doas;
+struct Run0;
+struct Pkexec;
+
+impl Escalation for Sudo {
+    fn program(&self) -> &'static str {
+        "sudo"
+    }
+
+    fn password_input(&self) -> PasswordInput {
+        PasswordInput::Stdin
+    }
+
+    // -k makes sudo always read the password line, cached credentials would leave it
+    // for the program to read
+    fn prompt_args(&self) -> &'static [&'static str] {
+        &["-k", "-S", "-p", ""]
+    }
+
+    fn non_interactive_args(&self) -> &'static [&'static str] {
+        &["-n"]
+    }
+
+    fn wrong_password(&self, error: &str) -> bool {
+        error.contains("Sorry, try again") || error.contains("incorrect password")
+    }
+
+    fn validate_args(&self) -> Option<&'static [&'static str]> {
+        Some(&["-k", "-S", "-p", "", "-v"])
+    }
+}
+
+impl Escalation for Doas {
+    fn program(&self) -> &'static str {
+        "doas"
+    }
+
+    fn password_input(&self) -> PasswordInput {
+        PasswordInput::Terminal
+    }
+
+    fn prompt_args(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn non_interactive_args(&self) -> &'static [&'static str] {
+        &["-n"]
+    }
+
+    fn wrong_password(&self, error: &str) -> bool {
+        error.contains("Authentication failed")
+    }
+}
+
+// A polkit agent of its own would draw over the interface, run0 only starts one on a
+// terminal and the helper's stdin is a pipe
+impl Escalation for Run0 {
+    fn program(&self) -> &'static str {
+        "run0"
+    }
+
+    fn password_input(&self) -> PasswordInput {
+        PasswordInput::Agent
+    }
+
+    fn prompt_args(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn non_interactive_args(&self) -> &'static [&'static str] {
+        &["--no-ask-password"]
+    }
+
+    // The agent lets the user try again itself, a refusal is final
+    fn wrong_password(&self, _error: &str) -> bool {
+        false
+    }
+}
+
+impl Escalation for Pkexec {
+    fn program(&self) -> &'static str {
+        "pkexec"
+    }
+
+    fn password_input(&self) -> PasswordInput {
+        PasswordInput::Agent
+    }
+
+    fn prompt_args(&self) -> &'static [&'static str] {
+        &["--disable-internal-agent"]
+    }
+
+    // pkexec has no way to refuse asking, without a session agent it fails
+    fn non_interactive_args(&self) -> &'static [&'static str] {
+        &["--disable-internal-agent"]
+    }
+
+    fn wrong_password(&self, _error: &str) -> bool {
+        false
+    }
+}
+
+pub fn prefer(preference: Preference) {
+    *PREFERRED.lock().unwrap_or_else(|e| e.into_inner()) = preference;
+}
+
+// The backend this system has, looked up each time as PATH may change under the tests.
+// When the configured one is not installed sudo and the rest stand in, in their order.
+pub fn current() -> Option<&'static dyn Escalation> {
+    let preferred = *PREFERRED.lock().unwrap_or_else(|e| e.into_inner());
+    let installed = |backend: &&dyn Escalation| tools::find(backend.program()).is_some();
+    BACKENDS
+        .into_iter()
+        .filter(|backend| preferred.allows(backend.program()))
+        .find(installed)
+        .or_else(|| BACKENDS.into_iter().find(installed))
+}
+
+pub fn require() -> Result<&'static dyn Escalation> {
+    current().context("None of sudo, doas, run0 or pkexec is installed on this system")
+}
+
+// Whether the installer has to ask for the password itself
+pub fn asks_password() -> bool {
+    current().is_none_or(|backend| backend.password_input() != PasswordInput::Agent)
+}
+
+pub fn wrong_password(error: &str) -> bool {
+    current().is_some_and(|backend| backend.wrong_password(error))
+}
+
+// "doas bootc update" for the confirmation's command list
+pub fn describe(command: &str) -> String {
+    format!("{} {}", current().unwrap_or(&Sudo).program(), command)
+}
+
+impl Preference {
+    fn allows(self, program: &str) -> bool {
+        match self {
+            Preference::Auto => true,
+            Preference::Polkit => matches!(program, "run0" | "pkexec"),
+            Preference::Sudo => program == "sudo",
+            Preference::Doas => program == "doas",
+            Preference::Run0 => program == "run0",
+            Preference::Pkexec => program == "pkexec",
+        }
+    }
+}
+
+// Overwrites a password before letting go of it, clear() alone leaves the bytes behind
+pub fn wipe(secret: &mut String) {
+    // SAFETY: zero bytes are valid UTF-8, the volatile writes are not optimized away
+    unsafe {
+        for byte in secret.as_mut_vec().iter_mut() {
+            std::ptr::write_volatile(byte, 0);
+        }
+    }
+    secret.clear();
+}
+
+// `program` as root, authenticating once `spawn` starts it
+pub fn command(backend: &dyn Escalation, program: impl AsRef<OsStr>) -> AsyncCommand {
+    let mut cmd = AsyncCommand::new(backend.program());
+    cmd.args(backend.prompt_args()).arg(program);
+    cmd
+}
+
+// `program` as root on credentials the backend still has, failing instead of asking.
+// Without any backend this names sudo, so the error says what is missing.
+pub fn non_interactive(program: impl AsRef<OsStr>) -> AsyncCommand {
+    let backend = current().unwrap_or(&Sudo);
+    let mut cmd = AsyncCommand::new(backend.program());
+    cmd.args(backend.non_interactive_args()).arg(program);
+    cmd
+}
+
+// Checks `password` before anything runs with it, Ok(false) when the backend turned it down
+pub async fn validate(backend: &dyn Escalation, password: &str) -> Result<bool> {
+    let mut cmd = match backend.validate_args() {
+        Some(args) => {
+            let mut cmd = AsyncCommand::new(backend.program());
+            cmd.args(args);
+            cmd
+        }
+        None => command(backend, "true"),
+    };
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // Closing stdin ends sudo's asking again after a wrong one
+    let output = spawn(backend, &mut cmd, password)
+        .await?
+        .wait_with_output()
+        .await?;
+    if output.status.success() {
+        return Ok(true);
+    }
+    let error = String::from_utf8_lossy(&output.stderr);
+    if backend.wrong_password(&error) {
+        Ok(false)
+    } else {
+        anyhow::bail!("{}", error.trim())
+    }
+}
+
+// Starts `cmd` from `command` and gives the backend the password the way it reads it.
+// With Stdin the command needs a piped stdin, the rest of it stays open for the program.
+pub async fn spawn(
+    backend: &dyn Escalation,
+    cmd: &mut AsyncCommand,
+    password: &str,
+) -> Result<Child> {
+    match backend.password_input() {
+        PasswordInput::Stdin => {
+            let mut child = cmd.spawn()?;
+            let stdin = child
+                .stdin
+                .as_mut()
+                .context("No stdin to pass the password on")?;
+            let mut line = String::with_capacity(password.len() + 1);
+            line.push_str(password);
+            line.push('\n');
+            let written = stdin.write_all(line.as_bytes()).await;
+            wipe(&mut line);
+            written?;
+            stdin.flush().await?;
+            Ok(child)
+        }
+        PasswordInput::Terminal => spawn_on_terminal(cmd, password),
+        PasswordInput::Agent => Ok(cmd.spawn()?),
+    }
+}
+
+// doas reads /dev/tty, so the command gets a pseudo-terminal as its controlling terminal
+// while its stdio stays as the caller set it up
+fn spawn_on_terminal(cmd: &mut AsyncCommand, password: &str) -> Result<Child> {
+    let (master, slave) = open_pty().context("Could not open a pseudo-terminal")?;
+    let slave_fd = slave.as_raw_fd();
+    // SAFETY: only async-signal-safe calls between fork and exec
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setsid() < 0 || libc::ioctl(slave_fd, libc::TIOCSCTTY, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = cmd.spawn()?;
+
+    // Owns the terminal until the program exits, closing it early would hang it up
+    let pid = child.id().context("The command exited right away")?;
+    // Room for the newline, growing would leave a copy behind
+    let mut answer = String::with_capacity(password.len() + 1);
+    answer.push_str(password);
+    std::thread::spawn(move || answer_prompt(File::from(master), slave, pid, answer));
+    Ok(child)
+}
+
+// Answers the first prompt once it shows, typing earlier is flushed when echo goes off.
+// The slave stays open here until the program is gone: between one open of /dev/tty and
+// the next the program holds none, the master would read as hung up and hang it up.
+// With nopass or persist no prompt comes and the output is only drained.
+fn answer_prompt(mut terminal: File, _slave: OwnedFd, pid: u32, mut password: String) {
+    let mut shown = Vec::new();
+    let mut buffer = [0u8; 256];
+    loop {
+        let mut poll = libc::pollfd {
+            fd: terminal.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: one valid pollfd
+        let ready = unsafe { libc::poll(&mut poll, 1, 200) };
+        if ready <= 0 {
+            // SAFETY: signal 0 only checks that the process exists
+            if unsafe { libc::kill(pid as libc::pid_t, 0) } != 0 {
+                break;
+            }
+            continue;
+        }
+        let read = match terminal.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        if password.is_empty() {
+            continue;
+        }
+        shown.extend_from_slice(&buffer[..read]);
+        if String::from_utf8_lossy(&shown).trim_end().ends_with(':') {
+            password.push('\n');
+            if let Err(e) = terminal.write_all(password.as_bytes()) {
+                logging::event!(Level::Warn, "escalation", "Could not answer doas: {}", e);
+            }
+            wipe(&mut password);
+        }
+    }
+}
+
+fn open_pty() -> io::Result<(OwnedFd, OwnedFd)> {
+    // SAFETY: each fd is checked before it is wrapped, ptsname_r writes into `name`
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if master < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let master = OwnedFd::from_raw_fd(master);
+        let mut name = [0 as libc::c_char; 128];
+        if libc::grantpt(master.as_raw_fd()) != 0
+            || libc::unlockpt(master.as_raw_fd()) != 0
+            || libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len()) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        let slave = libc::open(
+            name.as_ptr(),
+            libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC,
+        );
+        if slave < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((master, OwnedFd::from_raw_fd(slave)))
+    }
+}
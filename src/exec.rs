@@ -0,0 +1,309 @@
+use crate::ticks::Tick;
+use crate::{
+    App, AppState, InstallerOption, RecoveryAction, StatusType, SystemAction, logging, monitors,
+    pipeline, power, recovery, report, state, steps, sysroot, ticks, worker,
+};
+use anyhow::Result;
+use cli_log::*;
+use std::time::Duration;
+
+impl App {
+    pub(crate) fn step_context(&self) -> pipeline::StepContext {
+        pipeline::StepContext {
+            dry_run: self.dry_run,
+            variables: self.variables.clone(),
+            progress: None,
+            network: self.settings.network.clone(),
+            selinux: self.settings.selinux.clone(),
+            counters: Default::default(),
+        }
+    }
+
+    // Installs with the defaults of every setup screen, for provisioning without a user
+    pub(crate) async fn run_unattended(&mut self) -> Result<()> {
+        if self.dotfiles_marker.is_some() && !self.dotfiles_outdated {
+            logging::event!(
+                Level::Info,
+                "unattended",
+                "Dotfiles are installed and up to date"
+            );
+            return Ok(());
+        }
+
+        self.wizard_summary.clear();
+        self.apply_wallpaper_picks();
+        if let Some(root) = sysroot::get() {
+            logging::event!(
+                Level::Info,
+                "unattended",
+                "Installing into {}, per-user files go to {}",
+                root.display(),
+                sysroot::SKEL_HOME
+            );
+            self.variables
+                .insert("username".to_string(), "root".to_string());
+            self.variables
+                .insert("home".to_string(), sysroot::SKEL_HOME.to_string());
+            self.target_users = vec![pipeline::TargetUser {
+                name: "root".to_string(),
+                home: sysroot::SKEL_HOME.to_string(),
+                uid: 0,
+                gid: 0,
+            }];
+        } else if self.profile.configures_monitors() {
+            self.monitors = monitors::detect().await;
+            if !self.monitors.is_empty() {
+                self.apply_monitor_choices();
+            }
+        }
+        for line in &self.wizard_summary {
+            logging::event!(Level::Info, "unattended", "{}", line);
+        }
+
+        self.report = Some(report::RunReport::start("unattended", self.dry_run));
+        self.start_worker(InstallerOption::Default, self.install_job());
+        let result = self
+            .wait_for_worker()
+            .await
+            .map_or(Ok(()), |(_, result)| result);
+        self.complete_report(&result);
+        if !self.failed_checks.is_empty() {
+            logging::event!(
+                Level::Warn,
+                "unattended",
+                "{} {}",
+                self.text.messages.operation_degraded,
+                self.failed_checks.join(", ")
+            );
+        }
+        result
+    }
+
+    pub(crate) fn finish_worker(&mut self, option: &InstallerOption, result: Result<()>) {
+        // If authentication failed, return to password input
+        if let Err(ref e) = result
+            && e.to_string()
+                .contains(&self.text.messages.password_auth_failed)
+        {
+            self.progress_type = None;
+            // Either the stored password was just turned down or it was before this one
+            self.credential_rejected = true;
+            self.app_state = AppState::PasswordInput;
+            self.pending_operation = Some(option.clone());
+            self.forget_password();
+            self.status_message = Some((e.to_string(), StatusType::Error));
+            return;
+        }
+
+        self.finish_operation(option, result);
+    }
+
+    // Replays the scenario of the operation through the same progress channel real steps use
+    #[cfg(feature = "simulator")]
+    pub(crate) fn start_simulation(&mut self, option: &InstallerOption) {
+        let events = match option {
+            InstallerOption::UpdateSystem => self.scenario.update.clone(),
+            _ => Vec::new(),
+        };
+        let progress = self.progress.sender();
+        let pacing = &self.text.progress;
+        let ticker = self.ticker.get_or_insert_with(ticks::Ticker::new);
+        ticker.when_done(
+            crate::simulation::play(events, progress, pacing.dry_run_pace),
+            Tick::SimulationDone,
+        );
+        if pacing.dry_run_seconds > 0 {
+            ticker.after(
+                Duration::from_secs(pacing.dry_run_seconds),
+                Tick::SimulationDone,
+            );
+        }
+    }
+
+    // Built without the simulator there is nothing to replay, the dry run ends right away
+    #[cfg(not(feature = "simulator"))]
+    pub(crate) fn start_simulation(&mut self, _option: &InstallerOption) {
+        let ticker = self.ticker.get_or_insert_with(ticks::Ticker::new);
+        ticker.after(Duration::ZERO, Tick::SimulationDone);
+    }
+
+    pub(crate) fn install_job(&self) -> worker::Job {
+        let mut marker = state::InstallMarker::new(&self.profile);
+        marker.declined = self.declined_steps().into_iter().collect();
+        marker.variables = self.variables.clone();
+        marker.users = self.target_users.clone();
+        let steps = self
+            .profile
+            .steps
+            .iter()
+            .filter(|step| !marker.declined.contains(&step.id));
+        let steps = pipeline::plan_steps(steps, &marker.users);
+        worker::Job::Pipeline { steps, marker }
+    }
+
+    // Only the picked steps run, the rest count as declined so repair leaves them out too
+    pub(crate) fn custom_job(&self) -> worker::Job {
+        let picked: Vec<&pipeline::StepConfig> = self.custom_steps().collect();
+        let mut marker = match &self.dotfiles_marker {
+            Some(marker) => marker.clone(),
+            None => {
+                let mut marker = state::InstallMarker::new(&self.profile);
+                marker.declined = self
+                    .profile
+                    .steps
+                    .iter()
+                    .filter(|step| !picked.iter().any(|picked| picked.id == step.id))
+                    .map(|step| step.id.clone())
+                    .collect();
+                marker
+            }
+        };
+        marker
+            .declined
+            .retain(|id| !picked.iter().any(|step| step.id == *id));
+        marker.variables = self.variables.clone();
+        marker.users = self.target_users.clone();
+        let steps = pipeline::plan_steps(picked, &marker.users);
+        worker::Job::Pipeline { steps, marker }
+    }
+
+    pub(crate) fn recover_job(&mut self) -> worker::Job {
+        match self.recovery_action {
+            Some(RecoveryAction::Resume) => {
+                let marker = self.recovery_marker();
+                for (name, value) in &marker.variables {
+                    self.variables
+                        .entry(name.clone())
+                        .or_insert_with(|| value.clone());
+                }
+                let steps = recovery::remaining_steps(&self.profile, &marker);
+                worker::Job::Pipeline { steps, marker }
+            }
+            Some(RecoveryAction::Rollback) => {
+                worker::Job::Rollback(std::mem::take(&mut self.rollback_plan))
+            }
+            _ => worker::Job::Nothing,
+        }
+    }
+
+    pub(crate) fn repair_job(&mut self) -> worker::Job {
+        let steps = self.repair_plan.drain(..).map(|item| item.step).collect();
+        let marker = self
+            .dotfiles_marker
+            .clone()
+            .unwrap_or_else(|| state::InstallMarker::new(&self.profile));
+        worker::Job::Pipeline { steps, marker }
+    }
+
+    // Runs `job` on its own task, the interface keeps drawing and taking keys meanwhile
+    pub(crate) fn start_worker(&mut self, option: InstallerOption, job: worker::Job) {
+        let action = match &self.app_state {
+            AppState::Processing(action) => action.clone(),
+            _ => self.text.messages.progress_installing.clone(),
+        };
+        let operation = worker::Operation {
+            job,
+            action,
+            dry_run: self.dry_run,
+            sandbox: self.sandbox,
+            password: std::mem::take(&mut self.password_input),
+            uses_helper: self.uses_helper(),
+            text: self.text.clone(),
+            settings: self.settings.clone(),
+            profile: self.profile.clone(),
+            ctx: self.step_context(),
+            report: self.report.take(),
+            installed: self.dotfiles_marker.is_some(),
+            progress: self.progress.clone(),
+        };
+        self.worker = Some(worker::spawn(option, operation));
+    }
+
+    // Takes in what the worker sent, the operation and its result once it is over
+    pub(crate) fn apply_worker_event(
+        &mut self,
+        event: worker::AppEvent,
+    ) -> Option<(InstallerOption, Result<()>)> {
+        match event {
+            worker::AppEvent::Status(message) => self.set_status(&message),
+            worker::AppEvent::Checklist(checklist) => {
+                self.checklist = checklist;
+                self.publish();
+            }
+            worker::AppEvent::Finished(done) => {
+                let worker = self.worker.take()?;
+                self.report = done.report;
+                if let Some(failed_checks) = done.failed_checks {
+                    self.failed_checks = failed_checks;
+                }
+                if let Some(marker) = done.marker {
+                    if done.result.is_ok() {
+                        self.dotfiles_outdated = false;
+                    }
+                    self.dotfiles_marker = Some(marker);
+                    self.refresh_options();
+                }
+                if let Some(lines) = done.dry_run {
+                    self.show_dry_run(lines);
+                }
+                return Some((worker.option.clone(), done.result));
+            }
+        }
+        None
+    }
+
+    // Called from the interface loop between frames
+    pub(crate) fn poll_worker(&mut self) {
+        while let Some(event) = self.worker.as_mut().and_then(worker::Worker::try_next) {
+            if let Some((option, result)) = self.apply_worker_event(event) {
+                self.finish_worker(&option, result);
+            }
+        }
+    }
+
+    // Follows the running operation to its end where nothing draws in between
+    pub(crate) async fn wait_for_worker(&mut self) -> Option<(InstallerOption, Result<()>)> {
+        while let Some(worker) = self.worker.as_mut() {
+            let event = worker.next().await;
+            if let Some(finished) = self.apply_worker_event(event) {
+                return Some(finished);
+            }
+        }
+        None
+    }
+
+    pub(crate) fn uses_helper(&self) -> bool {
+        self.settings.privileges.helper && !steps::is_root()
+    }
+
+    pub(crate) async fn execute_reboot(&self) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        power::perform(&self.settings.system_actions, &SystemAction::Reboot)
+            .await
+            .map_err(|e| anyhow::anyhow!("Reboot failed: {}", e))
+    }
+
+    pub(crate) async fn execute_poweroff(&self) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        power::perform(&self.settings.system_actions, &SystemAction::Poweroff)
+            .await
+            .map_err(|e| anyhow::anyhow!("Poweroff failed: {}", e))
+    }
+}
+
+// Replaces the installer with the command, e.g. the desktop session, and only comes back
+// when it cannot be started
+pub(crate) fn launch(command: &[String]) {
+    use std::os::unix::process::CommandExt;
+    let Some((program, args)) = command.split_first() else {
+        eprintln!("No launch_command is set, nothing to start");
+        return;
+    };
+    logging::event!(Level::Info, "operation", "Starting {}", command.join(" "));
+    let err = std::process::Command::new(program).args(args).exec();
+    eprintln!("Cannot start {}: {}", program, err);
+}
@@ -0,0 +1,198 @@
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+// The shims replace PATH and HOME for the whole process, so only one mock system at a time
+// and no test reads the environment while one is set up or torn down
+static ACTIVE: Mutex<()> = Mutex::new(());
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// Held by every test that reads PATH, HOME or other variables a mock system changes,
+// directly or through `App::new`, conditions and the like
+pub fn lock_env() -> MutexGuard<'static, ()> {
+    ACTIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Records its arguments, then prints and exits with whatever the test scripted for it
+const SHIM: &str = r#"#!/bin/sh
+root="$(dirname "$(dirname "$0")")"
+name="$(basename "$0")"
+echo "$*" >> "$root/calls/$name"
+[ -f "$root/scripts/$name.stdout" ] && cat "$root/scripts/$name.stdout"
+[ -f "$root/scripts/$name.stderr" ] && cat "$root/scripts/$name.stderr" >&2
+exit "$(cat "$root/scripts/$name.status" 2>/dev/null || echo 0)"
+"#;
+
+// Checks the password read with -S against the scripted one, then runs the command
+const SUDO_SHIM: &str = r#"#!/bin/sh
+root="$(dirname "$(dirname "$0")")"
+echo "$*" >> "$root/calls/sudo"
+while [ $# -gt 0 ]; do
+    case "$1" in
+        -S) read -r password
+            if [ "$password" != "$(cat "$root/scripts/sudo.password" 2>/dev/null)" ]; then
+                echo "Sorry, try again." >&2
+                echo "sudo: 1 incorrect password attempt" >&2
+                exit 1
+            fi
+            shift ;;
+        -p) shift 2 ;;
+        -*) shift ;;
+        *) break ;;
+    esac
+done
+[ $# -eq 0 ] && exit 0
+exec "$@"
+"#;
+
+const SHIMMED: [&str; 7] = [
+    "bootc",
+    "systemctl",
+    "sudo",
+    "shutdown",
+    "loginctl",
+    "lsblk",
+    "journalctl",
+];
+
+// What a shimmed program prints and how it exits
+#[derive(Debug, Clone, Default)]
+pub struct Scripted {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: i32,
+}
+
+// Fake `bootc`, `sudo`, `systemctl`, `shutdown`, `loginctl`, `lsblk` and `journalctl` first in PATH, plus an empty HOME for state and reports
+pub struct MockSystem {
+    root: PathBuf,
+    saved: Vec<(&'static str, Option<OsString>)>,
+    _active: MutexGuard<'static, ()>,
+}
+
+impl MockSystem {
+    pub fn new() -> Self {
+        let active = lock_env();
+        let root = std::env::temp_dir().join(format!(
+            "sparrow-mock-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["bin", "calls", "scripts", "home"] {
+            fs::create_dir_all(root.join(dir)).expect("cannot create the mock system");
+        }
+        for program in SHIMMED {
+            let shim = root.join("bin").join(program);
+            let contents = if program == "sudo" { SUDO_SHIM } else { SHIM };
+            fs::write(&shim, contents).expect("cannot write a shim");
+            fs::set_permissions(&shim, fs::Permissions::from_mode(0o755))
+                .expect("cannot make a shim executable");
+        }
+
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let mut paths = vec![root.join("bin")];
+        paths.extend(std::env::split_paths(&path));
+        let mut system = Self {
+            saved: Vec::new(),
+            root: root.clone(),
+            _active: active,
+        };
+        system.set_env("PATH", Some(std::env::join_paths(paths).unwrap_or(path)));
+        system.set_env("HOME", Some(root.join("home").into()));
+        for unset in [
+            "SUDO_USER",
+            "NOTIFY_SOCKET",
+            "WATCHDOG_USEC",
+            "CREDENTIALS_DIRECTORY",
+        ] {
+            system.set_env(unset, None);
+        }
+        system
+    }
+
+    fn set_env(&mut self, name: &'static str, value: Option<OsString>) {
+        self.saved.push((name, std::env::var_os(name)));
+        // SAFETY: tests reading the environment hold the ACTIVE lock, through a mock system
+        // or lock_env, so nothing reads it while it changes
+        unsafe {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+    }
+
+    // The password `sudo -S` accepts, every other one fails the way sudo does
+    pub fn password(&self, password: &str) {
+        fs::write(self.root.join("scripts/sudo.password"), password).expect("cannot script sudo");
+    }
+
+    pub fn script(&self, program: &str, scripted: Scripted) {
+        let scripts = self.root.join("scripts");
+        fs::write(scripts.join(format!("{}.stdout", program)), scripted.stdout)
+            .and_then(|_| fs::write(scripts.join(format!("{}.stderr", program)), scripted.stderr))
+            .and_then(|_| {
+                fs::write(
+                    scripts.join(format!("{}.status", program)),
+                    scripted.status.to_string(),
+                )
+            })
+            .expect("cannot script a shim");
+    }
+
+    // Puts a shell script of the test's own in place of `program`, for tools whose effect
+    // matters more than their output
+    pub fn program(&self, program: &str, body: &str) {
+        let path = self.root.join("bin").join(program);
+        fs::write(&path, format!("#!/bin/sh\n{}", body))
+            .and_then(|_| fs::set_permissions(&path, fs::Permissions::from_mode(0o755)))
+            .expect("cannot write a program");
+    }
+
+    // Takes `program` off the system: its shim goes and PATH keeps only the shims and the few
+    // programs they run, so a copy installed on the test machine is not found either
+    pub fn remove(&mut self, program: &str) {
+        let _ = fs::remove_file(self.root.join("bin").join(program));
+        let basics = self.root.join("basics");
+        fs::create_dir_all(&basics).expect("cannot create the basics directory");
+        for tool in ["sh", "dirname", "basename", "cat", "true"] {
+            if let Some(path) = crate::tools::find(tool) {
+                let _ = std::os::unix::fs::symlink(path, basics.join(tool));
+            }
+        }
+        let paths = std::env::join_paths([self.root.join("bin"), basics]).expect("shim paths");
+        self.set_env("PATH", Some(paths));
+    }
+
+    // Arguments of every call to a shimmed program, in order
+    pub fn calls(&self, program: &str) -> Vec<String> {
+        fs::read_to_string(self.root.join("calls").join(program))
+            .map(|calls| calls.lines().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for MockSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for MockSystem {
+    fn drop(&mut self) {
+        for (name, value) in self.saved.drain(..).rev() {
+            // SAFETY: still holding the ACTIVE lock, see set_env
+            unsafe {
+                match value {
+                    Some(value) => std::env::set_var(name, value),
+                    None => std::env::remove_var(name),
+                }
+            }
+        }
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
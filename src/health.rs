@@ -0,0 +1,90 @@
+use crate::{HealthConfig, Messages, logging, tools, verify};
+use cli_log::Level;
+use std::ffi::CString;
+use std::time::Duration;
+use tokio::sync::watch;
+
+// Something worth knowing before starting anything from the main menu
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    Offline,
+    LowDisk(String),
+    StagedUpdate,
+}
+
+impl Issue {
+    pub fn describe(&self, messages: &Messages) -> String {
+        match self {
+            Issue::Offline => messages.health_offline.clone(),
+            Issue::LowDisk(path) => format!("{} {}", messages.health_low_disk, path),
+            Issue::StagedUpdate => messages.health_staged.clone(),
+        }
+    }
+}
+
+// The issues as one line for the banner, empty when all is well
+pub fn banner(issues: &[Issue], messages: &Messages) -> String {
+    if issues.is_empty() {
+        return String::new();
+    }
+    let issues: Vec<String> = issues
+        .iter()
+        .map(|issue| issue.describe(messages))
+        .collect();
+    format!("{} {}", messages.health_prefix, issues.join(" · "))
+}
+
+// Runs the checks now and every `refresh_seconds`, the receiver always has the latest
+pub fn spawn(config: HealthConfig) -> watch::Receiver<Vec<Issue>> {
+    let (tx, rx) = watch::channel(Vec::new());
+    tokio::spawn(async move {
+        loop {
+            let issues = check(&config).await;
+            if tx.send(issues).is_err() {
+                return;
+            }
+            if config.refresh_seconds == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_secs(config.refresh_seconds)).await;
+        }
+    });
+    rx
+}
+
+pub async fn check(config: &HealthConfig) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if !config.network_probe.is_empty() && !online(config).await {
+        issues.push(Issue::Offline);
+    }
+    for path in &config.disk_paths {
+        if let Some(free) = free_mb(path)
+            && free < config.min_free_mb
+        {
+            logging::event!(Level::Debug, "health", "{} MB free on {}", free, path);
+            issues.push(Issue::LowDisk(path.clone()));
+        }
+    }
+    if tools::find("bootc").is_some() && verify::bootc_staged().await {
+        issues.push(Issue::StagedUpdate);
+    }
+    issues
+}
+
+// A TCP connection to the probe address, DNS included
+async fn online(config: &HealthConfig) -> bool {
+    let timeout = Duration::from_secs(config.probe_timeout);
+    let connect = tokio::net::TcpStream::connect(config.network_probe.as_str());
+    matches!(tokio::time::timeout(timeout, connect).await, Ok(Ok(_)))
+}
+
+// Megabytes available to unprivileged users, None for paths that do not exist
+fn free_mb(path: &str) -> Option<u64> {
+    let path = CString::new(path).ok()?;
+    // SAFETY: statvfs only writes into the zeroed struct it is given
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64 / (1024 * 1024))
+}
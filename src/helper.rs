@@ -0,0 +1,789 @@
+use crate::pipeline::{ProfileConfig, StepAction};
+use crate::{audit, escalation, logging, steps};
+use anyhow::{Context, Result, bail};
+use cli_log::Level;
+use serde::{Deserialize, Serialize};
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::io::Write;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::{ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::process::{Child, Command as AsyncCommand};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+// The only programs the helper runs as root, the ones the steps and the update need
+const ALLOWED: [&str; 8] = [
+    "bootc",
+    "systemctl",
+    "install",
+    "chown",
+    "restorecon",
+    "touch",
+    "dconf",
+    "fc-cache",
+];
+
+// Their only arguments for those with a fixed job, the rest are checked by `refusal`
+const FIXED: [(&str, &[&str]); 5] = [
+    ("bootc", &["update"]),
+    ("bootc", &["status", "--json"]),
+    ("dconf", &["update"]),
+    ("fc-cache", &["-f"]),
+    ("touch", &["/.autorelabel"]),
+];
+
+// First line from the helper once sudo or another backend let it start
+const READY: &str = "ready";
+
+static CONNECTION: Mutex<Option<Connection>> = Mutex::const_new(None);
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Serialize, Deserialize)]
+struct Request {
+    program: String,
+    args: Vec<String>,
+    // What install's source holds, staged by the helper itself so it never copies a path
+    // the installer names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    input: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Response {
+    // Wait status as the helper got it, so signals survive the trip
+    status: i32,
+    stdout: String,
+    stderr: String,
+}
+
+// What the helper sends while a command runs: its output a line at a time, then the
+// response once it exited
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Reply {
+    Line { line: String },
+    Done(Response),
+}
+
+impl Response {
+    fn refused(reason: String) -> Self {
+        Response {
+            status: ExitStatus::from_raw(1 << 8).into_raw(),
+            stdout: String::new(),
+            stderr: reason,
+        }
+    }
+}
+
+struct Connection {
+    writer: Box<dyn AsyncWrite + Unpin + Send>,
+    reader: BufReader<Box<dyn AsyncRead + Unpin + Send>>,
+    // Dropping the connection kills the helper
+    _child: Option<Child>,
+}
+
+pub fn running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+// Starts the helper as root once per session, the password is not kept after this
+pub async fn start(password: &str) -> Result<()> {
+    if running() {
+        return Ok(());
+    }
+    let backend = escalation::require()?;
+    let mut cmd = escalation::command(backend, std::env::current_exe()?);
+    cmd.arg("privileged-helper")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = escalation::spawn(backend, &mut cmd, password).await?;
+    let stdin = child.stdin.take().context("No stdin for the helper")?;
+    let reader = handshake(&mut child, backend).await?;
+
+    logging::event!(Level::Info, "helper", "Privileged helper started");
+    attach(Box::new(stdin), reader, Some(child)).await;
+    Ok(())
+}
+
+// Waits for the helper to say it is ready, giving back its stdout to read answers from.
+// Warnings on stderr (e.g. sudo not resolving the hostname) are fine as long as it comes
+// up, only its exit fails the start.
+async fn handshake(
+    child: &mut Child,
+    backend: &dyn escalation::Escalation,
+) -> Result<BufReader<Box<dyn AsyncRead + Unpin + Send>>> {
+    let stdout = child.stdout.take().context("No stdout for the helper")?;
+    let stderr = child.stderr.take().context("No stderr for the helper")?;
+
+    let mut reader: BufReader<Box<dyn AsyncRead + Unpin + Send>> = BufReader::new(Box::new(stdout));
+    let mut errors = BufReader::new(stderr);
+    let mut line = Vec::new();
+    let mut error_line = Vec::new();
+    let mut error_msg = String::new();
+    let mut stderr_open = true;
+    // read_until keeps a partial line when the other branch wins
+    loop {
+        tokio::select! {
+            result = reader.read_until(b'\n', &mut line) => {
+                if result? == 0 {
+                    break;
+                }
+                if String::from_utf8_lossy(&line).trim() == READY {
+                    return Ok(reader);
+                }
+                line.clear();
+            }
+            result = errors.read_until(b'\n', &mut error_line), if stderr_open => {
+                if result? == 0 {
+                    stderr_open = false;
+                    continue;
+                }
+                error_msg.push_str(&String::from_utf8_lossy(&error_line));
+                error_line.clear();
+                // A wrong password makes sudo ask again on the open stdin, it never exits
+                if backend.wrong_password(&error_msg) {
+                    let _ = child.kill().await;
+                    break;
+                }
+            }
+        }
+    }
+
+    let status = child.wait().await?;
+    if stderr_open {
+        let mut rest = Vec::new();
+        errors.read_to_end(&mut rest).await?;
+        error_line.extend(rest);
+    }
+    error_msg.push_str(&String::from_utf8_lossy(&error_line));
+    logging::event!(
+        Level::Error,
+        "helper",
+        "Helper exited with {}: {}",
+        status,
+        error_msg
+    );
+    match error_msg.trim() {
+        "" => bail!("The privileged helper exited with {}", status),
+        error => bail!("{}", error),
+    }
+}
+
+async fn attach(
+    writer: Box<dyn AsyncWrite + Unpin + Send>,
+    reader: BufReader<Box<dyn AsyncRead + Unpin + Send>>,
+    child: Option<Child>,
+) {
+    *CONNECTION.lock().await = Some(Connection {
+        writer,
+        reader,
+        _child: child,
+    });
+    RUNNING.store(true, Ordering::SeqCst);
+}
+
+// Talks to a helper served over `reader` and `writer` instead of one started with sudo
+#[cfg(test)]
+pub async fn connect(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+    writer: impl AsyncWrite + Unpin + Send + 'static,
+) -> Result<()> {
+    let mut reader: BufReader<Box<dyn AsyncRead + Unpin + Send>> = BufReader::new(Box::new(reader));
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    if line.trim() != READY {
+        bail!("The helper did not start");
+    }
+    attach(Box::new(writer), reader, None).await;
+    Ok(())
+}
+
+// Closes the pipe, the helper exits once it reads the end
+pub async fn stop() {
+    RUNNING.store(false, Ordering::SeqCst);
+    *CONNECTION.lock().await = None;
+}
+
+async fn request(
+    program: &str,
+    args: &[String],
+    input: Option<Vec<u8>>,
+    mut on_line: impl FnMut(&str),
+) -> io::Result<Output> {
+    let mut connection = CONNECTION.lock().await;
+    let Some(helper) = connection.as_mut() else {
+        return Err(io::Error::other("the privileged helper is not running"));
+    };
+    let mut line = serde_json::to_string(&Request {
+        program: program.to_string(),
+        args: args.to_vec(),
+        input,
+    })?;
+    line.push('\n');
+    helper.writer.write_all(line.as_bytes()).await?;
+    helper.writer.flush().await?;
+
+    let response = loop {
+        let mut line = String::new();
+        if helper.reader.read_line(&mut line).await? == 0 {
+            *connection = None;
+            RUNNING.store(false, Ordering::SeqCst);
+            return Err(io::Error::other("the privileged helper exited"));
+        }
+        match serde_json::from_str(&line)? {
+            Reply::Line { line } => on_line(&line),
+            Reply::Done(response) => break response,
+        }
+    };
+    Ok(Output {
+        status: ExitStatus::from_raw(response.status),
+        stdout: response.stdout.into_bytes(),
+        stderr: response.stderr.into_bytes(),
+    })
+}
+
+// A program run as root, in the helper when one is running and directly otherwise.
+// `Local` is for the sysroot and the user's own manager, nothing there is audited.
+pub enum SystemCommand {
+    Local(AsyncCommand),
+    Privileged(AsyncCommand),
+    Helper { program: String, args: Vec<String> },
+}
+
+impl SystemCommand {
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        match self {
+            SystemCommand::Local(cmd) | SystemCommand::Privileged(cmd) => {
+                cmd.arg(arg);
+            }
+            SystemCommand::Helper { args, .. } => {
+                args.push(arg.as_ref().to_string_lossy().into_owned());
+            }
+        }
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    pub async fn output(&mut self) -> io::Result<Output> {
+        self.output_streaming(|_| {}).await
+    }
+
+    // Like `output`, handing each line the program prints to `on_line` while it runs
+    pub async fn output_streaming(&mut self, on_line: impl FnMut(&str)) -> io::Result<Output> {
+        match self {
+            SystemCommand::Local(cmd) => run_streaming(cmd, on_line).await,
+            SystemCommand::Privileged(cmd) => {
+                let output = run_streaming(cmd, on_line).await;
+                let argv = audit::argv(cmd.as_std());
+                audit::record(&argv, "direct", &output);
+                output
+            }
+            SystemCommand::Helper { program, args } => {
+                // The installer sends what it can read itself, anything else stays out
+                let input = match (program.as_str(), args.as_slice()) {
+                    ("install", [.., source, _]) => std::fs::read(source).ok(),
+                    _ => None,
+                };
+                // The helper writes the audit entry, it is the one running the command
+                request(program, args, input, on_line).await
+            }
+        }
+    }
+}
+
+async fn run_streaming(cmd: &mut AsyncCommand, on_line: impl FnMut(&str)) -> io::Result<Output> {
+    let child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    wait_streaming(child, on_line).await
+}
+
+// `wait_with_output` for a child with piped stdout and stderr that hands every line to
+// `on_line` as it comes, stdout and stderr in the order they were read
+pub async fn wait_streaming(mut child: Child, mut on_line: impl FnMut(&str)) -> io::Result<Output> {
+    // Closed like `wait_with_output` does, a child reading its input sees the end
+    drop(child.stdin.take());
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    read_lines(child.stdout.take(), false, sender.clone());
+    read_lines(child.stderr.take(), true, sender);
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    while let Some((from_stderr, line)) = receiver.recv().await {
+        let text = String::from_utf8_lossy(&line);
+        // Progress bars redraw their line with \r, show it the way it ended up
+        if let Some(shown) = text.rsplit('\r').map(str::trim_end).find(|s| !s.is_empty()) {
+            on_line(shown);
+        }
+        if from_stderr {
+            stderr.extend_from_slice(&line);
+        } else {
+            stdout.extend_from_slice(&line);
+        }
+    }
+    let status = child.wait().await?;
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+fn read_lines(
+    pipe: Option<impl AsyncRead + Unpin + Send + 'static>,
+    from_stderr: bool,
+    sender: UnboundedSender<(bool, Vec<u8>)>,
+) {
+    let Some(pipe) = pipe else {
+        return;
+    };
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(pipe);
+        loop {
+            let mut line = Vec::new();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if sender.send((from_stderr, line)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+// Runs requests from `reader` until the installer closes the pipe, only within `scope`
+pub async fn serve(
+    reader: impl AsyncBufRead + Unpin,
+    writer: impl AsyncWrite + Unpin,
+    scope: Scope,
+) -> Result<()> {
+    let staging = private_dir("sparrow-helper")?;
+    let served = serve_from(reader, writer, &scope, &staging).await;
+    let _ = std::fs::remove_dir_all(&staging);
+    served
+}
+
+async fn serve_from(
+    reader: impl AsyncBufRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    scope: &Scope,
+    staging: &Path,
+) -> Result<()> {
+    writer.write_all(format!("{}\n", READY).as_bytes()).await?;
+    writer.flush().await?;
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next_line().await? {
+        // Without a password prompt (NOPASSWD) the password line reaches the helper,
+        // it is dropped without an answer
+        let Ok(request) = serde_json::from_str::<Request>(&line) else {
+            continue;
+        };
+        // Output lines go out while the command runs, the installer shows them live
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let forward = async {
+            while let Some(line) = receiver.recv().await {
+                send(&mut writer, &Reply::Line { line }).await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        };
+        let (response, forwarded) = tokio::join!(
+            handle(request, scope, staging, move |line| {
+                let _ = sender.send(line.to_string());
+            }),
+            forward
+        );
+        forwarded?;
+        send(&mut writer, &Reply::Done(response)).await?;
+    }
+    Ok(())
+}
+
+async fn send(writer: &mut (impl AsyncWrite + Unpin), reply: &Reply) -> Result<()> {
+    let mut line = serde_json::to_string(reply)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+// What the profile changes on the system, read by the helper itself from the embedded
+// profile and the root-owned drop-ins rather than taken from the installer
+pub struct Scope {
+    // Paths the profile writes to
+    pub targets: Vec<PathBuf>,
+    // System units the profile enables
+    pub units: Vec<String>,
+}
+
+pub fn scope(profile: &ProfileConfig) -> Scope {
+    let mut targets = Vec::new();
+    let mut units = Vec::new();
+    for step in &profile.steps {
+        match &step.action {
+            StepAction::SystemdEnable(names) if !step.user => units.extend(names.iter().cloned()),
+            StepAction::Copy(file) | StepAction::Template(file) => {
+                let dest = Path::new(&file.dest);
+                if dest.is_absolute() && !file.dest.contains("{{") {
+                    targets.push(dest.to_path_buf());
+                }
+            }
+            StepAction::Dconf(dconf) => {
+                targets.extend(dconf.installed_keyfile());
+                targets.push(dconf.profile_path());
+            }
+            StepAction::Fonts(fonts) if fonts.system => targets.push(fonts.font_dir(None)),
+            _ => {}
+        }
+    }
+    Scope { targets, units }
+}
+
+// A fresh directory under the temporary folder that only its creator can open
+pub fn private_dir(prefix: &str) -> Result<PathBuf> {
+    let template = std::env::temp_dir().join(format!("{}-XXXXXX", prefix));
+    let mut bytes = CString::new(template.into_os_string().into_vec())?.into_bytes_with_nul();
+    // SAFETY: bytes is a writable NUL-terminated template ending in XXXXXX, mkdtemp
+    // only replaces those and creates the directory with mode 0700
+    let dir = unsafe { libc::mkdtemp(bytes.as_mut_ptr().cast()) };
+    if dir.is_null() {
+        return Err(io::Error::last_os_error().into());
+    }
+    bytes.pop();
+    Ok(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+// Why the helper turns the command down, None when it runs it. Sources of install are
+// either what the helper staged in `staging` or a target copied to its backup as it is.
+fn refusal(program: &str, args: &[String], scope: &Scope, staging: &Path) -> Option<String> {
+    if !ALLOWED.contains(&program) {
+        return Some(format!(
+            "{} is not a command the privileged helper runs",
+            program
+        ));
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let allowed = FIXED.contains(&(program, args.as_slice()))
+        || match (program, args.as_slice()) {
+            ("systemctl", ["enable", unit] | ["enable", "--now", unit]) => {
+                is_name(unit) && scope.units.iter().any(|name| name == unit)
+            }
+            ("restorecon", ["-R", paths @ ..]) => {
+                !paths.is_empty() && paths.iter().all(|path| is_plain_path(path))
+            }
+            ("install", ["-D", "-m", mode, source, dest]) if is_mode(mode) => {
+                let backup_of = steps::files::backed_up(Path::new(dest));
+                if Path::new(source).parent() == Some(staging) {
+                    is_target(dest, &scope.targets)
+                        || backup_of.is_some_and(|original| {
+                            is_target(&original.to_string_lossy(), &scope.targets)
+                        })
+                } else {
+                    // Deployed files are backed up next to themselves first, keeping
+                    // the permissions they have
+                    backup_of.is_some_and(|original| original == Path::new(source))
+                        && is_target(source, &scope.targets)
+                        && std::fs::symlink_metadata(source).is_ok_and(|meta| {
+                            meta.is_file() && has_mode(meta.permissions().mode(), mode)
+                        })
+                }
+            }
+            ("chown", [owner, dest]) => is_owner(owner) && is_target(dest, &scope.targets),
+            _ => false,
+        };
+    (!allowed).then(|| {
+        format!(
+            "The privileged helper does not run {} {}",
+            program,
+            args.join(" ")
+        )
+    })
+}
+
+fn is_name(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('-')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "@._-".contains(c))
+}
+
+// "user" or "user:group"
+fn is_owner(value: &str) -> bool {
+    value.split(':').count() <= 2 && value.split(':').all(is_name)
+}
+
+// Permission bits without setuid, setgid or sticky
+fn is_mode(value: &str) -> bool {
+    let digits = value.strip_prefix('0').unwrap_or(value);
+    digits.len() == 3 && digits.chars().all(|c| ('0'..='7').contains(&c))
+}
+
+fn has_mode(bits: u32, mode: &str) -> bool {
+    u32::from_str_radix(mode, 8).is_ok_and(|mode| mode == bits & 0o7777)
+}
+
+fn is_plain_path(value: &str) -> bool {
+    let path = Path::new(value);
+    path.is_absolute()
+        && path
+            .components()
+            .all(|c| !matches!(c, Component::ParentDir))
+}
+
+fn is_target(value: &str, targets: &[PathBuf]) -> bool {
+    is_plain_path(value)
+        && targets
+            .iter()
+            .any(|target| Path::new(value).starts_with(target))
+}
+
+async fn handle(
+    mut request: Request,
+    scope: &Scope,
+    staging: &Path,
+    on_line: impl FnMut(&str),
+) -> Response {
+    let staged = match stage(&mut request, staging) {
+        Ok(staged) => staged,
+        Err(e) => return Response::refused(format!("Cannot stage {}: {}", request.program, e)),
+    };
+    let response = run(&request, scope, staging, on_line).await;
+    if let Some(staged) = staged {
+        let _ = std::fs::remove_file(staged);
+    }
+    response
+}
+
+// Writes the input that came with an install into the helper's own folder, in place of
+// the source the installer named
+fn stage(request: &mut Request, staging: &Path) -> Result<Option<PathBuf>> {
+    let Some(input) = request.input.take() else {
+        return Ok(None);
+    };
+    let ("install", [.., source, _]) = (request.program.as_str(), request.args.as_mut_slice())
+    else {
+        bail!("only install takes input");
+    };
+    let staged = staging.join("input");
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&staged)?
+        .write_all(&input)?;
+    *source = staged.display().to_string();
+    Ok(Some(staged))
+}
+
+async fn run(
+    request: &Request,
+    scope: &Scope,
+    staging: &Path,
+    on_line: impl FnMut(&str),
+) -> Response {
+    let argv: Vec<String> = std::iter::once(request.program.clone())
+        .chain(request.args.iter().cloned())
+        .collect();
+    if let Some(reason) = refusal(&request.program, &request.args, scope, staging) {
+        logging::event!(Level::Warn, "helper", "{}", reason);
+        let refused = io::Error::new(io::ErrorKind::PermissionDenied, reason.clone());
+        audit::record(&argv, "helper", &Err(refused));
+        return Response::refused(reason);
+    }
+    let output = run_streaming(
+        AsyncCommand::new(&request.program).args(&request.args),
+        on_line,
+    )
+    .await;
+    audit::record(&argv, "helper", &output);
+    match output {
+        Ok(output) => Response {
+            status: output.status.into_raw(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        },
+        Err(e) => Response::refused(format!("Cannot run {}: {}", request.program, e)),
+    }
+}
+
+// `sparrow-installer privileged-helper`, started through sudo by `start`
+pub async fn command() -> Result<()> {
+    if !crate::steps::is_root() {
+        bail!("The privileged helper has to run as root");
+    }
+    let scope = scope(&ProfileConfig::load()?);
+    if let Err(e) = audit::init(&crate::SettingsConfig::load()?.audit) {
+        logging::event!(Level::Error, "helper", "Audit log disabled: {}", e);
+    }
+    serve(
+        BufReader::new(tokio::io::stdin()),
+        tokio::io::stdout(),
+        scope,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refused(argv: &str, scope: &Scope, staging: &Path) -> bool {
+        let mut words = argv.split(' ');
+        let program = words.next().expect("program");
+        let args: Vec<String> = words.map(str::to_string).collect();
+        refusal(program, &args, scope, staging).is_some()
+    }
+
+    #[test]
+    fn install_takes_only_staged_sources_and_backups_of_targets() {
+        let dir = private_dir("sparrow-helper-test").expect("temporary folder");
+        let staging = dir.join("staging");
+        let target = dir.join("motd");
+        std::fs::create_dir(&staging).expect("staging");
+        std::fs::write(&target, "hello\n").expect("target");
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o640)).expect("mode");
+        let scope = Scope {
+            targets: vec![target.clone()],
+            units: Vec::new(),
+        };
+        let target = target.display();
+        let staged = staging.join("input");
+        let staged = staged.display();
+        let backup = format!("{}.sparrow-bak-20261016120000", target);
+
+        assert!(!refused(
+            &format!("install -D -m 0644 {} {}", staged, target),
+            &scope,
+            &staging
+        ));
+        assert!(!refused(
+            &format!("install -D -m 0640 {} {}", target, backup),
+            &scope,
+            &staging
+        ));
+        // Root-only files are not published under a target, nor a target with looser bits
+        assert!(refused(
+            &format!("install -D -m 0644 /etc/shadow {}", target),
+            &scope,
+            &staging
+        ));
+        assert!(refused(
+            &format!("install -D -m 0644 {} {}", target, backup),
+            &scope,
+            &staging
+        ));
+        assert!(refused(
+            &format!("install -D -m 0644 {} /etc/sudoers", staged),
+            &scope,
+            &staging
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_the_profiles_system_units_are_enabled() {
+        let profile: ProfileConfig = toml::from_str(
+            r#"
+            [[steps]]
+            id = "printing"
+            name = "Printing"
+            systemd_enable = ["cups.service"]
+
+            [[steps]]
+            id = "sync"
+            name = "Sync"
+            user = true
+            systemd_enable = ["syncthing.service"]
+            "#,
+        )
+        .expect("profile");
+        let scope = scope(&profile);
+        let staging = Path::new("/nonexistent");
+        assert!(!refused(
+            "systemctl enable --now cups.service",
+            &scope,
+            staging
+        ));
+        assert!(refused("systemctl enable sshd.service", &scope, staging));
+        assert!(refused(
+            "systemctl enable syncthing.service",
+            &scope,
+            staging
+        ));
+    }
+
+    fn shell(script: &str) -> Child {
+        AsyncCommand::new("sh")
+            .args(["-c", script])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("sh")
+    }
+
+    #[tokio::test]
+    async fn only_an_exit_or_a_wrong_password_fails_the_start() {
+        let _system = crate::fixtures::MockSystem::new();
+        let backend = escalation::require().expect("the sudo shim");
+
+        // Warnings before the helper comes up, then its first answer
+        let mut child = shell(
+            "echo 'sudo: unable to resolve host sparrow' >&2; sleep 0.2; echo ready; echo answer",
+        );
+        let mut reader = handshake(&mut child, backend)
+            .await
+            .expect("stderr alone is not a failure");
+        let mut answer = String::new();
+        reader.read_line(&mut answer).await.expect("answer");
+        assert_eq!(answer, "answer\n");
+
+        let mut child = shell("echo 'helper: no staging folder' >&2; exit 3");
+        let error = handshake(&mut child, backend)
+            .await
+            .err()
+            .expect("the helper exited");
+        assert_eq!(error.to_string(), "helper: no staging folder");
+
+        let mut child = shell("exit 3");
+        let error = handshake(&mut child, backend)
+            .await
+            .err()
+            .expect("the helper exited");
+        assert!(error.to_string().contains("exited with"), "{}", error);
+
+        // sudo asks again and would wait for another password forever
+        let mut child = shell("echo 'Sorry, try again.' >&2; exec sleep 60");
+        let started = std::time::Instant::now();
+        let error = handshake(&mut child, backend)
+            .await
+            .err()
+            .expect("the password was wrong");
+        assert_eq!(error.to_string(), "Sorry, try again.");
+        assert!(started.elapsed() < std::time::Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{App, AppState, styles::Styles};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::Modifier;
+use ratatui::text::{Line, Span};
+
+// The keys the screen on display answers to and what they do, in the order handle_key
+// treats them. A key added to a screen there belongs in its list here.
+pub fn hints(app: &App) -> Vec<(&'static str, &str)> {
+    let keys = &app.text.keys;
+    let navigate = ("↑↓", keys.navigate.as_str());
+    let scroll = ("↑↓ PgUp/PgDn", keys.scroll.as_str());
+    let back = ("Esc", keys.back.as_str());
+
+    if app.tour.is_some() {
+        return vec![
+            ("Enter/→", &keys.next),
+            ("←", &keys.back),
+            ("Esc", &keys.end_tour),
+        ];
+    }
+    if app.app_state == AppState::PasswordInput {
+        return vec![("Enter", &keys.confirm), ("Tab", &keys.show_password), back];
+    }
+    if app.show_confirmation {
+        let mut hints = vec![("y/Enter", keys.yes.as_str()), ("n/Esc", &keys.no)];
+        if !app.confirmation_details.is_empty() {
+            hints.push(("d", &keys.details));
+        }
+        return hints;
+    }
+    if app.attached {
+        return vec![("q", &keys.detach)];
+    }
+    if app.progress_type.is_some() {
+        // Real operations cannot be interrupted, only a dry run can be cut short
+        return if app.dry_run {
+            vec![("Esc", &keys.cancel)]
+        } else {
+            Vec::new()
+        };
+    }
+
+    match &app.app_state {
+        AppState::UserSelect
+        | AppState::WallpaperSelect
+        | AppState::ComponentSelect
+        | AppState::CustomSelect => vec![
+            navigate,
+            ("Space", &keys.toggle),
+            ("Enter", &keys.next),
+            back,
+        ],
+        AppState::MonitorSelect => {
+            vec![navigate, ("←→", &keys.change), ("Enter", &keys.next), back]
+        }
+        AppState::Locked => vec![("q", &keys.quit)],
+        AppState::RecoverySelect | AppState::BugReport => {
+            vec![navigate, ("Enter", &keys.select), back]
+        }
+        AppState::RebootSelect => vec![navigate, ("Enter", &keys.select)],
+        AppState::SessionsWarning => vec![("y", &keys.power_off), back],
+        AppState::Failed => {
+            let mut hints = Vec::new();
+            if app.last_failure.is_some() {
+                hints.push(("r", keys.recover.as_str()));
+            }
+            hints.push(("b", &keys.report));
+            hints.push(("Enter", &keys.menu));
+            hints
+        }
+        AppState::VolumeSelect => vec![navigate, ("Enter", &keys.save), ("r", &keys.rescan), back],
+        AppState::PathInput => vec![("Tab", &keys.complete), ("Enter", &keys.save), back],
+        AppState::Plan => vec![scroll, ("Enter", &keys.next), back],
+        AppState::DryRunResult => vec![scroll, ("Enter", &keys.menu)],
+        AppState::LogView => vec![scroll, ("s", &keys.save_log), back],
+        _ => {
+            let mut hints = vec![navigate];
+            if app.theme.ui.show_numbers {
+                hints.push(("1-9", &keys.pick));
+            }
+            let document = app.options.get(app.selected).and_then(|option| {
+                app.option_document(option)
+                    .filter(|_| app.app_state == AppState::MainMenu)
+            });
+            if document.is_some() {
+                hints.push(("PgUp/PgDn", &keys.scroll));
+            }
+            hints.push(("Enter", &keys.select));
+            hints.push(("q", &keys.quit));
+            hints
+        }
+    }
+}
+
+// The key the bindings see. Held Control or Alt makes it none of them, so Ctrl+Q does not
+// quit and Alt+Y does not confirm. Shift is part of the character. Text fields take what
+// AltGr (Control+Alt) types through typing::typed.
+pub fn code(key: &KeyEvent) -> KeyCode {
+    if key
+        .modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        KeyCode::Null
+    } else {
+        key.code
+    }
+}
+
+// The hint bar's line, keys in bold ahead of what they do
+pub fn bar<'a>(hints: &[(&'a str, &'a str)], styles: &Styles) -> Line<'a> {
+    let mut spans = Vec::new();
+    for (i, (key, action)) in hints.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::styled("  ", styles.description));
+        }
+        spans.push(Span::styled(
+            *key,
+            styles.description.add_modifier(Modifier::BOLD),
+        ));
+        spans.push(Span::styled(format!(" {}", action), styles.description));
+    }
+    Line::from(spans)
+}
@@ -0,0 +1,419 @@
+use anyhow::Result;
+use clap::{ArgAction, Parser, Subcommand};
+use cli_log::*;
+use crossterm::{
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{Terminal, backend::CrosstermBackend};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+mod a11y;
+mod actions;
+mod app;
+mod audit;
+mod backdrop;
+mod bug_report;
+mod colors;
+mod condition;
+mod config;
+#[cfg(test)]
+mod config_tests;
+mod console;
+mod control;
+mod credentials;
+#[cfg(test)]
+mod e2e;
+// The installer without its interface, for front ends that embed it
+pub mod engine;
+mod escalation;
+mod exec;
+// Fake system programs, for driving the installer from other crates' tests too
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod fixtures;
+mod health;
+mod helper;
+mod keymap;
+mod lock;
+mod logging;
+mod markdown;
+mod metrics;
+mod migrate;
+mod monitors;
+mod notify;
+mod path_input;
+mod pipeline;
+mod plain;
+mod plan;
+mod power;
+mod progress;
+mod recovery;
+mod removable;
+// Frames for benches/render.rs, not part of the API
+#[cfg(any(test, feature = "bench"))]
+#[doc(hidden)]
+pub mod render_bench;
+mod report;
+mod sandbox;
+mod schema;
+mod selftest;
+#[cfg(feature = "simulator")]
+mod simulation;
+mod snapshot;
+mod state;
+mod steps;
+mod styles;
+mod support;
+mod sysroot;
+mod ticks;
+mod tools;
+mod tour;
+mod typing;
+mod ui;
+mod verify;
+mod widgets;
+mod worker;
+
+// Reachable as crate::App, crate::SettingsConfig and so on from every module
+pub(crate) use actions::*;
+pub(crate) use app::*;
+pub(crate) use config::*;
+pub(crate) use exec::*;
+pub(crate) use ui::*;
+
+#[derive(Parser)]
+#[command(name = "sparrow-installer")]
+#[command(about = "Sparrow atomic desktop installer")]
+pub struct Cli {
+    /// Enable dry-run mode (don't execute actual commands)
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Increase log verbosity (-v for debug, -vv for full command output)
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Only log warnings and errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Format of the installer log file
+    #[arg(long, value_enum)]
+    log_format: Option<logging::LogFormat>,
+
+    /// Install the default dotfiles without the interface, e.g. from a first-boot service
+    #[arg(long)]
+    unattended: bool,
+
+    /// Follow the progress of an installer already running elsewhere, read-only
+    #[arg(long, conflicts_with_all = ["unattended", "dry_run"])]
+    attach: bool,
+
+    /// Walk through each screen with explanations and a simulated update, then keep
+    /// exploring in dry-run mode
+    #[arg(long, conflicts_with_all = ["unattended", "attach"])]
+    tour: bool,
+
+    /// Replay this dry-run scenario instead of the built-in one
+    #[cfg(feature = "simulator")]
+    #[arg(long, value_name = "PATH", requires = "dry_run")]
+    scenario: Option<PathBuf>,
+
+    /// Run the steps that only write into the home directory for real inside a throwaway
+    /// bwrap overlay and list the files they change
+    #[arg(long, requires = "dry_run")]
+    sandbox: bool,
+
+    /// Draw without colors, for monochrome consoles
+    #[arg(long)]
+    no_color: bool,
+
+    /// Built-in theme to draw with; monochrome shows every state without color
+    #[arg(long, value_enum)]
+    theme: Option<ThemePreset>,
+
+    /// Also stream the installer log and all command output as plain text to this file
+    /// descriptor, stderr by default, for CI harnesses while the TUI owns stdout
+    #[arg(
+        long,
+        value_name = "FD",
+        num_args = 0..=1,
+        default_missing_value = "2",
+        conflicts_with = "attach"
+    )]
+    tee_output: Option<i32>,
+
+    /// Install into the mounted root of an image instead of the running system
+    #[arg(long, value_name = "PATH", requires = "unattended")]
+    sysroot: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Validate the configuration and profile, dry-run every step and check the terminal
+    SelfTest,
+    /// Collect logs, system status and hardware details into a .tar.gz for a bug report
+    SupportBundle {
+        /// Folder to write the bundle to, the current directory by default
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
+    /// Print the JSON Schema of a config file, for editor completion and validation
+    Schema {
+        #[arg(value_enum)]
+        file: schema::ConfigFile,
+    },
+    /// Upgrade an older theme, text, profile or pipeline file to the current format, keeping
+    /// the original as <FILE>.bak
+    MigrateConfig {
+        file: PathBuf,
+        /// What the file is, guessed from its tables when left out
+        #[arg(long, value_enum)]
+        kind: Option<schema::ConfigFile>,
+    },
+    /// Run one profile step, inside bwrap for --sandbox or as the account a per-user step
+    /// is for
+    #[command(hide = true)]
+    RunStep { id: String },
+    /// Run vetted commands as root for the installer, started once through sudo
+    #[command(hide = true)]
+    PrivilegedHelper,
+}
+
+// Everything the binary does, picked by the command line
+pub fn start(cli: Cli) -> Result<()> {
+    // Loads the configuration itself, so a broken file shows up in the report
+    if let Some(Command::SelfTest) = cli.command {
+        return tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(selftest::run());
+    }
+    if let Some(Command::SupportBundle { output }) = cli.command {
+        return tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(support::command(output));
+    }
+    if let Some(Command::Schema { file }) = cli.command {
+        return schema::command(file);
+    }
+    if let Some(Command::MigrateConfig { file, kind }) = &cli.command {
+        return migrate::command(file, *kind);
+    }
+    if let Some(Command::RunStep { id }) = &cli.command {
+        return tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(pipeline::step_command(id));
+    }
+    if let Some(Command::PrivilegedHelper) = cli.command {
+        return tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(helper::command());
+    }
+    report::install_crash_hook();
+    let settings = SettingsConfig::load()?;
+    let verbosity = logging::Verbosity::resolve(
+        &settings.logging.level,
+        cli.verbose,
+        cli.quiet,
+        std::env::var(logging::LOG_ENV_VAR).ok().as_deref(),
+    );
+
+    // cli_log reads its level from the environment, so set it before any threads exist
+    if let Some(level) = verbosity.cli {
+        // SAFETY: the tokio runtime has not been started yet, so this is single threaded
+        unsafe { std::env::set_var("SPARROW_INSTALLER_LOG", level.to_string().to_lowercase()) };
+    }
+    init_cli_log!();
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(cli, settings, verbosity))
+}
+
+async fn run(cli: Cli, settings: SettingsConfig, verbosity: logging::Verbosity) -> Result<()> {
+    if let Some(root) = &cli.sysroot {
+        sysroot::set(root)?;
+    }
+    if cli.unattended {
+        return run_unattended(cli, settings, verbosity).await;
+    }
+
+    // Read before the terminal switches screens, so a bad path is reported readably
+    #[cfg(feature = "simulator")]
+    let scenario = cli
+        .scenario
+        .as_deref()
+        .map(simulation::Scenario::from_file)
+        .transpose()?;
+
+    let plain = plain::wanted();
+    if plain && cli.attach {
+        return Err(anyhow::anyhow!(
+            "--attach needs a terminal that can draw the interface"
+        ));
+    }
+    if plain && cli.tour {
+        return Err(anyhow::anyhow!(
+            "--tour needs a terminal that can draw the interface"
+        ));
+    }
+    // Up before the configs load, so a broken override is reported in the log
+    let log_format = cli.log_format.unwrap_or(settings.logging.format);
+    match logging::init(&settings.logging, verbosity, log_format) {
+        Ok(path) => info!("Installer log at {}", path.display()),
+        Err(e) => warn!("Installer log disabled: {}", e),
+    }
+    if let Some(fd) = cli.tee_output {
+        logging::tee(fd)
+            .map_err(|e| anyhow::anyhow!("Cannot tee the output to fd {}: {}", fd, e))?;
+    }
+    // The tour only ever simulates
+    let mut app = App::new(cli.dry_run || cli.tour, settings)?;
+    app.sandbox = cli.sandbox;
+    #[cfg(feature = "simulator")]
+    if let Some(scenario) = scenario {
+        app.scenario = scenario;
+    }
+    if let Some(preset) = cli.theme {
+        app.set_theme(ThemeConfig::preset(preset)?);
+    }
+    if cli.no_color {
+        app.set_color_support(colors::ColorSupport::None);
+    }
+    match audit::init(&app.settings.audit) {
+        Ok(Some(path)) => info!("Audit log at {}", path.display()),
+        Ok(None) => {}
+        Err(e) => warn!("Audit log disabled: {}", e),
+    }
+    tools::log_probe();
+
+    let res = if plain {
+        // No escape sequences at all, one line at a time
+        app.take_interactive_lock();
+        plain::run(&mut app).await
+    } else {
+        // Setup terminal
+        enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        // A paste arrives as one event instead of keys, its newlines do not press Enter
+        execute!(
+            stdout,
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableBracketedPaste
+        )?;
+        let backend = CrosstermBackend::new(stdout);
+        let mut terminal = Terminal::new(backend)?;
+
+        let res = if cli.attach {
+            run_attached(&mut terminal, &mut app).await
+        } else {
+            app.take_interactive_lock();
+            if cli.tour {
+                tour::start(&mut app).await;
+            }
+            run_app(&mut terminal, &mut app)
+                .await
+                .map_err(anyhow::Error::from)
+        };
+
+        // Restore terminal
+        disable_raw_mode()?;
+        execute!(
+            terminal.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableBracketedPaste
+        )?;
+        terminal.show_cursor()?;
+        res
+    };
+
+    // Nothing runs as root past the interface
+    helper::stop().await;
+
+    // Check if there's a pending system action to execute
+    if let Some(system_action) = &app.pending_system_action
+        && !app.dry_run
+    {
+        match system_action {
+            SystemAction::Reboot => {
+                if let Err(err) = app.execute_reboot().await {
+                    eprintln!("Reboot failed: {}", err);
+                }
+            }
+            SystemAction::Poweroff => {
+                if let Err(err) = app.execute_poweroff().await {
+                    eprintln!("Poweroff failed: {}", err);
+                }
+            }
+        }
+    }
+
+    if let Err(err) = res {
+        println!("{err:?}");
+    }
+
+    if app.pending_launch && !app.dry_run {
+        launch(&app.settings.on_success.launch_command);
+    }
+
+    Ok(())
+}
+
+async fn run_unattended(
+    cli: Cli,
+    settings: SettingsConfig,
+    verbosity: logging::Verbosity,
+) -> Result<()> {
+    let log_format = cli.log_format.unwrap_or(settings.logging.format);
+    if let Err(e) = logging::init(&settings.logging, verbosity, log_format) {
+        eprintln!("Installer log disabled: {}", e);
+    }
+    if let Some(fd) = cli.tee_output {
+        logging::tee(fd)
+            .map_err(|e| anyhow::anyhow!("Cannot tee the output to fd {}: {}", fd, e))?;
+    }
+    let mut app = App::new(cli.dry_run, settings)?;
+    app.sandbox = cli.sandbox;
+    app.unattended = true;
+    if let Err(e) = audit::init(&app.settings.audit) {
+        eprintln!("Audit log disabled: {}", e);
+    }
+    tools::log_probe();
+    // The lock guards the running system, image builds may run side by side
+    if sysroot::get().is_none() {
+        app.take_lock("unattended")?;
+    }
+    if app.app_state == AppState::Locked {
+        let holder = app.lock_holder.as_ref().map_or_else(String::new, |owner| {
+            format!(
+                " (pid {}, {}, since {})",
+                owner.pid, owner.user, owner.started_at
+            )
+        });
+        return Err(anyhow::anyhow!(
+            "{}{}",
+            app.text.messages.locked_prompt,
+            holder
+        ));
+    }
+    notify::spawn_watchdog(Duration::from_secs(app.settings.watchdog.stall_timeout));
+    notify::notify("READY=1");
+
+    // An error is printed by main and fails the service
+    app.run_unattended().await?;
+    eprintln!("{}", app.text.messages.operation_success);
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use crate::steps;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+
+const LOCK_NAME: &str = "sparrow-installer.lock";
+const ROOT_RUNTIME_DIR: &str = "/run";
+
+// Written into the lock file so a second instance can tell who holds it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub user: String,
+    pub started_at: String,
+    pub mode: String,
+    #[serde(default)]
+    pub status: String,
+}
+
+pub struct InstanceLock {
+    file: File,
+    owner: LockOwner,
+}
+
+pub enum LockState {
+    Acquired(InstanceLock),
+    // Another instance holds the lock, with what it wrote about itself if readable
+    Held(PathBuf, Option<LockOwner>),
+}
+
+// Where an instance keeps its lock and control socket: /run for root, the user's own
+// runtime folder otherwise. Nobody else can create files in either.
+pub fn runtime_dir() -> Option<PathBuf> {
+    if steps::is_root() {
+        return Some(PathBuf::from(ROOT_RUNTIME_DIR));
+    }
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+// The runtime folder of a root instance, which user instances also wait for
+pub fn root_runtime_dir() -> PathBuf {
+    PathBuf::from(ROOT_RUNTIME_DIR)
+}
+
+pub fn acquire(mode: &str) -> Result<LockState> {
+    let dir = runtime_dir().context("No runtime folder for the instance lock")?;
+    // Users only read root's lock, taking it would lock root out
+    if !steps::is_root() {
+        let path = root_runtime_dir().join(LOCK_NAME);
+        if let Some(owner) = running_owner(&path) {
+            return Ok(LockState::Held(path, Some(owner)));
+        }
+    }
+    acquire_at(&dir.join(LOCK_NAME), mode)
+}
+
+fn acquire_at(path: &Path, mode: &str) -> Result<LockState> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .with_context(|| format!("Cannot open the lock file {}", path.display()))?;
+    // SAFETY: geteuid has no preconditions and cannot fail
+    if file.metadata()?.uid() != unsafe { libc::geteuid() } {
+        bail!("{} belongs to another user", path.display());
+    }
+    // SAFETY: flock only reads the descriptor, which stays open for the call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            return Ok(LockState::Held(path.to_path_buf(), running_owner(path)));
+        }
+        return Err(error).with_context(|| format!("Cannot lock {}", path.display()));
+    }
+
+    let mut lock = InstanceLock {
+        file,
+        owner: LockOwner {
+            pid: std::process::id(),
+            user: std::env::var("SUDO_USER")
+                .or_else(|_| std::env::var("USER"))
+                .unwrap_or_default(),
+            started_at: chrono::Local::now().to_rfc3339(),
+            mode: mode.to_string(),
+            status: String::new(),
+        },
+    };
+    lock.write_owner();
+    Ok(LockState::Acquired(lock))
+}
+
+// Details of a live holder, only from a lock file root or this user wrote
+fn running_owner(path: &Path) -> Option<LockOwner> {
+    let meta = std::fs::symlink_metadata(path).ok()?;
+    // SAFETY: geteuid has no preconditions and cannot fail
+    if !meta.is_file() || (meta.uid() != 0 && meta.uid() != unsafe { libc::geteuid() }) {
+        return None;
+    }
+    let owner: LockOwner = toml::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    PathBuf::from(format!("/proc/{}", owner.pid))
+        .exists()
+        .then_some(owner)
+}
+
+impl InstanceLock {
+    // Keeps the status shown to other instances current
+    pub fn set_status(&mut self, status: &str) {
+        self.owner.status = status.to_string();
+        self.write_owner();
+    }
+
+    fn write_owner(&mut self) {
+        let Ok(contents) = toml::to_string(&self.owner) else {
+            return;
+        };
+        let _ = self.file.set_len(0);
+        let _ = self.file.seek(SeekFrom::Start(0));
+        let _ = self.file.write_all(contents.as_bytes());
+    }
+}
+
+// A finished instance leaves no details behind for a user instance to wait on
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_second_instance_sees_the_holder_until_it_is_dropped() {
+        let dir = crate::helper::private_dir("sparrow-lock-test").expect("temporary folder");
+        let path = dir.join(LOCK_NAME);
+
+        let LockState::Acquired(mut first) = acquire_at(&path, "interactive").expect("lock") else {
+            panic!("nobody else holds a fresh lock");
+        };
+        first.set_status("Updating");
+        match acquire_at(&path, "unattended").expect("lock") {
+            LockState::Held(held, Some(owner)) => {
+                assert_eq!(held, path);
+                assert_eq!(owner.pid, std::process::id());
+                assert_eq!(owner.mode, "interactive");
+                assert_eq!(owner.status, "Updating");
+            }
+            _ => panic!("the first instance holds the lock"),
+        }
+
+        drop(first);
+        assert!(matches!(
+            acquire_at(&path, "unattended").expect("lock"),
+            LockState::Acquired(_)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
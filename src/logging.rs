@@ -0,0 +1,445 @@
+use crate::{LoggingConfig, escalation};
+use anyhow::Result;
+use clap::ValueEnum;
+use cli_log::{Level, LevelFilter};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::fd::{BorrowedFd, RawFd};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref INSTALL_LOG: Mutex<Option<InstallLog>> = Mutex::new(None);
+}
+
+// Record an installer log event tagged with the calling module
+macro_rules! event {
+    ($level:expr, $step:expr, $($arg:tt)+) => {
+        $crate::logging::record(module_path!(), $level, $step, &format!($($arg)+))
+    };
+}
+pub(crate) use event;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+// Environment variable accepting RUST_LOG-style directives, e.g. `debug` or `file=trace,cli=info`
+pub const LOG_ENV_VAR: &str = "SPARROW_LOG";
+
+// Levels for the installer log file and the cli_log debug file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Verbosity {
+    pub file: LevelFilter,
+    pub cli: Option<LevelFilter>,
+}
+
+impl Verbosity {
+    // Precedence: command line flags, then SPARROW_LOG, then the configured default
+    pub fn resolve(default: &str, verbose: u8, quiet: bool, env_spec: Option<&str>) -> Self {
+        let mut verbosity = Self {
+            file: LevelFilter::from_str(default).unwrap_or(LevelFilter::Info),
+            cli: None,
+        };
+
+        if let Some(spec) = env_spec {
+            verbosity.apply_directives(spec);
+        }
+
+        if quiet {
+            verbosity.file = LevelFilter::Warn;
+            verbosity.cli = Some(LevelFilter::Off);
+        } else if verbose > 0 {
+            let level = if verbose == 1 {
+                LevelFilter::Debug
+            } else {
+                LevelFilter::Trace
+            };
+            verbosity.file = level;
+            verbosity.cli = Some(level);
+        }
+
+        verbosity
+    }
+
+    fn apply_directives(&mut self, spec: &str) {
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (target, level) = match directive.split_once('=') {
+                Some((target, level)) => (Some(target.trim()), level.trim()),
+                None => (None, directive),
+            };
+            let Ok(level) = LevelFilter::from_str(level) else {
+                continue;
+            };
+            match target {
+                Some("file") => self.file = level,
+                Some("cli") => self.cli = Some(level),
+                None | Some("sparrow_installer") | Some("sparrow-installer") => {
+                    self.file = level;
+                    self.cli = Some(level);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Full child process output is only kept at trace level, otherwise just summaries
+    pub fn records_full_output(&self) -> bool {
+        self.file >= LevelFilter::Trace
+    }
+}
+
+// Strips secrets from text before it reaches any log file or export
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    secrets: Vec<String>,
+    // Passwords given during the session, only kept while the operation they were for runs
+    passwords: Vec<String>,
+    replacement: String,
+}
+
+impl Redactor {
+    pub fn new(config: &LoggingConfig) -> Result<Self> {
+        let patterns = config
+            .redact_patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut redactor = Self {
+            patterns,
+            secrets: Vec::new(),
+            passwords: Vec::new(),
+            replacement: config.redact_replacement.clone(),
+        };
+
+        if config.redact_username {
+            for var in ["SUDO_USER", "USER", "LOGNAME"] {
+                if let Ok(user) = std::env::var(var) {
+                    redactor.add_secret(&user);
+                }
+            }
+        }
+
+        Ok(redactor)
+    }
+
+    pub fn add_secret(&mut self, secret: &str) {
+        // Very short values would blank out unrelated text, so skip them
+        if secret.chars().count() < 3 || self.secrets.iter().any(|s| s == secret) {
+            return;
+        }
+        self.secrets.push(secret.to_string());
+    }
+
+    pub fn add_password(&mut self, password: &str) {
+        if password.chars().count() >= 3 && !self.passwords.iter().any(|p| p == password) {
+            self.passwords.push(password.to_string());
+        }
+    }
+
+    pub fn forget_passwords(&mut self) {
+        for password in &mut self.passwords {
+            escalation::wipe(password);
+        }
+        self.passwords.clear();
+    }
+
+    pub fn redact(&self, input: &str) -> String {
+        let mut secrets: Vec<&String> = self.secrets.iter().chain(&self.passwords).collect();
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        let mut output = input.to_string();
+        for secret in secrets {
+            output = output.replace(secret.as_str(), &self.replacement);
+        }
+        for pattern in &self.patterns {
+            output = pattern
+                .replace_all(&output, self.replacement.as_str())
+                .into_owned();
+        }
+        output
+    }
+}
+
+pub struct InstallLog {
+    file: Option<File>,
+    path: PathBuf,
+    redactor: Redactor,
+    verbosity: Verbosity,
+    format: LogFormat,
+    // --tee-output: plain text lines down to debug and all child output, whatever the file keeps
+    tee: Option<File>,
+}
+
+impl InstallLog {
+    fn open(config: &LoggingConfig, verbosity: Verbosity, format: LogFormat) -> Result<Self> {
+        let redactor = Redactor::new(config)?;
+        let path = log_file(config);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Self {
+            file: Some(file),
+            path,
+            redactor,
+            verbosity,
+            format,
+            tee: None,
+        })
+    }
+
+    fn write(&mut self, module: &str, level: Level, step: &str, message: &str) {
+        if level <= Level::Debug {
+            self.tee(level, step, message);
+        }
+        if level > self.verbosity.file {
+            return;
+        }
+        let now = chrono::Local::now();
+        let message = self.redactor.redact(message);
+        let line = match self.format {
+            LogFormat::Text => text_line(now, level, step, &message),
+            LogFormat::Json => serde_json::json!({
+                "timestamp": now.to_rfc3339(),
+                "level": level.as_str(),
+                "module": module,
+                "step": step,
+                "message": message,
+            })
+            .to_string(),
+        };
+        if let Some(file) = self.file.as_mut()
+            && writeln!(file, "{}", line).is_err()
+        {
+            // Stop writing rather than failing every subsequent event
+            self.file = None;
+        }
+    }
+
+    fn tee(&mut self, level: Level, step: &str, message: &str) {
+        let Some(tee) = self.tee.as_mut() else {
+            return;
+        };
+        let message = self.redactor.redact(message);
+        let line = text_line(chrono::Local::now(), level, step, &message);
+        if writeln!(tee, "{}", line).is_err() {
+            // The harness stopped reading, the TUI carries on without it
+            self.tee = None;
+        }
+    }
+}
+
+fn text_line(
+    now: chrono::DateTime<chrono::Local>,
+    level: Level,
+    step: &str,
+    message: &str,
+) -> String {
+    format!(
+        "{} [{}] {}: {}",
+        now.format("%Y-%m-%d %H:%M:%S%.3f"),
+        level,
+        step,
+        message
+    )
+}
+
+// Where the installer log goes, whether or not this process has opened it
+pub fn log_file(config: &LoggingConfig) -> PathBuf {
+    let directory = if config.directory.is_empty() {
+        crate::state_dir()
+    } else {
+        PathBuf::from(&config.directory)
+    };
+    directory.join(&config.file_name)
+}
+
+pub fn init(config: &LoggingConfig, verbosity: Verbosity, format: LogFormat) -> Result<PathBuf> {
+    let log = InstallLog::open(config, verbosity, format)?;
+    let path = log.path.clone();
+    *INSTALL_LOG.lock().unwrap() = Some(log);
+    Ok(path)
+}
+
+// Streams the log to `fd` as well, e.g. stderr while the TUI owns stdout
+pub fn tee(fd: RawFd) -> Result<()> {
+    let mut log = INSTALL_LOG.lock().unwrap();
+    let Some(log) = log.as_mut() else {
+        anyhow::bail!("the installer log is not open");
+    };
+    // SAFETY: only borrowed to duplicate it, a closed fd fails the duplication
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+    log.tee = Some(File::from(fd));
+    Ok(())
+}
+
+#[cfg(test)]
+pub fn close() {
+    *INSTALL_LOG.lock().unwrap() = None;
+}
+
+pub fn log_path() -> Option<PathBuf> {
+    INSTALL_LOG
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|log| log.path.clone())
+}
+
+// Register an accepted password so it never appears in logs while its operation runs
+pub fn register_password(password: &str) {
+    if let Some(log) = INSTALL_LOG.lock().unwrap().as_mut() {
+        log.redactor.add_password(password);
+    }
+}
+
+// Drops and wipes the registered passwords once their operation is over
+pub fn forget_passwords() {
+    if let Some(log) = INSTALL_LOG.lock().unwrap().as_mut() {
+        log.redactor.forget_passwords();
+    }
+}
+
+pub fn redact(input: &str) -> String {
+    match INSTALL_LOG.lock().unwrap().as_ref() {
+        Some(log) => log.redactor.redact(input),
+        None => input.to_string(),
+    }
+}
+
+// For the panic hook, which may run while this thread holds the log
+pub fn try_redact(input: &str) -> Option<String> {
+    let log = INSTALL_LOG.try_lock().ok()?;
+    Some(match log.as_ref() {
+        Some(log) => log.redactor.redact(input),
+        None => input.to_string(),
+    })
+}
+
+pub fn record(module: &str, level: Level, step: &str, message: &str) {
+    if let Some(log) = INSTALL_LOG.lock().unwrap().as_mut() {
+        log.write(module, level, step, message);
+    }
+}
+
+// Child process output is written line by line at trace level, otherwise only its size.
+// The tee always gets all of it.
+pub fn record_output(module: &str, step: &str, output: &[u8]) {
+    if let Some(log) = INSTALL_LOG.lock().unwrap().as_mut() {
+        let output = String::from_utf8_lossy(output);
+        for line in output.lines() {
+            log.tee(Level::Trace, step, line);
+        }
+        if log.verbosity.records_full_output() {
+            for line in output.lines() {
+                log.write(module, Level::Trace, step, line);
+            }
+        } else if !output.is_empty() {
+            let lines = output.lines().count();
+            log.write(
+                module,
+                Level::Debug,
+                step,
+                &format!("{} lines of output", lines),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_override_the_environment_which_overrides_the_default() {
+        let verbosity = Verbosity::resolve("info", 0, false, None);
+        assert_eq!(verbosity.file, LevelFilter::Info);
+        assert_eq!(verbosity.cli, None);
+        assert!(!verbosity.records_full_output());
+
+        // Unknown levels and targets are ignored, the rest still apply
+        let verbosity = Verbosity::resolve(
+            "bogus",
+            0,
+            false,
+            Some("file=trace, cli=warn,ui=debug,x=nope"),
+        );
+        assert_eq!(verbosity.file, LevelFilter::Trace);
+        assert_eq!(verbosity.cli, Some(LevelFilter::Warn));
+        assert!(verbosity.records_full_output());
+
+        let verbosity = Verbosity::resolve("info", 0, false, Some("debug"));
+        assert_eq!(verbosity.file, LevelFilter::Debug);
+        assert_eq!(verbosity.cli, Some(LevelFilter::Debug));
+
+        let verbosity = Verbosity::resolve("info", 1, false, Some("error"));
+        assert_eq!(verbosity.file, LevelFilter::Debug);
+        assert_eq!(verbosity.cli, Some(LevelFilter::Debug));
+
+        let verbosity = Verbosity::resolve("info", 2, false, None);
+        assert_eq!(verbosity.file, LevelFilter::Trace);
+        assert_eq!(verbosity.cli, Some(LevelFilter::Trace));
+
+        // --quiet wins over -v and the environment
+        let verbosity = Verbosity::resolve("info", 2, true, Some("trace"));
+        assert_eq!(verbosity.file, LevelFilter::Warn);
+        assert_eq!(verbosity.cli, Some(LevelFilter::Off));
+    }
+
+    #[test]
+    fn json_lines_carry_the_event_fields_redacted() {
+        let dir = crate::helper::private_dir("logging").expect("dir");
+        let config = LoggingConfig {
+            level: "info".to_string(),
+            format: LogFormat::Json,
+            directory: dir.display().to_string(),
+            file_name: "install.log".to_string(),
+            redact_replacement: "[redacted]".to_string(),
+            redact_username: false,
+            redact_patterns: vec!["token=\\S+".to_string()],
+        };
+        let verbosity = Verbosity::resolve(&config.level, 0, false, None);
+        let mut log = InstallLog::open(&config, verbosity, LogFormat::Json).expect("log");
+        log.redactor.add_password("hunter2");
+        log.write(
+            "sparrow_installer::worker",
+            Level::Info,
+            "fonts",
+            "ran with hunter2 token=abc",
+        );
+        log.write(
+            "sparrow_installer::worker",
+            Level::Debug,
+            "fonts",
+            "left out below info",
+        );
+        drop(log);
+
+        let written = fs::read_to_string(dir.join("install.log")).expect("read");
+        fs::remove_dir_all(&dir).ok();
+        let lines: Vec<serde_json::Value> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("one JSON object per line"))
+            .collect();
+        assert_eq!(lines.len(), 1, "{}", written);
+        let event = &lines[0];
+        assert_eq!(event["level"], "INFO");
+        assert_eq!(event["module"], "sparrow_installer::worker");
+        assert_eq!(event["step"], "fonts");
+        assert_eq!(event["message"], "ran with [redacted] [redacted]");
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(event["timestamp"].as_str().expect("timestamp"))
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use crate::styles::Styles;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+
+// Enough Markdown for option descriptions: headings, lists, quotes, code blocks and
+// **strong**, *emphasis* and `code` inline. Anything else shows as written.
+pub fn render(source: &str, styles: &Styles) -> Text<'static> {
+    let base = styles.content;
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for raw in source.lines() {
+        let trimmed = raw.trim_start();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(Line::styled(format!("  {}", raw), styles.code));
+        } else if let Some(heading) = heading(trimmed) {
+            lines.push(Line::styled(heading.to_string(), styles.heading));
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            let indent = " ".repeat(raw.len() - trimmed.len());
+            let mut spans = vec![Span::styled(format!("{}• ", indent), base)];
+            spans.extend(inline(item, base, styles));
+            lines.push(Line::from(spans));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            let quoted = base.add_modifier(Modifier::ITALIC);
+            let mut spans = vec![Span::styled("│ ", styles.border)];
+            spans.extend(inline(quote.trim_start(), quoted, styles));
+            lines.push(Line::from(spans));
+        } else {
+            lines.push(Line::from(inline(raw, base, styles)));
+        }
+    }
+    Text::from(lines)
+}
+
+// "# Title" up to "###### Title", the text without the marks
+fn heading(line: &str) -> Option<&str> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    line[level..].strip_prefix(' ').map(str::trim)
+}
+
+// Marks without a closing one are text like any other
+fn inline(text: &str, base: Style, styles: &Styles) -> Vec<Span<'static>> {
+    let marks = [
+        ("`", styles.code),
+        ("**", base.add_modifier(Modifier::BOLD)),
+        ("*", base.add_modifier(Modifier::ITALIC)),
+    ];
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+    'text: while let Some(c) = rest.chars().next() {
+        for (mark, style) in marks {
+            if let Some(after) = rest.strip_prefix(mark)
+                && let Some(end) = after.find(mark)
+                && end > 0
+            {
+                if !plain.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut plain), base));
+                }
+                spans.push(Span::styled(after[..end].to_string(), style));
+                rest = &after[end + mark.len()..];
+                continue 'text;
+            }
+        }
+        plain.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if !plain.is_empty() {
+        spans.push(Span::styled(plain, base));
+    }
+    spans
+}
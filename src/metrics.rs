@@ -0,0 +1,138 @@
+use crate::report::RunReport;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+// Counted by the steps while they run, each step gets fresh ones
+#[derive(Debug, Clone, Default)]
+pub struct Counters {
+    downloaded: Arc<AtomicU64>,
+    retries: Arc<AtomicU32>,
+}
+
+impl Counters {
+    pub fn downloaded(&self, bytes: u64) {
+        self.downloaded.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    // Another go at something that did not work or finish the first time, such as a
+    // download resumed from its partial file
+    pub fn retried(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// How one step went, kept in the report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepMetrics {
+    pub step: String,
+    pub seconds: f64,
+    pub bytes_downloaded: u64,
+    pub retries: u32,
+}
+
+impl StepMetrics {
+    pub fn new(step: &str, seconds: f64, counters: &Counters) -> Self {
+        StepMetrics {
+            step: step.to_string(),
+            seconds,
+            bytes_downloaded: counters.downloaded.load(Ordering::Relaxed),
+            retries: counters.retries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// The finished run in the Prometheus text format, for node_exporter's textfile collector
+pub fn textfile(report: &RunReport) -> String {
+    let operation = escape(&report.operation);
+    let mut out = String::new();
+    let mut metric = |name: &str, help: &str, kind: &str, samples: Vec<(String, f64)>| {
+        let _ = writeln!(out, "# HELP sparrow_installer_{} {}", name, help);
+        let _ = writeln!(out, "# TYPE sparrow_installer_{} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "sparrow_installer_{}{{{}}} {}", name, labels, value);
+        }
+    };
+    let run = format!("operation=\"{}\"", operation);
+    let step_samples = |value: fn(&StepMetrics) -> f64| {
+        report
+            .metrics
+            .iter()
+            .map(|step| {
+                (
+                    format!("{},step=\"{}\"", run, escape(&step.step)),
+                    value(step),
+                )
+            })
+            .collect()
+    };
+
+    metric(
+        "last_run_success",
+        "Whether the last run succeeded.",
+        "gauge",
+        vec![(
+            run.clone(),
+            f64::from(u8::from(report.success == Some(true))),
+        )],
+    );
+    if let Some(finished) = parse_time(report.finished_at.as_deref()) {
+        metric(
+            "last_run_timestamp_seconds",
+            "When the last run finished.",
+            "gauge",
+            vec![(run.clone(), finished.timestamp() as f64)],
+        );
+        if let Some(started) = parse_time(Some(&report.started_at)) {
+            let seconds = (finished - started).num_milliseconds() as f64 / 1000.0;
+            metric(
+                "last_run_duration_seconds",
+                "How long the last run took.",
+                "gauge",
+                vec![(run.clone(), seconds)],
+            );
+        }
+    }
+    metric(
+        "step_duration_seconds",
+        "How long each step of the last run took.",
+        "gauge",
+        step_samples(|step| step.seconds),
+    );
+    metric(
+        "step_downloaded_bytes",
+        "Bytes each step of the last run downloaded.",
+        "gauge",
+        step_samples(|step| step.bytes_downloaded as f64),
+    );
+    metric(
+        "step_retries",
+        "Retries each step of the last run needed.",
+        "gauge",
+        step_samples(|step| f64::from(step.retries)),
+    );
+    out
+}
+
+// Written next to the target and renamed over it, the collector never reads half a file
+pub fn write_textfile(path: &Path, report: &RunReport) -> Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".tmp");
+    std::fs::write(&partial, textfile(report))?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+fn parse_time(time: Option<&str>) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    chrono::DateTime::parse_from_rfc3339(time?).ok()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
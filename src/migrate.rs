@@ -0,0 +1,239 @@
+use crate::schema::{self, ConfigFile};
+use crate::{TEXT_CONFIG, THEME_CONFIG, TextConfig, ThemeConfig, pipeline};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+// Keys that moved, as dotted paths from the old name to the new one
+const RENAMES: [(ConfigFile, &str, &str); 1] = [(
+    ConfigFile::Text,
+    "messages.confirm_skipped",
+    "messages.plan_skipped",
+)];
+
+// Upgrades an older file to what this version reads: moved keys are renamed, keys nothing
+// reads anymore are dropped and new theme and text keys get their defaults. Comments and
+// the order of what is kept stay as they were.
+pub fn migrate(source: &str, file: ConfigFile) -> Result<(String, Vec<String>)> {
+    let mut doc: DocumentMut = source.parse()?;
+    let mut changes = Vec::new();
+    for (kind, from, to) in RENAMES {
+        if kind == file
+            && let Some(item) = take(doc.as_table_mut(), from)
+        {
+            if put(doc.as_table_mut(), to, item) {
+                changes.push(format!("renamed {} to {}", from, to));
+            } else {
+                changes.push(format!("removed {}, {} is already set", from, to));
+            }
+        }
+    }
+
+    let schema = serde_json::to_value(schema::schema(file))?;
+    drop_unknown(doc.as_table_mut(), &schema, &schema, "", &mut changes);
+
+    let defaults = match file {
+        ConfigFile::Theme => Some(THEME_CONFIG),
+        ConfigFile::Text => Some(TEXT_CONFIG),
+        // Steps are the author's own, there is nothing to fill in
+        ConfigFile::Profile | ConfigFile::Pipeline => None,
+    };
+    if let Some(defaults) = defaults {
+        let defaults: DocumentMut = defaults.parse()?;
+        fill(doc.as_table_mut(), defaults.as_table(), "", &mut changes);
+    }
+
+    let migrated = doc.to_string();
+    check(file, &migrated).context("the migrated file still does not load")?;
+    Ok((migrated, changes))
+}
+
+// The kind of file from the tables it has, for when the command line does not say. A
+// profile may only have steps like a drop-in, then its name tells them apart.
+pub fn guess(path: &Path, source: &str) -> Option<ConfigFile> {
+    let doc: DocumentMut = source.parse().ok()?;
+    let has = |key| doc.contains_key(key);
+    let named_profile = path
+        .file_stem()
+        .is_some_and(|stem| stem.to_string_lossy().contains("profile"));
+    if has("colors") {
+        Some(ConfigFile::Theme)
+    } else if has("messages") {
+        Some(ConfigFile::Text)
+    } else if has("wallpapers") || has("verify") || (named_profile && has("steps")) {
+        Some(ConfigFile::Profile)
+    } else if has("steps") {
+        Some(ConfigFile::Pipeline)
+    } else {
+        None
+    }
+}
+
+fn check(file: ConfigFile, source: &str) -> Result<()> {
+    match file {
+        ConfigFile::Theme => toml::from_str::<ThemeConfig>(source).map(drop),
+        ConfigFile::Text => toml::from_str::<TextConfig>(source).map(drop),
+        ConfigFile::Profile => toml::from_str::<pipeline::ProfileConfig>(source).map(drop),
+        ConfigFile::Pipeline => toml::from_str::<pipeline::DropIn>(source).map(drop),
+    }?;
+    Ok(())
+}
+
+fn take(table: &mut dyn TableLike, path: &str) -> Option<Item> {
+    match path.split_once('.') {
+        Some((key, rest)) => take(table.get_mut(key)?.as_table_like_mut()?, rest),
+        None => table.remove(path),
+    }
+}
+
+// False when the key is set already, the newer spelling wins
+fn put(table: &mut dyn TableLike, path: &str, item: Item) -> bool {
+    match path.split_once('.') {
+        Some((key, rest)) => {
+            if !table.contains_key(key) {
+                table.insert(key, Item::Table(Default::default()));
+            }
+            match table.get_mut(key).and_then(Item::as_table_like_mut) {
+                Some(inner) => put(inner, rest, item),
+                None => false,
+            }
+        }
+        None if table.contains_key(path) => false,
+        None => {
+            table.insert(path, item);
+            true
+        }
+    }
+}
+
+// Follows "$ref" to the definition it names
+fn resolve<'a>(node: &'a Value, root: &'a Value) -> &'a Value {
+    match node["$ref"]
+        .as_str()
+        .and_then(|r| r.strip_prefix("#/$defs/"))
+    {
+        Some(name) => &root["$defs"][name],
+        None => node,
+    }
+}
+
+// Schemas a value may match, the node itself and every branch of an enum or Option
+fn branches<'a>(node: &'a Value, root: &'a Value) -> Vec<&'a Value> {
+    let node = resolve(node, root);
+    let mut all = vec![node];
+    for keyword in ["oneOf", "anyOf", "allOf"] {
+        for branch in node[keyword].as_array().into_iter().flatten() {
+            all.extend(branches(branch, root));
+        }
+    }
+    all
+}
+
+fn property<'a>(node: &'a Value, root: &'a Value, key: &str) -> Option<&'a Value> {
+    branches(node, root)
+        .into_iter()
+        .find_map(|branch| branch["properties"].get(key))
+}
+
+fn drop_unknown(
+    table: &mut dyn TableLike,
+    node: &Value,
+    root: &Value,
+    path: &str,
+    changes: &mut Vec<String>,
+) {
+    // Maps such as the menu icons take any key
+    if !branches(node, root)
+        .iter()
+        .any(|branch| branch.get("properties").is_some())
+    {
+        return;
+    }
+    let keys: Vec<String> = table.iter().map(|(key, _)| key.to_string()).collect();
+    for key in keys {
+        let name = join(path, &key);
+        let Some(child) = property(node, root, &key) else {
+            table.remove(&key);
+            changes.push(format!("removed {}, it is no longer used", name));
+            continue;
+        };
+        match table.get_mut(&key) {
+            Some(Item::ArrayOfTables(tables)) => {
+                let items = &resolve(child, root)["items"];
+                for inner in tables.iter_mut() {
+                    drop_unknown(inner, items, root, &name, changes);
+                }
+            }
+            Some(item) => {
+                if let Some(inner) = item.as_table_like_mut() {
+                    drop_unknown(inner, child, root, &name, changes);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+fn fill(
+    table: &mut dyn TableLike,
+    defaults: &dyn TableLike,
+    path: &str,
+    changes: &mut Vec<String>,
+) {
+    for (key, default) in defaults.iter() {
+        let name = join(path, key);
+        match table.get_mut(key) {
+            None => {
+                table.insert(key, default.clone());
+                changes.push(format!("added {}", name));
+            }
+            Some(item) => {
+                if let (Some(inner), Some(default)) =
+                    (item.as_table_like_mut(), default.as_table_like())
+                {
+                    fill(inner, default, &name, changes);
+                }
+            }
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+// `sparrow-installer migrate-config`: rewrites the file in place, keeping the original
+// next to it as <file>.bak
+pub fn command(path: &Path, kind: Option<ConfigFile>) -> Result<()> {
+    let source =
+        std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let Some(kind) = kind.or_else(|| guess(path, &source)) else {
+        anyhow::bail!(
+            "cannot tell what kind of file {} is, pass --kind",
+            path.display()
+        );
+    };
+    let (migrated, changes) = migrate(&source, kind)?;
+    if changes.is_empty() {
+        println!("{} is up to date", path.display());
+        return Ok(());
+    }
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    std::fs::copy(path, &backup)?;
+    std::fs::write(path, migrated)?;
+    for change in &changes {
+        println!("{}", change);
+    }
+    println!(
+        "Migrated {}, the original is in {}",
+        path.display(),
+        Path::new(&backup).display()
+    );
+    Ok(())
+}
@@ -0,0 +1,299 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use tokio::process::Command as AsyncCommand;
+
+pub const SCALES: [f32; 8] = [1.0, 1.25, 1.5, 1.6, 1.75, 2.0, 2.5, 3.0];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mode {
+    pub width: u32,
+    pub height: u32,
+    // Unknown when only the DRM mode list is available
+    pub refresh: Option<f32>,
+}
+
+impl Mode {
+    pub fn label(&self) -> String {
+        match self.refresh {
+            Some(refresh) => format!("{}x{} @ {:.2} Hz", self.width, self.height, refresh),
+            None => format!("{}x{}", self.width, self.height),
+        }
+    }
+
+    // The resolution field of a Hyprland `monitor =` line
+    pub fn hyprland(&self) -> String {
+        match self.refresh {
+            Some(refresh) => format!("{}x{}@{:.2}", self.width, self.height, refresh),
+            None => format!("{}x{}", self.width, self.height),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    pub name: String,
+    pub description: String,
+    pub modes: Vec<Mode>,
+    // Physical size in millimetres, from the compositor or the EDID
+    pub size_mm: Option<(u32, u32)>,
+    pub mode: usize,
+    pub scale: usize,
+}
+
+impl Monitor {
+    fn new(
+        name: String,
+        description: String,
+        modes: Vec<Mode>,
+        preferred: usize,
+        size_mm: Option<(u32, u32)>,
+    ) -> Self {
+        let mut monitor = Self {
+            name,
+            description,
+            modes,
+            size_mm,
+            mode: preferred,
+            scale: 0,
+        };
+        monitor.scale = monitor.suggested_scale();
+        monitor
+    }
+
+    pub fn selected_mode(&self) -> Option<&Mode> {
+        self.modes.get(self.mode)
+    }
+
+    pub fn scale_value(&self) -> f32 {
+        SCALES[self.scale.min(SCALES.len() - 1)]
+    }
+
+    // Index into SCALES closest to a logical density of about 110 dpi
+    pub fn suggested_scale(&self) -> usize {
+        let (Some(mode), Some((width_mm, _))) = (self.selected_mode(), self.size_mm) else {
+            return 0;
+        };
+        if width_mm == 0 {
+            return 0;
+        }
+        let dpi = mode.width as f32 / (width_mm as f32 / 25.4);
+        let wanted = dpi / 110.0;
+        SCALES
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - wanted).abs().total_cmp(&(*b - wanted).abs()))
+            .map_or(0, |(index, _)| index)
+    }
+
+    pub fn hyprland_line(&self) -> String {
+        let mode = self
+            .selected_mode()
+            .map_or_else(|| "preferred".to_string(), Mode::hyprland);
+        format!(
+            "monitor = {}, {}, auto, {}",
+            self.name,
+            mode,
+            self.scale_value()
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct RandrOutput {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    physical_size: Option<RandrSize>,
+    #[serde(default)]
+    modes: Vec<RandrMode>,
+}
+
+#[derive(Deserialize)]
+struct RandrSize {
+    width: u32,
+    height: u32,
+}
+
+#[derive(Deserialize)]
+struct RandrMode {
+    width: u32,
+    height: u32,
+    refresh: f32,
+    #[serde(default)]
+    preferred: bool,
+}
+
+// Prefers the compositor's view (with refresh rates), falls back to DRM connectors
+pub async fn detect() -> Vec<Monitor> {
+    if let Some(monitors) = detect_wlr_randr().await
+        && !monitors.is_empty()
+    {
+        return monitors;
+    }
+    detect_drm(Path::new("/sys/class/drm"))
+}
+
+async fn detect_wlr_randr() -> Option<Vec<Monitor>> {
+    let output = AsyncCommand::new("wlr-randr")
+        .arg("--json")
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let outputs: Vec<RandrOutput> = serde_json::from_slice(&output.stdout).ok()?;
+    Some(
+        outputs
+            .into_iter()
+            .map(|output| {
+                let preferred = output
+                    .modes
+                    .iter()
+                    .position(|mode| mode.preferred)
+                    .unwrap_or(0);
+                let modes = output
+                    .modes
+                    .iter()
+                    .map(|mode| Mode {
+                        width: mode.width,
+                        height: mode.height,
+                        refresh: Some(mode.refresh),
+                    })
+                    .collect();
+                let size = output
+                    .physical_size
+                    .filter(|size| size.width > 0 && size.height > 0)
+                    .map(|size| (size.width, size.height));
+                Monitor::new(output.name, output.description, modes, preferred, size)
+            })
+            .collect(),
+    )
+}
+
+fn detect_drm(root: &Path) -> Vec<Monitor> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut monitors: Vec<Monitor> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            // Connectors look like card0-eDP-1, the part after the card is Hyprland's name
+            let (_, name) = file_name.split_once('-')?;
+            let status = fs::read_to_string(path.join("status")).ok()?;
+            if status.trim() != "connected" {
+                return None;
+            }
+
+            // The kernel lists the preferred mode first
+            let mut modes: Vec<Mode> = Vec::new();
+            for line in fs::read_to_string(path.join("modes")).ok()?.lines() {
+                let Some((width, height)) = line.trim().split_once('x') else {
+                    continue;
+                };
+                let height = height.trim_end_matches(|c: char| !c.is_ascii_digit());
+                let (Ok(width), Ok(height)) = (width.parse(), height.parse()) else {
+                    continue;
+                };
+                let mode = Mode {
+                    width,
+                    height,
+                    refresh: None,
+                };
+                if !modes.contains(&mode) {
+                    modes.push(mode);
+                }
+            }
+
+            let size = fs::read(path.join("edid"))
+                .ok()
+                .filter(|edid| edid.len() > 22 && edid[21] > 0 && edid[22] > 0)
+                .map(|edid| (edid[21] as u32 * 10, edid[22] as u32 * 10));
+            Some(Monitor::new(
+                name.to_string(),
+                String::new(),
+                modes,
+                0,
+                size,
+            ))
+        })
+        .collect();
+    monitors.sort_by(|a, b| a.name.cmp(&b.name));
+    monitors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+
+    #[test]
+    fn connected_drm_outputs_get_a_scale_for_their_density() {
+        let root = crate::helper::private_dir("sparrow-drm-test").expect("temporary folder");
+        let connector = |name: &str, status: &str, modes: &str, edid: Option<(u8, u8)>| {
+            let dir = root.join(name);
+            fs::create_dir(&dir).expect("connector");
+            fs::write(dir.join("status"), status).expect("status");
+            fs::write(dir.join("modes"), modes).expect("modes");
+            if let Some((width_cm, height_cm)) = edid {
+                let mut edid = vec![0u8; 128];
+                edid[21] = width_cm;
+                edid[22] = height_cm;
+                fs::write(dir.join("edid"), edid).expect("edid");
+            }
+        };
+        connector(
+            "card0-eDP-1",
+            "connected\n",
+            "2880x1800\n1920x1200\n2880x1800\n",
+            Some((30, 19)),
+        );
+        connector("card0-HDMI-A-1", "disconnected\n", "1920x1080\n", None);
+        connector("card0-DP-2", "connected\n", "1920x1080i\n", None);
+
+        let monitors = detect_drm(&root);
+        let names: Vec<&str> = monitors
+            .iter()
+            .map(|monitor| monitor.name.as_str())
+            .collect();
+        assert_eq!(names, ["DP-2", "eDP-1"]);
+        // No size without an EDID, so no scaling
+        assert_eq!(
+            monitors[0].hyprland_line(),
+            "monitor = DP-2, 1920x1080, auto, 1"
+        );
+        // 2880 pixels across 30 cm is about 244 dpi
+        assert_eq!(monitors[1].modes.len(), 2);
+        assert_eq!(
+            monitors[1].hyprland_line(),
+            "monitor = eDP-1, 2880x1800, auto, 2"
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn the_compositors_view_comes_first() {
+        let system = MockSystem::new();
+        system.program(
+            "wlr-randr",
+            r#"echo '[{"name":"DP-1","description":"Dell U2720Q","physical_size":{"width":600,"height":340},
+"modes":[{"width":1920,"height":1080,"refresh":60.0},{"width":3840,"height":2160,"refresh":59.997,"preferred":true}]}]'"#,
+        );
+
+        let monitors = detect().await;
+        assert_eq!(monitors.len(), 1);
+        assert_eq!(monitors[0].description, "Dell U2720Q");
+        assert_eq!(
+            monitors[0].selected_mode().map(Mode::label).as_deref(),
+            Some("3840x2160 @ 60.00 Hz")
+        );
+        assert_eq!(
+            monitors[0].hyprland_line(),
+            "monitor = DP-1, 3840x2160@60.00, auto, 1.5"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::logging;
+use cli_log::Level;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Milliseconds since the epoch of the last sign of life from the install
+static LAST_ACTIVITY: AtomicU64 = AtomicU64::new(0);
+
+// Sends a state such as "WATCHDOG=1" to systemd, a no-op when not started as a notify service
+pub fn notify(state: &str) -> bool {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return false;
+    };
+    let result = match path.as_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)
+            .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr)),
+        None => socket.send_to(state.as_bytes(), &path),
+    };
+    result.is_ok()
+}
+
+// Shown by `systemctl status` next to the unit
+pub fn status(message: &str) {
+    notify(&format!("STATUS={}", message.replace('\n', " ")));
+}
+
+// The WatchdogSec= of the service, when it applies to this process
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse() != Ok(std::process::id())
+    {
+        return None;
+    }
+    Some(Duration::from_micros(usec))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+// Called whenever the install makes progress, keeps the watchdog fed
+pub fn heartbeat() {
+    LAST_ACTIVITY.store(now(), Ordering::Relaxed);
+}
+
+// Pings the systemd watchdog while the install keeps making progress. Once nothing has
+// happened for `stall_timeout` the pings stop, so systemd restarts or fails the service
+// instead of provisioning waiting on a hung installer forever.
+pub fn spawn_watchdog(stall_timeout: Duration) {
+    let Some(interval) = watchdog_interval() else {
+        return;
+    };
+    heartbeat();
+    logging::event!(
+        Level::Info,
+        "watchdog",
+        "Pinging the systemd watchdog every {}s",
+        (interval / 2).as_secs_f32()
+    );
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval / 2);
+        loop {
+            ticker.tick().await;
+            let idle = now().saturating_sub(LAST_ACTIVITY.load(Ordering::Relaxed));
+            if u128::from(idle) > stall_timeout.as_millis() {
+                logging::event!(
+                    Level::Error,
+                    "watchdog",
+                    "No progress for {}s, leaving the service to the systemd watchdog",
+                    idle / 1000
+                );
+                return;
+            }
+            notify("WATCHDOG=1");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pings(socket: &UnixDatagram) -> usize {
+        let mut buffer = [0; 64];
+        let mut count = 0;
+        while let Ok(length) = socket.recv(&mut buffer) {
+            assert_eq!(&buffer[..length], b"WATCHDOG=1");
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn the_watchdog_goes_quiet_once_the_install_stalls() {
+        let _env = crate::fixtures::lock_env();
+        let dir = crate::helper::private_dir("sparrow-notify-test").expect("temporary folder");
+        let path = dir.join("notify");
+        let socket = UnixDatagram::bind(&path).expect("notify socket");
+        socket.set_nonblocking(true).expect("nonblocking");
+        // SAFETY: lock_env keeps other tests from reading the environment meanwhile
+        unsafe {
+            std::env::set_var("NOTIFY_SOCKET", &path);
+            std::env::set_var("WATCHDOG_USEC", "100000");
+            std::env::remove_var("WATCHDOG_PID");
+        }
+
+        // Pings on the runtime's own threads while this one waits
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_time()
+            .build()
+            .expect("runtime");
+        let _entered = runtime.enter();
+        spawn_watchdog(Duration::from_millis(300));
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(
+            pings(&socket) > 0,
+            "pinged while the install makes progress"
+        );
+        // No heartbeat since, the pings stop after the stall timeout
+        std::thread::sleep(Duration::from_millis(300));
+        pings(&socket);
+        std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(pings(&socket), 0);
+
+        unsafe {
+            std::env::remove_var("NOTIFY_SOCKET");
+            std::env::remove_var("WATCHDOG_USEC");
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn states_reach_systemd_only_under_a_notify_service() {
+        let _env = crate::fixtures::lock_env();
+        // SAFETY: lock_env keeps other tests from reading the environment meanwhile
+        unsafe { std::env::remove_var("NOTIFY_SOCKET") };
+        assert!(!notify("READY=1"));
+
+        let dir = crate::helper::private_dir("sparrow-notify-test").expect("temporary folder");
+        let path = dir.join("notify");
+        let socket = UnixDatagram::bind(&path).expect("notify socket");
+        unsafe { std::env::set_var("NOTIFY_SOCKET", &path) };
+        assert!(notify("READY=1"));
+        status("Installing dotfiles, step 3/9:\nKitty");
+        unsafe { std::env::remove_var("NOTIFY_SOCKET") };
+
+        let mut buffer = [0; 128];
+        let length = socket.recv(&mut buffer).expect("ready");
+        assert_eq!(&buffer[..length], b"READY=1");
+        let length = socket.recv(&mut buffer).expect("status");
+        assert_eq!(
+            &buffer[..length],
+            b"STATUS=Installing dotfiles, step 3/9: Kitty"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
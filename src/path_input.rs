@@ -0,0 +1,182 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// What the typed path has to point at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathKind {
+    // A file that is about to be written, only its directory has to exist
+    NewFile,
+    Directory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathError {
+    Empty,
+    // The directory the path would be in is missing
+    NoParent,
+    Missing,
+    NotADirectory,
+    NotAFile,
+}
+
+// A path typed into a prompt, with Tab completing it against the filesystem
+#[derive(Debug, Clone)]
+pub struct PathInput {
+    pub value: String,
+    pub kind: PathKind,
+    // Entries matching what was typed when Tab was last pressed
+    pub completions: Vec<String>,
+    // Next completion a repeated Tab puts in, once the common part is filled in
+    cycle: Option<usize>,
+    pub error: Option<PathError>,
+}
+
+impl PathInput {
+    pub fn new(value: impl Into<String>, kind: PathKind) -> Self {
+        Self {
+            value: value.into(),
+            kind,
+            completions: Vec::new(),
+            cycle: None,
+            error: None,
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.value.push(c);
+        self.edited();
+    }
+
+    pub fn pop(&mut self) {
+        self.value.pop();
+        self.edited();
+    }
+
+    fn edited(&mut self) {
+        self.completions.clear();
+        self.cycle = None;
+        self.error = None;
+    }
+
+    // Fills in as much as all matching entries share, then steps through them on later presses
+    pub fn complete(&mut self) {
+        if let Some(next) = self.cycle {
+            self.value = self.completions[next].clone();
+            self.cycle = Some((next + 1) % self.completions.len());
+            return;
+        }
+
+        self.completions = completions(&self.value, self.kind == PathKind::Directory);
+        self.error = None;
+        match self.completions.as_slice() {
+            [] => {}
+            [only] => {
+                self.value = only.clone();
+                self.completions.clear();
+            }
+            several => {
+                let common = common_prefix(several);
+                if common.len() > self.value.len() {
+                    self.value = common;
+                } else {
+                    self.value = several[0].clone();
+                    self.cycle = Some(1 % several.len());
+                }
+            }
+        }
+    }
+
+    // The path with ~ expanded, when it points at the right kind of thing
+    pub fn validate(&mut self) -> Result<PathBuf, PathError> {
+        let result = check(&self.value, self.kind);
+        self.error = result.as_ref().err().copied();
+        result
+    }
+}
+
+fn check(value: &str, kind: PathKind) -> Result<PathBuf, PathError> {
+    if value.trim().is_empty() {
+        return Err(PathError::Empty);
+    }
+    let path = expand(value);
+    match kind {
+        PathKind::NewFile => {
+            if path.is_dir() {
+                return Err(PathError::NotAFile);
+            }
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            if !parent.is_dir() {
+                return Err(PathError::NoParent);
+            }
+        }
+        PathKind::Directory if !path.exists() => return Err(PathError::Missing),
+        PathKind::Directory if !path.is_dir() => return Err(PathError::NotADirectory),
+        _ => {}
+    }
+    Ok(path)
+}
+
+// ~ stands for the home of whoever runs the installer
+pub fn expand(value: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match (value, home) {
+        ("~", Some(home)) => home,
+        (_, Some(home)) if value.starts_with("~/") => home.join(&value[2..]),
+        _ => PathBuf::from(value),
+    }
+}
+
+// Entries of the typed directory that start with the typed name, directories ending in /.
+// Hidden entries are offered once the name starts with a dot.
+pub fn completions(value: &str, directories_only: bool) -> Vec<String> {
+    let (dir, name) = match value.rfind('/') {
+        Some(slash) => value.split_at(slash + 1),
+        None => ("", value),
+    };
+    let listed = if dir.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand(dir)
+    };
+    let Ok(entries) = fs::read_dir(&listed) else {
+        return Vec::new();
+    };
+
+    let mut matches: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            if !file_name.starts_with(name)
+                || (file_name.starts_with('.') && !name.starts_with('.'))
+            {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            if directories_only && !is_dir {
+                return None;
+            }
+            let slash = if is_dir { "/" } else { "" };
+            Some(format!("{}{}{}", dir, file_name, slash))
+        })
+        .collect();
+    matches.sort();
+    matches
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let first = &candidates[0];
+    let mut end = first.len();
+    for other in &candidates[1..] {
+        end = first
+            .char_indices()
+            .zip(other.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8())
+            .min(end);
+    }
+    first[..end].to_string()
+}
@@ -0,0 +1,1075 @@
+use crate::condition;
+use crate::logging;
+use crate::snapshot::{expand_home, expand_home_in};
+use crate::state::{self, InstallMarker};
+use crate::steps;
+use crate::steps::default_apps::DefaultApps;
+use crate::steps::download::Download;
+use crate::steps::extract::Extract;
+use crate::steps::files::FileDeploy;
+use crate::steps::fonts::FontInstall;
+use crate::steps::git::GitCheckout;
+use crate::steps::gsettings::{DconfProfile, GsettingsKey};
+use crate::steps::hyprland::{HyprlandMerge, HyprlandMonitors};
+use anyhow::Result;
+use cli_log::Level;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::UnboundedSender;
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct ProfileConfig {
+    pub steps: Vec<StepConfig>,
+    #[serde(default)]
+    pub wallpapers: Vec<WallpaperPack>,
+    #[serde(default)]
+    pub verify: Vec<crate::verify::VerifyCheck>,
+    // Drop-ins left out because they did not load, the main menu points them out
+    #[serde(skip)]
+    pub ignored: Vec<PathBuf>,
+}
+
+// Offered on the wallpaper screen before the default install when the profile lists any
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct WallpaperPack {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // Pre-selected when the screen opens
+    #[serde(default)]
+    pub default: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct StepConfig {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub action: StepAction,
+    #[serde(default)]
+    pub expect_files: Vec<String>,
+    #[serde(default)]
+    pub expect_services: Vec<String>,
+    #[serde(default)]
+    pub expect_user_services: Vec<String>,
+    // Run systemd actions against the user manager instead of the system one
+    #[serde(default)]
+    pub user: bool,
+    // Only runs when picked on the optional components screen
+    #[serde(default)]
+    pub opt_in: bool,
+    #[serde(default)]
+    pub description: String,
+    // Paths whose SELinux labels are restored after the step, see [selinux] in settings
+    #[serde(default)]
+    pub relabel: Vec<String>,
+    // Repeat the step for every account picked on the users screen, guessed from the action when unset
+    #[serde(default)]
+    pub per_user: Option<bool>,
+    // CPU architectures the step is for, as Rust names them (x86_64, aarch64), all when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arch: Vec<String>,
+    // Checked right before the step runs, see condition.rs for what it can test
+    #[serde(default)]
+    pub condition: Option<String>,
+    // Seconds the step usually takes, for the plan screen, see [plan] in settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<u64>,
+    // Script from a third party, run in bwrap with the system read-only, see sandbox.rs
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub untrusted: bool,
+    // Untrusted scripts get the network only when they ask for it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub network: bool,
+    // The only paths an untrusted script can write besides /tmp
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub writable: Vec<String>,
+}
+
+// Extra steps dropped into ACTIONS_DIR by the image or an administrator, one table of
+// [[steps]] per file like the profile
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DropIn {
+    steps: Vec<StepConfig>,
+}
+
+pub const ACTIONS_DIR: &str = "/etc/sparrow-installer/actions.d";
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StepAction {
+    Script(String),
+    SystemdEnable(Vec<String>),
+    Copy(FileDeploy),
+    Template(FileDeploy),
+    Git(GitCheckout),
+    Download(Download),
+    Extract(Extract),
+    Gsettings(Vec<GsettingsKey>),
+    Dconf(DconfProfile),
+    HyprlandMerge(HyprlandMerge),
+    Fonts(FontInstall),
+    HyprlandMonitors(HyprlandMonitors),
+    DefaultApps(DefaultApps),
+}
+
+// Progress of a long running step, `fraction` is None when the total is unknown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepProgress {
+    pub step: String,
+    pub fraction: Option<f32>,
+    pub message: String,
+}
+
+// Everything a step needs to know about the run it is part of
+#[derive(Debug, Clone)]
+pub struct StepContext {
+    pub dry_run: bool,
+    // Values for `{{ name }}` placeholders, gathered from the system and the wizard screens
+    pub variables: BTreeMap<String, String>,
+    pub progress: Option<UnboundedSender<StepProgress>>,
+    pub network: crate::NetworkConfig,
+    pub selinux: crate::SelinuxConfig,
+    // Bytes and retries for the run's metrics
+    pub counters: crate::metrics::Counters,
+}
+
+impl StepContext {
+    // The account being set up, which is not root when running under sudo
+    pub fn username(&self) -> &str {
+        self.variables.get("username").map_or("", String::as_str)
+    }
+
+    // Home of that account, set per user by PlannedStep::context. `~/` in the step's paths
+    // is below it.
+    pub fn home(&self) -> Option<&str> {
+        self.variables.get("home").map(String::as_str)
+    }
+
+    pub fn expand_home(&self, path: &str) -> PathBuf {
+        expand_home_in(path, self.home())
+    }
+
+    // Variables exported to scripts as SPARROW_<NAME>, e.g. SPARROW_WALLPAPER_PACKS
+    pub fn environment(&self) -> Vec<(String, String)> {
+        self.variables
+            .iter()
+            .map(|(name, value)| (format!("SPARROW_{}", name.to_uppercase()), value.clone()))
+            .collect()
+    }
+
+    pub fn report_progress(&self, step: &str, fraction: Option<f32>, message: &str) {
+        if let Some(progress) = &self.progress {
+            // The receiver going away only means nobody is watching anymore
+            let _ = progress.send(StepProgress {
+                step: step.to_string(),
+                fraction,
+                message: message.to_string(),
+            });
+        }
+    }
+}
+
+// Shipped settings and no variables, for the steps' own tests
+#[cfg(test)]
+impl Default for StepContext {
+    fn default() -> Self {
+        let settings = crate::SettingsConfig::load().expect("settings.toml");
+        StepContext {
+            dry_run: false,
+            variables: BTreeMap::new(),
+            progress: None,
+            network: settings.network,
+            selinux: settings.selinux,
+            counters: Default::default(),
+        }
+    }
+}
+
+pub fn system_variables() -> BTreeMap<String, String> {
+    let mut variables = BTreeMap::new();
+
+    let username = std::env::var("SUDO_USER")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_default();
+    variables.insert("username".to_string(), username);
+
+    let hostname = std::fs::read_to_string("/etc/hostname")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default();
+    variables.insert("hostname".to_string(), hostname);
+
+    if let Ok(home) = std::env::var("HOME") {
+        variables.insert("home".to_string(), home);
+    }
+
+    variables
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    // Completed, but with something the user should look at
+    Warning,
+    // Not run, its condition does not hold on this system
+    Skipped,
+}
+
+// One line of the step checklist shown while the pipeline runs and kept in the report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChecklistItem {
+    pub label: String,
+    pub status: CheckStatus,
+    pub depth: u8,
+}
+
+impl ChecklistItem {
+    pub fn new(label: impl Into<String>, status: CheckStatus, depth: u8) -> Self {
+        Self {
+            label: label.into(),
+            status,
+            depth,
+        }
+    }
+}
+
+// A change a step would make, listed instead of made in a dry run
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    Execute(String),
+    Install(String),
+    Update(String),
+    Create(PathBuf),
+    Modify(PathBuf),
+    Remove(PathBuf),
+}
+
+impl Effect {
+    // Writing a file creates it or changes the one already there
+    pub fn write(path: PathBuf) -> Self {
+        if path.exists() {
+            Effect::Modify(path)
+        } else {
+            Effect::Create(path)
+        }
+    }
+
+    pub fn describe(&self, text: &crate::DryRun) -> String {
+        match self {
+            Effect::Execute(command) => format!("{} {}", text.would_execute, command),
+            Effect::Install(what) => format!("{} {}", text.would_install, what),
+            Effect::Update(what) => format!("{} {}", text.would_update, what),
+            Effect::Create(path) => format!("{} {}", text.would_create, path.display()),
+            Effect::Modify(path) => format!("{} {}", text.would_modify, path.display()),
+            Effect::Remove(path) => format!("{} {}", text.would_remove, path.display()),
+        }
+    }
+}
+
+// An account the per-user steps run for when root installs for several people
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetUser {
+    pub name: String,
+    pub home: String,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+// A step of the run, bound to one account when it writes per-user state
+#[derive(Debug, Clone)]
+pub struct PlannedStep {
+    pub step: StepConfig,
+    pub user: Option<TargetUser>,
+}
+
+// A step selected by repair together with why it has to run again
+#[derive(Debug, Clone)]
+pub struct RepairItem {
+    pub step: PlannedStep,
+    pub reasons: Vec<String>,
+}
+
+impl ProfileConfig {
+    pub fn load() -> Result<Self> {
+        Self::load_from(Path::new(ACTIONS_DIR))
+    }
+
+    // The embedded profile with the drop-ins of `dir`
+    pub fn load_from(dir: &Path) -> Result<Self> {
+        let mut config: ProfileConfig = toml::from_str(crate::PROFILE_CONFIG)?;
+        // A broken drop-in is left out rather than keeping the installer from starting,
+        // the self-test reports it
+        for (path, steps) in read_drop_ins(dir) {
+            match steps {
+                Ok(steps) => config.steps.extend(steps),
+                Err(e) => {
+                    logging::event!(
+                        Level::Warn,
+                        "profile",
+                        "Ignoring {}: {:#}",
+                        path.display(),
+                        e
+                    );
+                    config.ignored.push(path);
+                }
+            }
+        }
+        config.retain_arch(std::env::consts::ARCH);
+        Ok(config)
+    }
+
+    // Drops the steps meant for other architectures, so one profile serves every image
+    pub fn retain_arch(&mut self, arch: &str) {
+        self.steps.retain(|step| {
+            let keep = step.arch.is_empty() || step.arch.iter().any(|a| a == arch);
+            if !keep {
+                logging::event!(
+                    Level::Debug,
+                    &step.id,
+                    "Left out on {}, the step is for {}",
+                    arch,
+                    step.arch.join(", ")
+                );
+            }
+            keep
+        });
+    }
+
+    pub fn optional_steps(&self) -> impl Iterator<Item = &StepConfig> {
+        self.steps.iter().filter(|step| step.opt_in)
+    }
+
+    // Whether the display screen has anywhere to write its choices
+    pub fn configures_monitors(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| matches!(step.action, StepAction::HyprlandMonitors(_)))
+    }
+
+    // Combined fingerprint of every step, changes whenever the profile or its scripts do
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        for step in &self.steps {
+            hasher.update(step.fingerprint());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+impl StepConfig {
+    // Hash of the step definition plus the content of any file it executes
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(toml::to_string(self).unwrap_or_default());
+        let source = match &self.action {
+            StepAction::Script(path) => Some(path.as_str()),
+            StepAction::Copy(file) | StepAction::Template(file) => Some(file.source.as_str()),
+            StepAction::Extract(archive) => Some(archive.source.as_str()),
+            StepAction::Dconf(dconf) => Some(dconf.keyfile.as_str()),
+            StepAction::HyprlandMerge(merge) => Some(merge.source.as_str()),
+            StepAction::SystemdEnable(_)
+            | StepAction::Git(_)
+            | StepAction::Download(_)
+            | StepAction::Gsettings(_)
+            | StepAction::Fonts(_)
+            | StepAction::HyprlandMonitors(_)
+            | StepAction::DefaultApps(_) => None,
+        };
+        if let Some(source) = source {
+            hasher.update(state::content_hash(&expand_home(source)).unwrap_or_default());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    // Why the step does not apply to this system, None when it runs
+    pub fn skip_reason(&self, home: Option<&str>) -> Result<Option<String>> {
+        let Some(condition) = &self.condition else {
+            return Ok(None);
+        };
+        Ok(
+            (!condition::evaluate_in(condition, home)?)
+                .then(|| format!("`{}` is false", condition)),
+        )
+    }
+
+    // Whether the step writes into a user's home or session rather than the system
+    pub fn per_user(&self) -> bool {
+        if let Some(per_user) = self.per_user {
+            return per_user;
+        }
+        let in_home = |dest: &str| dest.starts_with("~/") || dest.contains("home }}");
+        match &self.action {
+            StepAction::SystemdEnable(_) => self.user,
+            StepAction::Copy(file) | StepAction::Template(file) => in_home(&file.dest),
+            StepAction::Git(git) => in_home(&git.dest),
+            StepAction::Download(file) => in_home(&file.dest),
+            StepAction::Extract(archive) => in_home(&archive.dest),
+            StepAction::Fonts(fonts) => !fonts.system,
+            StepAction::Gsettings(_)
+            | StepAction::HyprlandMerge(_)
+            | StepAction::HyprlandMonitors(_)
+            | StepAction::DefaultApps(_) => true,
+            StepAction::Script(_) | StepAction::Dconf(_) => false,
+        }
+    }
+
+    // Differences between the state this step should have produced and the system
+    pub async fn drift(&self, key: &str, marker: &InstallMarker, ctx: &StepContext) -> Vec<String> {
+        let mut reasons = Vec::new();
+
+        match marker.steps.get(key) {
+            None => reasons.push("did not complete".to_string()),
+            Some(fingerprint) if *fingerprint != self.fingerprint() => {
+                reasons.push("was modified".to_string())
+            }
+            _ => {}
+        }
+
+        for file in &self.expect_files {
+            if !ctx.expand_home(file).exists() {
+                reasons.push(format!("missing {}", file));
+            }
+        }
+
+        for unit in &self.expect_services {
+            if !steps::systemd::unit_enabled(unit, false).await {
+                reasons.push(format!("{} is not enabled", unit));
+            }
+        }
+        for unit in &self.expect_user_services {
+            if !steps::systemd::unit_enabled(unit, true).await {
+                reasons.push(format!("{} is not enabled", unit));
+            }
+        }
+        match &self.action {
+            StepAction::SystemdEnable(units) => {
+                for unit in units {
+                    if !steps::systemd::unit_enabled(unit, self.user).await {
+                        reasons.push(format!("{} is not enabled", unit));
+                    }
+                }
+            }
+            StepAction::Copy(file) | StepAction::Template(file) => {
+                if let Ok(dest) = file.dest_path(&ctx.variables)
+                    && !dest.exists()
+                {
+                    reasons.push(format!("missing {}", dest.display()));
+                }
+            }
+            StepAction::Git(git) => {
+                if let Ok(dest) = git.dest_path(&ctx.variables)
+                    && !dest.join(".git").exists()
+                {
+                    reasons.push(format!("missing checkout {}", dest.display()));
+                }
+            }
+            StepAction::Download(file) => {
+                if let Ok(dest) = file.dest_path(&ctx.variables)
+                    && !dest.exists()
+                {
+                    reasons.push(format!("missing {}", dest.display()));
+                }
+            }
+            StepAction::Extract(archive) => {
+                if let Ok(dest) = archive.dest_path(&ctx.variables)
+                    && !dest.exists()
+                {
+                    reasons.push(format!("missing {}", dest.display()));
+                }
+            }
+            StepAction::Gsettings(keys) => {
+                for key in keys {
+                    if !steps::gsettings::key_matches(key, ctx).await {
+                        reasons.push(format!("{} {} differs", key.schema, key.key));
+                    }
+                }
+            }
+            StepAction::Dconf(dconf) => {
+                if let Some(keyfile) = dconf.installed_keyfile()
+                    && !keyfile.exists()
+                {
+                    reasons.push(format!("missing {}", keyfile.display()));
+                }
+            }
+            StepAction::HyprlandMerge(merge) => {
+                if !merge.is_merged(ctx.home()) {
+                    reasons.push(format!("{} is not sourced", merge.include));
+                }
+            }
+            StepAction::Fonts(fonts) => {
+                let dir = fonts.font_dir(ctx.home());
+                if !dir.exists() {
+                    reasons.push(format!("missing {}", dir.display()));
+                }
+                for missing in steps::fonts::verify(fonts, ctx, &mut Vec::new()).await {
+                    reasons.push(format!("{} not available", missing));
+                }
+            }
+            StepAction::DefaultApps(apps) => {
+                for label in apps.drifted(ctx).await {
+                    reasons.push(format!("{} is no longer the default", label));
+                }
+            }
+            StepAction::Script(_) | StepAction::HyprlandMonitors(_) => {}
+        }
+
+        reasons
+    }
+
+    // Files the step writes whole, as opposed to ones it adds a line to
+    fn replaced_files(&self, ctx: &StepContext) -> Result<Vec<PathBuf>> {
+        Ok(match &self.action {
+            StepAction::Copy(file) | StepAction::Template(file) => {
+                vec![file.dest_path(&ctx.variables)?]
+            }
+            StepAction::Download(file) => vec![file.dest_path(&ctx.variables)?],
+            StepAction::HyprlandMerge(merge) => vec![ctx.expand_home(&merge.include)],
+            StepAction::HyprlandMonitors(monitors) => vec![ctx.expand_home(&monitors.path)],
+            _ => Vec::new(),
+        })
+    }
+
+    // What running the step would do here, with the paths and commands filled in
+    pub fn effects(&self, ctx: &StepContext) -> Result<Vec<Effect>> {
+        let mut effects = Vec::new();
+        match &self.action {
+            StepAction::Script(path) if self.untrusted => effects.push(Effect::Execute(format!(
+                "bash {} ({})",
+                path,
+                crate::sandbox::profile(self)
+            ))),
+            StepAction::Script(path) => effects.push(Effect::Execute(format!("bash {}", path))),
+            StepAction::SystemdEnable(units) => {
+                let scope = if self.user { "--user " } else { "" };
+                for unit in units {
+                    effects.push(Effect::Execute(format!(
+                        "systemctl {}enable --now {}",
+                        scope, unit
+                    )));
+                }
+            }
+            StepAction::Copy(file) | StepAction::Template(file) => {
+                effects.push(Effect::write(file.dest_path(&ctx.variables)?))
+            }
+            StepAction::Git(git) => {
+                let dest = git.dest_path(&ctx.variables)?;
+                effects.push(if dest.join(".git").exists() {
+                    Effect::Update(format!("{} from {}", dest.display(), git.url))
+                } else {
+                    Effect::Execute(format!("git clone {} {}", git.url, dest.display()))
+                });
+            }
+            StepAction::Download(file) => {
+                effects.push(Effect::write(file.dest_path(&ctx.variables)?))
+            }
+            StepAction::Extract(archive) => {
+                effects.push(Effect::write(archive.dest_path(&ctx.variables)?))
+            }
+            StepAction::Gsettings(keys) => {
+                for key in keys {
+                    effects.push(Effect::Execute(format!(
+                        "gsettings set {} {} {}",
+                        key.schema, key.key, key.value
+                    )));
+                }
+            }
+            StepAction::Dconf(dconf) => {
+                effects.extend(dconf.installed_keyfile().map(Effect::write));
+                effects.push(Effect::Execute("dconf update".to_string()));
+            }
+            StepAction::HyprlandMerge(merge) => {
+                effects.push(Effect::write(ctx.expand_home(&merge.include)));
+                effects.push(Effect::write(ctx.expand_home(&merge.config)));
+            }
+            StepAction::Fonts(fonts) => {
+                let dir = fonts.font_dir(ctx.home());
+                for source in &fonts.sources {
+                    effects.push(Effect::Install(format!(
+                        "{} into {}",
+                        source,
+                        dir.display()
+                    )));
+                }
+                effects.push(Effect::Execute("fc-cache -f".to_string()));
+            }
+            StepAction::HyprlandMonitors(monitors) => {
+                effects.push(Effect::write(ctx.expand_home(&monitors.path)));
+                effects.push(Effect::write(ctx.expand_home(&monitors.config)));
+            }
+            StepAction::DefaultApps(apps) => effects.extend(apps.effects(ctx)?),
+        }
+        if !self.relabel.is_empty() {
+            effects.extend(steps::selinux::planned(&self.relabel, ctx));
+        }
+        Ok(effects)
+    }
+
+    // Runs the step, appending per-item results (e.g. one line per unit) to the checklist
+    pub async fn run(&self, ctx: &StepContext, checklist: &mut Vec<ChecklistItem>) -> Result<()> {
+        logging::event!(Level::Info, &self.id, "Starting {}", self.name);
+        if ctx.dry_run {
+            return Ok(());
+        }
+
+        match &self.action {
+            StepAction::Script(path) if self.untrusted => {
+                crate::sandbox::run_untrusted(self, path, ctx).await?
+            }
+            StepAction::Script(path) => steps::script::run_script(&self.id, path, ctx).await?,
+            StepAction::SystemdEnable(units) => {
+                steps::systemd::enable_units(&self.id, units, self.user, checklist).await?
+            }
+            StepAction::Copy(file) => steps::files::deploy(&self.id, file, false, ctx).await?,
+            StepAction::Template(file) => steps::files::deploy(&self.id, file, true, ctx).await?,
+            StepAction::Git(git) => steps::git::checkout(&self.id, git, ctx).await?,
+            StepAction::Download(file) => steps::download::download(&self.id, file, ctx).await?,
+            StepAction::Extract(archive) => steps::extract::extract(&self.id, archive, ctx).await?,
+            StepAction::Gsettings(keys) => {
+                steps::gsettings::apply_keys(&self.id, keys, ctx, checklist).await?
+            }
+            StepAction::Dconf(dconf) => {
+                steps::gsettings::compile_profile(&self.id, dconf, ctx).await?
+            }
+            StepAction::HyprlandMerge(merge) => {
+                steps::hyprland::merge(&self.id, merge, ctx, checklist).await?
+            }
+            StepAction::Fonts(fonts) => {
+                steps::fonts::install(&self.id, fonts, ctx, checklist).await?
+            }
+            StepAction::HyprlandMonitors(monitors) => {
+                steps::hyprland::write_monitors(&self.id, monitors, ctx).await?
+            }
+            StepAction::DefaultApps(apps) => {
+                steps::default_apps::apply(&self.id, apps, ctx, checklist).await?
+            }
+        }
+
+        if !self.relabel.is_empty() {
+            steps::selinux::relabel(&self.id, &self.relabel, ctx, checklist).await;
+        }
+
+        logging::event!(Level::Info, &self.id, "Finished {}", self.name);
+        Ok(())
+    }
+}
+
+impl PlannedStep {
+    pub fn label(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{} ({})", self.step.name, user.name),
+            None => self.step.name.clone(),
+        }
+    }
+
+    // Per-user steps are recorded once per account in the install marker
+    pub fn marker_key(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{}@{}", self.step.id, user.name),
+            None => self.step.id.clone(),
+        }
+    }
+
+    pub fn context(&self, ctx: &StepContext) -> StepContext {
+        let mut ctx = ctx.clone();
+        if let Some(user) = &self.user {
+            ctx.variables
+                .insert("username".to_string(), user.name.clone());
+            ctx.variables.insert("home".to_string(), user.home.clone());
+        }
+        ctx
+    }
+
+    // Conditions on paths under ~ look at the account the step is for
+    pub fn skip_reason(&self) -> Result<Option<String>> {
+        self.step
+            .skip_reason(self.user.as_ref().map(|user| user.home.as_str()))
+    }
+
+    pub fn effects(&self, ctx: &StepContext) -> Result<Vec<Effect>> {
+        self.step.effects(&self.context(ctx))
+    }
+
+    pub async fn drift(&self, marker: &InstallMarker, ctx: &StepContext) -> Vec<String> {
+        self.step
+            .drift(&self.marker_key(), marker, &self.context(ctx))
+            .await
+    }
+
+    pub async fn run(&self, ctx: &StepContext, checklist: &mut Vec<ChecklistItem>) -> Result<()> {
+        let ctx = self.context(ctx);
+        match &self.user {
+            // Root writing into the home would follow whatever links its owner put there
+            Some(user)
+                if !ctx.dry_run
+                    && steps::is_root()
+                    && user.uid != 0
+                    && crate::sysroot::get().is_none() =>
+            {
+                run_as(&self.step, user, &ctx, checklist).await
+            }
+            _ => self.step.run(&ctx, checklist).await,
+        }
+    }
+}
+
+// The step's variables, handed to the installer started for one step
+pub const STEP_VARIABLES_ENV: &str = "SPARROW_STEP_VARIABLES";
+
+// What `step_command` reports on stdout, one JSON line each
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StepOutput {
+    Progress(StepProgress),
+    Item(ChecklistItem),
+}
+
+// Runs the step in an installer started as the account, so it only gets to write what
+// the account itself could
+async fn run_as(
+    step: &StepConfig,
+    user: &TargetUser,
+    ctx: &StepContext,
+    checklist: &mut Vec<ChecklistItem>,
+) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let child = steps::user_command(&exe.to_string_lossy(), &user.name)
+        .args(["run-step", &step.id])
+        .env(STEP_VARIABLES_ENV, serde_json::to_string(&ctx.variables)?)
+        .env("HOME", &user.home)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let output = crate::helper::wait_streaming(child, |line| match serde_json::from_str(line) {
+        Ok(StepOutput::Progress(progress)) => {
+            ctx.report_progress(&progress.step, progress.fraction, &progress.message)
+        }
+        Ok(StepOutput::Item(item)) => checklist.push(item),
+        Err(_) => {}
+    })
+    .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!(
+            "{}",
+            stderr
+                .lines()
+                .rfind(|line| !line.trim().is_empty())
+                .map_or_else(|| output.status.to_string(), str::to_string)
+        ));
+    }
+    Ok(())
+}
+
+// `sparrow-installer run-step ID`, started by `run_as` as the account and by the sandbox
+// inside bwrap
+pub async fn step_command(id: &str) -> Result<()> {
+    let settings = crate::SettingsConfig::load()?;
+    let profile = ProfileConfig::load()?;
+    let step = profile
+        .steps
+        .iter()
+        .find(|step| step.id == id)
+        .ok_or_else(|| anyhow::anyhow!("No step {} in the profile", id))?;
+    let variables = std::env::var(STEP_VARIABLES_ENV).unwrap_or_default();
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let ctx = StepContext {
+        dry_run: false,
+        variables: serde_json::from_str(&variables).unwrap_or_default(),
+        progress: Some(sender),
+        network: settings.network.clone(),
+        selinux: settings.selinux.clone(),
+        counters: Default::default(),
+    };
+    let print = |output: StepOutput| {
+        if let Ok(line) = serde_json::to_string(&output) {
+            println!("{}", line);
+        }
+    };
+    let mut checklist = Vec::new();
+    let run = async {
+        let result = step.run(&ctx, &mut checklist).await;
+        drop(ctx);
+        result
+    };
+    let forward = async {
+        while let Some(progress) = receiver.recv().await {
+            print(StepOutput::Progress(progress));
+        }
+    };
+    let (result, ()) = tokio::join!(run, forward);
+    for item in checklist {
+        print(StepOutput::Item(item));
+    }
+    result
+}
+
+// The steps of every *.toml in `dir`, in file name order
+pub fn read_drop_ins(dir: &Path) -> Vec<(PathBuf, Result<Vec<StepConfig>>)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let steps = read_drop_in(&path);
+            (path, steps)
+        })
+        .collect()
+}
+
+fn read_drop_in(path: &Path) -> Result<Vec<StepConfig>> {
+    let contents = std::fs::read_to_string(path)?;
+    let drop_in: DropIn = toml::from_str(&contents)?;
+    for step in &drop_in.steps {
+        if step.untrusted && !matches!(step.action, StepAction::Script(_)) {
+            anyhow::bail!("{}: untrusted steps can only run a script", step.id);
+        }
+    }
+    Ok(drop_in.steps)
+}
+
+// Repeats every per-user step for each account, system steps run once
+pub fn plan_steps<'a>(
+    steps: impl IntoIterator<Item = &'a StepConfig>,
+    users: &[TargetUser],
+) -> Vec<PlannedStep> {
+    let mut planned = Vec::new();
+    for step in steps {
+        if step.per_user() && !users.is_empty() {
+            planned.extend(users.iter().map(|user| PlannedStep {
+                step: step.clone(),
+                user: Some(user.clone()),
+            }));
+        } else {
+            planned.push(PlannedStep {
+                step: step.clone(),
+                user: None,
+            });
+        }
+    }
+    planned
+}
+
+// Two steps writing the same file would leave only the last one's version. Steps that
+// do not apply are left out, nor are the ones whose paths cannot be filled in, which
+// fail on their own when they are reached.
+pub fn check_conflicts(steps: &[PlannedStep], ctx: &StepContext) -> Result<()> {
+    let mut writers: BTreeMap<PathBuf, String> = BTreeMap::new();
+    for step in steps {
+        if !matches!(step.skip_reason(), Ok(None)) {
+            continue;
+        }
+        let Ok(files) = step.step.replaced_files(&step.context(ctx)) else {
+            continue;
+        };
+        for file in files {
+            if let Some(first) = writers.get(&file) {
+                anyhow::bail!(
+                    "{} and {} both write {}",
+                    first,
+                    step.label(),
+                    file.display()
+                );
+            }
+            writers.insert(file, step.label());
+        }
+    }
+    Ok(())
+}
+
+pub async fn plan_repair(
+    profile: &ProfileConfig,
+    marker: &InstallMarker,
+    ctx: &StepContext,
+) -> Vec<RepairItem> {
+    let mut items = Vec::new();
+    let steps = profile
+        .steps
+        .iter()
+        .filter(|step| !marker.declined.contains(&step.id));
+    for step in plan_steps(steps, &marker.users) {
+        // Nothing to repair in a step that does not apply, broken conditions show when it runs
+        if matches!(step.skip_reason(), Ok(Some(_))) {
+            continue;
+        }
+        let reasons = step.drift(marker, ctx).await;
+        if !reasons.is_empty() {
+            items.push(RepairItem { step, reasons });
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+
+    fn copy_step(id: &str, dest: &str) -> StepConfig {
+        toml::from_str(&format!(
+            r#"
+            id = "{id}"
+            name = "{id}"
+            copy = {{ source = "/usr/share/sparrow/{id}", dest = "{dest}" }}
+            "#
+        ))
+        .expect("step")
+    }
+
+    #[test]
+    fn two_steps_writing_one_file_conflict() {
+        let users: Vec<TargetUser> = ["ada", "alan"]
+            .iter()
+            .enumerate()
+            .map(|(index, name)| TargetUser {
+                name: name.to_string(),
+                home: format!("/home/{}", name),
+                uid: 1000 + index as u32,
+                gid: 1000 + index as u32,
+            })
+            .collect();
+        let ctx = StepContext::default();
+
+        let steps = [
+            copy_step("motd", "/etc/motd"),
+            copy_step("banner", "/etc/motd"),
+        ];
+        let error = check_conflicts(&plan_steps(&steps, &users), &ctx).expect_err("conflict");
+        assert_eq!(error.to_string(), "motd and banner both write /etc/motd");
+
+        // Each account's copy is a file of its own
+        let steps = [
+            copy_step("kitty", "~/.config/kitty/kitty.conf"),
+            copy_step("motd", "/etc/motd"),
+        ];
+        check_conflicts(&plan_steps(&steps, &users), &ctx).expect("no conflict");
+
+        let steps = [
+            copy_step("kitty", "~/.config/kitty/kitty.conf"),
+            copy_step("kitty-dark", "~/.config/kitty/kitty.conf"),
+        ];
+        let error = check_conflicts(&plan_steps(&steps, &users), &ctx).expect_err("conflict");
+        assert_eq!(
+            error.to_string(),
+            "kitty (ada) and kitty-dark (ada) both write /home/ada/.config/kitty/kitty.conf"
+        );
+    }
+
+    #[tokio::test]
+    async fn repair_picks_only_the_steps_that_drifted() {
+        let dir = crate::helper::private_dir("sparrow-repair-test").expect("temporary folder");
+        let dest = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let mut profile = ProfileConfig::load_from(&dir).expect("profile");
+        profile.steps = vec![
+            copy_step("intact", &dest("intact.conf")),
+            copy_step("edited", &dest("edited.conf")),
+            copy_step("deleted", &dest("deleted.conf")),
+            copy_step("declined", &dest("declined.conf")),
+        ];
+        std::fs::write(dest("intact.conf"), "").expect("intact");
+        std::fs::write(dest("edited.conf"), "").expect("edited");
+        let mut marker = InstallMarker::new(&profile);
+        for step in &profile.steps[..3] {
+            marker.steps.insert(step.id.clone(), step.fingerprint());
+        }
+        marker
+            .steps
+            .insert("edited".to_string(), "older".to_string());
+        marker.declined.insert("declined".to_string());
+
+        let items = plan_repair(&profile, &marker, &StepContext::default()).await;
+        let picked: Vec<(String, Vec<String>)> = items
+            .into_iter()
+            .map(|item| (item.step.step.id, item.reasons))
+            .collect();
+        assert_eq!(
+            picked,
+            [
+                ("edited".to_string(), vec!["was modified".to_string()]),
+                (
+                    "deleted".to_string(),
+                    vec![format!("missing {}", dest("deleted.conf"))]
+                ),
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn per_user_steps_run_as_their_account_and_report_back() {
+        // Only root switches accounts, anyone else runs the step in-process
+        if !steps::is_root() {
+            return;
+        }
+        let system = MockSystem::new();
+        // Stands in for the installer started as the account
+        system.program(
+            "runuser",
+            r#"echo "$*" > "$HOME/runuser-args"
+echo '{"progress":{"step":"dotfiles","fraction":0.5,"message":"Receiving objects"}}'
+echo '{"item":{"label":"kitty.conf","status":"done","depth":1}}'
+echo "not json, ignored"
+"#,
+        );
+        let home = std::env::var("HOME").expect("mock home");
+        let step: StepConfig = toml::from_str(
+            r#"
+            id = "dotfiles"
+            name = "Dotfiles"
+            copy = { source = "/usr/share/sparrow/kitty.conf", dest = "~/.config/kitty.conf" }
+            "#,
+        )
+        .expect("step");
+        let user = TargetUser {
+            name: "ada".to_string(),
+            home: format!("{}/ada", home),
+            uid: 1000,
+            gid: 1000,
+        };
+        std::fs::create_dir_all(&user.home).expect("home");
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let ctx = StepContext {
+            variables: BTreeMap::from([("home".to_string(), user.home.clone())]),
+            progress: Some(sender),
+            ..Default::default()
+        };
+        let mut checklist = Vec::new();
+
+        run_as(&step, &user, &ctx, &mut checklist)
+            .await
+            .expect("step run as ada");
+
+        // Started with the account's home
+        let args = std::fs::read_to_string(format!("{}/runuser-args", user.home)).expect("runuser");
+        assert!(args.starts_with("-u ada -- "), "{}", args);
+        assert!(args.trim_end().ends_with("run-step dotfiles"), "{}", args);
+        let progress = receiver.try_recv().expect("progress");
+        assert_eq!(progress.message, "Receiving objects");
+        assert_eq!(
+            checklist,
+            [ChecklistItem::new("kitty.conf", CheckStatus::Done, 1)]
+        );
+
+        system.program("runuser", "echo 'Permission denied' >&2; exit 1");
+        let error = run_as(&step, &user, &ctx, &mut checklist)
+            .await
+            .expect_err("failed step");
+        assert_eq!(error.to_string(), "Permission denied");
+    }
+}
@@ -0,0 +1,193 @@
+use crate::a11y::{self, Role};
+use crate::path_input::PathInput;
+use crate::{App, AppState, StatusType, handle_key};
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::io::{IsTerminal, Write};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::time::{self, Duration};
+
+// How often a running operation is checked for new output
+const POLL: Duration = Duration::from_millis(100);
+
+// Terminals that cannot draw the interface, and output captured into a file or pipe
+pub fn wanted() -> bool {
+    let term = std::env::var("TERM").unwrap_or_default();
+    term == "dumb" || !std::io::stdout().is_terminal()
+}
+
+// The interface as lines of text: each screen is printed as it changes and commands are read a
+// line at a time. Ends when the installer quits or the input is closed.
+pub async fn run(app: &mut App) -> Result<()> {
+    let mut input = BufReader::new(tokio::io::stdin()).lines();
+    let mut shown = String::new();
+    let mut progress = String::new();
+    let mut printed = 0;
+    // Whole lines cannot be held down or bounce, and piped answers come back to back
+    app.settings.input.confirm_delay_ms = 0;
+
+    println!("{}", app.text.messages.plain_help);
+    while !app.should_quit {
+        if app.progress_type.is_some() {
+            app.update_progress();
+            app.poll_worker();
+            let screen = a11y::screen(app);
+            if let Some(bar) = screen
+                .nodes
+                .iter()
+                .find(|node| node.role == Role::ProgressBar)
+            {
+                let line = bar.describe();
+                if line != progress {
+                    println!("{}", line);
+                    progress = line;
+                }
+            }
+            // The output restarts with each operation
+            printed = printed.min(app.action_output.len());
+            for line in &app.action_output[printed..] {
+                println!("  {}", line);
+            }
+            printed = app.action_output.len();
+            time::sleep(POLL).await;
+            continue;
+        }
+        progress.clear();
+
+        let screen = a11y::screen(app);
+        let description = screen.describe();
+        if description != shown {
+            println!("\n{}", description);
+            if let Some(focused) = screen.focused()
+                && !focused.description.is_empty()
+            {
+                println!("{}", focused.description);
+            }
+            shown = description;
+        }
+
+        print!("> ");
+        std::io::stdout().flush()?;
+        let line = {
+            let _hidden = (app.app_state == AppState::PasswordInput).then(NoEcho::new);
+            input.next_line().await?
+        };
+        let Some(line) = line else {
+            return Ok(());
+        };
+        command(app, &line).await;
+    }
+    Ok(())
+}
+
+async fn press(app: &mut App, code: KeyCode) {
+    handle_key(app, KeyEvent::from(code)).await;
+}
+
+// Turns a line into the keys the interface would get
+pub async fn command(app: &mut App, line: &str) {
+    if app.app_state == AppState::PasswordInput {
+        if line == "esc" {
+            press(app, KeyCode::Esc).await;
+        } else {
+            for c in line.chars() {
+                press(app, KeyCode::Char(c)).await;
+            }
+            press(app, KeyCode::Enter).await;
+        }
+        return;
+    }
+
+    // The whole line is the path, Tab completion belongs to the drawn prompt
+    if app.app_state == AppState::PathInput
+        && let Some(input) = app.path_input.as_mut()
+    {
+        if line == "esc" {
+            press(app, KeyCode::Esc).await;
+        } else {
+            *input = PathInput::new(line, input.kind);
+            press(app, KeyCode::Enter).await;
+        }
+        return;
+    }
+
+    let line = line.trim();
+    if let Ok(number) = line.parse::<usize>() {
+        choose(app, number).await;
+        return;
+    }
+    let code = match line {
+        "" => KeyCode::Enter,
+        "esc" | "back" => KeyCode::Esc,
+        "<" => KeyCode::Left,
+        ">" => KeyCode::Right,
+        _ if line.chars().count() == 1 => KeyCode::Char(line.chars().next().unwrap_or(' ')),
+        _ => {
+            app.status_message = Some((app.text.messages.plain_unknown.clone(), StatusType::Error));
+            return;
+        }
+    };
+    press(app, code).await;
+}
+
+// Moves focus to the numbered element the way the arrow keys would, then acts on it
+async fn choose(app: &mut App, number: usize) {
+    let count = a11y::screen(app).focus_order().count();
+    if number == 0 || number > count {
+        app.status_message = Some((app.text.messages.plain_unknown.clone(), StatusType::Error));
+        return;
+    }
+    for _ in 0..count {
+        if a11y::screen(app).focus_position() == Some(number - 1) {
+            break;
+        }
+        press(app, KeyCode::Down).await;
+    }
+
+    let screen = a11y::screen(app);
+    if screen.focus_position() != Some(number - 1) {
+        return;
+    }
+    if screen
+        .focused()
+        .is_some_and(|node| node.role == Role::CheckBox)
+    {
+        press(app, KeyCode::Char(' ')).await;
+    } else {
+        press(app, KeyCode::Enter).await;
+    }
+}
+
+// Keeps a typed password off a terminal that echoes input, until dropped
+struct NoEcho(Option<libc::termios>);
+
+impl NoEcho {
+    fn new() -> Self {
+        if !std::io::stdin().is_terminal() {
+            return Self(None);
+        }
+        // SAFETY: termios is plain data, filled in by tcgetattr before it is used
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return Self(None);
+            }
+            let saved = termios;
+            termios.c_lflag &= !libc::ECHO;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+            Self(Some(saved))
+        }
+    }
+}
+
+impl Drop for NoEcho {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.0 {
+            // SAFETY: restores the settings tcgetattr returned
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+            }
+            println!();
+        }
+    }
+}
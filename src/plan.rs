@@ -0,0 +1,104 @@
+use crate::PlanConfig;
+use crate::pipeline::{PlannedStep, StepAction, StepConfig};
+
+// What an install is about to do, shown before it is confirmed
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    // Label of each step that runs with what it does
+    pub run: Vec<(String, String)>,
+    // Label of each step left out with why
+    pub skipped: Vec<(String, String)>,
+    pub seconds: u64,
+}
+
+impl Plan {
+    pub(crate) fn build(steps: &[PlannedStep], config: &PlanConfig) -> Self {
+        let mut plan = Plan::default();
+        for step in steps {
+            match step.skip_reason() {
+                Ok(Some(reason)) => plan.skipped.push((step.label(), reason)),
+                // A broken condition fails the step when it is reached
+                Err(e) => plan.skipped.push((step.label(), e.to_string())),
+                Ok(None) => {
+                    plan.run.push((step.label(), summary(&step.step)));
+                    plan.seconds += estimate(&step.step, config);
+                }
+            }
+        }
+        plan
+    }
+}
+
+// Name of the action as the profile spells it
+pub fn kind(action: &StepAction) -> &'static str {
+    match action {
+        StepAction::Script(_) => "script",
+        StepAction::SystemdEnable(_) => "systemd_enable",
+        StepAction::Copy(_) => "copy",
+        StepAction::Template(_) => "template",
+        StepAction::Git(_) => "git",
+        StepAction::Download(_) => "download",
+        StepAction::Extract(_) => "extract",
+        StepAction::Gsettings(_) => "gsettings",
+        StepAction::Dconf(_) => "dconf",
+        StepAction::HyprlandMerge(_) => "hyprland_merge",
+        StepAction::Fonts(_) => "fonts",
+        StepAction::HyprlandMonitors(_) => "hyprland_monitors",
+        StepAction::DefaultApps(_) => "default_apps",
+    }
+}
+
+// One line on what the step does, close to the command it amounts to
+pub fn summary(step: &StepConfig) -> String {
+    match &step.action {
+        StepAction::Script(path) if step.untrusted => {
+            format!("run {} ({})", path, crate::sandbox::profile(step))
+        }
+        StepAction::Script(path) => format!("run {}", path),
+        StepAction::SystemdEnable(units) if step.user => {
+            format!("systemctl --user enable {}", units.join(" "))
+        }
+        StepAction::SystemdEnable(units) => format!("systemctl enable {}", units.join(" ")),
+        StepAction::Copy(file) => format!("copy {} to {}", file.source, file.dest),
+        StepAction::Template(file) => format!("render {} to {}", file.source, file.dest),
+        StepAction::Git(git) => format!("git clone {} {}", git.url, git.dest),
+        StepAction::Download(file) => format!("download {} to {}", file.url, file.dest),
+        StepAction::Extract(archive) => format!("extract {} to {}", archive.source, archive.dest),
+        StepAction::Gsettings(keys) => {
+            let keys: Vec<String> = keys
+                .iter()
+                .map(|key| format!("{} {}", key.schema, key.key))
+                .collect();
+            format!("gsettings set {}", keys.join(", "))
+        }
+        StepAction::Dconf(dconf) => format!("dconf update with {}", dconf.keyfile),
+        StepAction::HyprlandMerge(merge) => {
+            format!("include {} in {}", merge.source, merge.config)
+        }
+        StepAction::Fonts(fonts) => format!(
+            "install {} into {}",
+            fonts.sources.join(", "),
+            fonts.font_dir(None).display()
+        ),
+        StepAction::HyprlandMonitors(monitors) => {
+            format!("write the display settings to {}", monitors.path)
+        }
+        StepAction::DefaultApps(_) => "set the default applications".to_string(),
+    }
+}
+
+// Seconds the step usually takes, its own `estimate` or the one for its kind of action
+pub fn estimate(step: &StepConfig, config: &PlanConfig) -> u64 {
+    step.estimate.unwrap_or_else(|| {
+        config
+            .estimates
+            .get(kind(&step.action))
+            .copied()
+            .unwrap_or(config.default_estimate)
+    })
+}
+
+// Rounded up to whole minutes, nobody plans around seconds
+pub fn minutes(seconds: u64) -> u64 {
+    seconds.div_ceil(60).max(1)
+}
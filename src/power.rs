@@ -0,0 +1,200 @@
+use crate::logging;
+use crate::tools;
+use crate::{SystemAction, SystemActionsConfig};
+use anyhow::Result;
+use cli_log::Level;
+use serde::Deserialize;
+use tokio::process::Command as AsyncCommand;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    // The first of systemctl, loginctl and busctl that is installed
+    Auto,
+    Systemctl,
+    Loginctl,
+    // logind's Manager interface over the system bus, through busctl
+    Dbus,
+    // The commands configured in settings.toml
+    Command,
+    // Only logs the action, for tests, demos and containers
+    None,
+}
+
+// Names of the other people logged in, from logind's sessions. System accounts and greeters
+// are left out, as is the account running the installer and the one that invoked sudo.
+pub async fn other_users() -> Vec<String> {
+    let output = match AsyncCommand::new("loginctl")
+        .args(["list-sessions", "--no-legend"])
+        .output()
+        .await
+    {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            logging::event!(
+                Level::Warn,
+                "power",
+                "Cannot list sessions: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return Vec::new();
+        }
+        Err(e) => {
+            logging::event!(Level::Warn, "power", "Cannot list sessions: {}", e);
+            return Vec::new();
+        }
+    };
+
+    // SAFETY: getuid has no preconditions and cannot fail
+    let own_uid = unsafe { libc::getuid() };
+    let sudo_uid = std::env::var("SUDO_UID")
+        .ok()
+        .and_then(|uid| uid.parse::<u32>().ok());
+    parse_sessions(&String::from_utf8_lossy(&output.stdout), |uid| {
+        uid != own_uid && Some(uid) != sudo_uid
+    })
+}
+
+// `loginctl list-sessions` starts each line with the session id, uid and user name
+fn parse_sessions(listing: &str, other: impl Fn(u32) -> bool) -> Vec<String> {
+    let mut users = Vec::new();
+    for line in listing.lines() {
+        let mut columns = line.split_whitespace();
+        let (Some(_), Some(uid), Some(user)) = (columns.next(), columns.next(), columns.next())
+        else {
+            continue;
+        };
+        let Ok(uid) = uid.parse::<u32>() else {
+            continue;
+        };
+        if (1000..60000).contains(&uid) && other(uid) && !users.iter().any(|u| u == user) {
+            users.push(user.to_string());
+        }
+    }
+    users
+}
+
+// Program and arguments that carry out the action, empty for the no-op backend
+pub fn command_line(config: &SystemActionsConfig, action: &SystemAction) -> Vec<String> {
+    let verb = match action {
+        SystemAction::Reboot => "reboot",
+        SystemAction::Poweroff => "poweroff",
+    };
+    let backend = match config.backend {
+        Backend::Auto => match tools::first_available(&["systemctl", "loginctl", "busctl"]) {
+            Some("systemctl") => Backend::Systemctl,
+            Some("loginctl") => Backend::Loginctl,
+            Some(_) => Backend::Dbus,
+            // Fails with the name of the missing program
+            None => Backend::Systemctl,
+        },
+        backend => backend,
+    };
+    let words: Vec<&str> = match backend {
+        Backend::Auto | Backend::Systemctl => vec!["systemctl", verb],
+        Backend::Loginctl => vec!["loginctl", verb],
+        Backend::Dbus => {
+            let method = match action {
+                SystemAction::Reboot => "Reboot",
+                SystemAction::Poweroff => "PowerOff",
+            };
+            vec![
+                "busctl",
+                "call",
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1",
+                "org.freedesktop.login1.Manager",
+                method,
+                "b",
+                "true",
+            ]
+        }
+        Backend::Command => {
+            return match action {
+                SystemAction::Reboot => config.reboot_command.clone(),
+                SystemAction::Poweroff => config.poweroff_command.clone(),
+            };
+        }
+        Backend::None => Vec::new(),
+    };
+    words.into_iter().map(str::to_string).collect()
+}
+
+pub async fn perform(config: &SystemActionsConfig, action: &SystemAction) -> Result<()> {
+    let command_line = command_line(config, action);
+    let Some((program, args)) = command_line.split_first() else {
+        if config.backend == Backend::Command {
+            return Err(anyhow::anyhow!("No command is configured for {:?}", action));
+        }
+        logging::event!(
+            Level::Info,
+            "power",
+            "{:?} skipped, the system action backend is none",
+            action
+        );
+        return Ok(());
+    };
+
+    logging::event!(Level::Info, "power", "Running {}", command_line.join(" "));
+    tools::require(program)?;
+    let output = AsyncCommand::new(program).args(args).output().await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+// Asks the system to reboot in `minutes`, through shutdown or a transient systemd timer
+pub async fn schedule_reboot(config: &SystemActionsConfig, minutes: u32) -> Result<()> {
+    let command_line: Vec<String> = match config.backend {
+        Backend::None => {
+            logging::event!(
+                Level::Info,
+                "power",
+                "Reboot in {} minutes skipped, the system action backend is none",
+                minutes
+            );
+            return Ok(());
+        }
+        Backend::Command => {
+            if config.reboot_command.is_empty() {
+                return Err(anyhow::anyhow!("No command is configured for Reboot"));
+            }
+            let mut timer = vec![
+                "systemd-run".to_string(),
+                format!("--on-active={}m", minutes),
+            ];
+            timer.extend(config.reboot_command.iter().cloned());
+            timer
+        }
+        _ if tools::find("shutdown").is_some() => {
+            vec![
+                "shutdown".to_string(),
+                "-r".to_string(),
+                format!("+{}", minutes),
+            ]
+        }
+        _ => vec![
+            "systemd-run".to_string(),
+            format!("--on-active={}m", minutes),
+            "systemctl".to_string(),
+            "reboot".to_string(),
+        ],
+    };
+
+    logging::event!(Level::Info, "power", "Running {}", command_line.join(" "));
+    let output = AsyncCommand::new(&command_line[0])
+        .args(&command_line[1..])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use crate::console::ConsoleMirror;
+use crate::pipeline::StepProgress;
+use crate::{logging, notify};
+use cli_log::Level;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
+
+// Something following the install as it goes: the interface, the log, attached viewers,
+// systemd. Each gets the events as they are, nothing parses another one's output.
+pub trait ProgressSink: Send {
+    // The one-line status, "Installing, step 2/5: Fonts"
+    fn status(&mut self, _message: &str) {}
+    // A step's progress or a line of output from what it runs
+    fn step(&mut self, _progress: &StepProgress) {}
+}
+
+// Hands every event to all the sinks, clones share the same sinks
+#[derive(Clone, Default)]
+pub struct FanOut {
+    sinks: Arc<Mutex<Vec<Box<dyn ProgressSink>>>>,
+}
+
+impl FanOut {
+    pub fn add(&self, sink: impl ProgressSink + 'static) {
+        self.lock().push(Box::new(sink));
+    }
+
+    pub fn status(&self, message: &str) {
+        for sink in self.lock().iter_mut() {
+            sink.status(message);
+        }
+    }
+
+    pub fn step(&self, progress: &StepProgress) {
+        for sink in self.lock().iter_mut() {
+            sink.step(progress);
+        }
+    }
+
+    // A channel for the steps, which run without access to the sinks themselves
+    pub fn sender(&self) -> UnboundedSender<StepProgress> {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<StepProgress>();
+        let fan_out = self.clone();
+        tokio::spawn(async move {
+            while let Some(progress) = receiver.recv().await {
+                fan_out.step(&progress);
+            }
+        });
+        sender
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Box<dyn ProgressSink>>> {
+        // A sink that panicked leaves the others usable
+        self.sinks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Passes step progress on to a channel, the interface or an embedding front end
+pub struct ChannelSink(pub UnboundedSender<StepProgress>);
+
+impl ProgressSink for ChannelSink {
+    fn step(&mut self, progress: &StepProgress) {
+        // The receiver going away only means nobody is watching anymore
+        let _ = self.0.send(progress.clone());
+    }
+}
+
+// Step progress in the install log, one line per message change or tenth of the total
+#[derive(Default)]
+pub struct LogSink {
+    last: Option<(String, String, u32)>,
+}
+
+impl ProgressSink for LogSink {
+    fn step(&mut self, progress: &StepProgress) {
+        let tenth = progress.fraction.map_or(0, |f| (f * 10.0) as u32);
+        let key = (progress.step.clone(), progress.message.clone(), tenth);
+        if self.last.as_ref() == Some(&key) {
+            return;
+        }
+        self.last = Some(key);
+        match progress.fraction {
+            Some(fraction) => logging::event!(
+                Level::Debug,
+                &progress.step,
+                "{}: {:.0}%",
+                progress.message,
+                fraction * 100.0
+            ),
+            None => logging::event!(Level::Debug, &progress.step, "{}", progress.message),
+        }
+    }
+}
+
+// STATUS= for `systemctl status`, and every step update feeds the watchdog
+pub struct NotifySink;
+
+impl ProgressSink for NotifySink {
+    fn status(&mut self, message: &str) {
+        notify::status(message);
+    }
+
+    fn step(&mut self, _progress: &StepProgress) {
+        notify::heartbeat();
+    }
+}
+
+impl ProgressSink for ConsoleMirror {
+    fn status(&mut self, message: &str) {
+        self.line(message);
+    }
+}
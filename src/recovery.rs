@@ -0,0 +1,213 @@
+use crate::pipeline::{self, PlannedStep, ProfileConfig, StepAction, StepContext};
+use crate::report::RunReport;
+use crate::state::InstallMarker;
+use crate::steps::files;
+use anyhow::{Context, Result};
+use chrono::SubsecRound;
+use std::fs;
+use std::path::PathBuf;
+
+// A file the failed run replaced and the backup it made of the original
+#[derive(Debug, Clone)]
+pub struct Restore {
+    pub dest: PathBuf,
+    pub backup: PathBuf,
+}
+
+// Steps of the interrupted install that did not complete with their current definition
+pub fn remaining_steps(profile: &ProfileConfig, marker: &InstallMarker) -> Vec<PlannedStep> {
+    let steps = profile
+        .steps
+        .iter()
+        .filter(|step| !marker.declined.contains(&step.id));
+    pipeline::plan_steps(steps, &marker.users)
+        .into_iter()
+        .filter(|planned| {
+            marker.steps.get(&planned.marker_key()) != Some(&planned.step.fingerprint())
+        })
+        .collect()
+}
+
+fn started_at(report: &RunReport) -> Option<chrono::NaiveDateTime> {
+    let started = chrono::DateTime::parse_from_rfc3339(&report.started_at).ok()?;
+    Some(
+        started
+            .with_timezone(&chrono::Local)
+            .naive_local()
+            .trunc_subsecs(0),
+    )
+}
+
+// Only files deployed by copy/template steps are backed up, so only those can be put back
+pub fn rollback_plan(
+    profile: &ProfileConfig,
+    marker: &InstallMarker,
+    report: &RunReport,
+    ctx: &StepContext,
+) -> Vec<Restore> {
+    let Some(since) = started_at(report) else {
+        return Vec::new();
+    };
+    pipeline::plan_steps(&profile.steps, &marker.users)
+        .iter()
+        .filter_map(|planned| match &planned.step.action {
+            StepAction::Copy(file) | StepAction::Template(file) => {
+                file.dest_path(&planned.context(ctx).variables).ok()
+            }
+            _ => None,
+        })
+        .filter_map(|dest| {
+            let backup = files::backup_since(&dest, since)?;
+            Some(Restore { dest, backup })
+        })
+        .collect()
+}
+
+pub fn rollback(plan: &[Restore]) -> Result<()> {
+    for restore in plan {
+        fs::rename(&restore.backup, &restore.dest).with_context(|| {
+            format!(
+                "Cannot restore {} from {}",
+                restore.dest.display(),
+                restore.backup.display()
+            )
+        })?;
+    }
+    // The partial progress no longer matches what is on disk
+    InstallMarker::remove_partial();
+    Ok(())
+}
+
+// Lines of the installer log written while the report's run was going
+pub fn log_lines(report: &RunReport) -> Vec<String> {
+    let Some(contents) = report
+        .log_file
+        .as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+    else {
+        return Vec::new();
+    };
+    let Some(from) = started_at(report) else {
+        return Vec::new();
+    };
+    let to = report
+        .finished_at
+        .as_deref()
+        .and_then(|finished| chrono::DateTime::parse_from_rfc3339(finished).ok())
+        .map(|finished| finished.with_timezone(&chrono::Local).naive_local());
+
+    let mut lines = Vec::new();
+    let mut inside = false;
+    for line in contents.lines() {
+        // Lines without a timestamp continue the previous entry
+        if let Some(stamp) = line_time(line) {
+            inside = stamp >= from && to.is_none_or(|to| stamp <= to);
+        }
+        if inside {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+// Handles both the text and the JSON log format
+fn line_time(line: &str) -> Option<chrono::NaiveDateTime> {
+    if line.starts_with('{') {
+        let entry: serde_json::Value = serde_json::from_str(line).ok()?;
+        let stamp = chrono::DateTime::parse_from_rfc3339(entry["timestamp"].as_str()?).ok()?;
+        return Some(stamp.with_timezone(&chrono::Local).naive_local());
+    }
+    chrono::NaiveDateTime::parse_from_str(line.get(..23)?, "%Y-%m-%d %H:%M:%S%.3f").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+    use crate::pipeline::StepConfig;
+
+    fn copy_step(id: &str, dest: &std::path::Path) -> StepConfig {
+        toml::from_str(&format!(
+            "id = \"{id}\"\nname = \"{id}\"\ncopy = {{ source = \"/usr/share/sparrow/{id}\", dest = \"{}\" }}\n",
+            dest.display()
+        ))
+        .expect("step")
+    }
+
+    #[test]
+    fn an_interrupted_install_resumes_or_rolls_back_its_own_changes() {
+        let _system = MockSystem::new();
+        let dir = crate::helper::private_dir("sparrow-recovery-test").expect("temporary folder");
+        let (kitty, waybar) = (dir.join("kitty.conf"), dir.join("waybar.jsonc"));
+        let mut profile = ProfileConfig::load_from(&dir).expect("profile");
+        profile.steps = vec![copy_step("kitty", &kitty), copy_step("waybar", &waybar)];
+        let mut marker = InstallMarker::new(&profile);
+        marker
+            .steps
+            .insert("kitty".to_string(), profile.steps[0].fingerprint());
+
+        let remaining: Vec<String> = remaining_steps(&profile, &marker)
+            .into_iter()
+            .map(|planned| planned.step.id)
+            .collect();
+        assert_eq!(remaining, ["waybar"]);
+
+        // kitty.conf was replaced by the failed run, waybar's backup is from an earlier one
+        let mut report = RunReport::start("install", false);
+        report.install = true;
+        report.started_at = (chrono::Local::now() - chrono::Duration::hours(1)).to_rfc3339();
+        assert!(report.needs_recovery());
+        let stamp = chrono::Local::now().format("%Y%m%d%H%M%S");
+        fs::write(&kitty, "installed").expect("kitty");
+        fs::write(
+            dir.join(format!("kitty.conf.sparrow-bak-{}", stamp)),
+            "mine",
+        )
+        .expect("backup");
+        fs::write(&waybar, "installed").expect("waybar");
+        fs::write(dir.join("waybar.jsonc.sparrow-bak-20200101000000"), "old").expect("backup");
+
+        let plan = rollback_plan(&profile, &marker, &report, &StepContext::default());
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].dest, kitty);
+        rollback(&plan).expect("rolled back");
+        assert_eq!(fs::read_to_string(&kitty).expect("kitty"), "mine");
+        assert_eq!(fs::read_to_string(&waybar).expect("waybar"), "installed");
+
+        report.success = Some(true);
+        assert!(!report.needs_recovery());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn the_log_view_shows_only_the_failed_runs_lines() {
+        let dir = crate::helper::private_dir("sparrow-recovery-test").expect("temporary folder");
+        let log = dir.join("installer.log");
+        fs::write(
+            &log,
+            "2026-03-01 09:00:00.000 INFO earlier run\n\
+             2026-03-01 10:00:00.000 INFO step kitty\n\
+             \tcontinued output\n\
+             {\"timestamp\":\"2026-03-01T10:05:00+00:00\",\"level\":\"ERROR\",\"message\":\"failed\"}\n\
+             2026-03-01 11:00:00.000 INFO later run\n",
+        )
+        .expect("log");
+        let local = |stamp: &str| {
+            chrono::NaiveDateTime::parse_from_str(stamp, "%Y-%m-%d %H:%M:%S")
+                .expect("stamp")
+                .and_local_timezone(chrono::Local)
+                .unwrap()
+                .to_rfc3339()
+        };
+        let mut report = RunReport::start("install", false);
+        report.started_at = local("2026-03-01 10:00:00");
+        report.finished_at = Some(local("2026-03-01 10:30:00"));
+        report.log_file = Some(log.display().to_string());
+
+        let lines = log_lines(&report);
+        assert_eq!(lines[0], "2026-03-01 10:00:00.000 INFO step kitty");
+        assert_eq!(lines[1], "\tcontinued output");
+        assert!(!lines.iter().any(|line| line.contains("run")));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
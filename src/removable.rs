@@ -0,0 +1,131 @@
+use crate::logging;
+use anyhow::{Context, Result};
+use cli_log::Level;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command as AsyncCommand;
+
+// A mounted filesystem on a USB stick or another drive that can be unplugged
+#[derive(Debug, Clone, PartialEq)]
+pub struct Volume {
+    pub name: String,
+    pub label: Option<String>,
+    pub mount: PathBuf,
+    pub size: Option<u64>,
+}
+
+impl Volume {
+    pub fn title(&self) -> String {
+        let name = self.label.as_deref().unwrap_or(&self.name);
+        match self.size {
+            Some(bytes) => format!("{} ({})", name, size_label(bytes)),
+            None => name.to_string(),
+        }
+    }
+}
+
+pub async fn list() -> Vec<Volume> {
+    let output = AsyncCommand::new("lsblk")
+        .args([
+            "--json",
+            "--bytes",
+            "-o",
+            "NAME,LABEL,MOUNTPOINT,RM,HOTPLUG,SIZE,TRAN",
+        ])
+        .output()
+        .await;
+    match output {
+        Ok(output) if output.status.success() => parse(&String::from_utf8_lossy(&output.stdout)),
+        Ok(output) => {
+            logging::event!(
+                Level::Warn,
+                "export",
+                "Cannot list drives: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            Vec::new()
+        }
+        Err(e) => {
+            logging::event!(Level::Warn, "export", "Cannot list drives: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+// Mounted partitions of removable, hotplugged or USB disks in `lsblk --json` output
+pub fn parse(json: &str) -> Vec<Volume> {
+    let Ok(value) = serde_json::from_str::<Value>(json) else {
+        return Vec::new();
+    };
+    let mut volumes = Vec::new();
+    for device in value["blockdevices"].as_array().into_iter().flatten() {
+        collect(device, false, &mut volumes);
+    }
+    volumes
+}
+
+fn collect(device: &Value, removable_disk: bool, volumes: &mut Vec<Volume>) {
+    let removable = removable_disk
+        || flag(&device["rm"])
+        || flag(&device["hotplug"])
+        || device["tran"].as_str() == Some("usb");
+    if removable
+        && let Some(mount) = device["mountpoint"].as_str()
+        && mount.starts_with('/')
+    {
+        volumes.push(Volume {
+            name: device["name"].as_str().unwrap_or_default().to_string(),
+            label: device["label"].as_str().map(str::to_string),
+            mount: PathBuf::from(mount),
+            size: device["size"]
+                .as_u64()
+                .or_else(|| device["size"].as_str()?.parse().ok()),
+        });
+    }
+    for child in device["children"].as_array().into_iter().flatten() {
+        collect(child, removable, volumes);
+    }
+}
+
+// Newer lsblk prints booleans, older versions "0" and "1"
+fn flag(value: &Value) -> bool {
+    value.as_bool().unwrap_or(value.as_str() == Some("1"))
+}
+
+fn size_label(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+// Copies the files that exist into a new folder in `dir`, flushed so the drive can be pulled
+// out right away
+pub fn export(dir: &Path, files: &[PathBuf]) -> Result<PathBuf> {
+    let folder = dir.join(format!(
+        "sparrow-report-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    fs::create_dir_all(&folder).with_context(|| format!("Cannot create {}", folder.display()))?;
+    for file in files.iter().filter(|file| file.is_file()) {
+        let Some(name) = file.file_name() else {
+            continue;
+        };
+        fs::copy(file, folder.join(name))
+            .with_context(|| format!("Cannot copy {}", file.display()))?;
+    }
+    // SAFETY: sync has no preconditions and cannot fail
+    unsafe { libc::sync() };
+    logging::event!(
+        Level::Info,
+        "export",
+        "Saved the report to {}",
+        folder.display()
+    );
+    Ok(folder)
+}
@@ -0,0 +1,102 @@
+// Screens for the frame benchmark in benches/render.rs:
+// cargo bench --features bench
+use crate::{App, AppState, ProgressType, SettingsConfig, pipeline, ui};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use std::hint::black_box;
+#[cfg(test)]
+use std::time::{Duration, Instant};
+
+// What one frame may cost on the slow VMs the installer usually runs in, far below the 50ms poll
+#[cfg(test)]
+const FRAME_BUDGET: Duration = Duration::from_millis(2);
+#[cfg(test)]
+const BUDGET_FRAMES: u32 = 200;
+
+// An installer state to draw, the interface itself stays private to the crate
+pub struct Screen {
+    pub name: &'static str,
+    app: App,
+}
+
+impl Screen {
+    pub fn draw(&self, terminal: &mut Terminal<TestBackend>) {
+        terminal
+            .draw(|f| ui(f, black_box(&self.app)))
+            .expect("draw");
+    }
+}
+
+fn new_app() -> App {
+    let settings = SettingsConfig::load().expect("settings.toml");
+    App::new(false, settings).expect("app")
+}
+
+// The screens drawn most often: the menu and a running install with a full checklist
+pub fn screens() -> Vec<Screen> {
+    let menu = new_app();
+
+    let mut install = new_app();
+    install.app_state = AppState::Processing(install.text.messages.progress_installing.clone());
+    install.progress_type = Some(ProgressType::Indeterminate);
+    install.checklist = install
+        .profile
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let status = if i % 3 == 0 {
+                pipeline::CheckStatus::Done
+            } else {
+                pipeline::CheckStatus::Pending
+            };
+            pipeline::ChecklistItem::new(step.name.clone(), status, 0)
+        })
+        .collect();
+    install.action_output = (0..20).map(|i| format!("output line {}", i)).collect();
+
+    let mut confirm = new_app();
+    confirm.show_confirmation = true;
+    confirm.confirmation_message = confirm.text.messages.confirm_default_install.clone();
+
+    vec![
+        Screen {
+            name: "menu",
+            app: menu,
+        },
+        Screen {
+            name: "install",
+            app: install,
+        },
+        Screen {
+            name: "confirmation",
+            app: confirm,
+        },
+    ]
+}
+
+#[test]
+fn frames_render_within_budget() {
+    let _env = crate::fixtures::lock_env();
+    // Unoptimized test builds are many times slower than what ships
+    let budget = if cfg!(debug_assertions) {
+        FRAME_BUDGET * 10
+    } else {
+        FRAME_BUDGET
+    };
+    let mut terminal = Terminal::new(TestBackend::new(100, 30)).expect("terminal");
+    for screen in screens() {
+        let started = Instant::now();
+        for _ in 0..BUDGET_FRAMES {
+            screen.draw(&mut terminal);
+        }
+        let per_frame = started.elapsed() / BUDGET_FRAMES;
+        assert!(
+            per_frame < budget,
+            "{} frame took {:?}, over the {:?} budget",
+            screen.name,
+            per_frame,
+            budget
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crate::logging;
+use crate::metrics::StepMetrics;
+use crate::pipeline::ChecklistItem;
+use crate::snapshot::SnapshotDiff;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+// When the user chose to reboot into a freshly staged update
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RebootChoice {
+    Now,
+    Scheduled,
+    Later,
+}
+
+// Summary of a single installer operation, written to the state directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    pub version: String,
+    pub operation: String,
+    pub dry_run: bool,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub success: Option<bool>,
+    pub error: Option<String>,
+    pub changes: Option<SnapshotDiff>,
+    pub checklist: Vec<ChecklistItem>,
+    // Set when the pipeline succeeded but verify checks failed
+    #[serde(default)]
+    pub degraded: bool,
+    #[serde(default)]
+    pub failed_checks: Vec<String>,
+    // Set once the dotfiles pipeline starts, an unfinished install can be recovered
+    #[serde(default)]
+    pub install: bool,
+    #[serde(default)]
+    pub log_file: Option<String>,
+    #[serde(default)]
+    pub reboot: Option<RebootChoice>,
+    // Time the scheduled reboot is due
+    #[serde(default)]
+    pub reboot_at: Option<String>,
+    // Time, downloads and retries of every step that ran
+    #[serde(default)]
+    pub metrics: Vec<StepMetrics>,
+}
+
+impl RunReport {
+    pub fn start(operation: &str, dry_run: bool) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            operation: operation.to_string(),
+            dry_run,
+            started_at: chrono::Local::now().to_rfc3339(),
+            finished_at: None,
+            success: None,
+            error: None,
+            changes: None,
+            checklist: Vec::new(),
+            degraded: false,
+            failed_checks: Vec::new(),
+            install: false,
+            log_file: logging::log_path().map(|path| path.display().to_string()),
+            reboot: None,
+            reboot_at: None,
+            metrics: Vec::new(),
+        }
+    }
+
+    pub fn finish(&mut self, result: &Result<()>) {
+        self.finished_at = Some(chrono::Local::now().to_rfc3339());
+        self.success = Some(result.is_ok());
+        self.error = result
+            .as_ref()
+            .err()
+            .map(|e| logging::redact(&e.to_string()));
+    }
+
+    pub fn save(&self) -> Result<PathBuf> {
+        let dir = crate::state_dir();
+        fs::create_dir_all(&dir)?;
+        let path = report_path();
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    pub fn load() -> Option<Self> {
+        let contents = fs::read_to_string(report_path()).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    // A real dotfiles install that failed or never finished (e.g. power loss)
+    pub fn needs_recovery(&self) -> bool {
+        self.install && !self.dry_run && self.success != Some(true)
+    }
+}
+
+pub fn report_path() -> PathBuf {
+    crate::state_dir().join("last-report.json")
+}
+
+// Where a panic leaves its message and backtrace
+pub fn crash_path() -> PathBuf {
+    crate::state_dir().join("crash.txt")
+}
+
+// Writes a crash dump before the default hook prints the panic
+pub fn install_crash_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // The message is left out when it cannot be redacted
+        let message = logging::try_redact(&info.to_string()).unwrap_or_else(|| {
+            info.location()
+                .map_or_else(|| "panic".to_string(), |at| format!("panic at {}", at))
+        });
+        let dump = format!(
+            "{} {} {}\n{}\n\n{}\n",
+            chrono::Local::now().to_rfc3339(),
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            message,
+            std::backtrace::Backtrace::force_capture()
+        );
+        let _ = fs::create_dir_all(crate::state_dir()).and_then(|_| fs::write(crash_path(), dump));
+        previous(info);
+    }));
+}
@@ -0,0 +1,230 @@
+use crate::pipeline::{
+    Effect, PlannedStep, STEP_VARIABLES_ENV, StepAction, StepConfig, StepContext,
+};
+use crate::steps::files::render;
+use crate::{logging, tools};
+use anyhow::{Context, Result};
+use cli_log::Level;
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use tokio::process::Command as AsyncCommand;
+
+// What a step did to the home directory in the sandbox, and how it ended there
+pub struct SandboxRun {
+    pub changes: Vec<Effect>,
+    pub error: Option<String>,
+}
+
+// Steps that only write into the account's home. Anything needing root, the session bus or
+// the user manager would change the real system or fail, those are only listed.
+pub fn fits(step: &StepConfig) -> bool {
+    // Untrusted scripts run in a sandbox of their own already
+    if crate::sysroot::get().is_some() || step.untrusted {
+        return false;
+    }
+    match &step.action {
+        StepAction::Script(_) => true,
+        StepAction::Fonts(fonts) => !fonts.system,
+        StepAction::Copy(_)
+        | StepAction::Template(_)
+        | StepAction::Git(_)
+        | StepAction::Download(_)
+        | StepAction::Extract(_)
+        | StepAction::HyprlandMerge(_)
+        | StepAction::HyprlandMonitors(_) => step.per_user(),
+        StepAction::SystemdEnable(_)
+        | StepAction::Gsettings(_)
+        | StepAction::Dconf(_)
+        | StepAction::DefaultApps(_) => false,
+    }
+}
+
+// Runs the step for real inside bwrap, with the system read-only and the home directory an
+// overlay whose upper layer is thrown away afterwards, and lists what ended up in that layer
+pub async fn run(step: &PlannedStep, ctx: &StepContext) -> Result<SandboxRun> {
+    let bwrap = tools::require("bwrap")?;
+    let ctx = step.context(ctx);
+    let home = ctx
+        .variables
+        .get("home")
+        .cloned()
+        .or_else(|| std::env::var("HOME").ok())
+        .context("No home directory to run the step in")?;
+    let home = PathBuf::from(home);
+
+    // Outside the home, overlayfs refuses layers that overlap
+    let scratch = std::env::temp_dir().join(format!(
+        "sparrow-sandbox-{}-{}",
+        std::process::id(),
+        step.marker_key()
+    ));
+    let _ = fs::remove_dir_all(&scratch);
+    let upper = scratch.join("upper");
+    let work = scratch.join("work");
+    fs::create_dir_all(&upper)
+        .and_then(|_| fs::create_dir_all(&work))
+        .with_context(|| format!("Cannot create {}", scratch.display()))?;
+
+    let output = AsyncCommand::new(bwrap)
+        .args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"])
+        .arg("--overlay-src")
+        .arg(&home)
+        .arg("--overlay")
+        .arg(&upper)
+        .arg(&work)
+        .arg(&home)
+        // No bus or runtime sockets, nothing inside can reach the real session
+        .args(["--tmpfs", "/tmp", "--tmpfs", "/run"])
+        .args(["--unshare-pid", "--unshare-ipc", "--die-with-parent"])
+        .args(["--unsetenv", "DBUS_SESSION_BUS_ADDRESS"])
+        .arg("--setenv")
+        .arg("HOME")
+        .arg(&home)
+        .arg("--chdir")
+        .arg(&home)
+        .arg("--")
+        .arg(std::env::current_exe()?)
+        .args(["run-step", &step.step.id])
+        .env(STEP_VARIABLES_ENV, serde_json::to_string(&ctx.variables)?)
+        .kill_on_drop(true)
+        .output()
+        .await;
+
+    let error = match output {
+        Ok(output) if output.status.success() => None,
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Some(
+                stderr
+                    .lines()
+                    .rfind(|line| !line.trim().is_empty())
+                    .map_or_else(|| output.status.to_string(), str::to_string),
+            )
+        }
+        Err(e) => Some(format!("Cannot start bwrap: {}", e)),
+    };
+    let mut changes = Vec::new();
+    collect(&upper, &home, &mut changes);
+    let _ = fs::remove_dir_all(&scratch);
+
+    logging::event!(
+        Level::Info,
+        &step.step.id,
+        "{} changes in the sandbox",
+        changes.len()
+    );
+    Ok(SandboxRun { changes, error })
+}
+
+// Files in the overlay's upper layer, as changes to `target`. New directories are listed
+// once instead of with everything inside, a fresh git checkout would fill the screen.
+fn collect(layer: &Path, target: &Path, changes: &mut Vec<Effect>) {
+    let Ok(entries) = fs::read_dir(layer) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let Ok(meta) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        let path = target.join(entry.file_name());
+        // overlayfs marks removed files with a 0:0 character device
+        if meta.file_type().is_char_device() && meta.rdev() == 0 {
+            changes.push(Effect::Remove(path));
+        } else if meta.is_dir() && path.is_dir() {
+            collect(&entry.path(), &path, changes);
+        } else {
+            changes.push(Effect::write(path));
+        }
+    }
+}
+
+// What an untrusted script is allowed, for the plan and the dry run
+pub fn profile(step: &StepConfig) -> String {
+    let network = if step.network {
+        "network"
+    } else {
+        "no network"
+    };
+    let writable = if step.writable.is_empty() {
+        "writes only /tmp".to_string()
+    } else {
+        format!("writes {}", step.writable.join(", "))
+    };
+    format!("sandboxed: read-only system, {}, {}", network, writable)
+}
+
+// Untrusted scripts see /usr and /etc read-only, an empty home, the paths they list in
+// `writable` and the network only when they ask for it
+pub async fn run_untrusted(step: &StepConfig, script: &str, ctx: &StepContext) -> Result<()> {
+    let bwrap = tools::require("bwrap")?;
+    let home = ctx
+        .variables
+        .get("home")
+        .cloned()
+        .or_else(|| std::env::var("HOME").ok())
+        .unwrap_or_else(|| "/tmp".to_string());
+
+    let mut cmd = AsyncCommand::new(bwrap);
+    cmd.args(["--unshare-all", "--die-with-parent", "--new-session"]);
+    if step.network {
+        cmd.arg("--share-net");
+    }
+    cmd.args(["--ro-bind", "/usr", "/usr", "--ro-bind", "/etc", "/etc"])
+        .args([
+            "--symlink",
+            "usr/bin",
+            "/bin",
+            "--symlink",
+            "usr/sbin",
+            "/sbin",
+        ])
+        .args([
+            "--symlink",
+            "usr/lib",
+            "/lib",
+            "--symlink",
+            "usr/lib64",
+            "/lib64",
+        ])
+        .args([
+            "--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp", "--tmpfs", "/run",
+        ]);
+    if step.network {
+        // resolv.conf usually points at systemd-resolved's stub below /run
+        cmd.args([
+            "--ro-bind-try",
+            "/run/systemd/resolve",
+            "/run/systemd/resolve",
+        ]);
+    }
+    cmd.arg("--dir").arg(&home);
+    for path in &step.writable {
+        let path = ctx.expand_home(&render(path, &ctx.variables)?);
+        fs::create_dir_all(&path).with_context(|| format!("Cannot create {}", path.display()))?;
+        cmd.arg("--bind").arg(&path).arg(&path);
+    }
+    let output = cmd
+        .arg("--ro-bind")
+        .arg(script)
+        .arg(script)
+        .args(["--setenv", "HOME", &home, "--chdir", &home])
+        .args(["--", "bash", script])
+        .envs(ctx.environment())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    logging::record_output(module_path!(), &step.id, &output.stdout);
+
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        logging::event!(Level::Error, &step.id, "{}", error_msg);
+        return Err(anyhow::anyhow!(
+            "Setup script failed in its sandbox: {}",
+            error_msg
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use crate::{TextConfig, ThemeConfig, pipeline};
+use anyhow::Result;
+use clap::ValueEnum;
+use schemars::{Schema, schema_for};
+use std::io::Write;
+
+// The files downstream config authors write, each with a schema of its own
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ConfigFile {
+    Theme,
+    Text,
+    Profile,
+    // A drop-in of extra steps in /etc/sparrow-installer/actions.d
+    Pipeline,
+}
+
+pub fn schema(file: ConfigFile) -> Schema {
+    match file {
+        ConfigFile::Theme => schema_for!(ThemeConfig),
+        ConfigFile::Text => schema_for!(TextConfig),
+        ConfigFile::Profile => schema_for!(pipeline::ProfileConfig),
+        ConfigFile::Pipeline => schema_for!(pipeline::DropIn),
+    }
+}
+
+pub fn command(file: ConfigFile) -> Result<()> {
+    // An error rather than a panic when piped into something that stops reading
+    writeln!(
+        std::io::stdout(),
+        "{}",
+        serde_json::to_string_pretty(&schema(file))?
+    )?;
+    Ok(())
+}
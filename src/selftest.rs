@@ -0,0 +1,515 @@
+use crate::colors::ColorSupport;
+use crate::pipeline::{self, ProfileConfig, StepAction, StepConfig};
+use crate::snapshot::expand_home;
+use crate::verify;
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::IsTerminal;
+use std::path::Path;
+
+// Names std::env::consts::ARCH uses on the architectures images are built for. A typo like
+// "amd64" would quietly leave the step out everywhere.
+const KNOWN_ARCHES: [&str; 6] = ["x86_64", "aarch64", "riscv64", "powerpc64", "s390x", "x86"];
+
+// Smallest terminal the screens are laid out for
+const MIN_COLUMNS: u16 = 80;
+const MIN_ROWS: u16 = 24;
+
+enum Outcome {
+    Pass,
+    // Worth a look, but does not fail the self-test (e.g. CI without a terminal)
+    Warn(String),
+    Fail(String),
+    // Left out on this system, with the reason
+    Skip(String),
+}
+
+struct Report {
+    results: Vec<(&'static str, String, Outcome)>,
+}
+
+impl Report {
+    fn add(&mut self, area: &'static str, name: impl Into<String>, outcome: Outcome) {
+        self.results.push((area, name.into(), outcome));
+    }
+
+    fn check<T>(&mut self, area: &'static str, name: &str, result: Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.add(area, name, Outcome::Pass);
+                Some(value)
+            }
+            Err(e) => {
+                self.add(area, name, Outcome::Fail(format!("{:#}", e)));
+                None
+            }
+        }
+    }
+}
+
+// Checks what packaging can break without starting the interface, failing on any hard error
+pub async fn run() -> Result<()> {
+    let mut report = Report {
+        results: Vec::new(),
+    };
+
+    if report
+        .check("config", "theme.toml", crate::ThemeConfig::load())
+        .is_some()
+    {
+        check_colors(&mut report);
+        check_modifiers(&mut report);
+    }
+    report.check("config", "text.toml", crate::TextConfig::load());
+    // An override that does not load is skipped when the installer starts, here it fails
+    for dir in crate::config_dirs() {
+        let theme = dir.join("theme.toml");
+        if theme.exists() {
+            report.check(
+                "config",
+                &theme.display().to_string(),
+                crate::load_override::<crate::ThemeConfig>(&theme, crate::THEME_CONFIG),
+            );
+        }
+        let text = dir.join("text.toml");
+        if text.exists() {
+            report.check(
+                "config",
+                &text.display().to_string(),
+                crate::load_override::<crate::TextConfig>(&text, crate::TEXT_CONFIG),
+            );
+        }
+    }
+    let settings = report.check("config", "settings.toml", crate::SettingsConfig::load());
+    if let Some(settings) = &settings {
+        crate::escalation::prefer(settings.privileges.escalation);
+    }
+    let profile = report.check("config", "profile.toml", ProfileConfig::load());
+    for (path, steps) in pipeline::read_drop_ins(Path::new(pipeline::ACTIONS_DIR)) {
+        report.check("config", &path.display().to_string(), steps);
+    }
+    #[cfg(feature = "simulator")]
+    report.check(
+        "config",
+        "scenario.toml",
+        crate::simulation::Scenario::load(),
+    );
+
+    if let Some(profile) = &profile {
+        check_profile(&mut report, profile);
+        check_tools(&mut report, profile);
+        check_host_tools(&mut report);
+        if let Some(settings) = &settings {
+            dry_run(&mut report, profile, settings);
+        }
+    }
+    check_terminal(&mut report);
+
+    let mut failed = 0;
+    let mut warnings = 0;
+    for (area, name, outcome) in &report.results {
+        match outcome {
+            Outcome::Pass => println!("PASS  {:<9} {}", area, name),
+            Outcome::Skip(reason) => println!("SKIP  {:<9} {}: {}", area, name, reason),
+            Outcome::Warn(reason) => {
+                warnings += 1;
+                println!("WARN  {:<9} {}: {}", area, name, reason);
+            }
+            Outcome::Fail(reason) => {
+                failed += 1;
+                println!("FAIL  {:<9} {}: {}", area, name, reason);
+            }
+        }
+    }
+    let total = report.results.len();
+    println!(
+        "\n{} passed, {} warnings, {} failed",
+        total - warnings - failed,
+        warnings,
+        failed
+    );
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "Self-test failed {} of {} checks",
+            failed,
+            total
+        ));
+    }
+    Ok(())
+}
+
+// Names each color the embedded theme gets wrong, loading stops at the first
+fn check_colors(report: &mut Report) {
+    let Ok(config) = crate::THEME_CONFIG.parse::<toml::Table>() else {
+        return;
+    };
+    let mut invalid = Vec::new();
+    for table in ["colors", "progress"] {
+        let Some(values) = config.get(table).and_then(toml::Value::as_table) else {
+            continue;
+        };
+        for (key, value) in values {
+            if let Some(name) = value.as_str()
+                && let Err(e) = crate::parse_color(name)
+            {
+                invalid.push(format!("{}.{}: {}", table, key, e));
+            }
+        }
+    }
+    report.add(
+        "config",
+        "theme colors",
+        if invalid.is_empty() {
+            Outcome::Pass
+        } else {
+            Outcome::Fail(invalid.join(", "))
+        },
+    );
+}
+
+// Unknown modifier names are left out of the style just as quietly
+fn check_modifiers(report: &mut Report) {
+    let Ok(theme) = crate::ThemeConfig::load() else {
+        return;
+    };
+    let modifiers = &theme.modifiers;
+    let elements = [
+        ("title", &modifiers.title),
+        ("content", &modifiers.content),
+        ("selected", &modifiers.selected),
+        ("disabled", &modifiers.disabled),
+        ("confirmation", &modifiers.confirmation),
+        ("description", &modifiers.description),
+        ("success", &modifiers.success),
+        ("error", &modifiers.error),
+        ("fail", &modifiers.fail),
+        ("status", &modifiers.status),
+        ("progress", &modifiers.progress),
+    ];
+    let mut unknown = Vec::new();
+    for (element, names) in elements {
+        for name in names {
+            if crate::modifier_by_name(name).is_none() {
+                unknown.push(format!("modifiers.{} = {}", element, name));
+            }
+        }
+    }
+    report.add(
+        "config",
+        "theme modifiers",
+        if unknown.is_empty() {
+            Outcome::Pass
+        } else {
+            Outcome::Fail(format!("unknown {}", unknown.join(", ")))
+        },
+    );
+}
+
+// The files a step reads from the image
+fn sources(step: &StepConfig) -> Vec<&str> {
+    match &step.action {
+        StepAction::Script(path) => vec![path.as_str()],
+        StepAction::Copy(file) | StepAction::Template(file) => vec![file.source.as_str()],
+        StepAction::Extract(archive) if !archive.source.contains("{{") => {
+            vec![archive.source.as_str()]
+        }
+        StepAction::Dconf(dconf) => vec![dconf.keyfile.as_str()],
+        StepAction::HyprlandMerge(merge) if !merge.source.contains("{{") => {
+            vec![merge.source.as_str()]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn check_profile(report: &mut Report, profile: &ProfileConfig) {
+    let mut ids = BTreeSet::new();
+    for step in &profile.steps {
+        if !ids.insert(step.id.as_str()) {
+            report.add(
+                "profile",
+                &step.id,
+                Outcome::Fail("step id is used twice".to_string()),
+            );
+        }
+    }
+
+    let variables = test_variables();
+    for step in &profile.steps {
+        let mut problems: Vec<String> = sources(step)
+            .into_iter()
+            .filter(|source| !expand_home(source).exists())
+            .map(|source| format!("missing {}", source))
+            .collect();
+        problems.extend(
+            step.arch
+                .iter()
+                .filter(|arch| !KNOWN_ARCHES.contains(&arch.as_str()))
+                .map(|arch| format!("unknown arch {}", arch)),
+        );
+        if let Some(condition) = &step.condition
+            && let Err(e) = crate::condition::evaluate(condition)
+        {
+            problems.push(format!("{:#}", e));
+        }
+        let dest = match &step.action {
+            StepAction::Copy(file) | StepAction::Template(file) => Some(file.dest_path(&variables)),
+            StepAction::Git(git) => Some(git.dest_path(&variables)),
+            StepAction::Download(file) => Some(file.dest_path(&variables)),
+            StepAction::Extract(archive) => Some(archive.dest_path(&variables)),
+            _ => None,
+        };
+        if let Some(Err(e)) = dest {
+            problems.push(e.to_string());
+        }
+        report.add(
+            "profile",
+            format!("{} ({})", step.name, step.id),
+            if problems.is_empty() {
+                Outcome::Pass
+            } else {
+                Outcome::Fail(problems.join(", "))
+            },
+        );
+    }
+}
+
+// Programs the profile's actions run, which have to ship in the image
+fn check_tools(report: &mut Report, profile: &ProfileConfig) {
+    let mut tools = BTreeSet::new();
+    for step in &profile.steps {
+        if step.untrusted {
+            tools.insert("bwrap");
+        }
+        let tool = match &step.action {
+            StepAction::Script(_) => "bash",
+            StepAction::SystemdEnable(_) => "systemctl",
+            StepAction::Git(_) => "git",
+            StepAction::Gsettings(_) => "gsettings",
+            StepAction::Dconf(_) => "dconf",
+            StepAction::Fonts(_) => "fc-cache",
+            StepAction::DefaultApps(_) => "xdg-mime",
+            _ => continue,
+        };
+        tools.insert(tool);
+    }
+    for tool in tools {
+        report.add(
+            "tools",
+            tool,
+            if verify::in_path(tool) {
+                Outcome::Pass
+            } else {
+                Outcome::Fail("not found in PATH".to_string())
+            },
+        );
+    }
+}
+
+// Missing host tools only switch features off (e.g. no bootc, no update), so they warn
+fn check_host_tools(report: &mut Report) {
+    for (program, path) in crate::tools::probe() {
+        report.add(
+            "host",
+            program,
+            match path {
+                Some(_) => Outcome::Pass,
+                None => Outcome::Warn("not installed, the features using it are off".to_string()),
+            },
+        );
+    }
+    // Any one of them will do, only root can install without
+    report.add(
+        "host",
+        "privileges",
+        match crate::escalation::current() {
+            Some(_) => Outcome::Pass,
+            None => {
+                Outcome::Warn("no sudo, doas, run0 or pkexec, only root can install".to_string())
+            }
+        },
+    );
+}
+
+// What the setup screens would add, so templates render the way a default install does
+fn test_variables() -> BTreeMap<String, String> {
+    let mut variables = pipeline::system_variables();
+    for name in ["wallpaper_packs", "monitor_config"] {
+        variables.entry(name.to_string()).or_default();
+    }
+    variables
+}
+
+// Every step including the opt-in ones, through the same path a --dry-run install takes
+fn dry_run(report: &mut Report, profile: &ProfileConfig, settings: &crate::SettingsConfig) {
+    let ctx = pipeline::StepContext {
+        dry_run: true,
+        variables: test_variables(),
+        progress: None,
+        network: settings.network.clone(),
+        selinux: settings.selinux.clone(),
+        counters: Default::default(),
+    };
+    let mut failed = Vec::new();
+    for step in pipeline::plan_steps(&profile.steps, &[]) {
+        let result = match step.skip_reason() {
+            Ok(Some(reason)) => {
+                report.add("dry-run", step.label(), Outcome::Skip(reason));
+                continue;
+            }
+            Ok(None) => step.effects(&ctx).map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            failed.push(format!("{}: {}", step.label(), e));
+        }
+    }
+    report.add(
+        "dry-run",
+        format!("{} steps", profile.steps.len()),
+        if failed.is_empty() {
+            Outcome::Pass
+        } else {
+            Outcome::Fail(failed.join(", "))
+        },
+    );
+}
+
+fn check_terminal(report: &mut Report) {
+    if !std::io::stdout().is_terminal() {
+        report.add(
+            "terminal",
+            "interactive",
+            Outcome::Warn("stdout is not a terminal, skipping size checks".to_string()),
+        );
+    } else {
+        let outcome = match crossterm::terminal::size() {
+            Ok((columns, rows)) if columns >= MIN_COLUMNS && rows >= MIN_ROWS => Outcome::Pass,
+            Ok((columns, rows)) => Outcome::Warn(format!(
+                "{}x{} is smaller than {}x{}",
+                columns, rows, MIN_COLUMNS, MIN_ROWS
+            )),
+            Err(e) => Outcome::Fail(format!("cannot read the size: {}", e)),
+        };
+        report.add("terminal", "size", outcome);
+    }
+
+    let term = std::env::var("TERM").unwrap_or_default();
+    report.add(
+        "terminal",
+        "TERM",
+        if term.is_empty() || term == "dumb" {
+            Outcome::Warn(format!("'{}' gets the plain line mode", term))
+        } else {
+            Outcome::Pass
+        },
+    );
+
+    // Colors past what the terminal shows are mapped to the nearest, Gold is an RGB color
+    report.add(
+        "terminal",
+        "colors",
+        match crate::colors::detect() {
+            ColorSupport::TrueColor => Outcome::Pass,
+            ColorSupport::Indexed => {
+                Outcome::Warn("256 colors, theme colors are approximated".to_string())
+            }
+            ColorSupport::Basic => {
+                Outcome::Warn("16 colors, theme colors are approximated".to_string())
+            }
+            ColorSupport::None => Outcome::Warn("no colors, drawing in monochrome".to_string()),
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failures(report: &Report) -> Vec<(String, String)> {
+        report
+            .results
+            .iter()
+            .filter_map(|(_, name, outcome)| match outcome {
+                Outcome::Fail(reason) => Some((name.clone(), reason.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn profile_problems_fail_the_step_they_belong_to() {
+        let dir = crate::helper::private_dir("selftest").expect("dir");
+        let shipped = dir.join("shipped.conf");
+        std::fs::write(&shipped, "").expect("source");
+        let profile: ProfileConfig = toml::from_str(&format!(
+            r#"
+            [[steps]]
+            id = "good"
+            name = "Good"
+            copy = {{ source = "{shipped}", dest = "~/.config/good.conf" }}
+
+            [[steps]]
+            id = "missing"
+            name = "Missing"
+            copy = {{ source = "{dir}/gone.conf", dest = "~/.config/gone.conf" }}
+
+            [[steps]]
+            id = "arch"
+            name = "Arch"
+            arch = ["amd64"]
+            copy = {{ source = "{shipped}", dest = "~/.config/arch.conf" }}
+
+            [[steps]]
+            id = "good"
+            name = "Again"
+            copy = {{ source = "{shipped}", dest = "~/.config/again.conf" }}
+            "#,
+            shipped = shipped.display(),
+            dir = dir.display(),
+        ))
+        .expect("profile");
+
+        let mut report = Report {
+            results: Vec::new(),
+        };
+        check_profile(&mut report, &profile);
+        std::fs::remove_dir_all(&dir).ok();
+
+        let failures = failures(&report);
+        assert_eq!(failures.len(), 3, "{:?}", failures);
+        assert!(failures.contains(&("good".to_string(), "step id is used twice".to_string())));
+        assert!(failures.contains(&(
+            "Missing (missing)".to_string(),
+            format!("missing {}/gone.conf", dir.display())
+        )));
+        assert!(failures.contains(&("Arch (arch)".to_string(), "unknown arch amd64".to_string())));
+        assert!(
+            report
+                .results
+                .iter()
+                .any(|(_, name, outcome)| name == "Good (good)" && matches!(outcome, Outcome::Pass))
+        );
+    }
+
+    #[test]
+    fn the_shipped_profile_dry_runs_clean() {
+        let settings = crate::SettingsConfig::load().expect("settings");
+        let empty = crate::helper::private_dir("selftest").expect("dir");
+        let profile = ProfileConfig::load_from(&empty).expect("profile");
+        std::fs::remove_dir_all(&empty).ok();
+
+        let mut report = Report {
+            results: Vec::new(),
+        };
+        dry_run(&mut report, &profile, &settings);
+
+        assert!(failures(&report).is_empty(), "{:?}", failures(&report));
+        assert!(
+            report
+                .results
+                .iter()
+                .any(|(area, _, outcome)| *area == "dry-run" && matches!(outcome, Outcome::Pass))
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use crate::pipeline::StepProgress;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::{self, Duration, Instant};
+
+const SCENARIO_CONFIG: &str = include_str!("scenario.toml");
+
+// Name the simulated output is reported under, in place of a step id
+const STEP: &str = "dry-run";
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ScenarioEvent {
+    // Wait after the previous event
+    #[serde(default)]
+    pub delay_ms: u64,
+    pub line: Option<String>,
+    pub progress: Option<f32>,
+}
+
+// What a simulated update prints and when, installs list what their steps would do instead
+#[derive(Debug, Deserialize, Clone)]
+pub struct Scenario {
+    #[serde(default)]
+    pub update: Vec<ScenarioEvent>,
+}
+
+impl Scenario {
+    pub fn load() -> Result<Self> {
+        let scenario: Scenario = toml::from_str(SCENARIO_CONFIG)?;
+        Ok(scenario)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read scenario {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Invalid scenario {}", path.display()))
+    }
+}
+
+// Sends the events as step progress on their schedule, the way a real step reports its output.
+// `pace` scales the delays, below 1 plays faster.
+pub fn play(
+    events: Vec<ScenarioEvent>,
+    progress: UnboundedSender<StepProgress>,
+    pace: f64,
+) -> impl Future<Output = ()> {
+    // Out of range values would overflow the delays
+    let pace = if pace.is_finite() {
+        pace.clamp(0.0, 100.0)
+    } else {
+        1.0
+    };
+    // Scheduled from the start, so slow receivers do not stretch the scenario
+    let mut at = Instant::now();
+    async move {
+        for event in events {
+            at += Duration::from_millis(event.delay_ms).mul_f64(pace);
+            time::sleep_until(at).await;
+            let sent = progress.send(StepProgress {
+                step: STEP.to_string(),
+                fraction: event.progress,
+                message: event.line.unwrap_or_default(),
+            });
+            if sent.is_err() {
+                return;
+            }
+        }
+    }
+}
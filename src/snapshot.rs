@@ -0,0 +1,182 @@
+use crate::SnapshotConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+struct FileEntry {
+    size: u64,
+    hash: String,
+}
+
+// Manifest of every regular file below the configured roots
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    files: BTreeMap<PathBuf, FileEntry>,
+    truncated: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    pub truncated: bool,
+}
+
+impl Snapshot {
+    pub fn capture(config: &SnapshotConfig) -> Self {
+        let mut snapshot = Snapshot::default();
+        for root in &config.paths {
+            snapshot.walk(&expand_home(root), config);
+        }
+        snapshot
+    }
+
+    fn walk(&mut self, dir: &Path, config: &SnapshotConfig) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if self.files.len() >= config.max_files {
+                self.truncated = true;
+                return;
+            }
+            let path = entry.path();
+            // Symlinks are not followed to avoid loops and leaving the snapshot roots
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                self.walk(&path, config);
+            } else if file_type.is_file()
+                && let Some(file_entry) = hash_file(&path, config.max_hash_size)
+            {
+                self.files.insert(path, file_entry);
+            }
+        }
+    }
+
+    pub fn diff(&self, after: &Snapshot) -> SnapshotDiff {
+        let mut diff = SnapshotDiff {
+            truncated: self.truncated || after.truncated,
+            ..Default::default()
+        };
+
+        for (path, entry) in &after.files {
+            match self.files.get(path) {
+                None => diff.added.push(path.display().to_string()),
+                Some(before) if before != entry => diff.modified.push(path.display().to_string()),
+                _ => {}
+            }
+        }
+        for path in self.files.keys() {
+            if !after.files.contains_key(path) {
+                diff.removed.push(path.display().to_string());
+            }
+        }
+
+        diff
+    }
+}
+
+impl SnapshotDiff {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} added, {} modified, {} removed{}",
+            self.added.len(),
+            self.modified.len(),
+            self.removed.len(),
+            if self.truncated { " (truncated)" } else { "" }
+        )
+    }
+}
+
+fn hash_file(path: &Path, max_hash_size: u64) -> Option<FileEntry> {
+    let size = fs::metadata(path).ok()?.len();
+
+    // Large files are compared by size only to keep the snapshot fast
+    if size > max_hash_size {
+        return Some(FileEntry {
+            size,
+            hash: String::new(),
+        });
+    }
+
+    let mut file = File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let read = file.read(&mut buffer).ok()?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Some(FileEntry {
+        size,
+        hash: format!("{:x}", hasher.finalize()),
+    })
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    expand_home_in(path, None)
+}
+
+// `~/` below `home`, the account a step runs for as seen from inside the target root,
+// or below $HOME without one
+pub fn expand_home_in(path: &str, home: Option<&str>) -> PathBuf {
+    let home = home
+        .map(str::to_string)
+        .or_else(|| std::env::var("HOME").ok());
+    match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => crate::sysroot::target(Path::new(&home).join(rest)),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_diff_lists_what_the_install_changed() {
+        let dir = crate::helper::private_dir("sparrow-snapshot-test").expect("temporary folder");
+        fs::create_dir(dir.join("hypr")).expect("folder");
+        fs::write(dir.join("hypr/hyprland.conf"), "gaps_in = 5\n").expect("config");
+        fs::write(dir.join("bashrc"), "alias ll='ls -l'\n").expect("bashrc");
+        fs::write(dir.join("wallpaper.png"), [0u8; 64]).expect("wallpaper");
+        let mut config = SnapshotConfig {
+            enabled: true,
+            paths: vec![dir.to_string_lossy().into_owned()],
+            max_files: 100,
+            max_hash_size: 32,
+        };
+
+        let before = Snapshot::capture(&config);
+        fs::write(dir.join("hypr/hyprland.conf"), "gaps_in = 8\n").expect("config");
+        fs::remove_file(dir.join("bashrc")).expect("bashrc");
+        fs::write(dir.join("hypr/sparrow.conf"), "source = x\n").expect("include");
+        // Over max_hash_size, only a different size would count as modified
+        fs::write(dir.join("wallpaper.png"), [1u8; 64]).expect("wallpaper");
+        let diff = before.diff(&Snapshot::capture(&config));
+
+        let path = |name: &str| dir.join(name).display().to_string();
+        assert_eq!(diff.added, [path("hypr/sparrow.conf")]);
+        assert_eq!(diff.modified, [path("hypr/hyprland.conf")]);
+        assert_eq!(diff.removed, [path("bashrc")]);
+        assert_eq!(diff.summary(), "1 added, 1 modified, 1 removed");
+
+        config.max_files = 1;
+        let truncated = Snapshot::capture(&config);
+        assert_eq!(
+            truncated.diff(&truncated).summary(),
+            "0 added, 0 modified, 0 removed (truncated)"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::pipeline::{ProfileConfig, TargetUser};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DOTFILES_MARKER: &str = "dotfiles.marker";
+// Progress of a fresh install that has not completed yet
+const PARTIAL_MARKER: &str = "dotfiles.partial";
+
+// Written after a successful dotfiles install so later runs can offer a reinstall
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstallMarker {
+    pub version: String,
+    pub content_hash: String,
+    pub installed_at: String,
+    // Fingerprint of every pipeline step that completed, keyed by step id
+    #[serde(default)]
+    pub steps: BTreeMap<String, String>,
+    // Opt-in steps the user turned down, repair leaves these alone
+    #[serde(default)]
+    pub declined: BTreeSet<String>,
+    // Choices from the setup screens, so a resumed install renders the same values
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    // Accounts the per-user steps were installed for, empty for the invoking user only
+    #[serde(default)]
+    pub users: Vec<TargetUser>,
+}
+
+impl InstallMarker {
+    pub fn new(profile: &ProfileConfig) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            content_hash: profile.content_hash(),
+            installed_at: chrono::Local::now().to_rfc3339(),
+            steps: BTreeMap::new(),
+            declined: BTreeSet::new(),
+            variables: BTreeMap::new(),
+            users: Vec::new(),
+        }
+    }
+
+    pub fn load() -> Option<Self> {
+        Self::load_from(&marker_path())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&marker_path())
+    }
+
+    pub fn load_partial() -> Option<Self> {
+        Self::load_from(&partial_path())
+    }
+
+    pub fn save_partial(&self) -> Result<()> {
+        self.save_to(&partial_path())
+    }
+
+    pub fn remove_partial() {
+        let _ = fs::remove_file(partial_path());
+    }
+
+    fn load_from(path: &Path) -> Option<Self> {
+        let contents = fs::read_to_string(path).ok()?;
+        toml::from_str(&contents).ok()
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    // True when the installed content no longer matches what ships on the image
+    pub fn is_outdated(&self, profile: &ProfileConfig) -> bool {
+        profile.content_hash() != self.content_hash
+    }
+}
+
+fn marker_path() -> PathBuf {
+    crate::state_dir().join(DOTFILES_MARKER)
+}
+
+fn partial_path() -> PathBuf {
+    crate::state_dir().join(PARTIAL_MARKER)
+}
+
+pub fn content_hash(path: &Path) -> Result<String> {
+    let contents = fs::read(path)?;
+    Ok(format!("{:x}", Sha256::digest(&contents)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_marker_remembers_the_install_until_the_profile_changes() {
+        let dir = crate::helper::private_dir("sparrow-state-test").expect("temporary folder");
+        let mut profile = ProfileConfig::load_from(&dir).expect("profile");
+        let mut marker = InstallMarker::new(&profile);
+        marker.declined.insert("gaming".to_string());
+        let path = dir.join("state").join(DOTFILES_MARKER);
+        marker.save_to(&path).expect("saved");
+
+        let loaded = InstallMarker::load_from(&path).expect("loaded");
+        assert_eq!(loaded, marker);
+        assert_eq!(loaded.version, env!("CARGO_PKG_VERSION"));
+        assert!(!loaded.is_outdated(&profile));
+
+        // A newer image with different steps is offered again
+        profile.steps.pop();
+        assert!(loaded.is_outdated(&profile));
+        assert_eq!(InstallMarker::load_from(&dir.join("missing")), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,261 @@
+use super::user_command;
+use crate::logging;
+use crate::pipeline::{CheckStatus, ChecklistItem, Effect, StepContext};
+use crate::steps::files::render;
+use anyhow::{Context, Result};
+use cli_log::Level;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Desktop file ids, rendered so a picker can fill them from `{{ name }}` variables.
+// An empty value leaves that default untouched.
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct DefaultApps {
+    pub browser: Option<String>,
+    pub terminal: Option<String>,
+    pub file_manager: Option<String>,
+    // MIME type to desktop file id
+    #[serde(default)]
+    pub mime: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+enum Assignment {
+    Browser(String),
+    Terminal(String),
+    Mime(String, String),
+}
+
+impl Assignment {
+    fn label(&self) -> String {
+        match self {
+            Assignment::Browser(app) => format!("Browser: {}", app),
+            Assignment::Terminal(app) => format!("Terminal: {}", app),
+            Assignment::Mime(mime, app) => format!("{}: {}", mime, app),
+        }
+    }
+}
+
+impl DefaultApps {
+    fn assignments(&self, ctx: &StepContext) -> Result<Vec<Assignment>> {
+        let rendered = |value: Option<&str>| -> Result<Option<String>> {
+            let Some(value) = value else {
+                return Ok(None);
+            };
+            let value = render(value, &ctx.variables)?;
+            Ok(Some(value).filter(|value| !value.is_empty()))
+        };
+
+        let mut assignments = Vec::new();
+        if let Some(app) = rendered(self.browser.as_deref())? {
+            assignments.push(Assignment::Browser(app));
+        }
+        if let Some(app) = rendered(self.terminal.as_deref())? {
+            assignments.push(Assignment::Terminal(app));
+        }
+        if let Some(app) = rendered(self.file_manager.as_deref())? {
+            assignments.push(Assignment::Mime("inode/directory".to_string(), app));
+        }
+        for (mime, app) in &self.mime {
+            if let Some(app) = rendered(Some(app))? {
+                assignments.push(Assignment::Mime(mime.clone(), app));
+            }
+        }
+        Ok(assignments)
+    }
+
+    pub fn effects(&self, ctx: &StepContext) -> Result<Vec<Effect>> {
+        Ok(self
+            .assignments(ctx)?
+            .into_iter()
+            .map(|assignment| match assignment {
+                Assignment::Browser(app) => {
+                    Effect::Execute(format!("xdg-settings set default-web-browser {}", app))
+                }
+                Assignment::Terminal(_) => {
+                    Effect::write(ctx.expand_home("~/.config/xdg-terminals.list"))
+                }
+                Assignment::Mime(mime, app) => {
+                    Effect::Execute(format!("xdg-mime default {} {}", app, mime))
+                }
+            })
+            .collect())
+    }
+
+    // Labels of the defaults that no longer point at the configured application
+    pub async fn drifted(&self, ctx: &StepContext) -> Vec<String> {
+        let Ok(assignments) = self.assignments(ctx) else {
+            return Vec::new();
+        };
+        let mut drifted = Vec::new();
+        for assignment in assignments {
+            if !is_current(&assignment, ctx).await {
+                drifted.push(assignment.label());
+            }
+        }
+        drifted
+    }
+}
+
+pub async fn apply(
+    step: &str,
+    apps: &DefaultApps,
+    ctx: &StepContext,
+    checklist: &mut Vec<ChecklistItem>,
+) -> Result<()> {
+    let mut failed = Vec::new();
+
+    for assignment in apps.assignments(ctx)? {
+        let result = set_default(&assignment, ctx).await;
+        match &result {
+            Ok(()) => logging::event!(Level::Info, step, "Set {}", assignment.label()),
+            Err(e) => {
+                logging::event!(Level::Error, step, "{} failed: {}", assignment.label(), e);
+                failed.push(assignment.label());
+            }
+        }
+        checklist.push(ChecklistItem::new(
+            assignment.label(),
+            if result.is_ok() {
+                CheckStatus::Done
+            } else {
+                CheckStatus::Failed
+            },
+            1,
+        ));
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Could not set default applications: {}",
+            failed.join(", ")
+        ))
+    }
+}
+
+async fn set_default(assignment: &Assignment, ctx: &StepContext) -> Result<()> {
+    let mut cmd = match assignment {
+        Assignment::Browser(app) => {
+            let mut cmd = user_command("xdg-settings", ctx.username());
+            cmd.args(["set", "default-web-browser", app]);
+            cmd
+        }
+        // There is no xdg-settings key for terminals, xdg-terminal-exec reads this list
+        Assignment::Terminal(app) => {
+            let path = ctx.expand_home("~/.config/xdg-terminals.list");
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, format!("{}\n", app))
+                .with_context(|| format!("Cannot write {}", path.display()))?;
+            return Ok(());
+        }
+        Assignment::Mime(mime, app) => {
+            let mut cmd = user_command("xdg-mime", ctx.username());
+            cmd.args(["default", app, mime]);
+            cmd
+        }
+    };
+
+    let output = cmd.output().await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+async fn is_current(assignment: &Assignment, ctx: &StepContext) -> bool {
+    let (mut cmd, expected) = match assignment {
+        Assignment::Browser(app) => {
+            let mut cmd = user_command("xdg-settings", ctx.username());
+            cmd.args(["get", "default-web-browser"]);
+            (cmd, app)
+        }
+        Assignment::Terminal(app) => {
+            let list = std::fs::read_to_string(ctx.expand_home("~/.config/xdg-terminals.list"))
+                .unwrap_or_default();
+            return list.lines().next().map(str::trim) == Some(app.as_str());
+        }
+        Assignment::Mime(mime, app) => {
+            let mut cmd = user_command("xdg-mime", ctx.username());
+            cmd.args(["query", "default", mime]);
+            (cmd, app)
+        }
+    };
+    matches!(cmd.output().await, Ok(output)
+        if output.status.success()
+            && String::from_utf8_lossy(&output.stdout).trim() == expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+
+    #[tokio::test]
+    async fn defaults_are_set_from_the_picks_and_checked_afterwards() {
+        let system = MockSystem::new();
+        // Remembers what was set, so the queries answer with it
+        system.program(
+            "xdg-settings",
+            r#"case "$1" in
+    set) echo "$3" > "$HOME/browser" ;;
+    get) cat "$HOME/browser" ;;
+esac
+"#,
+        );
+        system.program(
+            "xdg-mime",
+            r#"case "$1" in
+    default) echo "$2" > "$HOME/mime-$(echo "$3" | tr / -)" ;;
+    query) cat "$HOME/mime-$(echo "$3" | tr / -)" 2>/dev/null ;;
+esac
+"#,
+        );
+        let home = std::env::var("HOME").expect("mock home");
+        let apps = DefaultApps {
+            browser: Some("{{ browser }}".to_string()),
+            terminal: Some("kitty.desktop".to_string()),
+            file_manager: Some("org.gnome.Nautilus.desktop".to_string()),
+            // Left alone when the picker chose nothing
+            mime: BTreeMap::from([("application/pdf".to_string(), "{{ pdf }}".to_string())]),
+        };
+        let ctx = StepContext {
+            variables: BTreeMap::from([
+                ("browser".to_string(), "firefox.desktop".to_string()),
+                ("pdf".to_string(), String::new()),
+                ("home".to_string(), home.clone()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            apps.drifted(&ctx).await,
+            [
+                "Browser: firefox.desktop",
+                "Terminal: kitty.desktop",
+                "inode/directory: org.gnome.Nautilus.desktop",
+            ]
+        );
+        let mut checklist = Vec::new();
+
+        apply("apps", &apps, &ctx, &mut checklist)
+            .await
+            .expect("applied");
+        assert_eq!(checklist.len(), 3);
+        assert!(
+            checklist
+                .iter()
+                .all(|item| item.status == CheckStatus::Done)
+        );
+        let terminals =
+            std::fs::read_to_string(format!("{}/.config/xdg-terminals.list", home)).expect("list");
+        assert_eq!(terminals, "kitty.desktop\n");
+        assert!(apps.drifted(&ctx).await.is_empty());
+    }
+}
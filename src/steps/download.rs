@@ -0,0 +1,200 @@
+use crate::logging;
+use crate::pipeline::StepContext;
+use crate::snapshot::expand_home_in;
+use crate::state;
+use crate::steps::files::render;
+use anyhow::{Context, Result};
+use cli_log::Level;
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct Download {
+    pub url: String,
+    pub dest: String,
+    // Hex digest, the file is only trusted when it matches. Without one a file already at
+    // dest is kept as it is.
+    pub sha256: Option<String>,
+}
+
+impl Download {
+    pub fn dest_path(&self, variables: &BTreeMap<String, String>) -> Result<PathBuf> {
+        Ok(crate::sysroot::target(expand_home_in(
+            &render(&self.dest, variables)?,
+            variables.get("home").map(String::as_str),
+        )))
+    }
+}
+
+// Downloads into `<dest>.part` first so an interrupted transfer continues with a range request
+pub async fn download(step: &str, file: &Download, ctx: &StepContext) -> Result<()> {
+    let dest = file.dest_path(&ctx.variables)?;
+    if dest.exists()
+        && file
+            .sha256
+            .as_ref()
+            .is_none_or(|expected| matches_checksum(&dest, expected))
+    {
+        logging::event!(Level::Info, step, "{} is up to date", dest.display());
+        return Ok(());
+    }
+
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial = partial_path(&dest);
+    let mut offset = std::fs::metadata(&partial).map(|m| m.len()).unwrap_or(0);
+
+    let client = build_client(&ctx.network)?;
+    let mut response = fetch(&client, &file.url, offset).await?;
+    // Nothing is left after the offset: the part file is complete and only the rename was
+    // missed, or it does not belong to the file on the server and starts over
+    if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        if remote_size(&response) == Some(offset) {
+            logging::event!(
+                Level::Info,
+                step,
+                "{} was already downloaded",
+                partial.display()
+            );
+            return finish(step, file, &partial, &dest, offset);
+        }
+        offset = 0;
+        response = fetch(&client, &file.url, 0).await?;
+    }
+    let mut response = response.error_for_status()?;
+
+    // Servers without range support answer with the whole file again
+    if offset > 0 && response.status() != StatusCode::PARTIAL_CONTENT {
+        offset = 0;
+    }
+    if offset > 0 {
+        ctx.counters.retried();
+        logging::event!(
+            Level::Info,
+            step,
+            "Resuming download at {}",
+            format_bytes(offset)
+        );
+    }
+
+    let mut output = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(&partial)
+        .await
+        .with_context(|| format!("Cannot write {}", partial.display()))?;
+
+    let total = response.content_length().map(|length| length + offset);
+    let started = Instant::now();
+    let mut last_report = Instant::now();
+    let mut received = 0u64;
+
+    while let Some(chunk) = response.chunk().await? {
+        output.write_all(&chunk).await?;
+        received += chunk.len() as u64;
+        ctx.counters.downloaded(chunk.len() as u64);
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            let done = offset + received;
+            let speed = received as f64 / started.elapsed().as_secs_f64().max(0.001);
+            let message = match total {
+                Some(total) => format!(
+                    "{} of {}, {}/s",
+                    format_bytes(done),
+                    format_bytes(total),
+                    format_bytes(speed as u64)
+                ),
+                None => format!("{}, {}/s", format_bytes(done), format_bytes(speed as u64)),
+            };
+            let fraction = total.map(|total| done as f32 / total.max(1) as f32);
+            ctx.report_progress(step, fraction, &message);
+        }
+    }
+    output.flush().await?;
+    drop(output);
+    finish(step, file, &partial, &dest, offset + received)
+}
+
+async fn fetch(client: &reqwest::Client, url: &str, offset: u64) -> Result<reqwest::Response> {
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    request
+        .send()
+        .await
+        .with_context(|| format!("Cannot reach {}", url))
+}
+
+// Size of the whole file from the `bytes */<size>` a 416 answer carries
+fn remote_size(response: &reqwest::Response) -> Option<u64> {
+    let range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    range.strip_prefix("bytes */")?.trim().parse().ok()
+}
+
+fn finish(step: &str, file: &Download, partial: &Path, dest: &Path, size: u64) -> Result<()> {
+    if let Some(expected) = &file.sha256
+        && !matches_checksum(partial, expected)
+    {
+        // A corrupt partial file would otherwise be resumed forever
+        let _ = std::fs::remove_file(partial);
+        return Err(anyhow::anyhow!("Checksum mismatch for {}", file.url));
+    }
+
+    std::fs::rename(partial, dest)?;
+    logging::event!(
+        Level::Info,
+        step,
+        "Downloaded {} to {}",
+        format_bytes(size),
+        dest.display()
+    );
+    Ok(())
+}
+
+// Proxies from HTTP_PROXY/HTTPS_PROXY/NO_PROXY apply unless settings name one explicitly
+pub fn build_client(network: &crate::NetworkConfig) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(concat!("sparrow-installer/", env!("CARGO_PKG_VERSION")))
+        .connect_timeout(Duration::from_secs(network.connect_timeout));
+    if !network.proxy.is_empty() {
+        builder = builder.proxy(reqwest::Proxy::all(&network.proxy)?);
+    }
+    Ok(builder.build()?)
+}
+
+fn matches_checksum(path: &Path, expected: &str) -> bool {
+    state::content_hash(path).is_ok_and(|hash| hash.eq_ignore_ascii_case(expected))
+}
+
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = dest.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
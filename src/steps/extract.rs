@@ -0,0 +1,359 @@
+use crate::logging;
+use crate::pipeline::StepContext;
+use crate::snapshot::expand_home_in;
+use crate::steps::files::render;
+use anyhow::{Context, Result};
+use cli_log::Level;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct Extract {
+    pub source: String,
+    pub dest: String,
+    // Leading path components dropped from every entry, like tar --strip-components
+    #[serde(default)]
+    pub strip_components: usize,
+}
+
+impl Extract {
+    pub fn dest_path(&self, variables: &BTreeMap<String, String>) -> Result<PathBuf> {
+        Ok(crate::sysroot::target(expand_home_in(
+            &render(&self.dest, variables)?,
+            variables.get("home").map(String::as_str),
+        )))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArchiveKind {
+    Tar,
+    TarGz,
+    TarXz,
+    Zip,
+}
+
+impl ArchiveKind {
+    fn detect(path: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Ok(Self::TarXz)
+        } else if name.ends_with(".tar") {
+            Ok(Self::Tar)
+        } else if name.ends_with(".zip") {
+            Ok(Self::Zip)
+        } else {
+            Err(anyhow::anyhow!(
+                "Unknown archive type for {}",
+                path.display()
+            ))
+        }
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    ArchiveKind::detect(path).is_ok()
+}
+
+pub async fn extract(step: &str, archive: &Extract, ctx: &StepContext) -> Result<()> {
+    let source = ctx.expand_home(&render(&archive.source, &ctx.variables)?);
+    let dest = archive.dest_path(&ctx.variables)?;
+    let strip = archive.strip_components;
+
+    let step_id = step.to_string();
+    let task_ctx = ctx.clone();
+    let task_source = source.clone();
+    let task_dest = dest.clone();
+    let entries = tokio::task::spawn_blocking(move || {
+        unpack_archive(&step_id, &task_source, &task_dest, strip, &task_ctx)
+    })
+    .await??;
+
+    logging::event!(
+        Level::Info,
+        step,
+        "Extracted {} entries from {} to {}",
+        entries,
+        source.display(),
+        dest.display()
+    );
+    Ok(())
+}
+
+// Blocking, returns the number of entries written below dest
+pub fn unpack_archive(
+    step: &str,
+    source: &Path,
+    dest: &Path,
+    strip: usize,
+    ctx: &StepContext,
+) -> Result<usize> {
+    let kind = ArchiveKind::detect(source)?;
+    let file = File::open(source).with_context(|| format!("Cannot open {}", source.display()))?;
+    let size = file.metadata()?.len();
+    fs::create_dir_all(dest)?;
+
+    match kind {
+        ArchiveKind::Zip => unpack_zip(step, file, dest, strip, ctx),
+        ArchiveKind::Tar | ArchiveKind::TarGz | ArchiveKind::TarXz => {
+            let (counter, consumed) = CountingReader::new(file);
+            let reader: Box<dyn Read> = match kind {
+                ArchiveKind::TarGz => Box::new(flate2::read::GzDecoder::new(counter)),
+                ArchiveKind::TarXz => Box::new(xz2::read::XzDecoder::new(counter)),
+                _ => Box::new(counter),
+            };
+            unpack_tar(step, reader, dest, strip, ctx, || {
+                consumed.get() as f32 / size.max(1) as f32
+            })
+        }
+    }
+}
+
+fn unpack_tar(
+    step: &str,
+    reader: Box<dyn Read>,
+    dest: &Path,
+    strip: usize,
+    ctx: &StepContext,
+    fraction: impl Fn() -> f32,
+) -> Result<usize> {
+    let mut archive = tar::Archive::new(reader);
+    // Permission bits only, an archive does not get to set setuid, setgid or sticky
+    archive.set_preserve_permissions(false);
+    let root = dest.canonicalize()?;
+    let mut count = 0;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let Some(relative) = strip_path(&path, strip)? else {
+            continue;
+        };
+        let target = dest.join(&relative);
+        let mut depth = 0;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+            // An earlier symlink entry must not redirect later entries outside dest
+            let Ok(inside) = parent
+                .canonicalize()?
+                .strip_prefix(&root)
+                .map(Path::to_path_buf)
+            else {
+                return Err(anyhow::anyhow!(
+                    "Unsafe path in archive: {}",
+                    path.display()
+                ));
+            };
+            depth = inside.components().count();
+        }
+        let kind = entry.header().entry_type();
+        if kind.is_hard_link() || kind.is_symlink() {
+            let Some(link) = entry.link_name()?.map(|link| link.into_owned()) else {
+                return Err(anyhow::anyhow!("Link without a target: {}", path.display()));
+            };
+            if kind.is_hard_link() {
+                // tar would take the target as given, relative to the working directory
+                let source = link_source(&link, strip, &root)
+                    .with_context(|| format!("Unsafe link in archive: {}", path.display()))?;
+                fs::hard_link(&source, &target)
+                    .with_context(|| format!("Cannot extract {}", path.display()))?;
+                count += 1;
+                ctx.report_progress(step, Some(fraction()), &relative.to_string_lossy());
+                continue;
+            }
+            if !symlink_stays_inside(depth, &link) {
+                return Err(anyhow::anyhow!(
+                    "Unsafe link in archive: {} -> {}",
+                    path.display(),
+                    link.display()
+                ));
+            }
+        }
+        entry
+            .unpack(&target)
+            .with_context(|| format!("Cannot extract {}", path.display()))?;
+        count += 1;
+        ctx.report_progress(step, Some(fraction()), &relative.to_string_lossy());
+    }
+    Ok(count)
+}
+
+fn unpack_zip(
+    step: &str,
+    file: File,
+    dest: &Path,
+    strip: usize,
+    ctx: &StepContext,
+) -> Result<usize> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let total = archive.len();
+    let mut count = 0;
+
+    for index in 0..total {
+        let mut entry = archive.by_index(index)?;
+        let Some(path) = entry.enclosed_name() else {
+            return Err(anyhow::anyhow!("Unsafe path in archive: {}", entry.name()));
+        };
+        let Some(relative) = strip_path(&path, strip)? else {
+            continue;
+        };
+        let target = dest.join(&relative);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut output = File::create(&target)
+                .with_context(|| format!("Cannot write {}", target.display()))?;
+            io::copy(&mut entry, &mut output)?;
+        }
+        if let Some(mode) = entry.unix_mode() {
+            fs::set_permissions(&target, fs::Permissions::from_mode(mode & 0o777))?;
+        }
+
+        count += 1;
+        let fraction = (index + 1) as f32 / total as f32;
+        ctx.report_progress(step, Some(fraction), &relative.to_string_lossy());
+    }
+    Ok(count)
+}
+
+// None for entries that disappear entirely after stripping, errors on paths escaping dest
+fn strip_path(path: &Path, strip: usize) -> Result<Option<PathBuf>> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unsafe path in archive: {}",
+                    path.display()
+                ));
+            }
+        }
+    }
+    let stripped: PathBuf = relative.components().skip(strip).collect();
+    if stripped.as_os_str().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(stripped))
+}
+
+// Hard link targets name another entry of the archive, which must already be below dest
+fn link_source(link: &Path, strip: usize, root: &Path) -> Result<PathBuf> {
+    let Some(relative) = strip_path(link, strip)? else {
+        return Err(anyhow::anyhow!("Empty link target {}", link.display()));
+    };
+    let source = root.join(relative).canonicalize()?;
+    if !source.starts_with(root) {
+        return Err(anyhow::anyhow!(
+            "{} is outside the destination",
+            link.display()
+        ));
+    }
+    Ok(source)
+}
+
+// `depth` is how far below dest the symlink sits. Going up is only allowed before going down,
+// past another link `..` would leave from wherever that link points.
+fn symlink_stays_inside(mut depth: usize, link: &Path) -> bool {
+    let mut descended = false;
+    for component in link.components() {
+        match component {
+            Component::Normal(_) => descended = true,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 && !descended => depth -= 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+// Tracks how much of the compressed file has been read, the only total known up front
+struct CountingReader<R> {
+    inner: R,
+    consumed: std::rc::Rc<std::cell::Cell<u64>>,
+}
+
+impl<R: Read> CountingReader<R> {
+    fn new(inner: R) -> (Self, std::rc::Rc<std::cell::Cell<u64>>) {
+        let consumed = std::rc::Rc::new(std::cell::Cell::new(0));
+        (
+            Self {
+                inner,
+                consumed: consumed.clone(),
+            },
+            consumed,
+        )
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.consumed.set(self.consumed.get() + read as u64);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archives_cannot_set_special_permission_bits() {
+        let dir = crate::helper::private_dir("sparrow-extract-test").expect("temporary folder");
+        let archive = dir.join("tools.tar");
+        let mut builder = tar::Builder::new(File::create(&archive).expect("archive"));
+        let mut header = tar::Header::new_gnu();
+        header.set_path("tools/su").expect("path");
+        header.set_size(4);
+        header.set_mode(0o4755);
+        header.set_cksum();
+        builder.append(&header, &b"#!/\n"[..]).expect("entry");
+        builder.finish().expect("archive written");
+        drop(builder);
+
+        let zipped = dir.join("tools.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&zipped).expect("zip"));
+        writer
+            .start_file(
+                "tools/sg",
+                zip::write::SimpleFileOptions::default().unix_permissions(0o2775),
+            )
+            .expect("zip entry");
+        io::Write::write_all(&mut writer, b"#!/\n").expect("zip contents");
+        writer.finish().expect("zip written");
+
+        let dest = dir.join("out");
+        fs::create_dir(&dest).expect("dest");
+        let ctx = StepContext::default();
+        let tar = File::open(&archive).expect("archive");
+        unpack_tar("tools", Box::new(tar), &dest, 0, &ctx, || 1.0).expect("tar");
+        unpack_zip("tools", File::open(&zipped).expect("zip"), &dest, 0, &ctx).expect("zip");
+
+        let mode = |name: &str| {
+            fs::metadata(dest.join("tools").join(name))
+                .expect("extracted")
+                .permissions()
+                .mode()
+                & 0o7777
+        };
+        assert_eq!(mode("su"), 0o755);
+        assert_eq!(mode("sg"), 0o775);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
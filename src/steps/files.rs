@@ -0,0 +1,331 @@
+use super::{is_root, privileged_command};
+use crate::pipeline::StepContext;
+use crate::snapshot::expand_home_in;
+use crate::{helper, logging};
+use anyhow::{Context, Result};
+use cli_log::Level;
+use regex::{Captures, Regex};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").expect("placeholder pattern")
+});
+
+// Shared by the `copy` and `template` step types
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct FileDeploy {
+    pub source: String,
+    pub dest: String,
+    // Octal permission bits such as "0644"
+    pub mode: Option<String>,
+    // "user" or "user:group", applied with chown
+    pub owner: Option<String>,
+    #[serde(default = "default_backup")]
+    pub backup: bool,
+}
+
+fn default_backup() -> bool {
+    true
+}
+
+impl FileDeploy {
+    pub fn dest_path(&self, variables: &BTreeMap<String, String>) -> Result<PathBuf> {
+        Ok(crate::sysroot::target(expand_home_in(
+            &render(&self.dest, variables)?,
+            variables.get("home").map(String::as_str),
+        )))
+    }
+}
+
+// Replaces `{{ name }}` placeholders, failing on names that have no value
+pub fn render(template: &str, variables: &BTreeMap<String, String>) -> Result<String> {
+    let mut missing = Vec::new();
+
+    let rendered =
+        PLACEHOLDER.replace_all(template, |caps: &Captures| match variables.get(&caps[1]) {
+            Some(value) => value.clone(),
+            None => {
+                missing.push(caps[1].to_string());
+                String::new()
+            }
+        });
+
+    if !missing.is_empty() {
+        missing.sort();
+        missing.dedup();
+        return Err(anyhow::anyhow!(
+            "Undefined template variables: {}",
+            missing.join(", ")
+        ));
+    }
+    Ok(rendered.into_owned())
+}
+
+pub async fn deploy(
+    step: &str,
+    file: &FileDeploy,
+    template: bool,
+    ctx: &StepContext,
+) -> Result<()> {
+    let source = ctx.expand_home(&file.source);
+    let dest = file.dest_path(&ctx.variables)?;
+
+    let contents = if template {
+        let text = fs::read_to_string(&source)
+            .with_context(|| format!("Cannot read template {}", source.display()))?;
+        render(&text, &ctx.variables)?.into_bytes()
+    } else {
+        fs::read(&source).with_context(|| format!("Cannot read {}", source.display()))?
+    };
+
+    // System files are written as root, by the helper when it runs
+    let privileged = !is_root() && !writable(&dest);
+
+    if !privileged && let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if dest.exists() {
+        if fs::read(&dest).is_ok_and(|existing| existing == contents) {
+            logging::event!(Level::Info, step, "{} is up to date", dest.display());
+        } else if file.backup {
+            let backup = backup_path(&dest);
+            if privileged {
+                let mode = fs::metadata(&dest)?.permissions().mode() & 0o777;
+                install(&dest, &backup, &format!("{:03o}", mode)).await?;
+            } else {
+                fs::rename(&dest, &backup)?;
+            }
+            logging::event!(
+                Level::Info,
+                step,
+                "Backed up {} to {}",
+                dest.display(),
+                backup.display()
+            );
+        }
+    }
+
+    if privileged {
+        // Nobody else can swap the staged copy before root reads it
+        let staging = helper::private_dir("sparrow-deploy")?;
+        let staged = staging.join("contents");
+        let result = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&staged)
+            .and_then(|mut out| out.write_all(&contents))
+        {
+            Ok(()) => install(&staged, &dest, file.mode.as_deref().unwrap_or("0644")).await,
+            Err(e) => Err(e.into()),
+        };
+        let _ = fs::remove_dir_all(&staging);
+        result?;
+    } else {
+        fs::write(&dest, &contents).with_context(|| format!("Cannot write {}", dest.display()))?;
+    }
+    logging::event!(Level::Info, step, "Deployed {}", dest.display());
+
+    if !privileged && let Some(mode) = &file.mode {
+        let bits = u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+            .with_context(|| format!("Invalid mode {}", mode))?;
+        fs::set_permissions(&dest, fs::Permissions::from_mode(bits))?;
+    }
+
+    if let Some(owner) = &file.owner {
+        let output = privileged_command("chown")
+            .arg(owner)
+            .arg(&dest)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "chown {} failed: {}",
+                owner,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Whether the installer can write `path` itself: an existing file and its folder, where
+// the backup is renamed to, or else the nearest folder above it that exists
+fn writable(path: &Path) -> bool {
+    if path.exists() {
+        return can_write(path) && path.parent().is_some_and(can_write);
+    }
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.exists())
+        .is_some_and(can_write)
+}
+
+fn can_write(path: &Path) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: path is a valid NUL-terminated string for the duration of the call
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+// In the form the privileged helper accepts: install -D -m MODE SRC DEST
+async fn install(source: &Path, dest: &Path, mode: &str) -> Result<()> {
+    let output = privileged_command("install")
+        .args(["-D", "-m", mode])
+        .arg(source)
+        .arg(dest)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Cannot install {}: {}",
+            dest.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+const BACKUP_SUFFIX: &str = ".sparrow-bak-";
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d%H%M%S";
+
+fn backup_path(dest: &Path) -> PathBuf {
+    let mut name = dest.as_os_str().to_owned();
+    name.push(format!(
+        "{}{}",
+        BACKUP_SUFFIX,
+        chrono::Local::now().format(BACKUP_TIME_FORMAT)
+    ));
+    PathBuf::from(name)
+}
+
+// The file `backup` was taken of, None when it is not named like a backup
+pub fn backed_up(backup: &Path) -> Option<PathBuf> {
+    let name = backup.file_name()?.to_str()?;
+    let (original, stamp) = name.rsplit_once(BACKUP_SUFFIX)?;
+    chrono::NaiveDateTime::parse_from_str(stamp, BACKUP_TIME_FORMAT).ok()?;
+    Some(backup.with_file_name(original))
+}
+
+// The oldest backup of `dest` taken at or after `since`, i.e. the file as it was before that run
+pub fn backup_since(dest: &Path, since: chrono::NaiveDateTime) -> Option<PathBuf> {
+    let name = dest.file_name()?.to_string_lossy().to_string();
+    let prefix = format!("{}{}", name, BACKUP_SUFFIX);
+    fs::read_dir(dest.parent()?)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let stamp = file_name.strip_prefix(&prefix)?;
+            let taken = chrono::NaiveDateTime::parse_from_str(stamp, BACKUP_TIME_FORMAT).ok()?;
+            (taken >= since).then(|| (taken, entry.path()))
+        })
+        .min_by_key(|(taken, _)| *taken)
+        .map(|(_, path)| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("user".to_string(), "ada".to_string()),
+            ("theme".to_string(), "dark".to_string()),
+        ])
+    }
+
+    #[test]
+    fn placeholders_take_their_values_with_or_without_spaces() {
+        let rendered = render("{{user}} uses {{ theme }} and {{  theme}}", &variables());
+        assert_eq!(rendered.expect("rendered"), "ada uses dark and dark");
+        // Not a placeholder, left as written
+        assert_eq!(
+            render("{{ 1x }}", &variables()).expect("rendered"),
+            "{{ 1x }}"
+        );
+    }
+
+    #[test]
+    fn missing_variables_are_all_named_once() {
+        let error = render(
+            "{{ shell }} {{ editor }} {{shell}} {{ user }}",
+            &variables(),
+        )
+        .expect_err("undefined variables");
+        assert_eq!(
+            error.to_string(),
+            "Undefined template variables: editor, shell"
+        );
+    }
+
+    #[test]
+    fn backups_are_named_after_the_file_and_found_by_time() {
+        let dir = helper::private_dir("sparrow-files-test").expect("temporary folder");
+        let dest = dir.join("kitty.conf");
+        let backup = backup_path(&dest);
+        assert_eq!(backed_up(&backup), Some(dest.clone()));
+        assert_eq!(backed_up(&dest), None);
+        assert_eq!(
+            backed_up(&dir.join("kitty.conf.sparrow-bak-yesterday")),
+            None
+        );
+
+        let stamp = |time: &str| {
+            chrono::NaiveDateTime::parse_from_str(time, BACKUP_TIME_FORMAT).expect("stamp")
+        };
+        for time in ["20260101090000", "20260102090000", "20260103090000"] {
+            fs::write(
+                dir.join(format!("kitty.conf{}{}", BACKUP_SUFFIX, time)),
+                time,
+            )
+            .expect("backup");
+        }
+        let found = backup_since(&dest, stamp("20260101120000")).expect("backup");
+        assert!(found.to_string_lossy().ends_with("20260102090000"));
+        assert_eq!(backup_since(&dest, stamp("20260104000000")), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn an_up_to_date_file_is_left_alone() {
+        let dir = helper::private_dir("sparrow-files-test").expect("temporary folder");
+        let source = dir.join("source.conf");
+        let dest = dir.join("kitty.conf");
+        fs::write(&source, "theme = {{ theme }}\n").expect("source");
+        fs::write(&dest, "theme = dark\n").expect("dest");
+        let file = FileDeploy {
+            source: source.to_string_lossy().into_owned(),
+            dest: dest.to_string_lossy().into_owned(),
+            mode: None,
+            owner: None,
+            backup: true,
+        };
+        let ctx = StepContext {
+            variables: variables(),
+            ..Default::default()
+        };
+
+        deploy("kitty", &file, true, &ctx).await.expect("deployed");
+        let files = fs::read_dir(&dir).expect("folder").count();
+        assert_eq!(files, 2, "no backup of an unchanged file");
+
+        fs::write(&dest, "theme = light\n").expect("changed dest");
+        deploy("kitty", &file, true, &ctx).await.expect("deployed");
+        assert_eq!(fs::read_to_string(&dest).expect("dest"), "theme = dark\n");
+        assert!(backup_since(&dest, chrono::NaiveDateTime::MIN).is_some());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,263 @@
+use super::{privileged_command, target_command, user_command};
+use crate::pipeline::{CheckStatus, ChecklistItem, StepContext};
+use crate::snapshot::expand_home_in;
+use crate::steps::extract;
+use crate::steps::files::render;
+use crate::{helper, logging};
+use anyhow::{Context, Result};
+use cli_log::Level;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FONT_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "woff2"];
+const SYSTEM_FONT_DIR: &str = "/usr/local/share/fonts/sparrow";
+const USER_FONT_DIR: &str = "~/.local/share/fonts/sparrow";
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct FontInstall {
+    // Archives or loose font files
+    pub sources: Vec<String>,
+    // Install below /usr/local/share/fonts instead of the user's font directory
+    #[serde(default)]
+    pub system: bool,
+    // Family names fontconfig must know about afterwards
+    #[serde(default)]
+    pub families: Vec<String>,
+    // Hex codepoints (e.g. "f303") some installed font must cover
+    #[serde(default)]
+    pub glyphs: Vec<String>,
+}
+
+impl FontInstall {
+    // `home` of the account the fonts are for, the system directory ignores it
+    pub fn font_dir(&self, home: Option<&str>) -> PathBuf {
+        if self.system {
+            crate::sysroot::target(PathBuf::from(SYSTEM_FONT_DIR))
+        } else {
+            expand_home_in(USER_FONT_DIR, home)
+        }
+    }
+}
+
+pub async fn install(
+    step: &str,
+    fonts: &FontInstall,
+    ctx: &StepContext,
+    checklist: &mut Vec<ChecklistItem>,
+) -> Result<()> {
+    // Root installs system fonts from here, nobody else may add files to it
+    let staging = helper::private_dir("sparrow-fonts")?;
+
+    let result = stage_and_install(step, fonts, ctx, &staging).await;
+    let _ = fs::remove_dir_all(&staging);
+    result?;
+
+    let output = if fonts.system {
+        target_command("fc-cache").arg("-f").output().await?
+    } else {
+        user_command("fc-cache", ctx.username())
+            .arg("-f")
+            .output()
+            .await?
+    };
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "fc-cache failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let missing = verify(fonts, ctx, checklist).await;
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Fonts installed but still missing: {}",
+            missing.join(", ")
+        ))
+    }
+}
+
+async fn stage_and_install(
+    step: &str,
+    fonts: &FontInstall,
+    ctx: &StepContext,
+    staging: &Path,
+) -> Result<()> {
+    for source in &fonts.sources {
+        let source = ctx.expand_home(&render(source, &ctx.variables)?);
+        if extract::is_archive(&source) {
+            let step_id = step.to_string();
+            let task_ctx = ctx.clone();
+            let target = staging.join(format!("archive-{}", fs::read_dir(staging)?.count()));
+            tokio::task::spawn_blocking(move || {
+                extract::unpack_archive(&step_id, &source, &target, 0, &task_ctx)
+            })
+            .await??;
+        } else {
+            let name = source
+                .file_name()
+                .with_context(|| format!("Invalid font path {}", source.display()))?;
+            fs::copy(&source, staging.join(name))
+                .with_context(|| format!("Cannot read {}", source.display()))?;
+        }
+    }
+
+    let mut files = Vec::new();
+    collect_fonts(staging, &mut files)?;
+    if files.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No font files found in {:?}",
+            fonts.sources
+        ));
+    }
+
+    let dest = fonts.font_dir(ctx.home());
+    for file in &files {
+        let name = file.file_name().unwrap_or_default();
+        let target = dest.join(name);
+        if fonts.system {
+            let output = privileged_command("install")
+                .args(["-D", "-m", "0644"])
+                .arg(file)
+                .arg(&target)
+                .output()
+                .await?;
+            if !output.status.success() {
+                return Err(anyhow::anyhow!(
+                    "Cannot install {}: {}",
+                    target.display(),
+                    String::from_utf8_lossy(&output.stderr)
+                ));
+            }
+        } else {
+            fs::create_dir_all(&dest)?;
+            fs::copy(file, &target)
+                .with_context(|| format!("Cannot write {}", target.display()))?;
+        }
+    }
+    logging::event!(
+        Level::Info,
+        step,
+        "Installed {} font files into {}",
+        files.len(),
+        dest.display()
+    );
+    Ok(())
+}
+
+fn collect_fonts(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_fonts(&path, files)?;
+        } else if path.extension().is_some_and(|ext| {
+            FONT_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+        }) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+async fn fc_list_matches(pattern: &str, ctx: &StepContext) -> bool {
+    let output = user_command("fc-list", ctx.username())
+        .arg(pattern)
+        .arg("family")
+        .output()
+        .await;
+    matches!(output, Ok(output) if output.status.success() && !output.stdout.is_empty())
+}
+
+// Returns what fontconfig still cannot find, one checklist line per requirement
+pub async fn verify(
+    fonts: &FontInstall,
+    ctx: &StepContext,
+    checklist: &mut Vec<ChecklistItem>,
+) -> Vec<String> {
+    let mut missing = Vec::new();
+    let requirements = fonts
+        .families
+        .iter()
+        .map(|family| (family.clone(), family.clone()))
+        .chain(fonts.glyphs.iter().map(|glyph| {
+            let glyph = glyph.trim_start_matches("U+").to_lowercase();
+            (
+                format!("glyph U+{}", glyph.to_uppercase()),
+                format!(":charset={}", glyph),
+            )
+        }));
+
+    for (label, pattern) in requirements {
+        let found = fc_list_matches(&pattern, ctx).await;
+        if !found {
+            missing.push(label.clone());
+        }
+        checklist.push(ChecklistItem::new(
+            label,
+            if found {
+                CheckStatus::Done
+            } else {
+                CheckStatus::Failed
+            },
+            1,
+        ));
+    }
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+
+    #[tokio::test]
+    async fn fonts_land_in_the_users_folder_and_missing_glyphs_are_reported() {
+        let system = MockSystem::new();
+        system.program("fc-cache", "");
+        // Knows the family but has no font covering the Arch logo
+        system.program(
+            "fc-list",
+            r#"case "$1" in
+    :charset=*) ;;
+    *) echo "JetBrainsMono Nerd Font" ;;
+esac
+"#,
+        );
+        let home = std::env::var("HOME").expect("mock home");
+        let source = Path::new(&home).join("downloads");
+        fs::create_dir_all(&source).expect("downloads");
+        fs::write(source.join("JetBrainsMono.TTF"), "font").expect("font");
+        fs::write(source.join("LICENSE.txt"), "license").expect("license");
+        let fonts = FontInstall {
+            sources: ["JetBrainsMono.TTF", "LICENSE.txt"]
+                .iter()
+                .map(|name| source.join(name).to_string_lossy().into_owned())
+                .collect(),
+            system: false,
+            families: vec!["JetBrainsMono Nerd Font".to_string()],
+            glyphs: vec!["U+f303".to_string()],
+        };
+        let mut checklist = Vec::new();
+
+        let error = install("fonts", &fonts, &StepContext::default(), &mut checklist)
+            .await
+            .expect_err("glyph missing");
+        assert_eq!(
+            error.to_string(),
+            "Fonts installed but still missing: glyph U+F303"
+        );
+        let installed = fonts.font_dir(Some(&home));
+        assert!(installed.join("JetBrainsMono.TTF").exists());
+        assert!(!installed.join("LICENSE.txt").exists());
+        assert_eq!(
+            checklist,
+            [
+                ChecklistItem::new("JetBrainsMono Nerd Font", CheckStatus::Done, 1),
+                ChecklistItem::new("glyph U+F303", CheckStatus::Failed, 1),
+            ]
+        );
+    }
+}
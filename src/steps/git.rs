@@ -0,0 +1,166 @@
+use crate::logging;
+use crate::pipeline::StepContext;
+use crate::snapshot::expand_home_in;
+use crate::steps::files::render;
+use anyhow::{Context, Result};
+use cli_log::Level;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command as AsyncCommand;
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct GitCheckout {
+    pub url: String,
+    // Branch, tag or commit, defaults to the remote HEAD
+    #[serde(rename = "ref")]
+    pub reference: Option<String>,
+    pub dest: String,
+    pub depth: Option<u32>,
+}
+
+impl GitCheckout {
+    pub fn dest_path(&self, variables: &BTreeMap<String, String>) -> Result<PathBuf> {
+        Ok(crate::sysroot::target(expand_home_in(
+            &render(&self.dest, variables)?,
+            variables.get("home").map(String::as_str),
+        )))
+    }
+}
+
+// Clones by init + fetch so an interrupted clone is simply fetched again on the next run
+pub async fn checkout(step: &str, git: &GitCheckout, ctx: &StepContext) -> Result<()> {
+    let dest = git.dest_path(&ctx.variables)?;
+    let reference = git.reference.as_deref().unwrap_or("HEAD");
+    let existing = dest.join(".git").exists();
+
+    if !existing {
+        if dest
+            .read_dir()
+            .is_ok_and(|mut entries| entries.next().is_some())
+        {
+            return Err(anyhow::anyhow!(
+                "{} exists and is not a git checkout",
+                dest.display()
+            ));
+        }
+        std::fs::create_dir_all(&dest)?;
+        git_command(&dest, &["init", "--quiet"]).await?;
+        git_command(&dest, &["remote", "add", "origin", &git.url]).await?;
+    } else {
+        logging::event!(Level::Info, step, "Resuming checkout in {}", dest.display());
+        git_command(&dest, &["remote", "set-url", "origin", &git.url]).await?;
+    }
+
+    let mut fetch = vec!["fetch".to_string(), "--progress".to_string()];
+    if let Some(depth) = git.depth {
+        fetch.push(format!("--depth={}", depth));
+    }
+    fetch.extend(["origin".to_string(), reference.to_string()]);
+    // Offline, or the remote is down: a checkout from an earlier run stays as it is
+    if let Err(e) = fetch_with_progress(step, &dest, &fetch, ctx).await {
+        if existing && has_commit(&dest).await {
+            logging::event!(
+                Level::Warn,
+                step,
+                "Keeping existing checkout at {}: {}",
+                dest.display(),
+                e
+            );
+            return Ok(());
+        }
+        return Err(e.context(format!("Cannot fetch {}", git.url)));
+    }
+
+    git_command(&dest, &["checkout", "--force", "--quiet", "FETCH_HEAD"]).await?;
+    logging::event!(
+        Level::Info,
+        step,
+        "Checked out {} at {}",
+        reference,
+        dest.display()
+    );
+    Ok(())
+}
+
+async fn git_command(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = AsyncCommand::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+async fn has_commit(dir: &Path) -> bool {
+    git_command(dir, &["rev-parse", "--verify", "--quiet", "HEAD"])
+        .await
+        .is_ok()
+}
+
+// git writes "Receiving objects:  42% (420/1000)" lines separated by carriage returns
+async fn fetch_with_progress(
+    step: &str,
+    dir: &Path,
+    args: &[String],
+    ctx: &StepContext,
+) -> Result<()> {
+    let progress_line = Regex::new(r"([A-Za-z ]+):\s+(\d+)%")?;
+    let mut git = AsyncCommand::new("git");
+    // The same proxy the downloads go through
+    if !ctx.network.proxy.is_empty() {
+        git.arg("-c")
+            .arg(format!("http.proxy={}", ctx.network.proxy));
+    }
+    let mut child = git
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run git")?;
+
+    let mut stderr = child.stderr.take().context("git stderr unavailable")?;
+    let mut buffer = [0u8; 4096];
+    let mut pending = String::new();
+    let mut errors = String::new();
+
+    loop {
+        let read = stderr.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        pending.push_str(&String::from_utf8_lossy(&buffer[..read]));
+        while let Some(end) = pending.find(['\r', '\n']) {
+            let line: String = pending.drain(..=end).collect();
+            let line = line.trim();
+            if let Some(caps) = progress_line.captures(line) {
+                let percent: f32 = caps[2].parse().unwrap_or(0.0);
+                ctx.report_progress(step, Some(percent / 100.0), caps[1].trim());
+            } else if !line.is_empty() {
+                errors.push_str(line);
+                errors.push('\n');
+            }
+        }
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("git fetch failed: {}", errors.trim()));
+    }
+    Ok(())
+}
@@ -0,0 +1,251 @@
+use super::{privileged_command, target_command, user_command};
+use crate::pipeline::{CheckStatus, ChecklistItem, StepContext};
+use crate::steps::files::render;
+use crate::{helper, logging};
+use anyhow::{Context, Result};
+use cli_log::Level;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct GsettingsKey {
+    pub schema: String,
+    pub key: String,
+    // GVariant text, so strings keep their quotes: "'prefer-dark'"
+    pub value: String,
+}
+
+impl GsettingsKey {
+    fn label(&self) -> String {
+        format!("{} {}", self.schema, self.key)
+    }
+}
+
+// System-wide defaults compiled into a dconf database instead of per-user keys
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct DconfProfile {
+    // Keyfile copied into /etc/dconf/db/<db>.d/
+    pub keyfile: String,
+    #[serde(default = "default_db")]
+    pub db: String,
+    #[serde(default = "default_profile")]
+    pub profile: String,
+}
+
+fn default_db() -> String {
+    "local".to_string()
+}
+
+fn default_profile() -> String {
+    "user".to_string()
+}
+
+impl DconfProfile {
+    pub fn installed_keyfile(&self) -> Option<std::path::PathBuf> {
+        let name = Path::new(&self.keyfile).file_name()?;
+        Some(crate::sysroot::target(
+            Path::new("/etc/dconf/db")
+                .join(format!("{}.d", self.db))
+                .join(name),
+        ))
+    }
+
+    pub fn profile_path(&self) -> std::path::PathBuf {
+        crate::sysroot::target(Path::new("/etc/dconf/profile").join(&self.profile))
+    }
+}
+
+pub async fn apply_keys(
+    step: &str,
+    keys: &[GsettingsKey],
+    ctx: &StepContext,
+    checklist: &mut Vec<ChecklistItem>,
+) -> Result<()> {
+    // gsettings writes through the session bus, which a target root does not have
+    if crate::sysroot::get().is_some() {
+        logging::event!(
+            Level::Warn,
+            step,
+            "Skipping gsettings keys in a target root, preset them with a dconf step"
+        );
+        checklist.extend(
+            keys.iter()
+                .map(|key| ChecklistItem::new(key.label(), CheckStatus::Warning, 1)),
+        );
+        return Ok(());
+    }
+
+    let mut failed = Vec::new();
+
+    for key in keys {
+        let output = user_command("gsettings", ctx.username())
+            .args(["set", &key.schema, &key.key, &key.value])
+            .output()
+            .await
+            .context("Failed to run gsettings")?;
+
+        if output.status.success() {
+            logging::event!(Level::Info, step, "Set {} to {}", key.label(), key.value);
+        } else {
+            logging::event!(
+                Level::Error,
+                step,
+                "Setting {} failed: {}",
+                key.label(),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            failed.push(key.label());
+        }
+        checklist.push(ChecklistItem::new(
+            key.label(),
+            if output.status.success() {
+                CheckStatus::Done
+            } else {
+                CheckStatus::Failed
+            },
+            1,
+        ));
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Could not set {}", failed.join(", ")))
+    }
+}
+
+pub async fn key_matches(key: &GsettingsKey, ctx: &StepContext) -> bool {
+    let output = user_command("gsettings", ctx.username())
+        .args(["get", &key.schema, &key.key])
+        .output()
+        .await;
+    matches!(output, Ok(output)
+        if output.status.success()
+            && String::from_utf8_lossy(&output.stdout).trim() == key.value.trim())
+}
+
+pub async fn compile_profile(step: &str, dconf: &DconfProfile, ctx: &StepContext) -> Result<()> {
+    let source = ctx.expand_home(&render(&dconf.keyfile, &ctx.variables)?);
+    let target = dconf
+        .installed_keyfile()
+        .context("dconf keyfile has no file name")?;
+    install_file(&source, &target).await?;
+
+    // The profile decides which databases are read, add ours unless it is already listed
+    let profile_path = dconf.profile_path();
+    let current = std::fs::read_to_string(&profile_path).unwrap_or_default();
+    let entry = format!("system-db:{}", dconf.db);
+    if !current.lines().any(|line| line.trim() == entry) {
+        let mut profile = if current.is_empty() {
+            "user-db:user\n".to_string()
+        } else {
+            current.clone()
+        };
+        if !profile.ends_with('\n') {
+            profile.push('\n');
+        }
+        profile.push_str(&entry);
+        profile.push('\n');
+
+        // Nobody else can swap the staged copy before root reads it
+        let staging = helper::private_dir("sparrow-dconf")?;
+        let staged = staging.join(&dconf.profile);
+        let result = match std::fs::write(&staged, profile) {
+            Ok(()) => install_file(&staged, &profile_path).await,
+            Err(e) => Err(e.into()),
+        };
+        let _ = std::fs::remove_dir_all(&staging);
+        result?;
+    }
+
+    let output = target_command("dconf").arg("update").output().await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "dconf update failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    logging::event!(
+        Level::Info,
+        step,
+        "Compiled dconf database {} for profile {}",
+        dconf.db,
+        dconf.profile
+    );
+    Ok(())
+}
+
+async fn install_file(source: &Path, target: &Path) -> Result<()> {
+    let output = privileged_command("install")
+        .args(["-D", "-m", "0644"])
+        .arg(source)
+        .arg(target)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Cannot install {}: {}",
+            target.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+
+    fn key(schema: &str, key: &str, value: &str) -> GsettingsKey {
+        GsettingsKey {
+            schema: schema.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn keys_are_set_one_by_one_and_checked_against_the_session() {
+        let system = MockSystem::new();
+        system.program(
+            "gsettings",
+            r#"case "$1 $2" in
+    "set org.gnome.nonexistent") echo "No such schema" >&2; exit 1 ;;
+    "get org.gnome.desktop.interface") echo "'prefer-dark'" ;;
+esac
+"#,
+        );
+        let keys = [
+            key(
+                "org.gnome.desktop.interface",
+                "color-scheme",
+                "'prefer-dark'",
+            ),
+            key("org.gnome.nonexistent", "key", "true"),
+        ];
+        let ctx = StepContext::default();
+        let mut checklist = Vec::new();
+
+        let error = apply_keys("gnome", &keys, &ctx, &mut checklist)
+            .await
+            .expect_err("unknown schema");
+        assert_eq!(error.to_string(), "Could not set org.gnome.nonexistent key");
+        assert_eq!(
+            checklist,
+            [
+                ChecklistItem::new(
+                    "org.gnome.desktop.interface color-scheme",
+                    CheckStatus::Done,
+                    1
+                ),
+                ChecklistItem::new("org.gnome.nonexistent key", CheckStatus::Failed, 1),
+            ]
+        );
+
+        assert!(key_matches(&keys[0], &ctx).await);
+        let light = key("org.gnome.desktop.interface", "color-scheme", "'default'");
+        assert!(!key_matches(&light, &ctx).await);
+    }
+}
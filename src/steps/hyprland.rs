@@ -0,0 +1,230 @@
+use crate::logging;
+use crate::pipeline::{CheckStatus, ChecklistItem, StepContext};
+use crate::snapshot::expand_home_in;
+use crate::steps::files::render;
+use anyhow::{Context, Result};
+use cli_log::Level;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Keywords that may appear many times and therefore never conflict
+const REPEATABLE_KEYWORDS: [&str; 10] = [
+    "bind",
+    "exec",
+    "windowrule",
+    "layerrule",
+    "workspace",
+    "monitor",
+    "env",
+    "source",
+    "submap",
+    "plugin",
+];
+
+const SOURCE_COMMENT: &str = "# Installer managed settings, remove this line to opt out";
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct HyprlandMerge {
+    // Installer content, rendered like a template
+    pub source: String,
+    #[serde(default = "default_include")]
+    pub include: String,
+    #[serde(default = "default_config")]
+    pub config: String,
+}
+
+fn default_include() -> String {
+    "~/.config/hypr/sparrow.conf".to_string()
+}
+
+fn default_config() -> String {
+    "~/.config/hypr/hyprland.conf".to_string()
+}
+
+// Writes the `monitor =` lines chosen on the display screen
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct HyprlandMonitors {
+    #[serde(default = "default_monitors")]
+    pub path: String,
+    #[serde(default = "default_config")]
+    pub config: String,
+}
+
+fn default_monitors() -> String {
+    "~/.config/hypr/monitors.conf".to_string()
+}
+
+impl HyprlandMerge {
+    // Whether the include file exists and the user's config still sources it
+    pub fn is_merged(&self, home: Option<&str>) -> bool {
+        let include = expand_home_in(&self.include, home);
+        let config = fs::read_to_string(expand_home_in(&self.config, home)).unwrap_or_default();
+        include.exists() && source_line_index(&config, &self.include, home).is_some()
+    }
+}
+
+pub async fn merge(
+    step: &str,
+    merge: &HyprlandMerge,
+    ctx: &StepContext,
+    checklist: &mut Vec<ChecklistItem>,
+) -> Result<()> {
+    let source = ctx.expand_home(&render(&merge.source, &ctx.variables)?);
+    let include = ctx.expand_home(&merge.include);
+    let config_path = ctx.expand_home(&merge.config);
+
+    let text =
+        fs::read_to_string(&source).with_context(|| format!("Cannot read {}", source.display()))?;
+    let managed = render(&text, &ctx.variables)?;
+    write_file(&include, &managed)?;
+    logging::event!(Level::Info, step, "Wrote {}", include.display());
+
+    let config = ensure_sourced(step, &config_path, &merge.include, ctx.home())?;
+    for conflict in conflicts(&config, &managed, &merge.include, ctx.home()) {
+        logging::event!(Level::Warn, step, "{}", conflict);
+        checklist.push(ChecklistItem::new(conflict, CheckStatus::Warning, 1));
+    }
+    Ok(())
+}
+
+pub async fn write_monitors(
+    step: &str,
+    monitors: &HyprlandMonitors,
+    ctx: &StepContext,
+) -> Result<()> {
+    let lines = ctx
+        .variables
+        .get("monitor_config")
+        .map(String::as_str)
+        .unwrap_or_default();
+    if lines.is_empty() {
+        logging::event!(
+            Level::Info,
+            step,
+            "No display choices, keeping Hyprland defaults"
+        );
+        return Ok(());
+    }
+
+    let path = ctx.expand_home(&monitors.path);
+    write_file(
+        &path,
+        &format!("# Written by the Sparrow installer\n{}\n", lines),
+    )?;
+    logging::event!(Level::Info, step, "Wrote {}", path.display());
+    ensure_sourced(
+        step,
+        &ctx.expand_home(&monitors.config),
+        &monitors.path,
+        ctx.home(),
+    )?;
+    Ok(())
+}
+
+// Appends a `source =` line for include unless the config already has one, returns the config
+fn ensure_sourced(
+    step: &str,
+    config_path: &Path,
+    include: &str,
+    home: Option<&str>,
+) -> Result<String> {
+    let mut config = fs::read_to_string(config_path).unwrap_or_default();
+    if source_line_index(&config, include, home).is_none() {
+        if !config.is_empty() && !config.ends_with('\n') {
+            config.push('\n');
+        }
+        config.push_str(&format!("\n{}\nsource = {}\n", SOURCE_COMMENT, include));
+        write_file(config_path, &config)?;
+        logging::event!(
+            Level::Info,
+            step,
+            "Added source line for {} to {}",
+            include,
+            config_path.display()
+        );
+    }
+    Ok(config)
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents).with_context(|| format!("Cannot write {}", path.display()))
+}
+
+fn source_line_index(config: &str, include: &str, home: Option<&str>) -> Option<usize> {
+    let wanted = expand_home_in(include, home);
+    config.lines().position(|line| {
+        let line = strip_comment(line);
+        line.split_once('=').is_some_and(|(key, value)| {
+            key.trim() == "source" && expand_home_in(value.trim(), home) == wanted
+        })
+    })
+}
+
+// Settings present in both files with different values. Hyprland applies lines in order,
+// so user lines after the source line win and lines before it are overridden.
+fn conflicts(config: &str, managed: &str, include: &str, home: Option<&str>) -> Vec<String> {
+    let split = source_line_index(config, include, home).unwrap_or(usize::MAX);
+    let lines: Vec<&str> = config.lines().collect();
+    let before = assignments(&lines[..split.min(lines.len())].join("\n"));
+    let after = assignments(&lines[split.saturating_add(1).min(lines.len())..].join("\n"));
+
+    let mut found = Vec::new();
+    for (key, value) in assignments(managed) {
+        if after.get(&key).is_some_and(|user| *user != value) {
+            found.push(format!("{} is overridden by your hyprland.conf", key));
+        } else if before.get(&key).is_some_and(|user| *user != value) {
+            found.push(format!("{} replaces your value {}", key, before[&key]));
+        }
+    }
+    found
+}
+
+// Flattens `category { key = value }` blocks into "category:key" entries
+fn assignments(text: &str) -> BTreeMap<String, String> {
+    let mut sections: Vec<String> = Vec::new();
+    let mut values = BTreeMap::new();
+
+    for line in text.lines() {
+        let line = strip_comment(line).trim();
+        if let Some(name) = line.strip_suffix('{') {
+            sections.push(name.trim().to_string());
+        } else if line == "}" {
+            sections.pop();
+        } else if let Some((key, value)) = line.split_once('=') {
+            let key = key.trim();
+            if REPEATABLE_KEYWORDS
+                .iter()
+                .any(|keyword| key.starts_with(keyword))
+            {
+                continue;
+            }
+            let mut path = sections.clone();
+            path.push(key.to_string());
+            values.insert(path.join(":"), value.trim().to_string());
+        }
+    }
+    values
+}
+
+// `##` is an escaped literal `#` in Hyprland configs
+fn strip_comment(line: &str) -> &str {
+    let bytes = line.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'#' {
+            if bytes.get(index + 1) == Some(&b'#') {
+                index += 2;
+                continue;
+            }
+            return &line[..index];
+        }
+        index += 1;
+    }
+    line
+}
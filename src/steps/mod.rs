@@ -0,0 +1,103 @@
+use crate::helper::{self, SystemCommand};
+use crate::pipeline::TargetUser;
+use crate::{escalation, sysroot};
+use tokio::process::Command as AsyncCommand;
+
+pub mod default_apps;
+pub mod download;
+pub mod extract;
+pub mod files;
+pub mod fonts;
+pub mod git;
+pub mod gsettings;
+pub mod hyprland;
+pub mod script;
+pub mod selinux;
+pub mod systemd;
+
+// System changes need root: the privileged helper runs them once the password started it,
+// otherwise rely on credentials sudo or the other backend still has when not already root
+pub fn privileged_command(program: &str) -> SystemCommand {
+    if helper::running() {
+        SystemCommand::Helper {
+            program: program.to_string(),
+            args: Vec::new(),
+        }
+    } else if is_root() {
+        SystemCommand::Privileged(AsyncCommand::new(program))
+    } else {
+        SystemCommand::Privileged(escalation::non_interactive(program))
+    }
+}
+
+// Programs that change the system, run inside the target root with --sysroot
+pub fn target_command(program: &str) -> SystemCommand {
+    match sysroot::get() {
+        Some(root) => SystemCommand::Local(sysroot::command(root, program)),
+        None => privileged_command(program),
+    }
+}
+
+// Per-user settings must be written by the user who will log in, not by root
+pub fn user_command(program: &str, username: &str) -> AsyncCommand {
+    // Nobody logs into an image, its skeleton home is owned by root
+    if let Some(root) = sysroot::get() {
+        return sysroot::command(root, program);
+    }
+    if !is_root() || username.is_empty() || username == "root" {
+        return AsyncCommand::new(program);
+    }
+    let mut cmd = AsyncCommand::new("runuser");
+    cmd.args(["-u", username, "--"]);
+    if let Some(uid) = user_id(username) {
+        cmd.arg("env")
+            .arg(format!("XDG_RUNTIME_DIR=/run/user/{}", uid))
+            .arg(format!(
+                "DBUS_SESSION_BUS_ADDRESS=unix:path=/run/user/{}/bus",
+                uid
+            ));
+    }
+    cmd.arg(program);
+    cmd
+}
+
+fn passwd_entries() -> Vec<Vec<String>> {
+    let passwd = std::fs::read_to_string("/etc/passwd").unwrap_or_default();
+    passwd
+        .lines()
+        .map(|line| line.split(':').map(str::to_string).collect())
+        .collect()
+}
+
+fn user_id(username: &str) -> Option<u32> {
+    passwd_entries()
+        .into_iter()
+        .find(|fields| fields.first().map(String::as_str) == Some(username))
+        .and_then(|fields| fields.get(2)?.parse().ok())
+}
+
+// Accounts people log in with, leaving out system users and those without a login shell
+pub fn human_users() -> Vec<TargetUser> {
+    passwd_entries()
+        .into_iter()
+        .filter_map(|fields| {
+            let [name, _, uid, gid, _, home, shell] = fields.as_slice() else {
+                return None;
+            };
+            let uid: u32 = uid.parse().ok()?;
+            let gid: u32 = gid.parse().ok()?;
+            let login = !shell.ends_with("nologin") && !shell.ends_with("false");
+            ((1000..60000).contains(&uid) && login).then(|| TargetUser {
+                name: name.clone(),
+                home: home.clone(),
+                uid,
+                gid,
+            })
+        })
+        .collect()
+}
+
+pub fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
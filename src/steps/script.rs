@@ -0,0 +1,75 @@
+use crate::pipeline::StepContext;
+use crate::{helper, logging};
+use anyhow::Result;
+use cli_log::Level;
+use std::process::Stdio;
+use tokio::process::Command as AsyncCommand;
+
+pub async fn run_script(step: &str, path: &str, ctx: &StepContext) -> Result<()> {
+    // The script lives on the build host, so it is fed to the shell inside the target root
+    let mut cmd = match crate::sysroot::get() {
+        Some(root) => {
+            let mut cmd = crate::sysroot::command(root, "bash");
+            cmd.arg("-s").stdin(Stdio::from(std::fs::File::open(path)?));
+            cmd
+        }
+        None => {
+            let mut cmd = AsyncCommand::new("bash");
+            cmd.arg(path).stdin(Stdio::null());
+            cmd
+        }
+    };
+    let child = cmd
+        .envs(ctx.environment())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let output =
+        helper::wait_streaming(child, |line| ctx.report_progress(step, None, line)).await?;
+    logging::record_output(module_path!(), step, &output.stdout);
+
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        logging::event!(Level::Error, step, "{}", error_msg);
+        return Err(anyhow::anyhow!("Setup script failed: {}", error_msg));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn scripts_see_the_wizard_choices_in_their_environment() {
+        let _system = MockSystem::new();
+        let home = std::env::var("HOME").expect("mock home");
+        let script = format!("{}/wallpapers.sh", home);
+        std::fs::write(
+            &script,
+            "echo \"$SPARROW_WALLPAPER_PACKS\" > \"$HOME/packs\"\necho 'no network' >&2\nexit \"$SPARROW_EXIT\"\n",
+        )
+        .expect("script");
+        let mut ctx = StepContext {
+            variables: BTreeMap::from([
+                ("wallpaper_packs".to_string(), "nature,space".to_string()),
+                ("exit".to_string(), "0".to_string()),
+            ]),
+            ..Default::default()
+        };
+
+        run_script("wallpapers", &script, &ctx)
+            .await
+            .expect("script ran");
+        let packs = std::fs::read_to_string(format!("{}/packs", home)).expect("packs");
+        assert_eq!(packs, "nature,space\n");
+
+        ctx.variables.insert("exit".to_string(), "3".to_string());
+        let error = run_script("wallpapers", &script, &ctx)
+            .await
+            .expect_err("script failed");
+        assert_eq!(error.to_string(), "Setup script failed: no network\n");
+    }
+}
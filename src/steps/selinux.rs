@@ -0,0 +1,190 @@
+use super::privileged_command;
+use crate::logging;
+use crate::pipeline::{CheckStatus, ChecklistItem, Effect, StepContext};
+use anyhow::Result;
+use cli_log::Level;
+use serde::Deserialize;
+use std::path::Path;
+
+const SELINUX_FS: &str = "/sys/fs/selinux";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RelabelMode {
+    Off,
+    // Fix labels right away with restorecon -R
+    Restorecon,
+    // Touch /.autorelabel so the whole system is relabeled on next boot
+    Autorelabel,
+}
+
+// None when SELinux is disabled, otherwise whether it is enforcing
+pub fn enforcing() -> Option<bool> {
+    let enforce = std::fs::read_to_string(Path::new(SELINUX_FS).join("enforce")).ok()?;
+    Some(enforce.trim() == "1")
+}
+
+// Restores labels on the paths a step wrote, reported as a checklist line but never fatal
+pub async fn relabel(
+    step: &str,
+    paths: &[String],
+    ctx: &StepContext,
+    checklist: &mut Vec<ChecklistItem>,
+) {
+    relabel_when(step, paths, ctx, checklist, enforcing()).await
+}
+
+async fn relabel_when(
+    step: &str,
+    paths: &[String],
+    ctx: &StepContext,
+    checklist: &mut Vec<ChecklistItem>,
+    enforcing: Option<bool>,
+) {
+    let mode = ctx.selinux.relabel;
+    if crate::sysroot::get().is_some() {
+        logging::event!(
+            Level::Debug,
+            step,
+            "Labels of the target root are left to the image build"
+        );
+        return;
+    }
+    let Some(enforcing) = enforcing else {
+        logging::event!(Level::Debug, step, "SELinux is disabled, skipping relabel");
+        return;
+    };
+    let (label, result) = match mode {
+        RelabelMode::Off => {
+            logging::event!(
+                Level::Info,
+                step,
+                "SELinux relabel is turned off in settings"
+            );
+            return;
+        }
+        RelabelMode::Restorecon => (
+            format!("SELinux relabel {}", paths.join(", ")),
+            restorecon(paths, ctx).await,
+        ),
+        RelabelMode::Autorelabel => (
+            "SELinux relabel scheduled for next boot".to_string(),
+            schedule_autorelabel().await,
+        ),
+    };
+
+    let status = match &result {
+        Ok(()) => {
+            logging::event!(Level::Info, step, "{} (enforcing: {})", label, enforcing);
+            CheckStatus::Done
+        }
+        Err(e) => {
+            logging::event!(Level::Warn, step, "{} failed: {}", label, e);
+            CheckStatus::Warning
+        }
+    };
+    checklist.push(ChecklistItem::new(label, status, 1));
+}
+
+// What relabel would run, nothing where it would be skipped
+pub fn planned(paths: &[String], ctx: &StepContext) -> Option<Effect> {
+    if crate::sysroot::get().is_some() || enforcing().is_none() {
+        return None;
+    }
+    match ctx.selinux.relabel {
+        RelabelMode::Off => None,
+        RelabelMode::Restorecon => {
+            let paths: Vec<String> = paths
+                .iter()
+                .map(|path| ctx.expand_home(path).display().to_string())
+                .collect();
+            Some(Effect::Execute(format!(
+                "restorecon -R {}",
+                paths.join(" ")
+            )))
+        }
+        RelabelMode::Autorelabel => Some(Effect::Create("/.autorelabel".into())),
+    }
+}
+
+async fn restorecon(paths: &[String], ctx: &StepContext) -> Result<()> {
+    let output = privileged_command("restorecon")
+        .arg("-R")
+        .args(paths.iter().map(|path| ctx.expand_home(path)))
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+async fn schedule_autorelabel() -> Result<()> {
+    let output = privileged_command("touch")
+        .arg("/.autorelabel")
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn labels_are_restored_only_where_selinux_is_on() {
+        let system = MockSystem::new();
+        system.program(
+            "restorecon",
+            r#"echo "$*" >> "$HOME/restorecon-calls"
+case "$*" in
+    *missing*) echo "lstat(missing) failed" >&2; exit 1 ;;
+esac
+"#,
+        );
+        let home = std::env::var("HOME").expect("mock home");
+        let mut ctx = StepContext {
+            variables: BTreeMap::from([("home".to_string(), home.clone())]),
+            ..Default::default()
+        };
+        ctx.selinux.relabel = RelabelMode::Restorecon;
+        let paths = ["~/.config/hypr".to_string(), "/etc/greetd".to_string()];
+        let mut checklist = Vec::new();
+
+        relabel_when("hypr", &paths, &ctx, &mut checklist, None).await;
+        assert!(checklist.is_empty(), "SELinux disabled");
+        relabel_when("hypr", &paths, &ctx, &mut checklist, Some(true)).await;
+        assert_eq!(
+            checklist,
+            [ChecklistItem::new(
+                "SELinux relabel ~/.config/hypr, /etc/greetd",
+                CheckStatus::Done,
+                1
+            )]
+        );
+        let calls = std::fs::read_to_string(format!("{}/restorecon-calls", home)).expect("calls");
+        assert_eq!(calls, format!("-R {}/.config/hypr /etc/greetd\n", home));
+
+        // A failed relabel is worth a look but does not fail the step
+        checklist.clear();
+        let missing = ["/missing".to_string()];
+        relabel_when("hypr", &missing, &ctx, &mut checklist, Some(false)).await;
+        assert_eq!(checklist[0].status, CheckStatus::Warning);
+
+        checklist.clear();
+        ctx.selinux.relabel = RelabelMode::Off;
+        relabel_when("hypr", &paths, &ctx, &mut checklist, Some(true)).await;
+        assert!(checklist.is_empty(), "turned off in settings");
+    }
+}
@@ -0,0 +1,167 @@
+use super::privileged_command;
+use crate::helper::SystemCommand;
+use crate::logging;
+use crate::pipeline::{CheckStatus, ChecklistItem};
+use anyhow::Result;
+use cli_log::Level;
+use std::time::Duration;
+use tokio::process::Command as AsyncCommand;
+
+// How long a freshly started unit gets to report itself active
+const UNIT_ACTIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn systemctl(user: bool) -> SystemCommand {
+    // Offline against the image, user units are enabled for every account
+    if let Some(root) = crate::sysroot::get() {
+        let mut cmd = AsyncCommand::new("systemctl");
+        cmd.arg(format!("--root={}", root.display()));
+        if user {
+            cmd.arg("--global");
+        }
+        return SystemCommand::Local(cmd);
+    }
+    if user {
+        let mut cmd = AsyncCommand::new("systemctl");
+        cmd.arg("--user");
+        SystemCommand::Local(cmd)
+    } else {
+        privileged_command("systemctl")
+    }
+}
+
+pub async fn enable_units(
+    step: &str,
+    units: &[String],
+    user: bool,
+    checklist: &mut Vec<ChecklistItem>,
+) -> Result<()> {
+    let mut failed = Vec::new();
+
+    // Nothing can be started in a target root, enabling it is all there is
+    let offline = crate::sysroot::get().is_some();
+    for unit in units {
+        let mut cmd = systemctl(user);
+        cmd.arg("enable");
+        if !offline {
+            cmd.arg("--now");
+        }
+        let output = cmd.arg(unit).output().await?;
+        let active = output.status.success() && (offline || wait_until_active(unit, user).await);
+
+        if active {
+            logging::event!(Level::Info, step, "{} enabled and active", unit);
+        } else {
+            let error_msg = String::from_utf8_lossy(&output.stderr);
+            logging::event!(
+                Level::Error,
+                step,
+                "{} failed to start: {}",
+                unit,
+                error_msg
+            );
+            failed.push(unit.clone());
+        }
+        checklist.push(ChecklistItem::new(
+            unit.clone(),
+            if active {
+                CheckStatus::Done
+            } else {
+                CheckStatus::Failed
+            },
+            1,
+        ));
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Units did not start: {}",
+            failed.join(", ")
+        ))
+    }
+}
+
+async fn wait_until_active(unit: &str, user: bool) -> bool {
+    let deadline = tokio::time::Instant::now() + UNIT_ACTIVE_TIMEOUT;
+    loop {
+        let mut cmd = AsyncCommand::new("systemctl");
+        if user {
+            cmd.arg("--user");
+        }
+        let active = cmd
+            .args(["is-active", "--quiet", unit])
+            .output()
+            .await
+            .map(|output| output.status.success())
+            .unwrap_or(false);
+
+        if active {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+pub async fn unit_enabled(unit: &str, user: bool) -> bool {
+    let mut cmd = if crate::sysroot::get().is_some() {
+        systemctl(user)
+    } else {
+        let mut cmd = AsyncCommand::new("systemctl");
+        if user {
+            cmd.arg("--user");
+        }
+        SystemCommand::Local(cmd)
+    };
+    cmd.args(["is-enabled", "--quiet", unit])
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+
+    #[tokio::test]
+    async fn each_unit_is_enabled_and_reported_on_its_own() {
+        let system = MockSystem::new();
+        system.program(
+            "systemctl",
+            r#"echo "$*" >> "$HOME/systemctl-calls"
+case "$*" in
+    *broken.service*) echo "Unit broken.service not found." >&2; exit 1 ;;
+esac
+"#,
+        );
+        let units = ["sddm.service".to_string(), "broken.service".to_string()];
+        let mut checklist = Vec::new();
+
+        let error = enable_units("services", &units, false, &mut checklist)
+            .await
+            .expect_err("broken.service fails");
+        assert_eq!(error.to_string(), "Units did not start: broken.service");
+        assert_eq!(
+            checklist,
+            [
+                ChecklistItem::new("sddm.service", CheckStatus::Done, 1),
+                ChecklistItem::new("broken.service", CheckStatus::Failed, 1),
+            ]
+        );
+        let home = std::env::var("HOME").expect("mock home");
+        let calls = std::fs::read_to_string(format!("{}/systemctl-calls", home)).expect("calls");
+        assert_eq!(
+            calls.lines().collect::<Vec<_>>(),
+            [
+                "enable --now sddm.service",
+                "is-active --quiet sddm.service",
+                "enable --now broken.service",
+            ]
+        );
+    }
+}
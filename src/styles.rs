@@ -0,0 +1,106 @@
+use crate::colors::{self, ColorSupport};
+use crate::{ThemeConfig, parse_alignment, parse_color, parse_modifiers};
+use ratatui::layout::Alignment;
+use ratatui::style::{Color, Modifier, Style};
+
+// The theme resolved into ratatui styles once, instead of parsing color names on every frame
+pub struct Styles {
+    pub title: Style,
+    pub main: Style,
+    // Background of bordered blocks
+    pub panel: Style,
+    pub content: Style,
+    pub selected: Style,
+    pub disabled: Style,
+    pub confirmation: Style,
+    pub description: Style,
+    // Background only, for lines that pick their own foreground
+    pub description_bg: Style,
+    pub status_text: Style,
+    pub success: Style,
+    pub error: Style,
+    pub fail: Style,
+    pub border: Style,
+    pub border_active: Style,
+    pub progress: Style,
+    // Mode badges in the title row
+    pub dry_run: Style,
+    pub badge: Style,
+    // Markdown in description documents
+    pub heading: Style,
+    pub code: Style,
+    // "SIMULATION" across the processing pane of a dry run
+    pub watermark: Style,
+    pub title_alignment: Alignment,
+    pub description_alignment: Alignment,
+    pub confirmation_alignment: Alignment,
+    // Leading spaces of list rows
+    pub padding: String,
+}
+
+impl Styles {
+    // Colors beyond what the terminal supports are mapped to the nearest it has. A dry run
+    // draws its borders and bars in dry_run_fg so it never passes for the real thing.
+    pub fn new(theme: &ThemeConfig, support: ColorSupport, dry_run: bool) -> Self {
+        let colors = &theme.colors;
+        // The theme checked its colors as it loaded
+        let color = |name: &str| colors::degrade(parse_color(name).unwrap_or_default(), support);
+        let pair = |bg: &str, fg: &str| Style::default().bg(color(bg)).fg(color(fg));
+        let description_bg = Style::default().bg(color(&colors.description_bg));
+        let tint = |name: &str| {
+            if dry_run {
+                color(&colors.dry_run_fg)
+            } else {
+                color(name)
+            }
+        };
+        // Without colors the selection is told apart by reverse video
+        let selected = if support == ColorSupport::None {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            pair(&colors.selected_bg, &colors.selected_fg)
+        };
+
+        let modifiers = &theme.modifiers;
+
+        Self {
+            title: pair(&colors.title_bg, &colors.title_fg)
+                .add_modifier(Modifier::BOLD | parse_modifiers(&modifiers.title)),
+            main: Style::default().bg(color(&colors.main_bg)),
+            panel: Style::default().bg(color(&colors.content_bg)),
+            content: pair(&colors.content_bg, &colors.content_fg)
+                .add_modifier(parse_modifiers(&modifiers.content)),
+            selected: selected.add_modifier(parse_modifiers(&modifiers.selected)),
+            disabled: pair(&colors.disabled_bg, &colors.disabled_fg)
+                .add_modifier(parse_modifiers(&modifiers.disabled)),
+            confirmation: pair(&colors.confirmation_bg, &colors.confirmation_fg)
+                .add_modifier(parse_modifiers(&modifiers.confirmation)),
+            description: pair(&colors.description_bg, &colors.description_fg)
+                .add_modifier(parse_modifiers(&modifiers.description)),
+            description_bg,
+            status_text: description_bg
+                .fg(colors::degrade(Color::White, support))
+                .add_modifier(parse_modifiers(&modifiers.status)),
+            success: pair(&colors.success_bg, &colors.success_fg)
+                .add_modifier(parse_modifiers(&modifiers.success)),
+            error: pair(&colors.error_bg, &colors.error_fg)
+                .add_modifier(parse_modifiers(&modifiers.error)),
+            fail: pair(&colors.fail_bg, &colors.fail_fg)
+                .add_modifier(parse_modifiers(&modifiers.fail)),
+            border: Style::default().fg(tint(&colors.primary)),
+            border_active: Style::default().fg(tint(&theme.progress.border_active_color)),
+            progress: description_bg
+                .fg(tint(&theme.progress.bar_color))
+                .add_modifier(parse_modifiers(&modifiers.progress)),
+            dry_run: pair(&colors.title_bg, &colors.dry_run_fg).add_modifier(Modifier::BOLD),
+            badge: pair(&colors.title_bg, &colors.accent).add_modifier(Modifier::BOLD),
+            heading: pair(&colors.content_bg, &colors.accent).add_modifier(Modifier::BOLD),
+            code: pair(&colors.content_bg, &colors.primary),
+            watermark: pair(&colors.content_bg, &colors.dry_run_fg).add_modifier(Modifier::BOLD),
+            title_alignment: parse_alignment(&theme.layout.title_alignment),
+            description_alignment: parse_alignment(&theme.layout.description_alignment),
+            confirmation_alignment: parse_alignment(&theme.layout.confirmation_alignment),
+            padding: " ".repeat(theme.layout.content_padding as usize),
+        }
+    }
+}
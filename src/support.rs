@@ -0,0 +1,140 @@
+use crate::logging::{self, Redactor};
+use crate::{SettingsConfig, report, tools};
+use anyhow::{Context, Result};
+use cli_log::Level;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tokio::process::Command as AsyncCommand;
+use tokio::time::{Duration, timeout};
+
+// A tool that hangs (e.g. lspci on broken hardware) must not hold up the bundle
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(20);
+
+// Everything a bug report needs, as one redacted .tar.gz in `dir`
+pub async fn collect(settings: &SettingsConfig, dir: &Path) -> Result<PathBuf> {
+    let redactor = Redactor::new(&settings.logging)?;
+    let journal_lines = settings.support.journal_lines.to_string();
+    let name = format!(
+        "sparrow-support-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let path = dir.join(format!("{}.tar.gz", name));
+
+    let mut entries: Vec<(String, String)> = Vec::new();
+    let files = [
+        ("installer.log", logging::log_file(&settings.logging)),
+        ("last-report.json", report::report_path()),
+        ("crash.txt", report::crash_path()),
+    ];
+    let os_release = tools::os_release_path().map(|path| ("os-release", path.to_path_buf()));
+    for (entry, file) in files.into_iter().chain(os_release) {
+        // Missing files are normal, e.g. no crash has happened yet
+        if let Ok(contents) = fs::read(&file) {
+            entries.push((entry.to_string(), String::from_utf8_lossy(&contents).into()));
+        }
+    }
+
+    let commands: [(&str, &str, &[&str]); 8] = [
+        ("bootc-status.txt", "bootc", &["status"]),
+        (
+            "journal.txt",
+            "journalctl",
+            &["-b", "-p", "warning", "--no-pager", "-n", &journal_lines],
+        ),
+        (
+            "journal-previous-boot.txt",
+            "journalctl",
+            &[
+                "-b",
+                "-1",
+                "-p",
+                "warning",
+                "--no-pager",
+                "-n",
+                &journal_lines,
+            ],
+        ),
+        ("hardware/uname.txt", "uname", &["-a"]),
+        ("hardware/lscpu.txt", "lscpu", &[]),
+        ("hardware/memory.txt", "free", &["-h"]),
+        (
+            "hardware/lsblk.txt",
+            "lsblk",
+            &["-o", "NAME,SIZE,TYPE,FSTYPE,MOUNTPOINT,MODEL"],
+        ),
+        ("hardware/lspci.txt", "lspci", &["-nn"]),
+    ];
+    for (entry, program, args) in commands {
+        entries.push((entry.to_string(), run(program, args).await));
+    }
+
+    let file = File::create(&path).with_context(|| format!("Cannot create {}", path.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mtime = chrono::Utc::now().timestamp().max(0) as u64;
+    for (entry, contents) in entries {
+        // The running installer also knows the sudo password, the global redactor holds it
+        let contents = logging::redact(&redactor.redact(&contents));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        archive
+            .append_data(
+                &mut header,
+                format!("{}/{}", name, entry),
+                contents.as_bytes(),
+            )
+            .with_context(|| format!("Cannot add {} to the bundle", entry))?;
+    }
+    archive
+        .into_inner()
+        .and_then(GzEncoder::finish)
+        .with_context(|| format!("Cannot write {}", path.display()))?;
+
+    logging::event!(
+        Level::Info,
+        "support",
+        "Saved the support bundle to {}",
+        path.display()
+    );
+    Ok(path)
+}
+
+// Output of a diagnostic command, or why there is none, so the bundle shows what was tried
+async fn run(program: &str, args: &[&str]) -> String {
+    let output = AsyncCommand::new(program)
+        .args(args)
+        .kill_on_drop(true)
+        .output();
+    match timeout(COMMAND_TIMEOUT, output).await {
+        Ok(Ok(output)) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !stderr.trim().is_empty() {
+                text.push_str(&format!("\n--- stderr ---\n{}", stderr));
+            }
+            if !output.status.success() {
+                text.push_str(&format!("\n--- {} ---\n", output.status));
+            }
+            text
+        }
+        Ok(Err(e)) => format!("Cannot run {}: {}\n", program, e),
+        Err(_) => format!(
+            "{} did not finish within {} seconds\n",
+            program,
+            COMMAND_TIMEOUT.as_secs()
+        ),
+    }
+}
+
+// `sparrow-installer support-bundle`, for when the interface itself does not start
+pub async fn command(output: Option<PathBuf>) -> Result<()> {
+    let settings = SettingsConfig::load()?;
+    let dir = output.unwrap_or_else(|| PathBuf::from("."));
+    let path = collect(&settings, &dir).await?;
+    println!("{}", path.display());
+    Ok(())
+}
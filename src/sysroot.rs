@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::process::Command as AsyncCommand;
+
+// Mounted root of the image being prepared with --sysroot
+static SYSROOT: OnceLock<PathBuf> = OnceLock::new();
+
+// Where per-user steps write inside the image, new accounts start from a copy of it
+pub const SKEL_HOME: &str = "/etc/skel";
+
+pub fn set(path: &Path) -> Result<()> {
+    SYSROOT
+        .set(system_root(path)?)
+        .map_err(|_| anyhow::anyhow!("The target root is already set"))
+}
+
+fn system_root(path: &Path) -> Result<PathBuf> {
+    let root = path
+        .canonicalize()
+        .with_context(|| format!("Cannot use {} as the target root", path.display()))?;
+    if !root.join("etc").is_dir() {
+        return Err(anyhow::anyhow!(
+            "{} does not look like a system root, it has no /etc",
+            root.display()
+        ));
+    }
+    Ok(root)
+}
+
+pub fn get() -> Option<&'static Path> {
+    SYSROOT.get().map(PathBuf::as_path)
+}
+
+// The place an absolute path of the running system has in the target
+pub fn target(path: PathBuf) -> PathBuf {
+    target_in(get(), path)
+}
+
+fn target_in(root: Option<&Path>, path: PathBuf) -> PathBuf {
+    match root {
+        Some(root) if path.is_absolute() && !path.starts_with(root) => {
+            root.join(path.strip_prefix("/").unwrap_or(&path))
+        }
+        _ => path,
+    }
+}
+
+// Runs a program of the image with the image as its root, through bwrap when installed
+pub fn command(root: &Path, program: &str) -> AsyncCommand {
+    let mut cmd = if crate::tools::find("bwrap").is_some() {
+        let mut cmd = AsyncCommand::new("bwrap");
+        cmd.arg("--bind")
+            .arg(root)
+            .arg("/")
+            .args(["--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"])
+            .arg("--die-with-parent");
+        cmd
+    } else if crate::steps::is_root() {
+        let mut cmd = AsyncCommand::new("chroot");
+        cmd.arg(root);
+        cmd
+    } else {
+        let mut cmd = crate::escalation::non_interactive("chroot");
+        cmd.arg(root);
+        cmd
+    };
+    cmd.arg(program).env("HOME", SKEL_HOME);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MockSystem;
+
+    fn argv(cmd: &AsyncCommand) -> Vec<String> {
+        crate::audit::argv(cmd.as_std())
+    }
+
+    #[test]
+    fn paths_and_programs_are_pointed_into_the_image() {
+        let dir = crate::helper::private_dir("sparrow-sysroot-test").expect("temporary folder");
+        let error = system_root(&dir).expect_err("no /etc");
+        assert!(
+            error
+                .to_string()
+                .ends_with("does not look like a system root, it has no /etc")
+        );
+        std::fs::create_dir(dir.join("etc")).expect("etc");
+        let root = system_root(&dir).expect("system root");
+
+        let target = |path: &str| target_in(Some(&root), PathBuf::from(path));
+        assert_eq!(target("/etc/skel/.bashrc"), root.join("etc/skel/.bashrc"));
+        // Already inside the image, or relative to the step
+        assert_eq!(
+            target(&root.join("usr").display().to_string()),
+            root.join("usr")
+        );
+        assert_eq!(target("wallpapers"), PathBuf::from("wallpapers"));
+        assert_eq!(
+            target_in(None, PathBuf::from("/etc")),
+            PathBuf::from("/etc")
+        );
+
+        let mut system = MockSystem::new();
+        system.program("bwrap", "");
+        let bwrap = command(&root, "fc-cache");
+        let args = argv(&bwrap);
+        assert_eq!(args[..3], ["bwrap", "--bind", &root.display().to_string()]);
+        assert_eq!(args.last().map(String::as_str), Some("fc-cache"));
+        system.remove("bwrap");
+        if crate::steps::is_root() {
+            let chroot = command(&root, "fc-cache");
+            assert_eq!(
+                argv(&chroot),
+                ["chroot", &root.display().to_string(), "fc-cache"]
+            );
+            let home = chroot.as_std().get_envs().find(|(name, _)| *name == "HOME");
+            assert_eq!(home.and_then(|(_, value)| value), Some(SKEL_HOME.as_ref()));
+        }
+        drop(system);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
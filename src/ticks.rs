@@ -0,0 +1,79 @@
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tick {
+    Spinner,
+    ProgressBar,
+    Countdown,
+    SimulationDone,
+}
+
+// Timers of the running operation, counted exactly however often the screen is drawn.
+// Dropping it stops them.
+pub struct Ticker {
+    tx: mpsc::UnboundedSender<Tick>,
+    rx: mpsc::UnboundedReceiver<Tick>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Ticker {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            tx,
+            rx,
+            tasks: Vec::new(),
+        }
+    }
+
+    // Sends `tick` once per period, the first one a full period from now
+    pub fn every(&mut self, period: Duration, tick: Tick) {
+        let tx = self.tx.clone();
+        // A zero period would make the interval panic
+        let period = period.max(Duration::from_millis(1));
+        // Timed from now rather than from whenever the task first runs
+        let mut interval = time::interval_at(Instant::now() + period, period);
+        self.tasks.push(tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                if tx.send(tick).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    pub fn after(&mut self, delay: Duration, tick: Tick) {
+        let tx = self.tx.clone();
+        let deadline = Instant::now() + delay;
+        self.tasks.push(tokio::spawn(async move {
+            time::sleep_until(deadline).await;
+            let _ = tx.send(tick);
+        }));
+    }
+
+    // Runs `task` until the ticker is dropped, sending `tick` once it is done
+    #[cfg_attr(not(feature = "simulator"), allow(dead_code))]
+    pub fn when_done(&mut self, task: impl Future<Output = ()> + Send + 'static, tick: Tick) {
+        let tx = self.tx.clone();
+        self.tasks.push(tokio::spawn(async move {
+            task.await;
+            let _ = tx.send(tick);
+        }));
+    }
+
+    // The next tick that is due, without waiting
+    pub fn try_next(&mut self) -> Option<Tick> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
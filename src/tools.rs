@@ -0,0 +1,90 @@
+use crate::logging;
+use anyhow::Result;
+use cli_log::Level;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+// Programs the installer itself calls on the running system. Image variants ship different
+// sets of them, so each is looked up when it is needed instead of assumed.
+pub const HOST_TOOLS: [&str; 10] = [
+    "bootc",
+    "systemctl",
+    "loginctl",
+    "busctl",
+    "shutdown",
+    "systemd-run",
+    "lsblk",
+    "journalctl",
+    "tput",
+    "bwrap",
+];
+
+// os-release moved to /usr/lib, /etc usually links to it
+const OS_RELEASE: [&str; 2] = ["/etc/os-release", "/usr/lib/os-release"];
+
+// The executable `program` resolves to through PATH on this system
+pub fn find(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return is_executable(&path).then_some(path);
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path))
+}
+
+pub fn require(program: &str) -> Result<PathBuf> {
+    find(program).ok_or_else(|| anyhow::anyhow!("{} is not installed on this system", program))
+}
+
+// The first of `programs` that is installed
+pub fn first_available<'a>(programs: &[&'a str]) -> Option<&'a str> {
+    programs
+        .iter()
+        .copied()
+        .find(|program| find(program).is_some())
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+// Each of HOST_TOOLS with where it was found, for the log and the self-test
+pub fn probe() -> Vec<(&'static str, Option<PathBuf>)> {
+    HOST_TOOLS
+        .iter()
+        .map(|program| (*program, find(program)))
+        .collect()
+}
+
+pub fn log_probe() {
+    for (program, path) in probe() {
+        match path {
+            Some(path) => {
+                logging::event!(Level::Debug, "tools", "{} at {}", program, path.display())
+            }
+            None => logging::event!(Level::Debug, "tools", "{} is not installed", program),
+        }
+    }
+    match crate::escalation::current() {
+        Some(backend) => {
+            logging::event!(Level::Debug, "tools", "Root through {}", backend.program())
+        }
+        None => logging::event!(Level::Debug, "tools", "No way to get root is installed"),
+    }
+}
+
+pub fn os_release_path() -> Option<&'static Path> {
+    OS_RELEASE.iter().map(Path::new).find(|path| path.is_file())
+}
+
+// A value of os-release, quotes removed
+pub fn os_release(key: &str) -> Option<String> {
+    let release = std::fs::read_to_string(os_release_path()?).ok()?;
+    release.lines().find_map(|line| {
+        let value = line.strip_prefix(key)?.strip_prefix('=')?;
+        Some(value.trim_matches(|c| c == '"' || c == '\'').to_string())
+    })
+}
@@ -0,0 +1,104 @@
+use crate::{App, AppState, InstallerOption, StatusType, escalation};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+// The screens the tour stops on, set up the way the installer would get there
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Screen {
+    Menu,
+    Plan,
+    Confirmation,
+    Password,
+    // A dry-run update playing its scenario
+    Operation,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct Stop {
+    pub screen: Screen,
+    pub title: String,
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct TourText {
+    pub stops: Vec<Stop>,
+    // Status note once the tour is over and the dry run is the user's to explore
+    pub finished: String,
+}
+
+// Which stop the tour is at, the popup explains it over the screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tour {
+    pub stop: usize,
+}
+
+pub async fn start(app: &mut App) {
+    app.tour = Some(Tour { stop: 0 });
+    show(app).await;
+}
+
+pub fn current(app: &App) -> Option<&Stop> {
+    app.tour
+        .as_ref()
+        .and_then(|tour| app.text.tour.stops.get(tour.stop))
+}
+
+// Moves `by` stops, past the last one the tour ends
+pub async fn advance(app: &mut App, by: isize) {
+    let Some(tour) = app.tour.as_mut() else {
+        return;
+    };
+    let stop = tour.stop.saturating_add_signed(by);
+    if stop == tour.stop {
+        return;
+    }
+    if stop >= app.text.tour.stops.len() {
+        end(app);
+        return;
+    }
+    tour.stop = stop;
+    show(app).await;
+}
+
+pub fn end(app: &mut App) {
+    app.tour = None;
+    reset(app);
+    app.status_message = Some((app.text.tour.finished.clone(), StatusType::Success));
+}
+
+async fn show(app: &mut App) {
+    let Some(stop) = current(app).cloned() else {
+        end(app);
+        return;
+    };
+    reset(app);
+    match stop.screen {
+        Screen::Menu => {
+            if let Some(index) = app
+                .options
+                .iter()
+                .position(|option| matches!(option, InstallerOption::Default))
+            {
+                app.selected = index;
+            }
+        }
+        Screen::Plan => app.show_plan(),
+        Screen::Confirmation => app.show_operation_confirmation(&InstallerOption::Default),
+        Screen::Password => app.show_password_input(InstallerOption::UpdateSystem),
+        Screen::Operation => app.run_operation(&InstallerOption::UpdateSystem).await,
+    }
+}
+
+// Back to the main menu from wherever the previous stop left the installer
+fn reset(app: &mut App) {
+    app.progress_type = None;
+    app.ticker = None;
+    app.action_output.clear();
+    app.checklist.clear();
+    escalation::wipe(&mut app.password_input);
+    app.show_password = false;
+    app.hide_confirmation();
+    app.app_state = AppState::MainMenu;
+}
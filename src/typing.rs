@@ -0,0 +1,71 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+// Letters a combining accent folds into, by accent. Compose keys and some input methods
+// send the letter and then the accent, the system compares the precomposed form.
+const COMPOSED: [(char, &str, &str); 8] = [
+    ('\u{300}', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('\u{301}', "aeiouycnszAEIOUYCNSZ", "áéíóúýćńśźÁÉÍÓÚÝĆŃŚŹ"),
+    ('\u{302}', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('\u{303}', "anoANO", "ãñõÃÑÕ"),
+    ('\u{308}', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+    ('\u{30a}', "auAU", "åůÅŮ"),
+    ('\u{30c}', "cenrszCENRSZ", "čěňřšžČĚŇŘŠŽ"),
+    ('\u{327}', "csCS", "çşÇŞ"),
+];
+
+// The character a key types into a text field. Only presses count: Windows and the kitty
+// keyboard protocol also report releases and repeats, which would type each letter again.
+// AltGr arrives as Control+Alt and types, Control alone does not.
+pub fn typed(key: &KeyEvent) -> Option<char> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    match key.code {
+        KeyCode::Char(c) if !c.is_control() && control == alt => Some(c),
+        _ => None,
+    }
+}
+
+// What a paste types into a one-line field: its first line without control characters, a
+// newline in it would otherwise act as Enter halfway through
+pub fn pasted(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.lines()
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+}
+
+// Appends `c`, folding a combining accent into the letter before it
+pub fn push(field: &mut String, c: char) {
+    if is_combining(c)
+        && let Some(composed) = field.chars().last().and_then(|base| compose(base, c))
+    {
+        field.pop();
+        field.push(composed);
+    } else {
+        field.push(c);
+    }
+}
+
+// Takes off the last character together with any accents left on it
+pub fn pop(field: &mut String) {
+    while field.pop().is_some_and(is_combining) {}
+}
+
+// Characters as they show, for the masked field
+pub fn visible_len(field: &str) -> usize {
+    field.chars().filter(|c| !is_combining(*c)).count()
+}
+
+fn compose(base: char, accent: char) -> Option<char> {
+    let (_, bases, composed) = COMPOSED.iter().find(|(mark, _, _)| *mark == accent)?;
+    let index = bases.chars().position(|c| c == base)?;
+    composed.chars().nth(index)
+}
+
+fn is_combining(c: char) -> bool {
+    matches!(c, '\u{300}'..='\u{36f}')
+}
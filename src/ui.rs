@@ -0,0 +1,937 @@
+use crate::{
+    App, AppState, BackdropPlacement, BorderKind, ElementBorders, PanelSide, PathPurpose,
+    ProgressType, StatusType, a11y, backdrop, control, escalation, handle_key, handle_paste,
+    health, keymap, logging, markdown, monitors, pipeline, tour, typing,
+};
+use anyhow::Result;
+use cli_log::*;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, poll};
+use ratatui::{
+    Frame, Terminal,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    symbols::scrollbar,
+    text::{Line, Span},
+    widgets::{
+        Clear, List, ListItem, ListState, Paragraph, Scrollbar, ScrollbarOrientation,
+        ScrollbarState, Wrap, block,
+    },
+};
+use std::fmt::Write as _;
+use std::io;
+use std::ops::Range;
+use std::time::Duration;
+
+// Menu scrollbar for terminals limited to ASCII
+pub(crate) const ASCII_SCROLLBAR: scrollbar::Set = scrollbar::Set {
+    track: "|",
+    thumb: "#",
+    begin: "^",
+    end: "v",
+};
+
+impl App {
+    pub(crate) fn get_title_text(&self) -> String {
+        if self.attached {
+            return self.text.messages.attach_prompt.clone();
+        }
+        if self.progress_type.is_some() {
+            return self.text.messages.dry_run_testing.clone();
+        }
+
+        match &self.app_state {
+            AppState::MainMenu => self.text.messages.welcome.clone(),
+            AppState::Confirmation => self.text.messages.confirmation_prompt.clone(),
+            AppState::PasswordInput => self.text.messages.password_prompt.clone(),
+            AppState::WallpaperSelect => self.text.messages.wallpaper_prompt.clone(),
+            AppState::ComponentSelect => self.text.messages.components_prompt.clone(),
+            AppState::CustomSelect => self.text.messages.custom_prompt.clone(),
+            AppState::UserSelect => self.text.messages.users_prompt.clone(),
+            AppState::MonitorSelect => self.text.messages.monitor_prompt.clone(),
+            AppState::Locked => self.text.messages.locked_prompt.clone(),
+            AppState::RecoverySelect => self.text.messages.recovery_prompt.clone(),
+            AppState::LogView => self.text.messages.log_prompt.clone(),
+            AppState::PathInput => match self.path_purpose {
+                PathPurpose::SaveLog => self.text.messages.log_save_prompt.clone(),
+                PathPurpose::Export => self.text.messages.export_prompt.clone(),
+                PathPurpose::SupportBundle => self.text.messages.support_prompt.clone(),
+                PathPurpose::BugReport => self.text.messages.bug_report_save_prompt.clone(),
+            },
+            AppState::VolumeSelect => self.text.messages.volumes_prompt.clone(),
+            AppState::RebootSelect => self.text.messages.reboot_prompt.clone(),
+            AppState::SessionsWarning => self.text.messages.sessions_prompt.clone(),
+            AppState::Failed => self.text.messages.failure_prompt.clone(),
+            AppState::BugReport => self.text.messages.bug_report_prompt.clone(),
+            AppState::Plan => self.text.messages.plan_prompt.clone(),
+            AppState::DryRunResult => self.text.messages.dry_run_complete.clone(),
+            AppState::Processing(action) => action.clone(),
+        }
+    }
+
+    pub(crate) fn get_spinner_char(&self) -> &str {
+        match &self.progress_type {
+            Some(ProgressType::Indeterminate) => {
+                &self.text.messages.spinner_chars[self.progress_step]
+            }
+            _ => "",
+        }
+    }
+
+    // Width of the progress bar and the cells of it drawn thick
+    pub(crate) fn progress_fill(&self, width: u16) -> (usize, Range<usize>) {
+        let bar_width = width.saturating_sub(4) as usize; // Account for margins
+        let filled = match self.progress_type {
+            // Steps that know their total fill the bar instead of sweeping across it
+            Some(ProgressType::Indeterminate) if let Some(fraction) = self.step_fraction => {
+                0..((fraction.clamp(0.0, 1.0) * bar_width as f32) as usize)
+            }
+            Some(ProgressType::Indeterminate) => {
+                // A short thick segment sweeping across, with extra room for smooth cycling
+                let cycle_length = bar_width + 6;
+                let pos = self.progress_bar_position % cycle_length;
+                pos.saturating_sub(3)..(pos + 1).min(bar_width)
+            }
+            Some(ProgressType::Determinant(_)) => {
+                // Countdown shrinking from full width
+                let filled = ((self.countdown_remaining as f32
+                    / self.text.progress.countdown_seconds as f32)
+                    * bar_width as f32) as usize;
+                0..filled.min(bar_width)
+            }
+            None => 0..0,
+        };
+        (bar_width, filled)
+    }
+}
+
+// Checkbox style list used by the multiple choice setup screens
+// The choices of a list screen, as described by its accessibility tree
+pub(crate) fn choice_list<'a>(app: &App, tree: &'a a11y::Tree) -> List<'a> {
+    let theme = &app.theme;
+    let styles = &app.styles;
+    let items: Vec<ListItem> = tree
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.focusable())
+        .map(|(i, node)| {
+            let (style, prefix) = if tree.focus == Some(i) {
+                (styles.selected, theme.ui.selection_prefix.as_str())
+            } else {
+                (styles.content, "")
+            };
+            let mark = match node.checked {
+                Some(true) => format!("{} ", theme.ui.toggle_on),
+                Some(false) => format!("{} ", theme.ui.toggle_off),
+                None => String::new(),
+            };
+
+            let display_text = format!("{}{}{}{}", styles.padding, prefix, mark, node.label);
+
+            ListItem::new(display_text).style(style)
+        })
+        .collect();
+
+    List::new(items).style(styles.main)
+}
+
+pub(crate) fn ui(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let styles = &app.styles;
+    let tree = a11y::screen(app);
+
+    let sizes = theme.sizes(f.size());
+    let main_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(sizes.title_height), // Title with separator and subtitle
+            Constraint::Min(0),                     // Main content
+            Constraint::Length(sizes.description_height), // Description/Status
+            Constraint::Length(1),                  // Key hints
+        ])
+        .split(f.size());
+
+    // Title area
+    let title_area = bordered(
+        f,
+        main_layout[0],
+        &theme.borders,
+        theme.borders.title,
+        styles.title,
+    );
+
+    let separator = if sizes.show_separator {
+        format!(
+            "\n{}",
+            theme.ui.separator_char.repeat(title_area.width as usize)
+        )
+    } else {
+        String::new()
+    };
+
+    let title_content = format!(
+        "{}{}{}{}",
+        app.text.ui_text.app_title,
+        separator,
+        if sizes.show_separator { "\n" } else { "" },
+        app.get_title_text()
+    );
+
+    let title = Paragraph::new(title_content)
+        .style(styles.title)
+        .alignment(styles.title_alignment);
+    f.render_widget(title, title_area);
+
+    // Version on the left and mode badges on the right of the first title row, drawn
+    // over it without a style of their own so the centered title stays
+    let title_row = Rect {
+        height: title_area.height.min(1),
+        ..title_area
+    };
+    if theme.ui.show_version {
+        let version = Paragraph::new(Span::styled(
+            format!(" v{}", env!("CARGO_PKG_VERSION")),
+            styles.title.remove_modifier(Modifier::BOLD),
+        ));
+        f.render_widget(version, title_row);
+    }
+    let badges = Paragraph::new(Line::from(title_badges(app))).alignment(Alignment::Right);
+    f.render_widget(badges, title_row);
+
+    // Main content area
+    if app.app_state == AppState::PasswordInput {
+        // Show password input dialog with bordered input box
+        let content_area = main_layout[1];
+
+        // Split content area to have instruction text and input box
+        let password_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),    // Instructions area
+                Constraint::Length(3), // Input box area (border + content + border)
+                Constraint::Length(1), // Bottom instructions
+            ])
+            .split(content_area);
+
+        // Show what the password is for, then the instructions
+        let mut instructions = Vec::new();
+        if let Some(reason) = app.password_reason() {
+            instructions.push(Line::styled(
+                reason,
+                styles.confirmation.add_modifier(Modifier::BOLD),
+            ));
+            if let Some(summary) = app.password_summary() {
+                instructions.push(Line::from(summary));
+            }
+            instructions.push(Line::default());
+        }
+        instructions.push(Line::from(app.text.messages.password_label.as_str()));
+        let instructions = Paragraph::new(instructions)
+            .style(styles.confirmation)
+            .alignment(styles.confirmation_alignment)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(instructions, password_layout[0]);
+
+        // Create bordered input box
+        let input_block = theme
+            .borders
+            .block(theme.borders.input)
+            .border_style(styles.border)
+            .style(styles.panel);
+
+        let input_area = input_block.inner(password_layout[1]);
+        f.render_widget(input_block, password_layout[1]);
+
+        // Show password with cursor
+        let password_display = if app.show_password {
+            format!("{}█", app.password_input)
+        } else {
+            format!("{}█", "*".repeat(typing::visible_len(&app.password_input)))
+        };
+
+        let password_input = Paragraph::new(password_display)
+            .style(styles.content)
+            .alignment(Alignment::Left);
+
+        f.render_widget(password_input, input_area);
+
+        // Show bottom instructions
+        let bottom_instructions = Paragraph::new(app.text.messages.password_instructions.clone())
+            .style(styles.confirmation)
+            .alignment(Alignment::Center);
+
+        f.render_widget(bottom_instructions, password_layout[2]);
+    } else if app.show_confirmation {
+        // Show confirmation dialog
+        let confirmation_text = format!(
+            "{}\n\n{}",
+            app.confirmation_text(),
+            app.text.messages.confirmation_help
+        );
+
+        let confirmation = Paragraph::new(confirmation_text)
+            .style(styles.confirmation)
+            .alignment(styles.confirmation_alignment)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(confirmation, main_layout[1]);
+    } else if app.progress_type.is_some() {
+        // Show action content with yellow border
+        let content_area = main_layout[1];
+
+        // Create bordered area, marked on its bottom edge when nothing is real
+        let mut border_block = theme
+            .borders
+            .block(theme.borders.operation)
+            .border_style(styles.border_active)
+            .style(styles.panel);
+        if app.dry_run {
+            border_block = border_block.title(
+                block::Title::from(Span::styled(
+                    format!(" {} ", app.text.ui_text.simulation_watermark),
+                    styles.watermark,
+                ))
+                .position(block::Position::Bottom)
+                .alignment(Alignment::Center),
+            );
+        }
+
+        let inner_area = border_block.inner(content_area);
+        f.render_widget(border_block, content_area);
+
+        // Show action output
+        let mut content_lines = Vec::new();
+
+        // Add current action description without spinner (spinner is in status area)
+        let action_desc = match &app.app_state {
+            AppState::Processing(desc) => desc.clone(),
+            _ => "Processing...".to_string(),
+        };
+        content_lines.push(action_desc);
+        content_lines.push(String::new()); // Empty line
+
+        // Add pipeline step checklist
+        for item in &app.checklist {
+            let mark = match item.status {
+                pipeline::CheckStatus::Pending => &theme.ui.check_pending,
+                pipeline::CheckStatus::Running => &theme.ui.check_running,
+                pipeline::CheckStatus::Done => &theme.ui.check_done,
+                pipeline::CheckStatus::Failed => &theme.ui.check_failed,
+                pipeline::CheckStatus::Warning => &theme.ui.check_warning,
+                pipeline::CheckStatus::Skipped => &theme.ui.check_skipped,
+            };
+            content_lines.push(format!(
+                "{}{} {}",
+                "  ".repeat(item.depth as usize),
+                mark,
+                item.label
+            ));
+        }
+        if !app.checklist.is_empty() {
+            content_lines.push(String::new()); // Empty line
+        }
+
+        // Add action output
+        for line in &app.action_output {
+            content_lines.push(line.clone());
+        }
+
+        if app.attached {
+            content_lines.push(String::new());
+            content_lines.push(app.text.messages.attach_help.clone());
+        }
+
+        // Add dry-run misc text if in dry-run mode
+        if app.dry_run {
+            content_lines.push(String::new()); // Empty line
+            for line in app.text.messages.dry_run_misc_text.split('\n') {
+                content_lines.push(line.to_string());
+            }
+        }
+
+        // Follows the newest output once it no longer fits, as a terminal would
+        let width = inner_area.width.max(1) as usize;
+        let rows: usize = content_lines
+            .iter()
+            .flat_map(|line| line.split('\n'))
+            .map(|line| Line::from(line).width().div_ceil(width).max(1))
+            .sum();
+        let scroll = rows.saturating_sub(inner_area.height as usize);
+        let action_content = Paragraph::new(content_lines.join("\n"))
+            .style(styles.content)
+            .alignment(Alignment::Left)
+            .wrap(Wrap { trim: true })
+            .scroll((scroll.min(u16::MAX as usize) as u16, 0));
+
+        f.render_widget(action_content, inner_area);
+    } else if matches!(
+        app.app_state,
+        AppState::UserSelect
+            | AppState::WallpaperSelect
+            | AppState::ComponentSelect
+            | AppState::CustomSelect
+    ) {
+        // Show accounts, wallpaper packs or opt-in steps with their selection marks
+        f.render_widget(choice_list(app, &tree), main_layout[1]);
+    } else if app.app_state == AppState::MonitorSelect {
+        // Show a resolution row and a scale row per display
+        let rows: Vec<ListItem> = app
+            .monitors
+            .iter()
+            .flat_map(|monitor| {
+                let mode = monitor
+                    .selected_mode()
+                    .map(monitors::Mode::label)
+                    .unwrap_or_default();
+                [
+                    format!(
+                        "{} {}: < {} >",
+                        monitor.name, app.text.messages.monitor_resolution, mode
+                    ),
+                    format!(
+                        "{} {}: < {} >",
+                        " ".repeat(monitor.name.chars().count()),
+                        app.text.messages.monitor_scale,
+                        monitor.scale_value()
+                    ),
+                ]
+            })
+            .enumerate()
+            .map(|(i, row)| {
+                let (style, prefix) = if i == app.monitor_cursor {
+                    (styles.selected, theme.ui.selection_prefix.as_str())
+                } else {
+                    (styles.content, "")
+                };
+                ListItem::new(format!("{}{}{}", styles.padding, prefix, row)).style(style)
+            })
+            .collect();
+
+        let monitor_list = List::new(rows).style(styles.main);
+
+        f.render_widget(monitor_list, main_layout[1]);
+    } else if app.app_state == AppState::Locked {
+        // Show who holds the lock and what it is doing
+        let details = app.lock_details();
+        let locked = Paragraph::new(details)
+            .style(styles.confirmation)
+            .alignment(styles.confirmation_alignment)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(locked, main_layout[1]);
+    } else if matches!(
+        app.app_state,
+        AppState::RecoverySelect | AppState::RebootSelect | AppState::VolumeSelect
+    ) {
+        // Show the ways to recover the failed installation, when to reboot into an update or
+        // where to save the report
+        f.render_widget(choice_list(app, &tree), main_layout[1]);
+    } else if app.app_state == AppState::SessionsWarning {
+        // Name who would be logged out
+        let warning = Paragraph::new(app.sessions_warning())
+            .style(styles.confirmation)
+            .alignment(styles.confirmation_alignment)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(warning, main_layout[1]);
+    } else if app.app_state == AppState::Failed {
+        // Show the error until it is dismissed
+        let failure = Paragraph::new(app.failure_error.as_str())
+            .style(styles.confirmation)
+            .alignment(styles.confirmation_alignment)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(failure, main_layout[1]);
+    } else if app.app_state == AppState::BugReport {
+        // Show what the report says above the ways to send it
+        let actions = app.bug_report_actions().len() as u16;
+        let report_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(actions + 1)])
+            .split(main_layout[1]);
+
+        let preview = Paragraph::new(app.bug_report_preview())
+            .style(styles.content)
+            .wrap(Wrap { trim: false });
+        f.render_widget(preview, report_layout[0]);
+        f.render_widget(choice_list(app, &tree), report_layout[1]);
+    } else if app.app_state == AppState::PathInput
+        && let Some(input) = &app.path_input
+    {
+        // Show the typed path in a box, with the completions or what is wrong below it
+        let path_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Input box (border + content + border)
+                Constraint::Min(0),    // Completions or error
+            ])
+            .split(main_layout[1]);
+
+        let input_block = theme
+            .borders
+            .block(theme.borders.input)
+            .border_style(styles.border)
+            .style(styles.panel);
+        let path = Paragraph::new(format!("{}█", input.value))
+            .block(input_block)
+            .style(styles.content);
+        f.render_widget(path, path_layout[0]);
+
+        let below = match input.error {
+            Some(error) => Paragraph::new(app.path_error(error)).style(styles.error),
+            None => Paragraph::new(
+                input
+                    .completions
+                    .iter()
+                    .map(|completion| format!("{}{}", styles.padding, completion))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+            .style(styles.content),
+        };
+        f.render_widget(below, path_layout[1]);
+    } else if app.app_state == AppState::Plan {
+        // Show the plan inside a border, scrolled like the log
+        let border_block = theme
+            .borders
+            .block(theme.borders.viewer)
+            .border_style(styles.border)
+            .style(styles.panel);
+        let plan = Paragraph::new(app.plan_lines.join("\n"))
+            .block(border_block)
+            .style(styles.content)
+            .scroll((app.plan_scroll.min(u16::MAX as usize) as u16, 0));
+
+        f.render_widget(plan, main_layout[1]);
+    } else if app.app_state == AppState::DryRunResult {
+        // Show what the dry run would have done inside a border
+        let border_block = theme
+            .borders
+            .block(theme.borders.viewer)
+            .border_style(styles.border)
+            .style(styles.panel);
+        let result = Paragraph::new(app.dry_run_lines.join("\n"))
+            .block(border_block)
+            .style(styles.content)
+            .scroll((app.dry_run_scroll.min(u16::MAX as usize) as u16, 0));
+
+        f.render_widget(result, main_layout[1]);
+    } else if app.app_state == AppState::LogView {
+        // Show the failed run's log lines inside a border
+        let border_block = theme
+            .borders
+            .block(theme.borders.viewer)
+            .border_style(styles.border)
+            .style(styles.panel);
+        let log = Paragraph::new(app.log_lines.join("\n"))
+            .block(border_block)
+            .style(styles.content)
+            .scroll((app.log_scroll.min(u16::MAX as usize) as u16, 0));
+
+        f.render_widget(log, main_layout[1]);
+    } else {
+        let mut menu_area = main_layout[1];
+        if app.app_state == AppState::MainMenu && !app.health.is_empty() {
+            let banner_layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(0)])
+                .split(menu_area);
+            let banner = Paragraph::new(health::banner(&app.health, &app.text.messages))
+                .style(styles.fail)
+                .alignment(styles.description_alignment);
+            f.render_widget(banner, banner_layout[0]);
+            menu_area = banner_layout[1];
+        }
+        menu_area = bordered(
+            f,
+            menu_area,
+            &theme.borders,
+            theme.borders.menu,
+            styles.main,
+        );
+
+        // The highlighted option's document beside or below the menu
+        let document = app
+            .options
+            .get(app.selected)
+            .filter(|_| app.app_state == AppState::MainMenu)
+            .and_then(|option| app.option_document(option));
+        if let Some(document) = document {
+            let direction = match sizes.document_panel {
+                PanelSide::Right => Direction::Horizontal,
+                PanelSide::Bottom => Direction::Vertical,
+            };
+            let document_layout = Layout::default()
+                .direction(direction)
+                .constraints([
+                    Constraint::Min(0),
+                    Constraint::Percentage(theme.layout.document_size.min(100)),
+                ])
+                .split(menu_area);
+            let border_block = theme
+                .borders
+                .block(theme.borders.document)
+                .border_style(styles.border)
+                .style(styles.panel);
+            let document = Paragraph::new(markdown::render(document, styles))
+                .block(border_block)
+                .style(styles.content)
+                .wrap(Wrap { trim: false })
+                .scroll((app.document_scroll.min(u16::MAX as usize) as u16, 0));
+            f.render_widget(document, document_layout[1]);
+            menu_area = document_layout[0];
+        }
+
+        // The theme's art, left out when the terminal is too small for it
+        let screen = f.size();
+        if let Some(art) = app.backdrop.as_ref().filter(|_| {
+            app.app_state == AppState::MainMenu
+                && screen.width >= theme.backdrop.min_width
+                && screen.height >= theme.backdrop.min_height
+        }) {
+            let art_area = match theme.backdrop.placement {
+                BackdropPlacement::Behind => menu_area,
+                BackdropPlacement::Beside => {
+                    let art_layout = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([
+                            Constraint::Min(0),
+                            Constraint::Length(backdrop::width(art).min(menu_area.width / 2)),
+                        ])
+                        .split(menu_area);
+                    menu_area = art_layout[0];
+                    art_layout[1]
+                }
+            };
+            f.render_widget(
+                Paragraph::new(art.clone()).alignment(Alignment::Right),
+                art_area,
+            );
+        }
+
+        // Show options list, rebuilt only when the selection or the options change. The
+        // offset is kept between frames so a long menu scrolls instead of jumping.
+        let mut menu_state = ListState::default()
+            .with_selected(Some(app.selected))
+            .with_offset(app.widgets.menu_offset.get());
+        f.render_stateful_widget(&*app.widgets.menu(app), menu_area, &mut menu_state);
+        app.widgets.menu_offset.set(menu_state.offset());
+
+        if app.options.len() > menu_area.height as usize {
+            let symbols = if app.ascii {
+                ASCII_SCROLLBAR
+            } else {
+                scrollbar::VERTICAL
+            };
+            let mut scrollbar_state = ScrollbarState::new(app.options.len())
+                .viewport_content_length(menu_area.height as usize)
+                .position(app.selected);
+            f.render_stateful_widget(
+                Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                    .symbols(symbols)
+                    .style(styles.border),
+                menu_area,
+                &mut scrollbar_state,
+            );
+        }
+    }
+
+    // Shown over whatever is on screen, the machine is about to be taken back
+    if let Some(warning) = app.session_warning() {
+        let content = main_layout[1];
+        let warning_area = Rect {
+            y: content.y + content.height.saturating_sub(1),
+            height: content.height.min(1),
+            ..content
+        };
+        f.render_widget(Clear, warning_area);
+        let warning = Paragraph::new(warning)
+            .style(styles.error.add_modifier(Modifier::BOLD))
+            .alignment(Alignment::Center);
+        f.render_widget(warning, warning_area);
+    }
+
+    // The tour's explanation over the lower part of the screen it is about
+    if let (Some(tour), Some(stop)) = (&app.tour, tour::current(app)) {
+        let content = main_layout[1];
+        let width = content.width.min(64);
+        let height = content.height.min(8);
+        let popup_area = Rect {
+            x: content.x + (content.width - width) / 2,
+            y: content.y + content.height - height,
+            width,
+            height,
+        };
+        f.render_widget(Clear, popup_area);
+        let border_block = theme
+            .borders
+            .block(theme.borders.tour)
+            .border_style(styles.border_active)
+            .style(styles.panel)
+            .title(Span::styled(format!(" {} ", stop.title), styles.heading))
+            .title(
+                block::Title::from(format!(" {}/{} ", tour.stop + 1, app.text.tour.stops.len()))
+                    .position(block::Position::Bottom)
+                    .alignment(Alignment::Right),
+            );
+        let popup = Paragraph::new(stop.body.as_str())
+            .block(border_block)
+            .style(styles.content)
+            .wrap(Wrap { trim: true });
+        f.render_widget(popup, popup_area);
+    }
+
+    // Description/Status area
+    let description_area = bordered(
+        f,
+        main_layout[2],
+        &theme.borders,
+        theme.borders.description,
+        styles.description_bg,
+    );
+
+    if app.progress_type.is_some() {
+        // Show spinner text above progress bar in status area
+        let spinner_text = match &app.app_state {
+            AppState::Processing(desc) => {
+                if let Some(ProgressType::Determinant(_)) = &app.progress_type {
+                    format!("{} {}", app.countdown_remaining, desc)
+                } else {
+                    format!("{} {}", app.get_spinner_char(), desc)
+                }
+            }
+            _ => "Processing...".to_string(),
+        };
+
+        let progress_bar = app.widgets.progress_bar(app, description_area.width);
+
+        let progress_widget =
+            Paragraph::new(vec![Line::from(spinner_text), Line::from(&*progress_bar)])
+                .style(styles.progress)
+                .alignment(Alignment::Center);
+
+        f.render_widget(progress_widget, description_area);
+    } else if let Some((message, status_type)) = &app.status_message {
+        // Show status message with navigation help
+        let (style, prefix) = match status_type {
+            StatusType::Success => (styles.success, &app.text.ui_text.success_prefix),
+            StatusType::Error => (styles.error, &app.text.ui_text.error_prefix),
+            StatusType::Fail => (styles.fail, &app.text.ui_text.fail_prefix),
+        };
+
+        let style = if app.status_fading() {
+            style.add_modifier(Modifier::DIM)
+        } else {
+            style
+        };
+        let status_line = vec![
+            Span::styled(prefix, style.add_modifier(Modifier::BOLD)),
+            Span::styled(format!(": {}", message), styles.status_text),
+        ];
+        let status = Paragraph::new(Line::from(status_line))
+            .style(styles.description_bg)
+            .alignment(styles.description_alignment);
+
+        f.render_widget(status, description_area);
+    } else {
+        // Show description or help text, the details of the focused element first
+        let focus_description = tree.focused().map_or("", |node| node.description.as_str());
+        let description_text = if app.show_confirmation {
+            // Don't show confirmation help in description area since it's already in the main confirmation dialog
+            "Review your selection carefully before confirming.".to_string()
+        } else if app.app_state == AppState::PasswordInput {
+            app.text.messages.password_help.clone()
+        } else {
+            match &app.app_state {
+                AppState::Processing(_) => app.text.messages.processing_help.clone(),
+                AppState::Locked => app.text.messages.locked_help.clone(),
+                _ => focus_description.to_string(),
+            }
+        };
+
+        let description = Paragraph::new(description_text)
+            .style(styles.description)
+            .alignment(styles.description_alignment)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(description, description_area);
+    }
+
+    let hints = keymap::hints(app);
+    let hint_bar = Paragraph::new(keymap::bar(&hints, styles))
+        .style(styles.description_bg)
+        .alignment(styles.description_alignment);
+    f.render_widget(hint_bar, main_layout[3]);
+}
+
+// Draws the snapshots of another instance with the usual progress view until q is pressed
+pub(crate) async fn run_attached<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+) -> Result<()> {
+    let mut attachment = control::attach().await?;
+    let mut connected = true;
+    app.attached = true;
+    app.progress_type = Some(ProgressType::Indeterminate);
+    app.app_state = AppState::Processing(String::new());
+    app.start_ticks();
+
+    loop {
+        terminal.draw(|f| ui(f, app))?;
+        app.update_progress();
+
+        let next = async {
+            if connected {
+                attachment.next().await
+            } else {
+                std::future::pending().await
+            }
+        };
+        tokio::select! {
+            snapshot = next => match snapshot {
+                Some(snapshot) => {
+                    let action = if snapshot.status.is_empty() {
+                        snapshot.title
+                    } else {
+                        snapshot.status
+                    };
+                    app.app_state = AppState::Processing(action);
+                    app.checklist = snapshot.checklist;
+                    app.action_output = snapshot.output;
+                    app.unattended = snapshot.unattended;
+                }
+                None => {
+                    connected = false;
+                    app.app_state = AppState::Processing(app.text.messages.attach_closed.clone());
+                }
+            },
+            _ = tokio::time::sleep(Duration::from_millis(50)) => {}
+        }
+
+        if poll(Duration::ZERO)?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && matches!(keymap::code(&key), KeyCode::Char('q') | KeyCode::Esc)
+        {
+            return Ok(());
+        }
+    }
+}
+
+pub(crate) async fn run_app<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+) -> io::Result<()> {
+    let mut announced: Option<a11y::Node> = None;
+    let mut announced_state: Option<AppState> = None;
+    loop {
+        app.expire_status();
+        app.poll_health();
+        app.check_session();
+        terminal.draw(|f| ui(f, app))?;
+
+        // Update progress if in progress mode (time-based, independent of events)
+        if app.progress_type.is_some() {
+            app.update_progress();
+        }
+        app.poll_worker();
+        if app.app_state == AppState::Locked {
+            app.check_lock();
+        }
+        app.publish();
+
+        // Use shorter timeout for responsive UI but progress updates are time-based
+        let timeout = std::time::Duration::from_millis(50);
+
+        if poll(timeout)? {
+            match event::read()? {
+                Event::Key(key) => handle_key(app, key).await,
+                // Can hold a password, which is wiped once it is in the field
+                Event::Paste(mut text) => {
+                    handle_paste(app, &text);
+                    escalation::wipe(&mut text);
+                }
+                _ => {}
+            }
+            // A text trail of each new screen and where focus went, for assistive technology
+            let screen = a11y::screen(app);
+            if announced_state.as_ref() != Some(&app.app_state) {
+                logging::event!(Level::Debug, "a11y", "{}", screen.describe());
+                announced_state = Some(app.app_state.clone());
+                announced = screen.focused().cloned();
+            } else if let Some(focused) = screen.focused()
+                && Some(focused) != announced.as_ref()
+            {
+                logging::event!(Level::Debug, "a11y", "{}", focused.describe());
+                announced = Some(focused.clone());
+            }
+        }
+
+        if app.should_quit {
+            return Ok(());
+        }
+    }
+}
+
+// Draws a border around `area` when the theme gives the element one, the area inside
+// is what is left for the element
+pub(crate) fn bordered(
+    f: &mut Frame,
+    area: Rect,
+    borders: &ElementBorders,
+    border: bool,
+    style: Style,
+) -> Rect {
+    if !border || borders.kind == BorderKind::None {
+        return area;
+    }
+    let block = borders.block(border).border_style(style).style(style);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    inner
+}
+
+// DRY-RUN, OFFLINE and UNATTENDED as they apply, then the clock if the theme wants one
+pub(crate) fn title_badges(app: &App) -> Vec<Span<'static>> {
+    let ui_text = &app.text.ui_text;
+    let styles = &app.styles;
+    let mut badges = Vec::new();
+    if app.dry_run {
+        badges.push(Span::styled(
+            format!(
+                " {} {} ",
+                app.theme.ui.dry_run_icon, ui_text.dry_run_indicator
+            ),
+            styles.dry_run,
+        ));
+    }
+    if app.health.contains(&health::Issue::Offline) {
+        badges.push(Span::styled(
+            format!(" {} ", ui_text.offline_indicator),
+            styles.badge,
+        ));
+    }
+    if app.unattended {
+        badges.push(Span::styled(
+            format!(" {} ", ui_text.unattended_indicator),
+            styles.badge,
+        ));
+    }
+    // write! reports a bad clock_format where format! would panic
+    let mut clock = String::new();
+    if app.theme.ui.show_clock
+        && write!(
+            clock,
+            " {} ",
+            chrono::Local::now().format(&app.theme.ui.clock_format)
+        )
+        .is_ok()
+    {
+        badges.push(Span::styled(
+            clock,
+            styles.title.remove_modifier(Modifier::BOLD),
+        ));
+    }
+    badges
+}
@@ -0,0 +1,228 @@
+use crate::logging;
+use crate::pipeline::{CheckStatus, ChecklistItem, StepContext};
+use crate::snapshot::expand_home;
+use crate::steps;
+use crate::tools::is_executable;
+use anyhow::Result;
+use cli_log::Level;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::time::Duration;
+
+// One assertion of the verify phase that runs after a successful pipeline
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct VerifyCheck {
+    pub name: String,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    File(String),
+    Unit(String),
+    UserUnit(String),
+    Command(String),
+    // A bootc deployment is staged for the next boot
+    BootcStaged(bool),
+    // Command and arguments run as the user, passing when it exits successfully
+    Smoke(Vec<String>),
+}
+
+impl Check {
+    async fn passes(&self, ctx: &StepContext, timeout: Duration) -> Result<()> {
+        let passed = match self {
+            Check::File(path) => ctx.expand_home(path).exists(),
+            Check::Unit(unit) => steps::systemd::unit_enabled(unit, false).await,
+            Check::UserUnit(unit) => steps::systemd::unit_enabled(unit, true).await,
+            Check::Command(command) => in_path(command),
+            Check::BootcStaged(expected) => bootc_staged().await == *expected,
+            Check::Smoke(command) => return smoke_test(command, ctx, timeout).await,
+        };
+        if passed {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("assertion not met"))
+        }
+    }
+}
+
+// Runs every check, adding a line per check to the checklist, and returns the failed names
+pub async fn run_checks(
+    checks: &[VerifyCheck],
+    ctx: &StepContext,
+    settings: &crate::VerifyConfig,
+    checklist: &mut Vec<ChecklistItem>,
+) -> Vec<String> {
+    let timeout = Duration::from_secs(settings.smoke_timeout);
+    let mut failed = Vec::new();
+    for check in checks {
+        if matches!(check.check, Check::Smoke(_)) && !settings.smoke_tests {
+            logging::event!(
+                Level::Debug,
+                "verify",
+                "Smoke tests are turned off, skipping {}",
+                check.name
+            );
+            continue;
+        }
+        let result = check.check.passes(ctx, timeout).await;
+        let passed = result.is_ok();
+        match result {
+            Ok(()) => logging::event!(Level::Info, "verify", "{} passed", check.name),
+            Err(e) => {
+                logging::event!(Level::Warn, "verify", "{} failed: {}", check.name, e);
+                failed.push(check.name.clone());
+            }
+        }
+        checklist.push(ChecklistItem::new(
+            check.name.clone(),
+            if passed {
+                CheckStatus::Done
+            } else {
+                CheckStatus::Failed
+            },
+            1,
+        ));
+    }
+    failed
+}
+
+pub fn in_path(command: &str) -> bool {
+    if command.contains('/') {
+        return is_executable(&crate::sysroot::target(expand_home(command)));
+    }
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths)
+            .any(|dir| is_executable(&crate::sysroot::target(dir.join(command))))
+    })
+}
+
+pub async fn bootc_staged() -> bool {
+    let Ok(output) = steps::privileged_command("bootc")
+        .args(["status", "--json"])
+        .output()
+        .await
+    else {
+        return false;
+    };
+    serde_json::from_slice::<serde_json::Value>(&output.stdout)
+        .is_ok_and(|status| !status["status"]["staged"].is_null())
+}
+
+async fn smoke_test(command: &[String], ctx: &StepContext, timeout: Duration) -> Result<()> {
+    let Some((program, args)) = command.split_first() else {
+        return Err(anyhow::anyhow!("empty smoke test command"));
+    };
+    let mut cmd = steps::user_command(program, ctx.username());
+    cmd.args(args.iter().map(|arg| ctx.expand_home(arg)))
+        .envs(ctx.environment())
+        .kill_on_drop(true);
+    let output = tokio::time::timeout(timeout, cmd.output())
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out after {}s", timeout.as_secs()))??;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!(
+            "{} ({})",
+            output.status,
+            stderr.lines().last().unwrap_or_default().trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{MockSystem, Scripted};
+
+    fn check(name: &str, check: Check) -> VerifyCheck {
+        VerifyCheck {
+            name: name.to_string(),
+            check,
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_assertions_are_named_and_marked_in_the_checklist() {
+        let system = MockSystem::new();
+        system.script(
+            "bootc",
+            Scripted {
+                stdout: r#"{"status":{"staged":{"image":"sparrow:latest"}}}"#.to_string(),
+                ..Scripted::default()
+            },
+        );
+        let home = std::env::var("HOME").expect("mock home");
+        std::fs::write(format!("{}/hyprland.conf", home), "").expect("config");
+        let checks = [
+            check(
+                "Hyprland config",
+                Check::File("~/hyprland.conf".to_string()),
+            ),
+            check("Waybar config", Check::File("~/waybar.jsonc".to_string())),
+            check("bootc installed", Check::Command("bootc".to_string())),
+            check("no-such-tool", Check::Command("no-such-tool".to_string())),
+            check("Update staged", Check::BootcStaged(true)),
+        ];
+        let settings = crate::SettingsConfig::load().expect("settings").verify;
+        let mut checklist = Vec::new();
+
+        let failed = run_checks(&checks, &StepContext::default(), &settings, &mut checklist).await;
+        assert_eq!(failed, ["Waybar config", "no-such-tool"]);
+        let statuses: Vec<CheckStatus> = checklist.iter().map(|item| item.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                CheckStatus::Done,
+                CheckStatus::Failed,
+                CheckStatus::Done,
+                CheckStatus::Failed,
+                CheckStatus::Done,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn smoke_tests_report_why_they_failed_and_can_be_turned_off() {
+        let system = MockSystem::new();
+        system.program("hyprctl", "echo 'Hyprland 0.45.0'\n");
+        system.program("waybar", "echo 'Error in config file' >&2\nexit 2\n");
+        system.program("slow", "sleep 5\n");
+        let checks = [
+            check(
+                "hyprctl",
+                Check::Smoke(vec!["hyprctl".to_string(), "version".to_string()]),
+            ),
+            check(
+                "waybar",
+                Check::Smoke(vec!["waybar".to_string(), "--version".to_string()]),
+            ),
+            check("slow", Check::Smoke(vec!["slow".to_string()])),
+        ];
+        let mut settings = crate::SettingsConfig::load().expect("settings").verify;
+        settings.smoke_timeout = 1;
+        let ctx = StepContext::default();
+        let mut checklist = Vec::new();
+
+        assert_eq!(
+            run_checks(&checks, &ctx, &settings, &mut checklist).await,
+            ["waybar", "slow"]
+        );
+        let error = smoke_test(&["waybar".to_string()], &ctx, Duration::from_secs(1))
+            .await
+            .expect_err("waybar fails");
+        assert_eq!(error.to_string(), "exit status: 2 (Error in config file)");
+
+        settings.smoke_tests = false;
+        checklist.clear();
+        assert!(
+            run_checks(&checks, &ctx, &settings, &mut checklist)
+                .await
+                .is_empty()
+        );
+        assert!(checklist.is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{App, InstallerOption};
+use ratatui::style::Modifier;
+use ratatui::widgets::{List, ListItem};
+use std::cell::{Cell, Ref, RefCell};
+use std::ops::Range;
+
+// What the main menu was built from
+struct MenuKey {
+    options: Vec<InstallerOption>,
+    selected: usize,
+    reinstall: bool,
+}
+
+impl MenuKey {
+    fn matches(&self, app: &App) -> bool {
+        self.selected == app.selected
+            && self.reinstall == app.dotfiles_marker.is_some()
+            && self.options == app.options
+    }
+}
+
+// Width of the progress bar and its thick cells
+type ProgressFill = (usize, Range<usize>);
+
+// Widgets that stay the same across most frames, rebuilt only when what they show changes
+#[derive(Default)]
+pub struct WidgetCache {
+    menu: RefCell<Option<(MenuKey, List<'static>)>>,
+    // First menu row on screen the last time it was drawn
+    pub menu_offset: Cell<usize>,
+    progress_bar: RefCell<(ProgressFill, String)>,
+}
+
+impl WidgetCache {
+    pub fn menu(&self, app: &App) -> Ref<'_, List<'static>> {
+        let stale = match &*self.menu.borrow() {
+            Some((key, _)) => !key.matches(app),
+            None => true,
+        };
+        if stale {
+            let key = MenuKey {
+                options: app.options.clone(),
+                selected: app.selected,
+                reinstall: app.dotfiles_marker.is_some(),
+            };
+            *self.menu.borrow_mut() = Some((key, build_menu(app)));
+        }
+        Ref::map(self.menu.borrow(), |menu| {
+            &menu.as_ref().expect("menu built above").1
+        })
+    }
+
+    pub fn progress_bar(&self, app: &App, width: u16) -> Ref<'_, str> {
+        let fill = app.progress_fill(width);
+        if self.progress_bar.borrow().0 != fill {
+            let mut cached = self.progress_bar.borrow_mut();
+            let (bar_width, filled) = &fill;
+            cached.1.clear();
+            // Line characters, thick for the filled part
+            cached
+                .1
+                .extend((0..*bar_width).map(|i| if filled.contains(&i) { '━' } else { '─' }));
+            cached.0 = fill;
+        }
+        Ref::map(self.progress_bar.borrow(), |(_, bar)| bar.as_str())
+    }
+}
+
+fn build_menu(app: &App) -> List<'static> {
+    let styles = &app.styles;
+    let options: Vec<ListItem> = app
+        .options
+        .iter()
+        .enumerate()
+        .map(|(i, option)| {
+            let (style, prefix) = if i == app.selected {
+                (styles.selected, app.theme.ui.selection_prefix.as_str())
+            } else if !option.is_enabled() {
+                (styles.disabled, "")
+            } else if matches!(option, InstallerOption::Recover) {
+                // Stands out so a failed install is not missed
+                (styles.fail, "")
+            } else {
+                (styles.content, "")
+            };
+
+            let icon = app
+                .option_icon(option)
+                .map_or_else(String::new, |icon| format!("{} ", icon));
+            let content = if !option.is_enabled() {
+                format!(
+                    "{}{}{}",
+                    icon,
+                    app.option_title(option),
+                    app.theme.ui.disabled_suffix
+                )
+            } else {
+                format!("{}{}", icon, app.option_title(option))
+            };
+
+            let number = match i {
+                _ if !app.theme.ui.show_numbers => String::new(),
+                0..9 => format!("{}. ", i + 1),
+                // Lined up with the numbered entries
+                _ => "   ".to_string(),
+            };
+
+            let display_text = format!("{}{}{}{}", styles.padding, prefix, number, content);
+
+            ListItem::new(display_text).style(style.add_modifier(if i == app.selected {
+                Modifier::BOLD
+            } else {
+                Modifier::empty()
+            }))
+        })
+        .collect();
+
+    List::new(options).style(styles.main)
+}
@@ -0,0 +1,494 @@
+use crate::pipeline::{self, ChecklistItem, PlannedStep, ProfileConfig, StepContext};
+use crate::progress::FanOut;
+use crate::report::RunReport;
+use crate::state::InstallMarker;
+use crate::{
+    InstallerOption, SettingsConfig, TextConfig, escalation, helper, logging, metrics, notify,
+    recovery, sandbox, snapshot, steps, tools, verify,
+};
+use anyhow::Result;
+use cli_log::Level;
+use tokio::process::Command as AsyncCommand;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender, error::TryRecvError};
+use tokio::task::JoinHandle;
+
+// What the operation on the worker tells the interface while it runs
+pub enum AppEvent {
+    Status(String),
+    Checklist(Vec<ChecklistItem>),
+    Finished(Box<Finished>),
+}
+
+// The state the interface takes back once the operation is over
+pub struct Finished {
+    pub result: Result<()>,
+    pub report: Option<RunReport>,
+    // The install marker as the run left it, None when it did not write one
+    pub marker: Option<InstallMarker>,
+    // Set by the runs that verify the install
+    pub failed_checks: Option<Vec<String>>,
+    // What a dry run would have done, shown instead of the finished screen
+    pub dry_run: Option<Vec<String>>,
+}
+
+impl Finished {
+    fn new(report: Option<RunReport>) -> Self {
+        Finished {
+            result: Ok(()),
+            report,
+            marker: None,
+            failed_checks: None,
+            dry_run: None,
+        }
+    }
+}
+
+// The work itself, prepared from what was picked in the interface
+pub enum Job {
+    Pipeline {
+        steps: Vec<PlannedStep>,
+        marker: InstallMarker,
+    },
+    Rollback(Vec<recovery::Restore>),
+    Update,
+    Nothing,
+}
+
+// Everything an operation reads, moved onto the worker so the interface keeps drawing
+// and answering keys while it runs
+pub struct Operation {
+    pub job: Job,
+    // "Installing", the start of each step's status line
+    pub action: String,
+    pub dry_run: bool,
+    pub sandbox: bool,
+    // Starts the helper or goes to sudo, empty when neither needs it
+    pub password: String,
+    pub uses_helper: bool,
+    pub text: TextConfig,
+    pub settings: SettingsConfig,
+    pub profile: ProfileConfig,
+    pub ctx: StepContext,
+    pub report: Option<RunReport>,
+    // Dotfiles were installed before, a failed repair keeps its partial marker
+    pub installed: bool,
+    pub progress: FanOut,
+}
+
+pub struct Worker {
+    pub option: InstallerOption,
+    events: UnboundedReceiver<AppEvent>,
+    task: JoinHandle<()>,
+}
+
+pub fn spawn(option: InstallerOption, operation: Operation) -> Worker {
+    let (sender, events) = mpsc::unbounded_channel();
+    let task = tokio::spawn(operation.run(sender));
+    Worker {
+        option,
+        events,
+        task,
+    }
+}
+
+impl Worker {
+    // The next event if one is waiting, for the interface loop
+    pub fn try_next(&mut self) -> Option<AppEvent> {
+        match self.events.try_recv() {
+            Ok(event) => Some(event),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(stopped()),
+        }
+    }
+
+    pub async fn next(&mut self) -> AppEvent {
+        self.events.recv().await.unwrap_or_else(stopped)
+    }
+}
+
+// A worker that panicked never sends its result, the operation counts as failed
+fn stopped() -> AppEvent {
+    AppEvent::Finished(Box::new(Finished {
+        result: Err(anyhow::anyhow!("The operation stopped unexpectedly")),
+        ..Finished::new(None)
+    }))
+}
+
+// Dropping the worker, as cancelling a dry run does, stops the operation
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Run {
+    op: Operation,
+    events: UnboundedSender<AppEvent>,
+    checklist: Vec<ChecklistItem>,
+    done: Finished,
+}
+
+impl Operation {
+    async fn run(mut self, events: UnboundedSender<AppEvent>) {
+        let job = std::mem::replace(&mut self.job, Job::Nothing);
+        let report = self.report.take();
+        let mut run = Run {
+            op: self,
+            events,
+            checklist: Vec::new(),
+            done: Finished::new(report),
+        };
+        let result = match run.start_helper().await {
+            Err(e) => Err(e),
+            Ok(()) => match job {
+                Job::Pipeline { steps, marker } => run.pipeline(steps, marker).await,
+                Job::Rollback(plan) => run.rollback(plan),
+                Job::Update => run.update_system().await,
+                Job::Nothing => Ok(()),
+            },
+        };
+        run.done.result = result;
+        let _ = run.events.send(AppEvent::Finished(Box::new(run.done)));
+    }
+}
+
+impl Run {
+    fn status(&self, message: String) {
+        // The interface going away only means nobody is watching anymore
+        let _ = self.events.send(AppEvent::Status(message));
+    }
+
+    fn publish_checklist(&self) {
+        let _ = self
+            .events
+            .send(AppEvent::Checklist(self.checklist.clone()));
+    }
+
+    // The password the action asked for starts the helper, the steps then run their system
+    // commands through it
+    async fn start_helper(&mut self) -> Result<()> {
+        let op = &mut self.op;
+        // A polkit agent asks for the password itself
+        let needs_password = escalation::asks_password();
+        if op.dry_run || !op.uses_helper || (needs_password && op.password.is_empty()) {
+            return Ok(());
+        }
+        let started = helper::start(&op.password).await;
+        escalation::wipe(&mut op.password);
+        started.map_err(|e| {
+            if escalation::wrong_password(&e.to_string()) {
+                anyhow::anyhow!("{}", op.text.messages.password_auth_failed)
+            } else {
+                e
+            }
+        })
+    }
+
+    // Runs the steps in order, recording every completed step in the install marker
+    async fn pipeline(&mut self, steps: Vec<PlannedStep>, mut marker: InstallMarker) -> Result<()> {
+        pipeline::check_conflicts(&steps, &self.op.ctx)?;
+        if self.op.dry_run {
+            return self.dry_run_pipeline(&steps).await;
+        }
+
+        // Saved now so an install cut short by a crash or power loss shows up as unfinished
+        if let Some(report) = self.done.report.as_mut() {
+            report.install = true;
+            if let Err(e) = report.save() {
+                logging::event!(Level::Warn, "report", "Could not write report: {}", e);
+            }
+        }
+
+        let snapshot_config = self.op.settings.snapshot.clone();
+        let before = if snapshot_config.enabled {
+            let config = snapshot_config.clone();
+            Some(tokio::task::spawn_blocking(move || snapshot::Snapshot::capture(&config)).await?)
+        } else {
+            None
+        };
+
+        self.checklist = steps
+            .iter()
+            .map(|step| {
+                pipeline::ChecklistItem::new(step.label(), pipeline::CheckStatus::Pending, 0)
+            })
+            .collect();
+
+        let ctx = pipeline::StepContext {
+            progress: Some(self.op.progress.sender()),
+            ..self.op.ctx.clone()
+        };
+        let mut result = Ok(());
+        let mut position = 0;
+        for (index, step) in steps.iter().enumerate() {
+            self.checklist[position].status = pipeline::CheckStatus::Running;
+            notify::heartbeat();
+            self.publish_checklist();
+            self.status(format!(
+                "{}, step {}/{}: {}",
+                self.op.action,
+                index + 1,
+                steps.len(),
+                step.label()
+            ));
+
+            let mut details = Vec::new();
+            let step_result = match step.skip_reason() {
+                Ok(Some(reason)) => {
+                    // Not recorded as done, so repair runs it once the condition holds
+                    logging::event!(Level::Info, &step.step.id, "Skipped, {}", reason);
+                    self.checklist[position].status = pipeline::CheckStatus::Skipped;
+                    self.checklist.insert(
+                        position + 1,
+                        pipeline::ChecklistItem::new(reason, pipeline::CheckStatus::Skipped, 1),
+                    );
+                    position += 2;
+                    self.publish_checklist();
+                    continue;
+                }
+                Ok(None) => {
+                    let ctx = pipeline::StepContext {
+                        counters: Default::default(),
+                        ..ctx.clone()
+                    };
+                    let started = std::time::Instant::now();
+                    let result = step.run(&ctx, &mut details).await;
+                    if let Some(report) = self.done.report.as_mut() {
+                        report.metrics.push(metrics::StepMetrics::new(
+                            &step.marker_key(),
+                            started.elapsed().as_secs_f64(),
+                            &ctx.counters,
+                        ));
+                    }
+                    result
+                }
+                Err(e) => Err(e),
+            };
+            self.checklist[position].status = if step_result.is_ok() {
+                pipeline::CheckStatus::Done
+            } else {
+                pipeline::CheckStatus::Failed
+            };
+            let detail_count = details.len();
+            self.checklist.splice(position + 1..position + 1, details);
+            position += 1 + detail_count;
+            self.publish_checklist();
+
+            if let Err(e) = step_result {
+                // Forget the step so repair picks it up again
+                marker.steps.remove(&step.marker_key());
+                result = Err(e);
+                break;
+            }
+            marker
+                .steps
+                .insert(step.marker_key(), step.step.fingerprint());
+            let saved = if self.op.installed {
+                marker.save()
+            } else {
+                marker.save_partial()
+            };
+            if let Err(e) = saved {
+                logging::event!(Level::Warn, "install", "Could not record progress: {}", e);
+            }
+        }
+
+        let mut failed_checks = Vec::new();
+        if result.is_ok() && !self.op.profile.verify.is_empty() {
+            let header = self.checklist.len();
+            self.checklist.push(pipeline::ChecklistItem::new(
+                "Verify installation",
+                pipeline::CheckStatus::Running,
+                0,
+            ));
+            self.publish_checklist();
+            let mut details = Vec::new();
+            failed_checks = verify::run_checks(
+                &self.op.profile.verify,
+                &ctx,
+                &self.op.settings.verify,
+                &mut details,
+            )
+            .await;
+            self.checklist[header].status = if failed_checks.is_empty() {
+                pipeline::CheckStatus::Done
+            } else {
+                pipeline::CheckStatus::Warning
+            };
+            self.checklist.extend(details);
+            self.publish_checklist();
+        }
+
+        if let Some(report) = self.done.report.as_mut() {
+            report.checklist = self.checklist.clone();
+            report.degraded = !failed_checks.is_empty();
+            report.failed_checks = failed_checks.clone();
+        }
+        self.done.failed_checks = Some(failed_checks);
+
+        // Compare even when a step failed, that is when the changes matter most
+        if let Some(before) = before {
+            let after =
+                tokio::task::spawn_blocking(move || snapshot::Snapshot::capture(&snapshot_config))
+                    .await?;
+            let diff = before.diff(&after);
+            logging::event!(Level::Info, "snapshot", "{}", diff.summary());
+            if let Some(report) = self.done.report.as_mut() {
+                report.changes = Some(diff);
+            }
+        }
+
+        // A failed fresh install leaves no marker, a failed repair keeps the partial one
+        if result.is_ok() || self.op.installed {
+            if result.is_ok() {
+                marker.content_hash = self.op.profile.content_hash();
+                marker.installed_at = chrono::Local::now().to_rfc3339();
+                InstallMarker::remove_partial();
+            }
+            if let Err(e) = marker.save() {
+                logging::event!(
+                    Level::Warn,
+                    "install",
+                    "Could not write install marker: {}",
+                    e
+                );
+            }
+            self.done.marker = Some(marker);
+        }
+
+        result
+    }
+
+    // Lists what each step would do instead of doing it, a step that cannot be evaluated fails
+    // the dry run like it would fail the install
+    async fn dry_run_pipeline(&mut self, steps: &[PlannedStep]) -> Result<()> {
+        let ctx = &self.op.ctx;
+        let text = &self.op.text.dry_run;
+        let mut lines = Vec::new();
+        for step in steps {
+            lines.push(step.label());
+            if let Some(reason) = step.skip_reason()? {
+                lines.push(format!(
+                    "  {} {}",
+                    self.op.text.messages.plan_skipped, reason
+                ));
+                continue;
+            }
+            let effects = if self.op.sandbox && sandbox::fits(&step.step) {
+                let run = sandbox::run(step, ctx).await?;
+                if let Some(error) = run.error {
+                    lines.push(format!("  {} {}", text.sandbox_failed, error));
+                }
+                run.changes
+            } else {
+                step.effects(ctx)?
+            };
+            lines.extend(
+                effects
+                    .iter()
+                    .map(|effect| format!("  {}", effect.describe(text))),
+            );
+        }
+        self.done.dry_run = Some(lines);
+        Ok(())
+    }
+
+    fn rollback(&mut self, plan: Vec<recovery::Restore>) -> Result<()> {
+        if self.op.dry_run {
+            let text = &self.op.text.dry_run;
+            let lines = plan
+                .iter()
+                .map(|restore| format!("{} {}", text.would_modify, restore.dest.display()))
+                .collect();
+            self.done.dry_run = Some(lines);
+            return Ok(());
+        }
+        recovery::rollback(&plan)?;
+        for restore in &plan {
+            logging::event!(
+                Level::Info,
+                "recovery",
+                "Restored {} from {}",
+                restore.dest.display(),
+                restore.backup.display()
+            );
+        }
+        Ok(())
+    }
+
+    async fn update_system(&mut self) -> Result<()> {
+        logging::event!(Level::Debug, "update", "Dry-run State: {}", self.op.dry_run);
+        if self.op.dry_run {
+            return Ok(());
+        }
+        self.status(self.op.text.messages.progress_updating.clone());
+
+        tools::require("bootc")?;
+        let progress = self.op.progress.clone();
+        let output = if self.op.uses_helper {
+            steps::privileged_command("bootc")
+                .arg("update")
+                .output_streaming(|line| show_update_line(&progress, line))
+                .await?
+        } else {
+            self.escalated_update().await?
+        };
+        logging::record_output(module_path!(), "update", &output.stdout);
+
+        if !output.status.success() {
+            let error_msg = String::from_utf8_lossy(&output.stderr);
+            logging::event!(Level::Error, "update", "{}", error_msg);
+            return Err(self.authentication_error(&error_msg));
+        }
+
+        logging::event!(Level::Info, "update", "bootc update finished");
+        Ok(())
+    }
+
+    // Without the helper, or as root where it is not needed, sudo or whichever backend
+    // is installed gets the password itself
+    async fn escalated_update(&mut self) -> Result<std::process::Output> {
+        // Recovery images may run as root without any of them installed
+        let backend = escalation::current();
+        let mut cmd = match backend {
+            Some(backend) => escalation::command(backend, "bootc"),
+            None if steps::is_root() => AsyncCommand::new("bootc"),
+            None => escalation::command(escalation::require()?, "bootc"),
+        };
+        // Only stages the new image, the user picks when to reboot into it
+        cmd.arg("update");
+        cmd.stdin(std::process::Stdio::piped());
+        cmd.stdout(std::process::Stdio::piped());
+        cmd.stderr(std::process::Stdio::piped());
+
+        let argv = crate::audit::argv(cmd.as_std());
+        let spawned = match backend {
+            Some(backend) => escalation::spawn(backend, &mut cmd, &self.op.password).await,
+            None => cmd.spawn().map_err(anyhow::Error::from),
+        };
+        escalation::wipe(&mut self.op.password);
+        let child = spawned?;
+
+        let progress = self.op.progress.clone();
+        let output = helper::wait_streaming(child, |line| show_update_line(&progress, line)).await;
+        crate::audit::record(&argv, "direct", &output);
+        Ok(output?)
+    }
+
+    fn authentication_error(&self, error_msg: &str) -> anyhow::Error {
+        if escalation::wrong_password(error_msg) {
+            anyhow::anyhow!("{}", self.op.text.messages.password_auth_failed)
+        } else {
+            anyhow::anyhow!("System update failed: {}", error_msg)
+        }
+    }
+}
+
+// A line bootc printed, shown in the processing view while the update runs
+fn show_update_line(progress: &FanOut, line: &str) {
+    progress.step(&pipeline::StepProgress {
+        step: "update".to_string(),
+        fraction: None,
+        message: line.to_string(),
+    });
+}